@@ -2,6 +2,10 @@
 
 use thiserror::Error;
 
+pub mod mode;
+
+pub use mode::CaptureMode;
+
 /// Camera errors
 #[derive(Debug, Error)]
 pub enum CameraError {
@@ -120,6 +124,37 @@ fn yuyv_to_rgb_strided(data: &[u8], width: u32, height: u32, stride: u32) -> Vec
     rgb
 }
 
+/// Expand an 8-bit grayscale (GREY) buffer to RGB888, dropping the per-row
+/// padding.
+fn gray_to_rgb_strided(data: &[u8], width: u32, height: u32, stride: u32) -> Vec<u8> {
+    let row_bytes = width as usize;
+    let stride = stride.max(width) as usize;
+    let mut rgb = Vec::with_capacity(row_bytes * 3 * height as usize);
+    for row in 0..height as usize {
+        let row_start = row * stride;
+        match data.get(row_start..row_start + row_bytes) {
+            Some(row_data) => rgb.extend(row_data.iter().flat_map(|&y| [y, y, y])),
+            None => break,
+        }
+    }
+    rgb
+}
+
+/// Copy a packed RGB24 buffer row by row, dropping the per-row padding.
+fn rgb_strided(data: &[u8], width: u32, height: u32, stride: u32) -> Vec<u8> {
+    let row_bytes = (width * 3) as usize;
+    let stride = stride.max(width * 3) as usize;
+    let mut rgb = Vec::with_capacity(row_bytes * height as usize);
+    for row in 0..height as usize {
+        let row_start = row * stride;
+        match data.get(row_start..row_start + row_bytes) {
+            Some(row_data) => rgb.extend_from_slice(row_data),
+            None => break,
+        }
+    }
+    rgb
+}
+
 /// Capture `num_frames` frames from V4L2 in YUYV and deliver them as RGB via callback.
 ///
 /// Opens `/dev/video0` (or the given path), configures YUYV 640x480, creates a **single**
@@ -238,45 +273,176 @@ pub fn scan_cameras() -> CameraInventory {
     }
 }
 
-/// Opens `device_path` and negotiates 640x480 capture in the given FourCC
-/// pixel format — shared by capture_gray_stream_v4l2/capture_rgb_stream_v4l2/
-/// capture_rgb_stream_until, which each repeated this exact open/negotiate
-/// sequence, differing only in the FourCC.
+/// Every (FourCC, size) `dev` advertises, with the frame rates offered at
+/// each. Stepwise ranges are reduced to the point closest to `request`.
+/// Empty if the driver doesn't implement enumeration.
+#[cfg(feature = "v4l2")]
+fn enumerate_modes(dev: &v4l::Device, request: &CaptureMode) -> Vec<mode::DeviceMode> {
+    use v4l::frameinterval::FrameIntervalEnum;
+    use v4l::framesize::FrameSizeEnum;
+    use v4l::video::Capture;
+
+    let mut modes = Vec::new();
+    for description in dev.enum_formats().unwrap_or_default() {
+        let fourcc = description.fourcc;
+        for size in dev.enum_framesizes(fourcc).unwrap_or_default() {
+            let (width, height) = match size.size {
+                FrameSizeEnum::Discrete(d) => (d.width, d.height),
+                FrameSizeEnum::Stepwise(s) => (
+                    mode::closest_in_range(request.width, s.min_width, s.max_width, s.step_width),
+                    mode::closest_in_range(
+                        request.height,
+                        s.min_height,
+                        s.max_height,
+                        s.step_height,
+                    ),
+                ),
+            };
+            let fps = dev
+                .enum_frameintervals(fourcc, width, height)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|interval| match interval.interval {
+                    FrameIntervalEnum::Discrete(f) => interval_to_fps(f),
+                    // The longest interval is the lowest rate
+                    FrameIntervalEnum::Stepwise(s) => Some(request.fps.clamp(
+                        interval_to_fps(s.max).unwrap_or(1),
+                        interval_to_fps(s.min).unwrap_or(request.fps).max(1),
+                    )),
+                })
+                .collect();
+            modes.push(mode::DeviceMode {
+                fourcc: fourcc.repr,
+                width,
+                height,
+                fps,
+            });
+        }
+    }
+    modes
+}
+
+/// Frames per second for a frame interval in seconds, rounded
+#[cfg(feature = "v4l2")]
+fn interval_to_fps(interval: v4l::Fraction) -> Option<u32> {
+    if interval.numerator == 0 {
+        return None;
+    }
+    Some((interval.denominator + interval.numerator / 2) / interval.numerator)
+}
+
+/// Opens `device_path` and negotiates the advertised mode closest to
+/// `request` (see [`mode::negotiate`]) — shared by every capture function
+/// here. Logs the mode the driver actually applied, which can still differ
+/// from the negotiated one: drivers are free to adjust `set_format`.
 #[cfg(feature = "v4l2")]
 fn open_and_configure(
     device_path: &str,
-    fourcc: &[u8; 4],
+    request: &CaptureMode,
 ) -> Result<(v4l::Device, v4l::Format), CameraError> {
     use v4l::video::Capture;
 
     let dev = v4l::Device::with_path(device_path)
         .map_err(|e| CameraError::NotAvailable(format!("{}: {}", device_path, e)))?;
 
+    if request.format == FrameFormat::MjPeg {
+        tracing::info!(
+            "{}: MJPEG requested, capturing uncompressed instead (no JPEG decoder)",
+            device_path
+        );
+    }
+    let modes = enumerate_modes(&dev, request);
+    let chosen = match mode::negotiate(request, &modes) {
+        Some(chosen) => chosen,
+        // Nothing usable advertised — or nothing advertised at all, as
+        // with some loopback drivers: ask for the request as-is and let
+        // set_format sort it out.
+        None => mode::NegotiatedMode {
+            fourcc: request.decodable_fourccs()[0],
+            width: request.width,
+            height: request.height,
+            fps: request.fps,
+        },
+    };
+
     let mut fmt = dev
         .format()
         .map_err(|e| CameraError::OpenFailed(e.to_string()))?;
-    fmt.width = 640;
-    fmt.height = 480;
-    fmt.fourcc = v4l::format::FourCC::new(fourcc);
+    fmt.width = chosen.width;
+    fmt.height = chosen.height;
+    fmt.fourcc = v4l::format::FourCC::new(&chosen.fourcc);
 
     let applied = dev.set_format(&fmt).map_err(|e| {
         CameraError::OpenFailed(format!(
             "set_format {}: {}",
-            String::from_utf8_lossy(fourcc),
+            String::from_utf8_lossy(&chosen.fourcc),
             e
         ))
     })?;
 
+    // Not every driver supports setting the frame rate; the stream still
+    // works at whatever rate it runs at.
+    let fps = dev
+        .set_params(&v4l::video::capture::Parameters::with_fps(
+            chosen.fps.max(1),
+        ))
+        .or_else(|_| dev.params())
+        .ok()
+        .and_then(|params| interval_to_fps(params.interval));
+
+    tracing::info!(
+        "Camera mode applied: {} {} {}x{} @ {} (requested {})",
+        device_path,
+        applied.fourcc,
+        applied.width,
+        applied.height,
+        fps.map_or_else(|| "? fps".to_string(), |fps| format!("{} fps", fps)),
+        request
+    );
+
     Ok((dev, applied))
 }
 
+/// Convert a frame in the applied format to RGB888 — YUYV or packed RGB3,
+/// the two formats [`CaptureMode::decodable_fourccs`] negotiates for RGB,
+/// or GREY, what it negotiates for `detection.preferred_format = "Gray8"`.
+#[cfg(feature = "v4l2")]
+fn to_rgb(data: &[u8], applied: &v4l::Format) -> Result<Vec<u8>, CameraError> {
+    match &applied.fourcc.repr {
+        b"YUYV" => Ok(yuyv_to_rgb_strided(
+            data,
+            applied.width,
+            applied.height,
+            applied.stride,
+        )),
+        b"RGB3" => Ok(rgb_strided(
+            data,
+            applied.width,
+            applied.height,
+            applied.stride,
+        )),
+        b"GREY" => Ok(gray_to_rgb_strided(
+            data,
+            applied.width,
+            applied.height,
+            applied.stride,
+        )),
+        _ => Err(CameraError::UnsupportedFormat(format!(
+            "driver applied {} instead of YUYV/RGB3/GREY",
+            applied.fourcc
+        ))),
+    }
+}
+
 /// Capture `num_frames` frames in GREY (8-bit grayscale) from a V4L2 device.
 ///
-/// Used for IR cameras (e.g. Logitech Brio infrared channel).
+/// Used for IR cameras (e.g. Logitech Brio infrared channel). `mode` is
+/// negotiated as grayscale whatever its `format` says.
 /// Callback: `on_frame(gray_data: Vec<u8>, width, height)`
 #[cfg(feature = "v4l2")]
 pub fn capture_gray_stream_v4l2<F>(
     device_path: &str,
+    mode: &CaptureMode,
     num_frames: u32,
     timeout_ms: u64,
     mut on_frame: F,
@@ -287,7 +453,13 @@ where
     use v4l::buffer::Type;
     use v4l::io::traits::CaptureStream;
 
-    let (dev, applied) = open_and_configure(device_path, b"GREY")?;
+    let (dev, applied) = open_and_configure(device_path, &mode.gray())?;
+    if &applied.fourcc.repr != b"GREY" {
+        return Err(CameraError::UnsupportedFormat(format!(
+            "driver applied {} instead of GREY",
+            applied.fourcc
+        )));
+    }
     let width = applied.width;
     let height = applied.height;
 
@@ -319,6 +491,7 @@ where
 #[cfg(feature = "v4l2")]
 pub fn capture_rgb_stream_v4l2<F>(
     device_path: &str,
+    mode: &CaptureMode,
     num_frames: u32,
     timeout_ms: u64,
     mut on_frame: F,
@@ -329,7 +502,7 @@ where
     use v4l::buffer::Type;
     use v4l::io::traits::CaptureStream;
 
-    let (dev, applied) = open_and_configure(device_path, b"YUYV")?;
+    let (dev, applied) = open_and_configure(device_path, mode)?;
     let width = applied.width;
     let height = applied.height;
 
@@ -349,7 +522,7 @@ where
             .next()
             .map_err(|e| CameraError::CaptureFailed(format!("Capture error: {}", e)))?;
 
-        let rgb = yuyv_to_rgb_strided(buf, width, height, applied.stride);
+        let rgb = to_rgb(buf, &applied)?;
         on_frame(rgb, width, height);
     }
 
@@ -374,6 +547,7 @@ where
 #[cfg(feature = "v4l2")]
 pub fn capture_rgb_stream_until<F>(
     device_path: &str,
+    mode: &CaptureMode,
     timeout_ms: u64,
     mut on_frame: F,
 ) -> Result<(), CameraError>
@@ -383,7 +557,7 @@ where
     use v4l::buffer::Type;
     use v4l::io::traits::CaptureStream;
 
-    let (dev, applied) = open_and_configure(device_path, b"YUYV")?;
+    let (dev, applied) = open_and_configure(device_path, mode)?;
     let width = applied.width;
     let height = applied.height;
 
@@ -399,7 +573,7 @@ where
     while start.elapsed() < deadline {
         match stream.next() {
             Ok((buf, _meta)) => {
-                let rgb = yuyv_to_rgb_strided(buf, width, height, applied.stride);
+                let rgb = to_rgb(buf, &applied)?;
                 if on_frame(rgb, width, height) {
                     return Ok(());
                 }
@@ -422,6 +596,40 @@ mod tests {
         assert_eq!(FrameFormat::Gray8.channels(), 1);
     }
 
+    #[cfg(feature = "v4l2")]
+    #[test]
+    fn test_rgb_frames_are_decoded_from_a_negotiated_gray8_mode() {
+        let request = CaptureMode {
+            width: 2,
+            height: 2,
+            fps: 30,
+            format: FrameFormat::Gray8,
+        };
+        let advertised = [mode::DeviceMode {
+            fourcc: *b"GREY",
+            width: 2,
+            height: 2,
+            fps: vec![30],
+        }];
+        let chosen = mode::negotiate(&request, &advertised).unwrap();
+        let mut applied = v4l::Format::new(
+            chosen.width,
+            chosen.height,
+            v4l::FourCC::new(&chosen.fourcc),
+        );
+        applied.stride = 4;
+
+        #[rustfmt::skip]
+        let frame = [
+            10u8, 20, 0, 0, // row 0, then 2 padding bytes
+            30u8, 40, 0, 0, // row 1
+        ];
+        assert_eq!(
+            to_rgb(&frame, &applied).unwrap(),
+            vec![10, 10, 10, 20, 20, 20, 30, 30, 30, 40, 40, 40]
+        );
+    }
+
     #[test]
     fn test_yuyv_to_rgb_strided_gray_input_is_gray_output() {
         // Y=128, U=128, V=128 -> u=v=0 -> R=G=B=Y for both pixels.
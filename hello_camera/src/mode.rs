@@ -0,0 +1,241 @@
+//! Capture mode negotiation
+//!
+//! A [`CaptureMode`] is what the configuration asks for (`[detection]`
+//! width/height/fps/preferred_format); a camera rarely offers exactly that.
+//! [`negotiate`] picks, among the modes the device advertises, the one
+//! closest to the request — size first, then frame rate, then the pixel
+//! formats this crate can decode in order of preference — so a 1280x720
+//! request on a camera that only does 1280x800 still gets an HD stream
+//! rather than an error. The V4L2 side (enumeration, `set_format`,
+//! `set_params`) lives in `open_and_configure`; everything here is pure so
+//! it can be tested without a camera.
+
+use crate::FrameFormat;
+
+/// Requested capture mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureMode {
+    /// Width in pixels
+    pub width: u32,
+
+    /// Height in pixels
+    pub height: u32,
+
+    /// Frames per second
+    pub fps: u32,
+
+    /// Format frames are delivered in. `MjPeg` is accepted but, with no
+    /// JPEG decoder here, negotiated down to an uncompressed format.
+    pub format: FrameFormat,
+}
+
+impl Default for CaptureMode {
+    /// 640x480 RGB at 30 fps — what every capture used before modes were
+    /// configurable
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+            fps: 30,
+            format: FrameFormat::Rgb8,
+        }
+    }
+}
+
+impl CaptureMode {
+    /// Same size and rate, delivered as 8-bit grayscale (IR cameras)
+    pub fn gray(self) -> Self {
+        Self {
+            format: FrameFormat::Gray8,
+            ..self
+        }
+    }
+
+    /// V4L2 FourCCs this crate can turn into `format`, best first
+    pub fn decodable_fourccs(&self) -> &'static [[u8; 4]] {
+        const RGB: &[[u8; 4]] = &[*b"YUYV", *b"RGB3"];
+        const GRAY: &[[u8; 4]] = &[*b"GREY"];
+        match self.format {
+            FrameFormat::Rgb8 | FrameFormat::MjPeg => RGB,
+            FrameFormat::Gray8 => GRAY,
+        }
+    }
+}
+
+impl std::fmt::Display for CaptureMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self.format {
+            FrameFormat::Rgb8 => "RGB8",
+            FrameFormat::Gray8 => "Gray8",
+            FrameFormat::MjPeg => "MJPEG",
+        };
+        write!(
+            f,
+            "{} {}x{} @ {} fps",
+            format, self.width, self.height, self.fps
+        )
+    }
+}
+
+/// One frame size a device offers in one pixel format, with the frame
+/// rates it offers at that size (empty if the driver doesn't say)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceMode {
+    pub fourcc: [u8; 4],
+    pub width: u32,
+    pub height: u32,
+    pub fps: Vec<u32>,
+}
+
+/// The mode [`negotiate`] settled on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegotiatedMode {
+    pub fourcc: [u8; 4],
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+/// Pick the advertised mode closest to `request`, or `None` if the device
+/// offers none of the formats `request` can be decoded from.
+///
+/// Distance is compared as (size, frame rate, format preference): the
+/// difference in width plus the difference in height, then how far the
+/// nearest advertised rate is from the requested one. A mode that lists no
+/// rates keeps the requested rate and leaves it to the driver.
+pub fn negotiate(request: &CaptureMode, modes: &[DeviceMode]) -> Option<NegotiatedMode> {
+    let fourccs = request.decodable_fourccs();
+    modes
+        .iter()
+        .filter_map(|mode| {
+            let preference = fourccs.iter().position(|f| *f == mode.fourcc)?;
+            let fps = mode
+                .fps
+                .iter()
+                .copied()
+                .min_by_key(|fps| fps.abs_diff(request.fps))
+                .unwrap_or(request.fps);
+            let size_distance =
+                mode.width.abs_diff(request.width) + mode.height.abs_diff(request.height);
+            let chosen = NegotiatedMode {
+                fourcc: mode.fourcc,
+                width: mode.width,
+                height: mode.height,
+                fps,
+            };
+            Some((
+                (size_distance, fps.abs_diff(request.fps), preference),
+                chosen,
+            ))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, chosen)| chosen)
+}
+
+/// The value of `min..=max` in steps of `step` closest to `requested` —
+/// for drivers that advertise stepwise (continuous) sizes or rates instead
+/// of a list.
+pub fn closest_in_range(requested: u32, min: u32, max: u32, step: u32) -> u32 {
+    let clamped = requested.clamp(min, max.max(min));
+    let step = step.max(1);
+    let below = min + (clamped - min) / step * step;
+    let above = below.saturating_add(step);
+    if above <= max && above - clamped < clamped - below {
+        above
+    } else {
+        below
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(fourcc: &[u8; 4], width: u32, height: u32, fps: &[u32]) -> DeviceMode {
+        DeviceMode {
+            fourcc: *fourcc,
+            width,
+            height,
+            fps: fps.to_vec(),
+        }
+    }
+
+    fn request(width: u32, height: u32, fps: u32) -> CaptureMode {
+        CaptureMode {
+            width,
+            height,
+            fps,
+            format: FrameFormat::Rgb8,
+        }
+    }
+
+    #[test]
+    fn test_negotiate_takes_an_exact_match() {
+        let modes = [
+            mode(b"YUYV", 640, 480, &[30, 15]),
+            mode(b"YUYV", 1280, 720, &[10, 5]),
+        ];
+        let chosen = negotiate(&request(1280, 720, 10), &modes).unwrap();
+        assert_eq!((chosen.width, chosen.height, chosen.fps), (1280, 720, 10));
+    }
+
+    #[test]
+    fn test_negotiate_picks_the_closest_size_then_the_closest_rate() {
+        let modes = [
+            mode(b"YUYV", 640, 480, &[30]),
+            mode(b"YUYV", 1280, 800, &[10, 5]),
+            mode(b"YUYV", 1920, 1080, &[5]),
+        ];
+        let chosen = negotiate(&request(1280, 720, 30), &modes).unwrap();
+        assert_eq!((chosen.width, chosen.height, chosen.fps), (1280, 800, 10));
+    }
+
+    #[test]
+    fn test_negotiate_skips_formats_it_cannot_decode() {
+        // MJPEG offers the exact size, but only YUYV can be decoded here
+        let modes = [
+            mode(b"MJPG", 1280, 720, &[30]),
+            mode(b"YUYV", 640, 480, &[30]),
+        ];
+        let chosen = negotiate(&request(1280, 720, 30), &modes).unwrap();
+        assert_eq!(&chosen.fourcc, b"YUYV");
+        assert_eq!((chosen.width, chosen.height), (640, 480));
+
+        let mjpeg_only = [mode(b"MJPG", 640, 480, &[30])];
+        assert_eq!(negotiate(&request(640, 480, 30), &mjpeg_only), None);
+    }
+
+    #[test]
+    fn test_negotiate_prefers_yuyv_over_rgb3_at_the_same_size() {
+        let modes = [
+            mode(b"RGB3", 640, 480, &[30]),
+            mode(b"YUYV", 640, 480, &[30]),
+        ];
+        let chosen = negotiate(&request(640, 480, 30), &modes).unwrap();
+        assert_eq!(&chosen.fourcc, b"YUYV");
+    }
+
+    #[test]
+    fn test_negotiate_gray_only_accepts_grey() {
+        let modes = [mode(b"YUYV", 640, 480, &[30]), mode(b"GREY", 340, 340, &[])];
+        let chosen = negotiate(&CaptureMode::default().gray(), &modes).unwrap();
+        assert_eq!(&chosen.fourcc, b"GREY");
+        // No advertised rates: the requested one is passed through
+        assert_eq!(chosen.fps, 30);
+    }
+
+    #[test]
+    fn test_closest_in_range_clamps_and_rounds_to_the_step() {
+        assert_eq!(closest_in_range(1280, 160, 1920, 16), 1280);
+        assert_eq!(closest_in_range(1000, 160, 1920, 16), 992);
+        assert_eq!(closest_in_range(1006, 160, 1920, 16), 1008);
+        assert_eq!(closest_in_range(4000, 160, 1920, 16), 1920);
+        assert_eq!(closest_in_range(10, 160, 1920, 16), 160);
+        assert_eq!(closest_in_range(30, 1, 60, 0), 30);
+    }
+
+    #[test]
+    fn test_capture_mode_display() {
+        assert_eq!(CaptureMode::default().to_string(), "RGB8 640x480 @ 30 fps");
+    }
+}
@@ -0,0 +1,368 @@
+//! Editing a config file in place (`linux-hello config set/unset`)
+//!
+//! Loading goes through `toml`, which drops comments and formatting; a
+//! file edited that way would lose every explanation the example config
+//! ships with. [`ConfigDocument`] uses `toml_edit` instead, so a `set`
+//! changes one value and leaves every other byte of the file — comments,
+//! ordering, blank lines, a trailing comment on the value itself — as it
+//! was.
+//!
+//! Every change is checked with [`Config::check_text`] before it's kept,
+//! the same type and range checks the daemon applies when it loads the
+//! file, so a `set` can't save something the daemon would then refuse to
+//! start with.
+
+use std::path::{Path, PathBuf};
+
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::{policy, Config, ConfigError};
+
+/// Mode a config file [`ConfigDocument::save`] creates gets: everyone
+/// reads the configuration, as the daemon and the PAM module need to
+const NEW_FILE_MODE: u32 = 0o644;
+
+/// One config file, parsed with its formatting kept
+#[derive(Debug, Clone)]
+pub struct ConfigDocument {
+    path: PathBuf,
+    document: DocumentMut,
+}
+
+impl ConfigDocument {
+    /// Read `path`. A missing file is an empty document — `set` on it
+    /// creates the file when saved.
+    pub fn open(path: &Path) -> Result<Self, ConfigError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(source) => {
+                return Err(ConfigError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        Self::parse(path, &text)
+    }
+
+    /// Parse `text` as the contents of `path`
+    pub fn parse(path: &Path, text: &str) -> Result<Self, ConfigError> {
+        let document = text
+            .parse::<DocumentMut>()
+            .map_err(|e| ConfigError::Parse {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            document,
+        })
+    }
+
+    /// File this document was read from (and is saved to)
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The value of a dotted key as written in this file (e.g. `0.7`,
+    /// `"/dev/video2"`), or `None` if the file doesn't set it.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let mut item = self.document.as_item();
+        for segment in key.split('.') {
+            item = item.as_table_like()?.get(segment)?;
+        }
+        let mut value = item.as_value()?.clone();
+        value.decor_mut().clear();
+        Some(value.to_string())
+    }
+
+    /// Set a dotted key. `raw` is read as a TOML value (`0.7`, `true`,
+    /// `"text"`); anything that isn't one — `RGB8`, `/dev/video2` — is
+    /// taken as a string, as is everything for a key whose type is a
+    /// string. Missing tables are created. The file as a whole must still
+    /// pass [`Config::check_text`], otherwise it's left unchanged.
+    pub fn set(&mut self, key: &str, raw: &str) -> Result<(), ConfigError> {
+        let known = policy::known_keys();
+        if !policy::is_known_key(&known, key) {
+            return Err(ConfigError::UnknownKey(key.to_string()));
+        }
+        let (parents, leaf) = match key.rsplit_once('.') {
+            Some((parents, leaf)) => (parents.split('.').collect::<Vec<_>>(), leaf),
+            None => (Vec::new(), key),
+        };
+
+        let value = if expects_string(key) {
+            toml_edit::Value::from(raw)
+        } else {
+            raw.parse::<toml_edit::Value>()
+                .unwrap_or_else(|_| toml_edit::Value::from(raw))
+        };
+
+        let previous = self.document.clone();
+        let result = insert_value(&mut self.document, &parents, leaf, value)
+            .map_err(|segment| ConfigError::Parse {
+                path: self.path.clone(),
+                message: format!("`{segment}` is not a table"),
+            })
+            .and_then(|()| self.check());
+        if result.is_err() {
+            self.document = previous;
+        }
+        result
+    }
+
+    /// Remove a dotted key from this file, so the next layer down (or the
+    /// default) applies again. Returns whether the file set it.
+    pub fn unset(&mut self, key: &str) -> bool {
+        let (parents, leaf) = match key.rsplit_once('.') {
+            Some((parents, leaf)) => (parents.split('.').collect::<Vec<_>>(), leaf),
+            None => (Vec::new(), key),
+        };
+        let mut table: &mut dyn TableLike = self.document.as_table_mut();
+        for segment in parents {
+            table = match table.get_mut(segment).and_then(Item::as_table_like_mut) {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+        table.remove(leaf).is_some()
+    }
+
+    /// Type-check and range-check this file as the daemon would.
+    pub fn check(&self) -> Result<(), ConfigError> {
+        Config::check_text(&self.path, &self.document.to_string()).map(|_| ())
+    }
+
+    /// Write the document back to [`ConfigDocument::path`], creating its
+    /// directory if needed. Written to a temporary file in the same
+    /// directory, with the original's permissions (`0644` for a new file,
+    /// whatever the umask), synced and renamed over the original — the
+    /// daemon's file watcher never reads half a file, and a crash leaves
+    /// either the old file or the new one.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let io_error = |source| ConfigError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir).map_err(io_error)?;
+        let mode = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.permissions().mode() & 0o7777,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => NEW_FILE_MODE,
+            Err(e) => return Err(io_error(e)),
+        };
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp)
+            .and_then(|mut file| {
+                // Not left to the umask, nor to a stale temp file's mode
+                file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+                file.write_all(self.document.to_string().as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&temp, &self.path))
+            .and_then(|()| std::fs::File::open(dir)?.sync_all())
+            .map_err(io_error)
+    }
+}
+
+impl std::fmt::Display for ConfigDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+/// Insert `value` at `parents.leaf`, creating missing tables as implicit
+/// (no header of their own until they hold a value). Fails with the
+/// segment that exists but isn't a table.
+fn insert_value<'a>(
+    document: &mut DocumentMut,
+    parents: &[&'a str],
+    leaf: &str,
+    mut value: toml_edit::Value,
+) -> Result<(), &'a str> {
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for segment in parents {
+        if table.get(segment).is_none() {
+            let mut child = toml_edit::Table::new();
+            child.set_implicit(true);
+            table.insert(segment, Item::Table(child));
+        }
+        table = table
+            .get_mut(segment)
+            .and_then(Item::as_table_like_mut)
+            .ok_or(*segment)?;
+    }
+    // Replaced through `get_mut` rather than `insert` so the key keeps its
+    // own decor (comment lines above it), and the value keeps a trailing
+    // `# comment`.
+    match table.get_mut(leaf) {
+        Some(item) => {
+            if let Some(old) = item.as_value() {
+                *value.decor_mut() = old.decor().clone();
+            }
+            *item = Item::Value(value);
+        }
+        None => {
+            table.insert(leaf, Item::Value(value));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `key` holds a string (or a string-named enum) in [`Config`] —
+/// its raw value is then never parsed, so `camera_device = 2` can't
+/// happen by accident.
+fn expects_string(key: &str) -> bool {
+    let config = Config::with_options_set();
+    matches!(config.value(key), Some(toml::Value::String(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMENTED: &str = "\
+# My settings
+[recognition]
+# Stricter than the default
+similarity_threshold = 0.65   # raised after a false accept
+
+[detection]
+width = 640
+";
+
+    fn document(text: &str) -> ConfigDocument {
+        ConfigDocument::parse(Path::new("config.toml"), text).unwrap()
+    }
+
+    #[test]
+    fn set_replaces_one_value_and_keeps_every_comment() {
+        let mut doc = document(COMMENTED);
+        doc.set("recognition.similarity_threshold", "0.7").unwrap();
+
+        assert_eq!(
+            doc.to_string(),
+            COMMENTED.replace("= 0.65   #", "= 0.7   #")
+        );
+        assert_eq!(
+            doc.get("recognition.similarity_threshold").as_deref(),
+            Some("0.7")
+        );
+    }
+
+    #[test]
+    fn set_creates_missing_tables_and_quotes_strings() {
+        let mut doc = document("");
+        doc.set("detection.camera_device", "/dev/video2").unwrap();
+        doc.set("detection.preferred_format", "MJPEG").unwrap();
+        doc.set("recognition.contexts.sudo.similarity_threshold", "0.75")
+            .unwrap();
+
+        let text = doc.to_string();
+        let parsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(
+            parsed.detection.camera_device.as_deref(),
+            Some("/dev/video2")
+        );
+        assert_eq!(parsed.detection.preferred_format, crate::PixelFormat::Mjpeg);
+        assert_eq!(
+            parsed.recognition.contexts["sudo"].similarity_threshold,
+            Some(0.75)
+        );
+        // No empty `[recognition]` header for the implicit parent table
+        assert!(!text.contains("[recognition]\n"), "{text}");
+    }
+
+    #[test]
+    fn set_refuses_unknown_keys_and_invalid_values_without_changing_anything() {
+        let mut doc = document(COMMENTED);
+
+        assert!(matches!(
+            doc.set("recognition.similarity_treshold", "0.7"),
+            Err(ConfigError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            doc.set("recognition.similarity_threshold", "1.5"),
+            Err(ConfigError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            doc.set("detection.width", "wide"),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert_eq!(doc.to_string(), COMMENTED);
+    }
+
+    #[test]
+    fn unset_removes_only_the_key() {
+        let mut doc = document(COMMENTED);
+        assert!(doc.unset("detection.width"));
+        assert!(!doc.unset("detection.width"));
+        assert!(!doc.unset("backup.backup_path"));
+        assert_eq!(doc.get("detection.width"), None);
+        assert!(doc.to_string().contains("# raised after a false accept"));
+    }
+
+    #[test]
+    fn save_creates_the_directory_and_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("linux-hello").join("config.toml");
+
+        let mut doc = ConfigDocument::open(&path).unwrap();
+        doc.set("capture.enrollment_samples", "7").unwrap();
+        doc.save().unwrap();
+
+        let reopened = ConfigDocument::open(&path).unwrap();
+        assert_eq!(
+            reopened.get("capture.enrollment_samples").as_deref(),
+            Some("7")
+        );
+        let loaded = Config::load_from(&[path]).unwrap();
+        assert_eq!(loaded.config.capture.enrollment_samples, 7);
+    }
+
+    #[test]
+    fn save_keeps_the_original_permissions_and_leaves_no_temp_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+
+        let mut doc = ConfigDocument::open(&path).unwrap();
+        doc.set("detection.width", "1280").unwrap();
+        doc.save().unwrap();
+        assert_eq!(mode(&path), NEW_FILE_MODE);
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let mut doc = ConfigDocument::open(&path).unwrap();
+        doc.set("detection.width", "640").unwrap();
+        doc.save().unwrap();
+        assert_eq!(mode(&path), 0o640);
+        assert_eq!(
+            ConfigDocument::open(&path)
+                .unwrap()
+                .get("detection.width")
+                .as_deref(),
+            Some("640")
+        );
+        let entries: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![std::ffi::OsString::from("config.toml")]);
+    }
+}
@@ -0,0 +1,1416 @@
+//! Typed configuration for Linux Hello
+//!
+//! Loads `linux-hello.config.toml.example`'s documented format from two
+//! layers — the system-wide `/etc/linux-hello/config.toml` first, then the
+//! user's own `~/.config/linux-hello/config.toml` on top — fills in
+//! defaults for every key neither file sets, and validates the result.
+//!
+//! An administrator policy (`/etc/linux-hello/policy.toml`, see [`policy`])
+//! is applied last: it overrides both files and locks the keys it names
+//! against the per-user one.
+//!
+//! Every default below matches the value the daemon hard-coded before this
+//! crate existed, so a machine with no config file at all behaves exactly
+//! as it did before.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub mod edit;
+pub mod policy;
+pub mod schema;
+
+pub use edit::ConfigDocument;
+pub use policy::{LockedKeyOverride, Policy, POLICY_PATH};
+
+/// System-wide configuration file, read before (and overridden by) the
+/// per-user one.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/linux-hello/config.toml";
+
+/// Lowest similarity threshold any context can be configured to. Below
+/// this, cosine similarity between two *different* people's ArcFace
+/// embeddings is no longer reliably rejected — a config asking for less is
+/// a range error here, and `hello_daemon::matcher::FaceMatcher` clamps to
+/// it again in case a `Config` was built some other way.
+pub const MIN_SIMILARITY_THRESHOLD: f32 = 0.50;
+
+/// Longest `max_template_age_days` a config may ask for (about ten years).
+pub const MAX_TEMPLATE_AGE_DAYS: u32 = 3650;
+
+/// Per-user configuration file, relative to `$XDG_CONFIG_HOME`.
+const USER_CONFIG_RELATIVE_PATH: &str = "linux-hello/config.toml";
+
+/// Configuration errors
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Cannot read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid configuration in {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+
+    #[error("Invalid configuration: {}", join_range_errors(.0))]
+    OutOfRange(Vec<RangeError>),
+
+    #[error("Unknown configuration key `{0}`")]
+    UnknownKey(String),
+}
+
+fn join_range_errors(errors: &[RangeError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// A single key whose value parsed fine but falls outside what the daemon
+/// accepts (e.g. a similarity threshold of `1.5`).
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{key} = {value} is out of range (expected {expected})")]
+pub struct RangeError {
+    /// Dotted key path, e.g. `recognition.similarity_threshold`
+    pub key: String,
+    /// The offending value, as written
+    pub value: String,
+    /// Human-readable description of the accepted range
+    pub expected: String,
+}
+
+/// A key present in a config file that this version doesn't recognize —
+/// most often a typo (`similarity_treshold`), which would otherwise be
+/// silently ignored and leave the default in effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownKey {
+    /// File the key was found in
+    pub path: PathBuf,
+    /// Dotted key path, e.g. `recognition.similarity_treshold`
+    pub key: String,
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown key `{}` in {}", self.key, self.path.display())
+    }
+}
+
+/// Result of loading the layered configuration
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    /// The effective, validated configuration
+    pub config: Config,
+    /// Files that actually existed and were read, lowest precedence first
+    pub sources: Vec<PathBuf>,
+    /// Unrecognized keys found along the way — reported, not fatal
+    pub unknown_keys: Vec<UnknownKey>,
+    /// Administrator policy that was applied (empty if none exists)
+    pub policy: Policy,
+    /// Keys the user's file set but the policy locks — dropped, reported
+    pub locked_overrides: Vec<LockedKeyOverride>,
+    /// Layer each explicitly set key (dotted, leaves only) took its
+    /// effective value from. Keys no file sets aren't listed — see
+    /// [`LoadedConfig::origin`].
+    pub origins: BTreeMap<String, ValueOrigin>,
+}
+
+/// Where an effective configuration value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueOrigin {
+    /// No file sets it: the built-in default
+    Default,
+    /// The system-wide config file
+    System(PathBuf),
+    /// The user's own config file
+    User(PathBuf),
+    /// The administrator policy (the key is locked, too)
+    Policy(PathBuf),
+}
+
+impl std::fmt::Display for ValueOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueOrigin::Default => write!(f, "default"),
+            ValueOrigin::System(path) => write!(f, "system ({})", path.display()),
+            ValueOrigin::User(path) => write!(f, "user ({})", path.display()),
+            ValueOrigin::Policy(path) => write!(f, "policy ({})", path.display()),
+        }
+    }
+}
+
+impl LoadedConfig {
+    /// Layer `key`'s effective value came from
+    pub fn origin(&self, key: &str) -> ValueOrigin {
+        self.origins
+            .get(key)
+            .cloned()
+            .unwrap_or(ValueOrigin::Default)
+    }
+
+    /// Every effective leaf value with its dotted key and origin, in key
+    /// order. Unset optional keys (`detection.camera_device`, ...) have no
+    /// value and aren't listed.
+    pub fn effective_values(&self) -> Vec<(String, toml::Value, ValueOrigin)> {
+        self.config
+            .leaf_values()
+            .into_iter()
+            .map(|(key, value)| {
+                let origin = self.origin(&key);
+                (key, value, origin)
+            })
+            .collect()
+    }
+}
+
+/// Complete Linux Hello configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Which authentication contexts face auth is offered for
+    pub authentication: AuthenticationConfig,
+    /// Camera selection and capture mode
+    pub detection: DetectionConfig,
+    /// Recognition thresholds
+    pub recognition: RecognitionConfig,
+    /// Capture and enrollment settings
+    pub capture: CaptureConfig,
+    /// User-facing behavior preferences
+    pub behavior: BehaviorConfig,
+    /// Daemon/PAM plumbing
+    pub advanced: AdvancedConfig,
+    /// Feature flags for integrations that aren't implemented yet
+    pub features: FeaturesConfig,
+    /// Backup settings (not acted on yet)
+    pub backup: BackupConfig,
+    /// Development-only switches
+    pub debug: DebugConfig,
+}
+
+/// `[authentication]` — per-context off switches. Whether a service uses
+/// face auth at all is still decided by its `/etc/pam.d` line; these only
+/// let it be turned off for one context without editing that file, so
+/// they all default to `true` (a `false` default would silently disable
+/// the `polkit`/`sddm` lines `install-pam.sh` sets up).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthenticationConfig {
+    pub enable_for_login: bool,
+    pub enable_for_sudo: bool,
+    pub enable_for_screenlock: bool,
+    pub enable_for_sddm: bool,
+    pub enable_for_polkit: bool,
+    /// Failed matches in a row, per user and context, before each further
+    /// attempt has to wait
+    pub backoff_after_failures: u32,
+    /// Wait after the first of those; it doubles with every failure after
+    pub backoff_base_secs: u32,
+    /// Longest wait between two attempts
+    pub backoff_max_secs: u32,
+    /// Failed matches in a row after which face auth stays off for the
+    /// context until a password authentication; 0 never locks
+    pub lockout_after_failures: u32,
+}
+
+impl AuthenticationConfig {
+    /// The `enable_for_*` key governing a PAM `context=` name, or `None`
+    /// for a custom context no flag covers.
+    pub fn enable_key(context: &str) -> Option<&'static str> {
+        match context {
+            "login" => Some("authentication.enable_for_login"),
+            "sudo" => Some("authentication.enable_for_sudo"),
+            "screenlock" => Some("authentication.enable_for_screenlock"),
+            "sddm" => Some("authentication.enable_for_sddm"),
+            "polkit" => Some("authentication.enable_for_polkit"),
+            _ => None,
+        }
+    }
+
+    /// Whether face auth is enabled for `context`, or `None` for a custom
+    /// context no flag covers.
+    pub fn is_enabled_for(&self, context: &str) -> Option<bool> {
+        match context {
+            "login" => Some(self.enable_for_login),
+            "sudo" => Some(self.enable_for_sudo),
+            "screenlock" => Some(self.enable_for_screenlock),
+            "sddm" => Some(self.enable_for_sddm),
+            "polkit" => Some(self.enable_for_polkit),
+            _ => None,
+        }
+    }
+}
+
+impl Default for AuthenticationConfig {
+    fn default() -> Self {
+        Self {
+            enable_for_login: true,
+            enable_for_sudo: true,
+            enable_for_screenlock: true,
+            enable_for_sddm: true,
+            enable_for_polkit: true,
+            backoff_after_failures: 3,
+            backoff_base_secs: 30,
+            backoff_max_secs: 900,
+            lockout_after_failures: 10,
+        }
+    }
+}
+
+/// Pixel format requested from the camera
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PixelFormat {
+    #[default]
+    #[serde(rename = "RGB8")]
+    Rgb8,
+    #[serde(rename = "Gray8")]
+    Gray8,
+    #[serde(rename = "MJPEG")]
+    Mjpeg,
+}
+
+impl PixelFormat {
+    /// Every format, in documentation order
+    pub const ALL: [PixelFormat; 3] = [PixelFormat::Rgb8, PixelFormat::Gray8, PixelFormat::Mjpeg];
+}
+
+/// `[detection]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionConfig {
+    /// RGB camera device. `None` (the default) auto-detects it by scanning
+    /// `/dev/video*`, same as before this setting existed.
+    pub camera_device: Option<String>,
+    pub preferred_format: PixelFormat,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            camera_device: None,
+            preferred_format: PixelFormat::Rgb8,
+            width: 640,
+            height: 480,
+            fps: 30,
+        }
+    }
+}
+
+/// `[recognition]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecognitionConfig {
+    /// Minimum detector confidence for a face to count as detected
+    pub confidence_threshold: f32,
+    /// Cosine similarity needed to accept a match, for any context without
+    /// its own entry in `contexts`
+    pub similarity_threshold: f32,
+    /// Accept frames containing more than one face during verification
+    pub allow_multiple_faces: bool,
+    /// How a probe is scored against the samples of one enrollment
+    pub fusion: FusionRule,
+    /// Samples averaged by [`FusionRule::TopKMean`]
+    pub fusion_top_k: u32,
+    /// Days after enrollment a face stops being accepted, for any context
+    /// without its own limit in `contexts`; 0 for no limit
+    pub max_template_age_days: u32,
+    /// How many days before a face expires the user is reminded to enroll
+    /// it again
+    pub expiry_warning_days: u32,
+    /// Per-context overrides, keyed by PAM `context=` name. Any name is
+    /// accepted, not just the built-in ones (`login`, `sudo`, ...), so a
+    /// custom PAM service line using e.g. `context=kiosk` can get its own
+    /// threshold too.
+    pub contexts: BTreeMap<String, ContextConfig>,
+}
+
+impl Default for RecognitionConfig {
+    fn default() -> Self {
+        Self {
+            confidence_threshold: 0.95,
+            similarity_threshold: 0.58,
+            allow_multiple_faces: false,
+            fusion: FusionRule::TopKMean,
+            fusion_top_k: 2,
+            max_template_age_days: 0,
+            expiry_warning_days: 14,
+            contexts: BTreeMap::new(),
+        }
+    }
+}
+
+impl RecognitionConfig {
+    /// Days after enrollment a face stops being accepted for `context`:
+    /// its own `contexts` entry, else `max_template_age_days`. `None` for no
+    /// limit.
+    pub fn max_template_age_days(&self, context: &str) -> Option<u32> {
+        let days = self
+            .contexts
+            .get(context)
+            .and_then(|c| c.max_template_age_days)
+            .unwrap_or(self.max_template_age_days);
+        (days > 0).then_some(days)
+    }
+
+    /// The shortest age limit any context has — how long until a face
+    /// stops being accepted somewhere. `None` if no context has one.
+    pub fn shortest_template_age_days(&self) -> Option<u32> {
+        std::iter::once(self.max_template_age_days)
+            .chain(
+                self.contexts
+                    .values()
+                    .filter_map(|c| c.max_template_age_days),
+            )
+            .filter(|days| *days > 0)
+            .min()
+    }
+}
+
+/// How the samples kept for one enrolled face are scored against a probe
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionRule {
+    /// The best-matching sample's score: most forgiving of pose changes
+    Max,
+    /// The mean of the `fusion_top_k` best-matching samples: one lucky
+    /// sample alone can't carry a match
+    #[default]
+    TopKMean,
+    /// The score against the average of all samples, as before samples
+    /// were kept
+    Centroid,
+}
+
+impl FusionRule {
+    /// Every rule, in documentation order
+    pub const ALL: [FusionRule; 3] = [FusionRule::Max, FusionRule::TopKMean, FusionRule::Centroid];
+}
+
+/// One `[recognition.contexts]` entry
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextConfig {
+    /// Overrides `recognition.similarity_threshold` for this context
+    pub similarity_threshold: Option<f32>,
+    pub require_confirmation: bool,
+    /// Overrides `recognition.max_template_age_days` for this context (0:
+    /// no limit here even if there is one globally)
+    pub max_template_age_days: Option<u32>,
+}
+
+/// `[capture]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// Capture timeout used when a request doesn't specify its own
+    pub default_timeout_ms: u64,
+    pub enrollment_samples: u32,
+    pub enrollment_quality_threshold: f32,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            default_timeout_ms: 5000,
+            enrollment_samples: 3,
+            enrollment_quality_threshold: 0.85,
+        }
+    }
+}
+
+/// Accepted `behavior.logging_level` values
+const LOGGING_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+/// `[behavior]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BehaviorConfig {
+    /// Fold confident matches back into the matched face (see
+    /// `hello_daemon::adaptive`), within the `adaptive_*` limits
+    pub auto_enroll_improvements: bool,
+    /// How far above the context's similarity threshold a match must score
+    /// to be folded in
+    pub adaptive_min_margin: f32,
+    /// Hours between two probes folded into the same face
+    pub adaptive_min_interval_hours: u32,
+    /// Similarity to the enrolled (averaged) embedding every folded-in
+    /// probe must keep — how far adaptation may drift from the enrollment
+    pub adaptive_min_enrollment_similarity: f32,
+    /// Folded-in probes kept per face; the oldest goes first
+    pub adaptive_max_samples: u32,
+    /// One of `trace`, `debug`, `info`, `warn`, `error`
+    pub logging_level: String,
+    pub allow_fallback_to_password: bool,
+}
+
+impl Default for BehaviorConfig {
+    fn default() -> Self {
+        Self {
+            auto_enroll_improvements: true,
+            adaptive_min_margin: 0.08,
+            adaptive_min_interval_hours: 24,
+            adaptive_min_enrollment_similarity: 0.80,
+            adaptive_max_samples: 5,
+            logging_level: "info".to_string(),
+            allow_fallback_to_password: true,
+        }
+    }
+}
+
+/// `[advanced]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdvancedConfig {
+    pub daemon_socket: String,
+    pub daemon_call_timeout_ms: u64,
+    pub pam_conversation_timeout_ms: u64,
+    /// Extra verify rounds the PAM module runs after a face isn't
+    /// recognized, before giving up and letting the password module ask
+    pub max_retries: u32,
+    /// Ceiling on the PAM module's wall time across all of those rounds
+    pub pam_total_timeout_ms: u64,
+}
+
+impl Default for AdvancedConfig {
+    fn default() -> Self {
+        Self {
+            daemon_socket: "system".to_string(),
+            daemon_call_timeout_ms: 10_000,
+            pam_conversation_timeout_ms: 8000,
+            max_retries: 3,
+            pam_total_timeout_ms: 30_000,
+        }
+    }
+}
+
+/// `[features]` — parsed so they aren't reported as unknown keys, but
+/// nothing reads them yet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeaturesConfig {
+    pub kdesu_integration: bool,
+    pub polkit_integration: bool,
+    pub sddm_ui_plugin: bool,
+    pub audit_logging: bool,
+}
+
+/// `[backup]` — parsed so they aren't reported as unknown keys, but
+/// nothing reads them yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub auto_backup: bool,
+    pub backup_path: Option<String>,
+    pub backup_frequency_days: u32,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            auto_backup: false,
+            backup_path: None,
+            backup_frequency_days: 30,
+        }
+    }
+}
+
+/// `[debug]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugConfig {
+    pub debug: bool,
+    /// Save the frames of failed verify attempts, with what was detected
+    /// and scored on each, to `dump_dir`
+    pub dump_frames: bool,
+    /// Save the embeddings extracted from failed verify attempts (alongside
+    /// the detections and scores, without the frames unless `dump_frames`)
+    pub dump_embeddings: bool,
+    /// Where dumps go. `None` (the default) is
+    /// `$XDG_STATE_HOME/linux-hello/dumps`.
+    pub dump_dir: Option<String>,
+    /// Oldest attempts are deleted beyond this many
+    pub dump_max_attempts: u32,
+    /// ...and beyond this much disk space in total
+    pub dump_max_size_mb: u32,
+    /// Let `linux-hello calibrate record` collect labelled match scores
+    /// (no frames, no embeddings) for threshold calibration
+    pub record_scores: bool,
+    pub mock_daemon: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            debug: false,
+            dump_frames: false,
+            dump_embeddings: false,
+            dump_dir: None,
+            dump_max_attempts: 20,
+            dump_max_size_mb: 200,
+            record_scores: false,
+            mock_daemon: false,
+        }
+    }
+}
+
+/// Keys that exist but have no value in [`Config::default`] (they're
+/// `Option`s); `None` means "work it out", as each one's doc says.
+pub const OPTIONAL_KEYS: &[&str] = &[
+    "detection.camera_device",
+    "backup.backup_path",
+    "debug.dump_dir",
+];
+
+/// Path of the current user's config file, if a config directory can be
+/// determined at all.
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(USER_CONFIG_RELATIVE_PATH))
+}
+
+/// Every file [`Config::load`] reads, in layering order: the system file,
+/// the user's file (when a config directory exists), then the policy. None
+/// of them has to exist — this is what a caller watching for edits polls.
+pub fn layer_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(SYSTEM_CONFIG_PATH)];
+    paths.extend(user_config_path());
+    paths.push(PathBuf::from(POLICY_PATH));
+    paths
+}
+
+impl Config {
+    /// Load the system file, then the current user's file on top of it,
+    /// then the administrator policy. Used by the per-user `hello-daemon`
+    /// and the CLI.
+    pub fn load() -> Result<LoadedConfig, ConfigError> {
+        Self::load_layered(
+            Path::new(SYSTEM_CONFIG_PATH),
+            user_config_path().as_deref(),
+            Path::new(POLICY_PATH),
+        )
+    }
+
+    /// Load the system file and the administrator policy only. Used by
+    /// `hello-daemon-system`, which runs as root before anyone logs in —
+    /// root's own `~/.config` has nothing to do with the login screen, so
+    /// it's deliberately not consulted.
+    pub fn load_system() -> Result<LoadedConfig, ConfigError> {
+        Self::load_layered(Path::new(SYSTEM_CONFIG_PATH), None, Path::new(POLICY_PATH))
+    }
+
+    /// Load and merge `paths` in order, later files overriding earlier ones
+    /// key by key (a user file setting only `recognition.similarity_threshold`
+    /// keeps every other `[recognition]` key from the system file). Missing
+    /// files are skipped; a file that exists but can't be read or parsed is
+    /// an error rather than silently falling back to defaults. No policy is
+    /// applied.
+    pub fn load_from(paths: &[PathBuf]) -> Result<LoadedConfig, ConfigError> {
+        Self::load_layers(paths, &[], Policy::default(), Vec::new())
+    }
+
+    /// Load `system`, then `user` with every key `policy_path` locks
+    /// removed, then the policy's own values on top of both. Missing files
+    /// are skipped, as in [`Config::load_from`].
+    pub fn load_layered(
+        system: &Path,
+        user: Option<&Path>,
+        policy_path: &Path,
+    ) -> Result<LoadedConfig, ConfigError> {
+        let (policy, unknown_keys) = Policy::load(policy_path)?;
+        let user: Vec<PathBuf> = user.map(Path::to_path_buf).into_iter().collect();
+        Self::load_layers(&[system.to_path_buf()], &user, policy, unknown_keys)
+    }
+
+    fn load_layers(
+        admin_paths: &[PathBuf],
+        user_paths: &[PathBuf],
+        policy: Policy,
+        policy_unknown_keys: Vec<UnknownKey>,
+    ) -> Result<LoadedConfig, ConfigError> {
+        let mut merged = toml::Table::new();
+        let mut sources = Vec::new();
+        let mut unknown_keys = Vec::new();
+        let mut locked_overrides = Vec::new();
+        let mut origins = BTreeMap::new();
+
+        let layers = admin_paths
+            .iter()
+            .map(|path| (path, false))
+            .chain(user_paths.iter().map(|path| (path, true)));
+        for (path, is_user) in layers {
+            let text = match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(source) => {
+                    return Err(ConfigError::Io {
+                        path: path.clone(),
+                        source,
+                    })
+                }
+            };
+            let mut table = parse_layer(path, &text, &mut unknown_keys)?;
+            if is_user {
+                policy.strip_locked(&mut table, path, &mut locked_overrides);
+            }
+            let origin = if is_user {
+                ValueOrigin::User(path.clone())
+            } else {
+                ValueOrigin::System(path.clone())
+            };
+            record_origins(&table, "", &origin, &mut origins);
+            merge_tables(&mut merged, table);
+            sources.push(path.clone());
+        }
+
+        // The policy's values were type-checked by `Policy::parse`; merging
+        // them last is what gives them precedence over both files.
+        merge_tables(&mut merged, policy.values().clone());
+        unknown_keys.extend(policy_unknown_keys);
+        if let Some(path) = policy.source() {
+            let origin = ValueOrigin::Policy(path.to_path_buf());
+            record_origins(policy.values(), "", &origin, &mut origins);
+            sources.push(path.to_path_buf());
+        }
+
+        // Each layer was already type-checked on its own by `parse_layer`,
+        // so a failure here can only come from the merge itself — still
+        // attributed to the highest-precedence file rather than unwrapped.
+        let config =
+            Config::deserialize(toml::Value::Table(merged)).map_err(|e| ConfigError::Parse {
+                path: sources.last().cloned().unwrap_or_default(),
+                message: e.to_string(),
+            })?;
+        config.validate()?;
+
+        Ok(LoadedConfig {
+            config,
+            sources,
+            unknown_keys,
+            policy,
+            locked_overrides,
+            origins,
+        })
+    }
+
+    /// Type-check and range-check the text of a single config file as if it
+    /// were the only layer, without touching disk — what `linux-hello
+    /// config set` runs before saving. Unknown keys are returned, not
+    /// fatal, as when loading.
+    pub fn check_text(path: &Path, text: &str) -> Result<Vec<UnknownKey>, ConfigError> {
+        let mut unknown_keys = Vec::new();
+        let table = parse_layer(path, text, &mut unknown_keys)?;
+        let config =
+            Config::deserialize(toml::Value::Table(table)).map_err(|e| ConfigError::Parse {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+        config.validate()?;
+        Ok(unknown_keys)
+    }
+
+    /// The defaults with every [`OPTIONAL_KEYS`] entry set to an empty
+    /// string, so walking its table sees every key there is.
+    pub(crate) fn with_options_set() -> Self {
+        let mut config = Config::default();
+        config.detection.camera_device = Some(String::new());
+        config.backup.backup_path = Some(String::new());
+        config.debug.dump_dir = Some(String::new());
+        config
+    }
+
+    /// The effective value of a dotted key — a scalar for a leaf, a table
+    /// for a section — or `None` if it doesn't exist or is an unset option.
+    pub fn value(&self, key: &str) -> Option<toml::Value> {
+        let mut value = toml::Value::Table(self.to_table()?);
+        for segment in key.split('.') {
+            value = match value {
+                toml::Value::Table(mut table) => table.remove(segment)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Every leaf value, keyed by its dotted path, in key order
+    pub fn leaf_values(&self) -> Vec<(String, toml::Value)> {
+        let mut out = Vec::new();
+        if let Some(table) = self.to_table() {
+            flatten_table(table, "", &mut out);
+        }
+        out
+    }
+
+    /// Serialize to a table, floats as they'd be written: every float here
+    /// is an `f32`, which would otherwise widen to `0.699999988079071`.
+    fn to_table(&self) -> Option<toml::Table> {
+        match toml::Value::try_from(self).ok()? {
+            toml::Value::Table(mut table) => {
+                shorten_floats(&mut table);
+                Some(table)
+            }
+            _ => None,
+        }
+    }
+
+    /// Check every numeric setting against the range the daemon accepts,
+    /// reporting all violations at once rather than just the first.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+
+        check_range(
+            &mut errors,
+            "authentication.backoff_after_failures",
+            self.authentication.backoff_after_failures,
+            1,
+            100,
+        );
+        check_range(
+            &mut errors,
+            "authentication.backoff_base_secs",
+            self.authentication.backoff_base_secs,
+            0,
+            3600,
+        );
+        check_range(
+            &mut errors,
+            "authentication.backoff_max_secs",
+            self.authentication.backoff_max_secs,
+            0,
+            86400,
+        );
+        check_range(
+            &mut errors,
+            "authentication.lockout_after_failures",
+            self.authentication.lockout_after_failures,
+            0,
+            1000,
+        );
+        check_unit_interval(
+            &mut errors,
+            "recognition.confidence_threshold",
+            self.recognition.confidence_threshold,
+        );
+        check_range(
+            &mut errors,
+            "recognition.similarity_threshold",
+            self.recognition.similarity_threshold,
+            MIN_SIMILARITY_THRESHOLD,
+            1.0,
+        );
+        for (name, context) in &self.recognition.contexts {
+            if let Some(threshold) = context.similarity_threshold {
+                check_range(
+                    &mut errors,
+                    &format!("recognition.contexts.{name}.similarity_threshold"),
+                    threshold,
+                    MIN_SIMILARITY_THRESHOLD,
+                    1.0,
+                );
+            }
+            if let Some(days) = context.max_template_age_days {
+                check_range(
+                    &mut errors,
+                    &format!("recognition.contexts.{name}.max_template_age_days"),
+                    days,
+                    0,
+                    MAX_TEMPLATE_AGE_DAYS,
+                );
+            }
+        }
+        check_unit_interval(
+            &mut errors,
+            "capture.enrollment_quality_threshold",
+            self.capture.enrollment_quality_threshold,
+        );
+
+        check_range(
+            &mut errors,
+            "detection.width",
+            self.detection.width,
+            1,
+            8192,
+        );
+        check_range(
+            &mut errors,
+            "detection.height",
+            self.detection.height,
+            1,
+            8192,
+        );
+        check_range(&mut errors, "detection.fps", self.detection.fps, 1, 240);
+        check_range(
+            &mut errors,
+            "capture.default_timeout_ms",
+            self.capture.default_timeout_ms,
+            100,
+            120_000,
+        );
+        check_range(
+            &mut errors,
+            "recognition.fusion_top_k",
+            self.recognition.fusion_top_k,
+            1,
+            20,
+        );
+        check_range(
+            &mut errors,
+            "recognition.max_template_age_days",
+            self.recognition.max_template_age_days,
+            0,
+            MAX_TEMPLATE_AGE_DAYS,
+        );
+        check_range(
+            &mut errors,
+            "recognition.expiry_warning_days",
+            self.recognition.expiry_warning_days,
+            1,
+            365,
+        );
+        check_range(
+            &mut errors,
+            "capture.enrollment_samples",
+            self.capture.enrollment_samples,
+            1,
+            20,
+        );
+        check_range(
+            &mut errors,
+            "advanced.daemon_call_timeout_ms",
+            self.advanced.daemon_call_timeout_ms,
+            100,
+            300_000,
+        );
+        check_range(
+            &mut errors,
+            "advanced.pam_conversation_timeout_ms",
+            self.advanced.pam_conversation_timeout_ms,
+            100,
+            300_000,
+        );
+        check_range(
+            &mut errors,
+            "advanced.max_retries",
+            self.advanced.max_retries,
+            0,
+            10,
+        );
+        check_range(
+            &mut errors,
+            "advanced.pam_total_timeout_ms",
+            self.advanced.pam_total_timeout_ms,
+            1000,
+            600_000,
+        );
+
+        check_range(
+            &mut errors,
+            "debug.dump_max_attempts",
+            self.debug.dump_max_attempts,
+            1,
+            1000,
+        );
+        check_range(
+            &mut errors,
+            "debug.dump_max_size_mb",
+            self.debug.dump_max_size_mb,
+            1,
+            100_000,
+        );
+
+        check_range(
+            &mut errors,
+            "behavior.adaptive_min_margin",
+            self.behavior.adaptive_min_margin,
+            0.0,
+            0.5,
+        );
+        check_range(
+            &mut errors,
+            "behavior.adaptive_min_interval_hours",
+            self.behavior.adaptive_min_interval_hours,
+            0,
+            720,
+        );
+        check_range(
+            &mut errors,
+            "behavior.adaptive_min_enrollment_similarity",
+            self.behavior.adaptive_min_enrollment_similarity,
+            MIN_SIMILARITY_THRESHOLD,
+            1.0,
+        );
+        check_range(
+            &mut errors,
+            "behavior.adaptive_max_samples",
+            self.behavior.adaptive_max_samples,
+            1,
+            20,
+        );
+
+        if !LOGGING_LEVELS.contains(&self.behavior.logging_level.as_str()) {
+            errors.push(RangeError {
+                key: "behavior.logging_level".to_string(),
+                value: format!("{:?}", self.behavior.logging_level),
+                expected: format!("one of {}", LOGGING_LEVELS.join(", ")),
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::OutOfRange(errors))
+        }
+    }
+}
+
+/// Parse one file into a raw table, type-checking it against `Config` on
+/// its own first so both unknown keys and type errors are attributed to
+/// the file they're actually in.
+fn parse_layer(
+    path: &Path,
+    text: &str,
+    unknown_keys: &mut Vec<UnknownKey>,
+) -> Result<toml::Table, ConfigError> {
+    let parse_error = |message: String| ConfigError::Parse {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let deserializer = toml::Deserializer::parse(text).map_err(|e| parse_error(e.to_string()))?;
+    let _: Config = serde_ignored::deserialize(deserializer, |key| {
+        unknown_keys.push(UnknownKey {
+            path: path.to_path_buf(),
+            key: key.to_string(),
+        });
+    })
+    .map_err(|e| parse_error(e.to_string()))?;
+
+    text.parse::<toml::Table>()
+        .map_err(|e| parse_error(e.to_string()))
+}
+
+/// Recursively overlay `overlay` onto `base`: nested tables are merged key
+/// by key, anything else (scalars, arrays) is replaced wholesale.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Record `origin` against every leaf `table` sets. Later layers call this
+/// after earlier ones, so the highest-precedence layer wins.
+fn record_origins(
+    table: &toml::Table,
+    prefix: &str,
+    origin: &ValueOrigin,
+    origins: &mut BTreeMap<String, ValueOrigin>,
+) {
+    for (key, value) in table {
+        let dotted = policy::join_key(prefix, key);
+        match value {
+            toml::Value::Table(child) => record_origins(child, &dotted, origin, origins),
+            _ => {
+                origins.insert(dotted, origin.clone());
+            }
+        }
+    }
+}
+
+fn shorten_floats(table: &mut toml::Table) {
+    for (_, value) in table.iter_mut() {
+        match value {
+            toml::Value::Table(child) => shorten_floats(child),
+            toml::Value::Float(float) => {
+                let shortened = (*float as f32).to_string().parse().unwrap_or(*float);
+                *value = toml::Value::Float(shortened);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn flatten_table(table: toml::Table, prefix: &str, out: &mut Vec<(String, toml::Value)>) {
+    for (key, value) in table {
+        let dotted = policy::join_key(prefix, &key);
+        match value {
+            toml::Value::Table(child) => flatten_table(child, &dotted, out),
+            value => out.push((dotted, value)),
+        }
+    }
+}
+
+fn check_unit_interval(errors: &mut Vec<RangeError>, key: &str, value: f32) {
+    if !(0.0..=1.0).contains(&value) {
+        errors.push(RangeError {
+            key: key.to_string(),
+            value: value.to_string(),
+            expected: "0.0 to 1.0".to_string(),
+        });
+    }
+}
+
+fn check_range<T>(errors: &mut Vec<RangeError>, key: &str, value: T, min: T, max: T)
+where
+    T: PartialOrd + std::fmt::Display,
+{
+    if value < min || value > max {
+        errors.push(RangeError {
+            key: key.to_string(),
+            value: value.to_string(),
+            expected: format!("{min} to {max}"),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &tempfile::TempDir, name: &str, contents: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn defaults_match_the_previously_hard_coded_values() {
+        let config = Config::default();
+        assert_eq!(config.capture.default_timeout_ms, 5000);
+        assert_eq!(config.recognition.similarity_threshold, 0.58);
+        assert_eq!(config.detection.camera_device, None);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn every_optional_key_is_unset_by_default_and_known() {
+        let with_options = Config::with_options_set();
+        for key in OPTIONAL_KEYS {
+            assert_eq!(Config::default().value(key), None, "{key}");
+            assert!(with_options.value(key).is_some(), "{key}");
+        }
+    }
+
+    #[test]
+    fn no_files_at_all_yields_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let loaded = Config::load_from(&[dir.path().join("missing.toml")]).unwrap();
+        assert_eq!(loaded.config, Config::default());
+        assert!(loaded.sources.is_empty());
+        assert!(loaded.unknown_keys.is_empty());
+    }
+
+    #[test]
+    fn the_shipped_example_file_parses_without_unknown_keys() {
+        let example = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("linux-hello.config.toml.example");
+        let loaded = Config::load_from(&[example]).unwrap();
+        assert!(loaded.unknown_keys.is_empty(), "{:?}", loaded.unknown_keys);
+        // Commented out, so copying the example doesn't pin /dev/video0
+        assert_eq!(loaded.config.detection.camera_device, None);
+        assert_eq!(loaded.config.detection.width, 640);
+        assert_eq!(
+            loaded.config.recognition.contexts["sudo"].similarity_threshold,
+            Some(0.70)
+        );
+        assert!(loaded.config.recognition.contexts["sudo"].require_confirmation);
+    }
+
+    #[test]
+    fn user_file_overrides_system_file_key_by_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = write(
+            &dir,
+            "system.toml",
+            "[recognition]\nsimilarity_threshold = 0.70\nallow_multiple_faces = true\n",
+        );
+        let user = write(
+            &dir,
+            "user.toml",
+            "[recognition]\nsimilarity_threshold = 0.65\n",
+        );
+
+        let loaded = Config::load_from(&[system.clone(), user.clone()]).unwrap();
+
+        assert_eq!(loaded.config.recognition.similarity_threshold, 0.65);
+        assert!(loaded.config.recognition.allow_multiple_faces);
+        assert_eq!(loaded.sources, vec![system, user]);
+    }
+
+    #[test]
+    fn policy_overrides_both_files_and_drops_locked_user_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = write(&dir, "system.toml", "[capture]\nenrollment_samples = 5\n");
+        let user = write(
+            &dir,
+            "user.toml",
+            "[authentication]\nenable_for_sudo = true\n\n\
+             [recognition]\nsimilarity_threshold = 0.52\n\n\
+             [capture]\nenrollment_samples = 1\ndefault_timeout_ms = 3000\n",
+        );
+        let policy = write(
+            &dir,
+            "policy.toml",
+            "locked = [\"capture.enrollment_samples\"]\n\n\
+             [authentication]\nenable_for_sudo = false\n\n\
+             [recognition]\nsimilarity_threshold = 0.65\n",
+        );
+
+        let loaded = Config::load_layered(&system, Some(&user), &policy).unwrap();
+
+        assert!(!loaded.config.authentication.enable_for_sudo);
+        assert_eq!(loaded.config.recognition.similarity_threshold, 0.65);
+        // Locked without a value: pinned at the system file's setting
+        assert_eq!(loaded.config.capture.enrollment_samples, 5);
+        // Not locked: the user's value still wins
+        assert_eq!(loaded.config.capture.default_timeout_ms, 3000);
+
+        let dropped: Vec<&str> = loaded
+            .locked_overrides
+            .iter()
+            .map(|o| o.key.as_str())
+            .collect();
+        assert_eq!(
+            dropped,
+            vec![
+                "authentication.enable_for_sudo",
+                "capture.enrollment_samples",
+                "recognition.similarity_threshold"
+            ]
+        );
+        assert!(loaded.locked_overrides.iter().all(|o| o.path == user));
+        assert_eq!(loaded.sources, vec![system, user, policy]);
+    }
+
+    #[test]
+    fn each_effective_value_reports_the_layer_it_came_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = write(
+            &dir,
+            "system.toml",
+            "[capture]\nenrollment_samples = 5\ndefault_timeout_ms = 2000\n",
+        );
+        let user = write(
+            &dir,
+            "user.toml",
+            "[capture]\ndefault_timeout_ms = 3000\n\n\
+             [recognition]\nsimilarity_threshold = 0.7\n",
+        );
+        let policy = write(
+            &dir,
+            "policy.toml",
+            "[recognition]\nsimilarity_threshold = 0.65\n",
+        );
+
+        let loaded = Config::load_layered(&system, Some(&user), &policy).unwrap();
+
+        assert_eq!(
+            loaded.origin("capture.enrollment_samples"),
+            ValueOrigin::System(system)
+        );
+        assert_eq!(
+            loaded.origin("capture.default_timeout_ms"),
+            ValueOrigin::User(user)
+        );
+        assert_eq!(
+            loaded.origin("recognition.similarity_threshold"),
+            ValueOrigin::Policy(policy)
+        );
+        assert_eq!(loaded.origin("detection.width"), ValueOrigin::Default);
+
+        let effective = loaded.effective_values();
+        let (_, value, origin) = effective
+            .iter()
+            .find(|(key, _, _)| key == "recognition.similarity_threshold")
+            .unwrap();
+        assert_eq!(value.to_string(), "0.65");
+        assert!(matches!(origin, ValueOrigin::Policy(_)));
+        // Unset options have no effective value to show
+        assert!(!effective
+            .iter()
+            .any(|(key, _, _)| key == "detection.camera_device"));
+    }
+
+    #[test]
+    fn check_text_applies_the_load_time_checks_to_one_file() {
+        let path = Path::new("config.toml");
+        assert!(Config::check_text(path, "[detection]\nwidth = 1280\n")
+            .unwrap()
+            .is_empty());
+        let unknown = Config::check_text(path, "[detection]\nwidht = 1280\n").unwrap();
+        assert_eq!(unknown[0].key, "detection.widht");
+        assert!(matches!(
+            Config::check_text(path, "[detection]\nfps = 0\n"),
+            Err(ConfigError::OutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn policy_values_are_validated_like_any_other_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = write(
+            &dir,
+            "policy.toml",
+            "[recognition]\nsimilarity_threshold = 0.2\n",
+        );
+        let result = Config::load_layered(&dir.path().join("system.toml"), None, &policy);
+        assert!(matches!(result, Err(ConfigError::OutOfRange(_))));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_file_but_not_fatal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            &dir,
+            "user.toml",
+            "[recognition]\nsimilarity_treshold = 0.9\n\n[nonsense]\nx = 1\n",
+        );
+
+        let loaded = Config::load_from(std::slice::from_ref(&path)).unwrap();
+
+        let keys: Vec<&str> = loaded.unknown_keys.iter().map(|k| k.key.as_str()).collect();
+        assert_eq!(keys, vec!["nonsense", "recognition.similarity_treshold"]);
+        assert!(loaded.unknown_keys.iter().all(|k| k.path == path));
+        assert_eq!(loaded.config.recognition.similarity_threshold, 0.58);
+    }
+
+    #[test]
+    fn out_of_range_values_are_all_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            &dir,
+            "user.toml",
+            "[recognition]\nsimilarity_threshold = 1.5\n\n[detection]\nfps = 0\n\n\
+             [behavior]\nlogging_level = \"loud\"\n",
+        );
+
+        let Err(ConfigError::OutOfRange(errors)) = Config::load_from(&[path]) else {
+            panic!("expected a range error");
+        };
+        let keys: Vec<&str> = errors.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "recognition.similarity_threshold",
+                "detection.fps",
+                "behavior.logging_level"
+            ]
+        );
+    }
+
+    #[test]
+    fn context_threshold_out_of_range_is_reported() {
+        let mut config = Config::default();
+        config.recognition.contexts.insert(
+            "sudo".to_string(),
+            ContextConfig {
+                similarity_threshold: Some(-0.1),
+                require_confirmation: false,
+                max_template_age_days: None,
+            },
+        );
+        let Err(ConfigError::OutOfRange(errors)) = config.validate() else {
+            panic!("expected a range error");
+        };
+        assert_eq!(
+            errors[0].key,
+            "recognition.contexts.sudo.similarity_threshold"
+        );
+    }
+
+    #[test]
+    fn similarity_thresholds_below_the_floor_are_rejected() {
+        let mut config = Config::default();
+        config.recognition.similarity_threshold = 0.3;
+        config.recognition.contexts.insert(
+            "screenlock".to_string(),
+            ContextConfig {
+                similarity_threshold: Some(0.49),
+                require_confirmation: false,
+                max_template_age_days: None,
+            },
+        );
+        config.recognition.contexts.insert(
+            "kiosk".to_string(),
+            ContextConfig {
+                similarity_threshold: Some(MIN_SIMILARITY_THRESHOLD),
+                require_confirmation: false,
+                max_template_age_days: None,
+            },
+        );
+
+        let Err(ConfigError::OutOfRange(errors)) = config.validate() else {
+            panic!("expected a range error");
+        };
+        let keys: Vec<&str> = errors.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "recognition.similarity_threshold",
+                "recognition.contexts.screenlock.similarity_threshold"
+            ]
+        );
+    }
+
+    #[test]
+    fn template_age_limits_fall_back_to_the_global_one() {
+        let mut config = Config::default();
+        assert_eq!(config.recognition.shortest_template_age_days(), None);
+
+        config.recognition.max_template_age_days = 365;
+        for (name, days) in [("sudo", Some(90)), ("screenlock", Some(0)), ("login", None)] {
+            config.recognition.contexts.insert(
+                name.to_string(),
+                ContextConfig {
+                    similarity_threshold: None,
+                    require_confirmation: false,
+                    max_template_age_days: days,
+                },
+            );
+        }
+
+        let recognition = &config.recognition;
+        assert_eq!(recognition.max_template_age_days("sudo"), Some(90));
+        assert_eq!(recognition.max_template_age_days("screenlock"), None);
+        assert_eq!(recognition.max_template_age_days("login"), Some(365));
+        assert_eq!(recognition.max_template_age_days("other"), Some(365));
+        assert_eq!(recognition.shortest_template_age_days(), Some(90));
+    }
+
+    #[test]
+    fn wrong_type_names_the_offending_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "user.toml", "[detection]\nwidth = \"wide\"\n");
+
+        match Config::load_from(std::slice::from_ref(&path)) {
+            Err(ConfigError::Parse { path: p, .. }) => assert_eq!(p, path),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_pixel_format_is_a_parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            &dir,
+            "user.toml",
+            "[detection]\npreferred_format = \"YUV\"\n",
+        );
+        assert!(matches!(
+            Config::load_from(&[path]),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+}
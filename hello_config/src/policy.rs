@@ -0,0 +1,324 @@
+//! Administrator policy (`/etc/linux-hello/policy.toml`)
+//!
+//! `config.toml` is a preference: the per-user file overrides the system
+//! one, so on a shared or managed machine any user can lower their own
+//! similarity threshold or turn face auth on for `sudo`. The policy file is
+//! the layer above both that a user can't touch:
+//!
+//! ```toml
+//! # Keys kept at their system-config (or built-in default) value. Must come
+//! # before the first [table] header, like any top-level TOML key.
+//! locked = ["recognition.contexts", "capture.enrollment_samples"]
+//!
+//! # Every key set below is enforced AND locked.
+//! [authentication]
+//! enable_for_sudo = false
+//!
+//! [recognition]
+//! similarity_threshold = 0.65
+//! ```
+//!
+//! Locking a table (`recognition.contexts`) locks every key under it. A
+//! locked key set in the user's file is dropped while loading (and
+//! reported, see [`LockedKeyOverride`]), never merged. Liveness gating has
+//! no config knob at all — it can't be disabled from any file, so there's
+//! nothing to lock.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::{Config, ConfigError, UnknownKey};
+
+/// Administrator policy file, applied after (and taking precedence over)
+/// both config files.
+pub const POLICY_PATH: &str = "/etc/linux-hello/policy.toml";
+
+/// Top-level policy key listing keys to lock without setting them.
+const LOCKED_KEY: &str = "locked";
+
+/// A key set in a user's config file that the policy locks — dropped, so
+/// the system/policy value stays in effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedKeyOverride {
+    /// File the key was found in
+    pub path: PathBuf,
+    /// Dotted key path, e.g. `recognition.similarity_threshold`
+    pub key: String,
+}
+
+impl std::fmt::Display for LockedKeyOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` in {} is locked by administrator policy",
+            self.key,
+            self.path.display()
+        )
+    }
+}
+
+/// Parsed administrator policy. `Policy::default()` — what a machine
+/// without `policy.toml` gets — enforces and locks nothing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    /// Values the policy enforces, in `config.toml` layout
+    values: toml::Table,
+    /// Dotted keys users can't change: every leaf of `values`, plus every
+    /// entry of the `locked` list
+    locked: BTreeSet<String>,
+    /// File the policy was read from, if one existed
+    source: Option<PathBuf>,
+}
+
+impl Policy {
+    /// Read `path`. A missing file is an empty policy, not an error — most
+    /// machines aren't managed.
+    pub fn load(path: &Path) -> Result<(Self, Vec<UnknownKey>), ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(path, &text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(source) => Err(ConfigError::Io {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    /// Parse policy text read from `path`. Values are type-checked against
+    /// [`Config`] like any config file; unknown keys — including `locked`
+    /// entries naming keys that don't exist — are returned rather than
+    /// fatal, so a typo is logged instead of keeping the daemon down.
+    pub fn parse(path: &Path, text: &str) -> Result<(Self, Vec<UnknownKey>), ConfigError> {
+        let parse_error = |message: String| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        };
+        let unknown = |key: String| UnknownKey {
+            path: path.to_path_buf(),
+            key,
+        };
+
+        let mut values = text
+            .parse::<toml::Table>()
+            .map_err(|e| parse_error(e.to_string()))?;
+
+        let mut locked = BTreeSet::new();
+        let mut unknown_keys = Vec::new();
+
+        if let Some(list) = values.remove(LOCKED_KEY) {
+            let toml::Value::Array(entries) = list else {
+                return Err(parse_error(
+                    "`locked` must be an array of dotted key names".to_string(),
+                ));
+            };
+            let known = known_keys();
+            for entry in entries {
+                let toml::Value::String(key) = entry else {
+                    return Err(parse_error(
+                        "`locked` must be an array of dotted key names".to_string(),
+                    ));
+                };
+                if is_known_key(&known, &key) {
+                    locked.insert(key);
+                } else {
+                    unknown_keys.push(unknown(format!("{LOCKED_KEY}: {key}")));
+                }
+            }
+        }
+
+        let _: Config = serde_ignored::deserialize(toml::Value::Table(values.clone()), |key| {
+            unknown_keys.push(unknown(key.to_string()));
+        })
+        .map_err(|e| parse_error(e.to_string()))?;
+
+        collect_leaves(&values, "", &mut locked);
+
+        Ok((
+            Self {
+                values,
+                locked,
+                source: Some(path.to_path_buf()),
+            },
+            unknown_keys,
+        ))
+    }
+
+    /// Whether `key` (dotted, e.g. `authentication.enable_for_sudo`) is
+    /// locked, either itself or through a locked parent table.
+    pub fn is_locked(&self, key: &str) -> bool {
+        self.locked.iter().any(|locked| {
+            key == locked
+                || key
+                    .strip_prefix(locked.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+
+    /// Every locked key, as written (tables as tables, not expanded)
+    pub fn locked_keys(&self) -> impl Iterator<Item = &str> {
+        self.locked.iter().map(String::as_str)
+    }
+
+    /// File the policy was read from, or `None` for the empty default
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// The values the policy enforces, in `config.toml` layout
+    pub(crate) fn values(&self) -> &toml::Table {
+        &self.values
+    }
+
+    /// Remove every locked leaf from a user-layer table, recording each
+    /// one against `path`.
+    pub(crate) fn strip_locked(
+        &self,
+        table: &mut toml::Table,
+        path: &Path,
+        removed: &mut Vec<LockedKeyOverride>,
+    ) {
+        if self.locked.is_empty() {
+            return;
+        }
+        self.strip_locked_under(table, "", path, removed);
+    }
+
+    fn strip_locked_under(
+        &self,
+        table: &mut toml::Table,
+        prefix: &str,
+        path: &Path,
+        removed: &mut Vec<LockedKeyOverride>,
+    ) {
+        table.retain(|key, value| {
+            let dotted = join_key(prefix, key);
+            if let toml::Value::Table(child) = value {
+                self.strip_locked_under(child, &dotted, path, removed);
+                return true;
+            }
+            if self.is_locked(&dotted) {
+                removed.push(LockedKeyOverride {
+                    path: path.to_path_buf(),
+                    key: dotted,
+                });
+                return false;
+            }
+            true
+        });
+    }
+}
+
+pub(crate) fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// Add the dotted path of every non-table value under `table`.
+fn collect_leaves(table: &toml::Table, prefix: &str, out: &mut BTreeSet<String>) {
+    for (key, value) in table {
+        let dotted = join_key(prefix, key);
+        match value {
+            toml::Value::Table(child) => collect_leaves(child, &dotted, out),
+            _ => {
+                out.insert(dotted);
+            }
+        }
+    }
+}
+
+/// Every dotted key (tables included) a config file can set, taken from
+/// the serialized defaults so it can't drift from the structs. Optional
+/// keys are filled in first since `None` isn't serialized.
+pub(crate) fn known_keys() -> BTreeSet<String> {
+    let config = Config::with_options_set();
+    let mut keys = BTreeSet::new();
+    if let Ok(toml::Value::Table(table)) = toml::Value::try_from(&config) {
+        collect_all(&table, "", &mut keys);
+    }
+    keys
+}
+
+fn collect_all(table: &toml::Table, prefix: &str, out: &mut BTreeSet<String>) {
+    for (key, value) in table {
+        let dotted = join_key(prefix, key);
+        if let toml::Value::Table(child) = value {
+            collect_all(child, &dotted, out);
+        }
+        out.insert(dotted);
+    }
+}
+
+/// `recognition.contexts` is keyed by free-form context names, so anything
+/// below it is accepted as long as it isn't empty.
+pub(crate) fn is_known_key(known: &BTreeSet<String>, key: &str) -> bool {
+    known.contains(key)
+        || key
+            .strip_prefix("recognition.contexts.")
+            .is_some_and(|rest| !rest.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (Policy, Vec<UnknownKey>) {
+        Policy::parse(Path::new("/etc/linux-hello/policy.toml"), text).unwrap()
+    }
+
+    #[test]
+    fn set_values_and_locked_list_are_both_locked() {
+        let (policy, unknown) = parse(
+            "locked = [\"recognition.contexts\"]\n\n\
+             [authentication]\nenable_for_sudo = false\n",
+        );
+        assert!(unknown.is_empty(), "{unknown:?}");
+        assert!(policy.is_locked("authentication.enable_for_sudo"));
+        assert!(policy.is_locked("recognition.contexts"));
+        assert!(policy.is_locked("recognition.contexts.sudo.similarity_threshold"));
+        assert!(!policy.is_locked("authentication.enable_for_login"));
+        assert!(!policy.is_locked("recognition.contexts_extra"));
+    }
+
+    #[test]
+    fn unknown_locked_names_and_values_are_reported() {
+        let (policy, unknown) = parse(
+            "locked = [\"recognition.similarity_treshold\", \"recognition.contexts.kiosk\"]\n\n\
+             [capture]\nsamples = 5\n",
+        );
+        let keys: Vec<&str> = unknown.iter().map(|k| k.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec!["locked: recognition.similarity_treshold", "capture.samples"]
+        );
+        assert!(policy.is_locked("recognition.contexts.kiosk.require_confirmation"));
+    }
+
+    #[test]
+    fn the_shipped_example_policy_parses_without_unknown_keys() {
+        let example = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("linux-hello.policy.toml.example");
+        let (policy, unknown) = Policy::load(&example).unwrap();
+        assert!(unknown.is_empty(), "{unknown:?}");
+        assert!(policy.is_locked("authentication.enable_for_sudo"));
+        assert!(policy.is_locked("capture.enrollment_quality_threshold"));
+    }
+
+    #[test]
+    fn malformed_locked_list_is_a_parse_error() {
+        let result = Policy::parse(Path::new("policy.toml"), "locked = \"capture\"\n");
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let (policy, unknown) = Policy::load(&dir.path().join("policy.toml")).unwrap();
+        assert_eq!(policy, Policy::default());
+        assert!(unknown.is_empty());
+        assert_eq!(policy.locked_keys().count(), 0);
+    }
+}
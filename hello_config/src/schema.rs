@@ -0,0 +1,569 @@
+//! JSON Schema for `config.toml`
+//!
+//! The settings GUI and admin tooling render and check settings from
+//! [`json_schema`] instead of hard-coding each key. Types and defaults come
+//! from serializing [`Config::default`], so they can't drift from the Rust
+//! types; what serialization can't say — a description, the valid range,
+//! the allowed strings, whether a running daemon picks a change up or needs
+//! a restart — is in [`KEYS`], which the tests below check against both the
+//! types (every key documented, nothing extra) and [`Config::validate`]
+//! (every range exactly what it accepts).
+//!
+//! Besides standard JSON Schema (draft 2020-12), each setting carries
+//! `"x-restart-required"`: `true` for the few keys read once at daemon
+//! startup, which `config_reload` can't apply to a running daemon.
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    Config, FusionRule, PixelFormat, LOGGING_LEVELS, MAX_TEMPLATE_AGE_DAYS,
+    MIN_SIMILARITY_THRESHOLD,
+};
+
+/// What the schema says about one key beyond its type and default
+struct KeyDoc {
+    key: &'static str,
+    description: &'static str,
+    range: Option<(f64, f64)>,
+    restart_required: bool,
+}
+
+const fn key(key: &'static str, description: &'static str) -> KeyDoc {
+    KeyDoc {
+        key,
+        description,
+        range: None,
+        restart_required: false,
+    }
+}
+
+impl KeyDoc {
+    const fn range(mut self, min: f64, max: f64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    const fn restart_required(mut self) -> Self {
+        self.restart_required = true;
+        self
+    }
+}
+
+const MIN_SIMILARITY: f64 = MIN_SIMILARITY_THRESHOLD as f64;
+
+/// Top-level sections, in `config.toml` order
+const SECTIONS: &[(&str, &str)] = &[
+    (
+        "authentication",
+        "Per-context off switches; whether a service uses face auth at all is still decided by its /etc/pam.d line",
+    ),
+    ("detection", "Camera selection and capture mode"),
+    ("recognition", "Face detection and matching thresholds"),
+    ("capture", "Capture and enrollment settings"),
+    ("behavior", "User-facing behavior"),
+    ("advanced", "Daemon and PAM plumbing"),
+    ("features", "Integrations that aren't implemented yet"),
+    ("backup", "Backups of enrolled faces"),
+    ("debug", "Development and troubleshooting switches"),
+];
+
+/// Every leaf key; `recognition.contexts.*.` stands for any context name
+const KEYS: &[KeyDoc] = &[
+    key(
+        "authentication.enable_for_login",
+        "Face auth for the login context",
+    ),
+    key("authentication.enable_for_sudo", "Face auth for sudo"),
+    key(
+        "authentication.enable_for_screenlock",
+        "Face auth for the screen locker",
+    ),
+    key(
+        "authentication.enable_for_sddm",
+        "Face auth at the SDDM login screen (read from the system config only)",
+    ),
+    key(
+        "authentication.enable_for_polkit",
+        "Face auth for polkit prompts",
+    ),
+    key(
+        "authentication.backoff_after_failures",
+        "Failed matches in a row before face auth has to wait between attempts",
+    )
+    .range(1.0, 100.0),
+    key(
+        "authentication.backoff_base_secs",
+        "First wait after those failures, doubling with each further one",
+    )
+    .range(0.0, 3600.0),
+    key(
+        "authentication.backoff_max_secs",
+        "Longest wait between two face auth attempts",
+    )
+    .range(0.0, 86400.0),
+    key(
+        "authentication.lockout_after_failures",
+        "Failed matches in a row after which face auth needs a password to come back; 0 never locks",
+    )
+    .range(0.0, 1000.0),
+    key(
+        "detection.camera_device",
+        "RGB camera device, e.g. /dev/video2; unset to use the first one found",
+    ),
+    key(
+        "detection.preferred_format",
+        "Pixel format to capture in; MJPEG is captured uncompressed",
+    ),
+    key(
+        "detection.width",
+        "Requested frame width; the camera's closest mode is used",
+    )
+    .range(1.0, 8192.0),
+    key(
+        "detection.height",
+        "Requested frame height; the camera's closest mode is used",
+    )
+    .range(1.0, 8192.0),
+    key(
+        "detection.fps",
+        "Requested frame rate; the camera's closest rate is used",
+    )
+    .range(1.0, 240.0),
+    key(
+        "recognition.confidence_threshold",
+        "Minimum detector confidence for a face to count as detected",
+    )
+    .range(0.0, 1.0),
+    key(
+        "recognition.similarity_threshold",
+        "Similarity needed to accept a match, for contexts without their own; higher is stricter",
+    )
+    .range(MIN_SIMILARITY, 1.0),
+    key(
+        "recognition.allow_multiple_faces",
+        "Accept frames with more than one face during verification",
+    ),
+    key(
+        "recognition.fusion",
+        "How a probe is scored against an enrollment's samples: best sample, mean of the best fusion_top_k, or their average",
+    ),
+    key(
+        "recognition.fusion_top_k",
+        "Best-matching samples averaged by the top_k_mean fusion rule",
+    )
+    .range(1.0, 20.0),
+    key(
+        "recognition.max_template_age_days",
+        "Days after enrollment a face stops being accepted, for contexts without their own limit; 0 for no limit",
+    )
+    .range(0.0, MAX_TEMPLATE_AGE_DAYS as f64),
+    key(
+        "recognition.expiry_warning_days",
+        "How many days before a face expires the user is reminded to enroll it again",
+    )
+    .range(1.0, 365.0),
+    key(
+        "recognition.contexts.*.similarity_threshold",
+        "Similarity threshold for this PAM context, overriding recognition.similarity_threshold",
+    )
+    .range(MIN_SIMILARITY, 1.0),
+    key(
+        "recognition.contexts.*.require_confirmation",
+        "Ask for confirmation after a match in this context",
+    ),
+    key(
+        "recognition.contexts.*.max_template_age_days",
+        "Days after enrollment a face stops being accepted in this PAM context, overriding recognition.max_template_age_days; 0 for no limit",
+    )
+    .range(0.0, MAX_TEMPLATE_AGE_DAYS as f64),
+    key(
+        "capture.default_timeout_ms",
+        "How long a capture runs when the request doesn't say",
+    )
+    .range(100.0, 120_000.0),
+    key(
+        "capture.enrollment_samples",
+        "Frames captured per enrolled face, each kept as a sample",
+    )
+    .range(1.0, 20.0),
+    key(
+        "capture.enrollment_quality_threshold",
+        "Minimum quality for an enrollment sample",
+    )
+    .range(0.0, 1.0),
+    key(
+        "behavior.auto_enroll_improvements",
+        "Refine enrolled faces from successful matches",
+    ),
+    key(
+        "behavior.adaptive_min_margin",
+        "How far above the similarity threshold a match must score to refine its face",
+    )
+    .range(0.0, 0.5),
+    key(
+        "behavior.adaptive_min_interval_hours",
+        "Hours between two refinements of the same face",
+    )
+    .range(0.0, 720.0),
+    key(
+        "behavior.adaptive_min_enrollment_similarity",
+        "Similarity to the original enrollment a refinement must keep; caps how far a face can drift",
+    )
+    .range(MIN_SIMILARITY, 1.0),
+    key(
+        "behavior.adaptive_max_samples",
+        "Refinements kept per face, the oldest dropped first",
+    )
+    .range(1.0, 20.0),
+    key("behavior.logging_level", "Daemon log level").restart_required(),
+    key(
+        "behavior.allow_fallback_to_password",
+        "Fall back to the password prompt when face auth fails",
+    ),
+    key(
+        "advanced.daemon_socket",
+        "Bus the PAM module reaches the daemon on",
+    )
+    .restart_required(),
+    key(
+        "advanced.daemon_call_timeout_ms",
+        "How long the PAM module waits for the daemon",
+    )
+    .range(100.0, 300_000.0),
+    key(
+        "advanced.pam_conversation_timeout_ms",
+        "How long the PAM module waits on its conversation",
+    )
+    .range(100.0, 300_000.0),
+    key(
+        "advanced.max_retries",
+        "Face attempts the PAM module retries before falling back",
+    )
+    .range(0.0, 10.0),
+    key(
+        "advanced.pam_total_timeout_ms",
+        "Wall-time cap on all of the PAM module's face attempts",
+    )
+    .range(1000.0, 600_000.0),
+    key("features.kdesu_integration", "kdesu integration"),
+    key("features.polkit_integration", "polkit agent integration"),
+    key("features.sddm_ui_plugin", "SDDM greeter plugin"),
+    key(
+        "features.audit_logging",
+        "Audit log of authentication attempts",
+    ),
+    key("backup.auto_backup", "Back up enrolled faces automatically"),
+    key("backup.backup_path", "Directory backups are written to"),
+    key(
+        "backup.backup_frequency_days",
+        "Days between automatic backups",
+    ),
+    key("debug.debug", "Debug mode"),
+    key(
+        "debug.dump_frames",
+        "Save frames, detections and scores of failed verify attempts",
+    ),
+    key(
+        "debug.dump_embeddings",
+        "Save the embeddings of failed verify attempts",
+    ),
+    key(
+        "debug.dump_dir",
+        "Where dumps go; unset for $XDG_STATE_HOME/linux-hello/dumps",
+    ),
+    key(
+        "debug.dump_max_attempts",
+        "Oldest dumps are deleted beyond this many attempts",
+    )
+    .range(1.0, 1000.0),
+    key(
+        "debug.dump_max_size_mb",
+        "Oldest dumps are deleted beyond this much disk space",
+    )
+    .range(1.0, 100_000.0),
+    key(
+        "debug.record_scores",
+        "Let `linux-hello calibrate record` collect labelled match scores",
+    ),
+    key("debug.mock_daemon", "Use a mock daemon"),
+];
+
+/// The strings a key accepts, for the keys that only accept some
+fn allowed_strings(key: &str) -> Option<Vec<Value>> {
+    match key {
+        "detection.preferred_format" => Some(
+            PixelFormat::ALL
+                .iter()
+                .filter_map(|format| to_json(&toml::Value::try_from(format).ok()?))
+                .collect(),
+        ),
+        "behavior.logging_level" => Some(LOGGING_LEVELS.iter().map(|level| json!(level)).collect()),
+        "recognition.fusion" => Some(
+            FusionRule::ALL
+                .iter()
+                .filter_map(|rule| to_json(&toml::Value::try_from(rule).ok()?))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// JSON Schema for `config.toml`, see the module docs
+pub fn json_schema() -> Value {
+    let defaults = Config::default().to_table().unwrap_or_default();
+    let types = Config::with_options_set().to_table().unwrap_or_default();
+
+    let mut sections = Map::new();
+    for (section, description) in SECTIONS {
+        let defaults = defaults.get(*section).and_then(toml::Value::as_table);
+        let types = types.get(*section).and_then(toml::Value::as_table);
+        let mut properties = Map::new();
+        for doc in KEYS {
+            let Some(leaf) = doc
+                .key
+                .strip_prefix(section)
+                .and_then(|rest| rest.strip_prefix('.'))
+            else {
+                continue;
+            };
+            if leaf.contains('.') {
+                continue; // recognition.contexts.*, below
+            }
+            let value_type = types.and_then(|t| t.get(leaf));
+            let default = defaults.and_then(|d| d.get(leaf));
+            properties.insert(leaf.to_string(), key_schema(doc, value_type, default));
+        }
+        if *section == "recognition" {
+            properties.insert("contexts".to_string(), contexts_schema());
+        }
+        sections.insert(
+            section.to_string(),
+            json!({
+                "type": "object",
+                "description": description,
+                "properties": properties,
+                "additionalProperties": false,
+            }),
+        );
+    }
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Linux Hello configuration (config.toml)",
+        "type": "object",
+        "properties": sections,
+        "additionalProperties": false,
+    })
+}
+
+/// `recognition.contexts`: a table of any context name to its overrides
+fn contexts_schema() -> Value {
+    let mut properties = Map::new();
+    for (leaf, json_type) in [
+        ("similarity_threshold", "number"),
+        ("require_confirmation", "boolean"),
+        ("max_template_age_days", "integer"),
+    ] {
+        let key = format!("recognition.contexts.*.{leaf}");
+        let doc = KEYS
+            .iter()
+            .find(|doc| doc.key == key)
+            .expect("every context key is documented");
+        let mut schema = key_schema(doc, None, None);
+        schema["type"] = json!(json_type);
+        properties.insert(leaf.to_string(), schema);
+    }
+    json!({
+        "type": "object",
+        "description": "Per-context overrides, keyed by the context= name on a pam_linux_hello.so line",
+        "additionalProperties": {
+            "type": "object",
+            "properties": properties,
+            "additionalProperties": false,
+        },
+        "x-restart-required": false,
+    })
+}
+
+fn key_schema(
+    doc: &KeyDoc,
+    value_type: Option<&toml::Value>,
+    default: Option<&toml::Value>,
+) -> Value {
+    let json_type = match value_type {
+        Some(toml::Value::Boolean(_)) => "boolean",
+        Some(toml::Value::Integer(_)) => "integer",
+        Some(toml::Value::Float(_)) => "number",
+        _ => "string",
+    };
+    let mut schema = Map::new();
+    schema.insert("type".to_string(), json!(json_type));
+    schema.insert("description".to_string(), json!(doc.description));
+    if let Some(default) = default.and_then(to_json) {
+        schema.insert("default".to_string(), default);
+    }
+    if let Some((min, max)) = doc.range {
+        let bound = |value: f64| {
+            if json_type == "integer" {
+                json!(value as i64)
+            } else {
+                json!(value)
+            }
+        };
+        schema.insert("minimum".to_string(), bound(min));
+        schema.insert("maximum".to_string(), bound(max));
+    }
+    if let Some(allowed) = allowed_strings(doc.key) {
+        schema.insert("enum".to_string(), Value::Array(allowed));
+    }
+    schema.insert(
+        "x-restart-required".to_string(),
+        json!(doc.restart_required),
+    );
+    Value::Object(schema)
+}
+
+fn to_json(value: &toml::Value) -> Option<Value> {
+    match value {
+        toml::Value::Boolean(b) => Some(json!(b)),
+        toml::Value::Integer(i) => Some(json!(i)),
+        // Through the shortest decimal, so 0.58 stays 0.58
+        toml::Value::Float(f) => f
+            .to_string()
+            .parse::<serde_json::Number>()
+            .ok()
+            .map(Value::Number),
+        toml::Value::String(s) => Some(json!(s)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy;
+    use crate::ContextConfig;
+    use std::path::Path;
+
+    fn property<'a>(schema: &'a Value, key: &str) -> &'a Value {
+        let (section, leaf) = key.split_once('.').unwrap();
+        &schema["properties"][section]["properties"][leaf]
+    }
+
+    #[test]
+    fn every_key_is_documented_and_every_documented_key_exists() {
+        let known = policy::known_keys();
+        let documented: Vec<&str> = KEYS
+            .iter()
+            .map(|doc| doc.key)
+            .filter(|key| !key.starts_with("recognition.contexts."))
+            .collect();
+
+        let types = Config::with_options_set();
+        for key in &known {
+            let is_leaf = !matches!(types.value(key), Some(toml::Value::Table(_)));
+            if is_leaf {
+                assert!(documented.contains(&key.as_str()), "{key} is undocumented");
+            }
+        }
+        for key in documented {
+            assert!(known.contains(key), "{key} is not a config key");
+        }
+    }
+
+    #[test]
+    fn ranges_match_what_validate_accepts() {
+        let mut types = Config::with_options_set();
+        types.recognition.contexts.insert(
+            "test".to_string(),
+            ContextConfig {
+                similarity_threshold: Some(0.6),
+                require_confirmation: false,
+                max_template_age_days: Some(0),
+            },
+        );
+        for doc in KEYS {
+            let Some((min, max)) = doc.range else {
+                continue;
+            };
+            let (table, leaf) = match doc.key.strip_prefix("recognition.contexts.*.") {
+                Some(leaf) => ("recognition.contexts.test", leaf),
+                None => doc.key.split_once('.').unwrap(),
+            };
+            let value = types.value(&format!("{table}.{leaf}"));
+            let is_integer = matches!(value, Some(toml::Value::Integer(_)));
+            let format = |value: f64| {
+                if is_integer {
+                    format!("{}", value as i64)
+                } else {
+                    format!("{value:?}")
+                }
+            };
+            let step = if is_integer { 1.0 } else { 0.01 };
+            let check = |value: f64| {
+                let text = format!("[{table}]\n{leaf} = {}\n", format(value));
+                Config::check_text(Path::new("schema-test.toml"), &text).is_ok()
+            };
+
+            assert!(check(min), "{} rejects its minimum {min}", doc.key);
+            assert!(check(max), "{} rejects its maximum {max}", doc.key);
+            if min - step >= 0.0 {
+                assert!(!check(min - step), "{} accepts below {min}", doc.key);
+            }
+            assert!(!check(max + step), "{} accepts above {max}", doc.key);
+        }
+    }
+
+    #[test]
+    fn schema_carries_types_defaults_ranges_and_restart_markers() {
+        let schema = json_schema();
+
+        let threshold = property(&schema, "recognition.similarity_threshold");
+        assert_eq!(threshold["type"], "number");
+        assert_eq!(threshold["default"], json!(0.58));
+        assert_eq!(threshold["minimum"], json!(0.5));
+        assert_eq!(threshold["x-restart-required"], false);
+
+        let samples = property(&schema, "capture.enrollment_samples");
+        assert_eq!(samples["type"], "integer");
+        assert_eq!(samples["default"], 3);
+        assert_eq!(samples["maximum"], 20);
+
+        let format = property(&schema, "detection.preferred_format");
+        assert_eq!(format["enum"], json!(["RGB8", "Gray8", "MJPEG"]));
+
+        let device = property(&schema, "detection.camera_device");
+        assert_eq!(device["type"], "string");
+        assert!(device.get("default").is_none());
+
+        assert_eq!(
+            property(&schema, "behavior.logging_level")["x-restart-required"],
+            true
+        );
+        assert_eq!(
+            property(&schema, "recognition.contexts")["additionalProperties"]["properties"]
+                ["similarity_threshold"]["maximum"],
+            json!(1.0)
+        );
+    }
+
+    #[test]
+    fn every_default_is_in_its_own_range_and_enum() {
+        let schema = json_schema();
+        for doc in KEYS
+            .iter()
+            .filter(|doc| !doc.key.starts_with("recognition.contexts."))
+        {
+            let property = property(&schema, doc.key);
+            let Some(default) = property.get("default") else {
+                continue;
+            };
+            if let Some(allowed) = property.get("enum") {
+                assert!(allowed.as_array().unwrap().contains(default), "{}", doc.key);
+            }
+            if let (Some(min), Some(value)) = (property["minimum"].as_f64(), default.as_f64()) {
+                assert!(value >= min && value <= property["maximum"].as_f64().unwrap());
+            }
+        }
+    }
+}
@@ -78,6 +78,8 @@ async fn main() -> anyhow::Result<()> {
     let config = DaemonConfig {
         storage_path: cli.storage.clone(),
         root_mode: false,
+        settings: hello_config::Config::default(),
+        policy: hello_config::Policy::default(),
     };
 
     let daemon = FaceAuthDaemon::new(config)?;
@@ -92,6 +94,7 @@ async fn main() -> anyhow::Result<()> {
                 context: context.clone(),
                 timeout_ms: 5000,
                 num_samples: 3,
+                allowed_contexts: Vec::new(),
             };
 
             match daemon.register_face(request).await {
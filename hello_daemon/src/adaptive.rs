@@ -0,0 +1,400 @@
+//! Refining enrolled faces from confident verifications
+//!
+//! A face enrolled once slowly stops looking like its owner (haircut,
+//! glasses, a new camera). With `behavior.auto_enroll_improvements` on, a
+//! successful `verify_with_storage` — two consecutive matching frames, each
+//! past the liveness gate — adds the final probe to the matched face's
+//! adapted samples, which the matcher scores alongside the enrollment
+//! samples (see `matcher::FaceTemplate`).
+//!
+//! Adapting on one's own matches can walk a template toward whoever
+//! happens to match it, so every probe has to clear [`AdaptationPolicy`]:
+//!
+//! - it scored at least `adaptive_min_margin` above the context's
+//!   threshold, so borderline matches never feed back;
+//! - the face wasn't adapted in the last `adaptive_min_interval_hours`, so
+//!   a burst of attempts can't fill its samples;
+//! - it is at least `adaptive_min_enrollment_similarity` similar to the
+//!   enrolled centroid — the cap on drift: the original enrollment stays
+//!   the anchor, however many samples came after it.
+//!
+//! Only the last `adaptive_max_samples` are kept. The drift cap is checked
+//! again on every load, so tightening it takes effect at once.
+//!
+//! Nobody re-authenticates during a login, so an adapted sample can't be
+//! signed the way an enrollment is. `hello-storage-key` signs it instead
+//! (see `record_auth::RecordAuthenticator::sign_adapted`), once it has run
+//! the margin and drift checks itself, with its own limits, against the
+//! enrollment the face's MAC covers — not on the daemon's word. Samples
+//! without a valid MAC are ignored, so one planted in the database is
+//! never scored.
+//! `linux-hello rollback` drops them all (`FaceAuthDaemon::reset_adaptation`).
+
+use crate::matcher::{FaceMatcher, FaceTemplate};
+use crate::record_auth::{self, RecordAuthenticator};
+use crate::storage::FaceStorage;
+use crate::{DaemonError, FaceRecord};
+use hello_config::BehaviorConfig;
+use hello_face_core::Embedding;
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
+
+/// The guards a probe has to clear before it is folded into a face
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptationPolicy {
+    /// Score above the threshold required
+    pub min_margin: f32,
+
+    /// Seconds between two adaptations of the same face
+    pub min_interval_secs: u64,
+
+    /// Similarity to the enrolled centroid every adapted sample keeps
+    pub min_enrollment_similarity: f32,
+
+    /// Adapted samples kept per face
+    pub max_samples: usize,
+}
+
+/// Why a probe was not folded in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Skipped {
+    /// It matched, but not by `min_margin`
+    BelowMargin { margin: f32 },
+
+    /// The face was adapted less than `min_interval_secs` ago
+    TooSoon,
+
+    /// It is further from the enrollment than the drift cap allows
+    Drifted { similarity: f32 },
+
+    /// Extracted by another model than the enrollment's
+    OtherModel,
+}
+
+impl AdaptationPolicy {
+    /// From `[behavior]`; `None` with `auto_enroll_improvements` off
+    pub fn from_config(behavior: &BehaviorConfig) -> Option<Self> {
+        behavior
+            .auto_enroll_improvements
+            .then(|| Self::limits(behavior))
+    }
+
+    /// The `adaptive_*` limits of `[behavior]`, whether adaptation is on
+    /// or not
+    pub fn limits(behavior: &BehaviorConfig) -> Self {
+        Self {
+            min_margin: behavior.adaptive_min_margin,
+            min_interval_secs: u64::from(behavior.adaptive_min_interval_hours) * 3600,
+            min_enrollment_similarity: behavior.adaptive_min_enrollment_similarity,
+            max_samples: behavior.adaptive_max_samples as usize,
+        }
+    }
+
+    /// Whether `probe`, which matched `template` with `score` against
+    /// `threshold`, may be folded into it at `now`
+    #[allow(clippy::too_many_arguments)]
+    pub fn check(
+        &self,
+        matcher: &FaceMatcher,
+        template: &FaceTemplate,
+        probe: &Embedding,
+        score: f32,
+        threshold: f32,
+        last_adapted_at: Option<u64>,
+        now: u64,
+    ) -> Result<(), Skipped> {
+        if !probe.is_comparable_with(&template.centroid) {
+            return Err(Skipped::OtherModel);
+        }
+        let margin = score - threshold;
+        if margin < self.min_margin {
+            return Err(Skipped::BelowMargin { margin });
+        }
+        if last_adapted_at.is_some_and(|at| now < at.saturating_add(self.min_interval_secs)) {
+            return Err(Skipped::TooSoon);
+        }
+        let similarity = matcher.cosine_similarity(&probe.vector, &template.centroid.vector);
+        if similarity < self.min_enrollment_similarity {
+            return Err(Skipped::Drifted { similarity });
+        }
+        Ok(())
+    }
+
+    /// Drop the adapted samples of `template` past the drift cap (or from
+    /// another model). Returns how many were dropped.
+    pub fn drop_drifted(&self, matcher: &FaceMatcher, template: &mut FaceTemplate) -> usize {
+        let before = template.adapted.len();
+        let centroid = &template.centroid;
+        template.adapted.retain(|sample| {
+            sample.is_comparable_with(centroid)
+                && matcher.cosine_similarity(&sample.vector, &centroid.vector)
+                    >= self.min_enrollment_similarity
+        });
+        before - template.adapted.len()
+    }
+}
+
+/// Fold `probe` into `record`'s face if `policy` allows it, signed by
+/// `record_auth` against the face's `mac`. Never fails the verification it
+/// follows: problems are logged.
+#[allow(clippy::too_many_arguments)]
+pub async fn fold_in(
+    storage: &FaceStorage,
+    record_auth: &RecordAuthenticator,
+    policy: &AdaptationPolicy,
+    matcher: &FaceMatcher,
+    record: &FaceRecord,
+    mac: Option<&[u8]>,
+    template: &FaceTemplate,
+    probe: &Embedding,
+    context: &str,
+    score: f32,
+    threshold: f32,
+    now: u64,
+) {
+    let (user_id, face_id) = (record.user_id, record.face_id.as_str());
+    let last_adapted_at = match storage.last_adapted_at(user_id, face_id) {
+        Ok(at) => at,
+        Err(e) => {
+            warn!("Could not adapt face {}: {}", face_id, e);
+            return;
+        }
+    };
+    match policy.check(
+        matcher,
+        template,
+        probe,
+        score,
+        threshold,
+        last_adapted_at,
+        now,
+    ) {
+        Ok(()) => {
+            let sample_mac = match record_auth
+                .sign_adapted(record, template, mac, context, probe)
+                .await
+            {
+                Ok(sample_mac) => sample_mac,
+                Err(e) => {
+                    warn!("Could not adapt face {}: {}", face_id, e);
+                    return;
+                }
+            };
+            match storage.add_adapted_sample(
+                user_id,
+                face_id,
+                probe,
+                sample_mac.as_deref(),
+                now,
+                policy.max_samples,
+            ) {
+                Ok(()) => info!(
+                    "Face {} of user_id={} adapted (score={:.3})",
+                    face_id, user_id, score
+                ),
+                Err(e) => warn!("Could not adapt face {}: {}", face_id, e),
+            }
+        }
+        Err(skipped) => debug!("Face {} not adapted: {:?}", face_id, skipped),
+    }
+}
+
+/// Drop the adapted samples of `user_id`'s `templates` whose MAC is
+/// missing or doesn't check out under `record_auth`: only the ones
+/// `hello-storage-key` signed as they were folded in are scored. Returns
+/// how many were dropped.
+pub async fn drop_unsigned(
+    storage: &FaceStorage,
+    record_auth: &RecordAuthenticator,
+    user_id: u32,
+    templates: &mut HashMap<String, FaceTemplate>,
+) -> Result<usize, DaemonError> {
+    if !record_auth.is_enabled() {
+        return Ok(0);
+    }
+    let macs = storage.load_adapted_macs(user_id)?;
+    let mut dropped = 0;
+    for (face_id, template) in templates.iter_mut() {
+        let sample_macs = macs.get(face_id).map(Vec::as_slice).unwrap_or_default();
+        let mut signed = Vec::new();
+        for (i, sample) in std::mem::take(&mut template.adapted)
+            .into_iter()
+            .enumerate()
+        {
+            let mac = sample_macs.get(i).and_then(Option::as_deref);
+            let digest = record_auth::adapted_digest(user_id, face_id, &template.centroid, &sample);
+            match record_auth.check(&digest, mac).await {
+                Ok(true) => {
+                    signed.push(sample);
+                    continue;
+                }
+                Ok(false) if mac.is_none() => debug!(
+                    "Adapted sample {} of face {} isn't signed and is ignored",
+                    i, face_id
+                ),
+                Ok(false) => warn!(
+                    target: "audit",
+                    "Adapted sample {} of face {} of user_id={} failed its integrity check \
+                     and is ignored",
+                    i,
+                    face_id,
+                    user_id
+                ),
+                Err(e) => warn!(
+                    "Could not check adapted sample {} of face {}: {}",
+                    i, face_id, e
+                ),
+            }
+            dropped += 1;
+        }
+        template.adapted = signed;
+    }
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hello_face_core::EmbeddingMetadata;
+
+    fn embedding(vector: &[f32]) -> Embedding {
+        Embedding {
+            vector: vector.to_vec(),
+            metadata: EmbeddingMetadata {
+                model: "test".to_string(),
+                model_version: "1".to_string(),
+                extracted_at: 0,
+                quality_score: 0.9,
+            },
+        }
+    }
+
+    fn policy() -> AdaptationPolicy {
+        AdaptationPolicy::from_config(&BehaviorConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn only_confident_probes_near_the_enrollment_are_folded_in() {
+        let matcher = FaceMatcher::default();
+        let template = FaceTemplate::from(embedding(&[1.0, 0.0]));
+        // 0.6 and 0.95 similar to the enrollment
+        let far = embedding(&[0.6, 0.8]);
+        let near = embedding(&[0.95, 0.312]);
+        let check = |probe: &Embedding, score: f32, last: Option<u64>| {
+            policy().check(&matcher, &template, probe, score, 0.62, last, 100_000)
+        };
+
+        assert_eq!(check(&near, 0.75, None), Ok(()));
+        assert!(matches!(
+            check(&near, 0.65, None),
+            Err(Skipped::BelowMargin { .. })
+        ));
+        assert_eq!(
+            check(&near, 0.75, Some(100_000 - 3600)),
+            Err(Skipped::TooSoon)
+        );
+        assert_eq!(check(&near, 0.75, Some(100_000 - 24 * 3600)), Ok(()));
+        assert!(matches!(
+            check(&far, 0.75, None),
+            Err(Skipped::Drifted { .. })
+        ));
+
+        let mut other_model = near.clone();
+        other_model.metadata.model = "other".to_string();
+        assert_eq!(check(&other_model, 0.75, None), Err(Skipped::OtherModel));
+    }
+
+    #[test]
+    fn drifted_samples_are_dropped_on_load() {
+        let matcher = FaceMatcher::default();
+        let mut template = FaceTemplate::from(embedding(&[1.0, 0.0]));
+        template.adapted = vec![embedding(&[0.95, 0.312]), embedding(&[0.6, 0.8])];
+
+        assert_eq!(policy().drop_drifted(&matcher, &mut template), 1);
+        assert_eq!(template.adapted.len(), 1);
+        assert_eq!(template.adapted[0].vector, vec![0.95, 0.312]);
+    }
+
+    #[tokio::test]
+    async fn only_signed_adapted_samples_are_kept() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        let record = FaceRecord {
+            face_id: "face_1000_1".to_string(),
+            user_id: 1000,
+            quality_score: 0.9,
+            registered_at: 0,
+            context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: crate::storage::FaceStats::default(),
+        };
+        let centroid = embedding(&[1.0, 0.0]);
+        storage.save_face(&record, &centroid).unwrap();
+        let auth = RecordAuthenticator::Local(record_auth::RecordKey::from_bytes([3; 32]));
+        let template = FaceTemplate::from(centroid);
+
+        let signed = embedding(&[0.95, 0.312]);
+        let mac = auth
+            .sign_adapted(&record, &template, None, "test", &signed)
+            .await
+            .unwrap()
+            .unwrap();
+        let planted = embedding(&[0.9, 0.436]);
+        for (at, mac) in [(10, Some(mac.as_slice())), (20, None), (30, Some(&mac))] {
+            let sample = if at == 10 { &signed } else { &planted };
+            storage
+                .add_adapted_sample(1000, &record.face_id, sample, mac, at, 5)
+                .unwrap();
+        }
+        let load = || {
+            storage
+                .load_face_templates(1000, [&record.face_id])
+                .unwrap()
+        };
+
+        // Planted without a MAC, or with another sample's
+        let mut templates = load();
+        assert_eq!(
+            drop_unsigned(&storage, &auth, 1000, &mut templates)
+                .await
+                .unwrap(),
+            2
+        );
+        let adapted = &templates[&record.face_id].adapted;
+        assert_eq!(adapted.len(), 1);
+        assert_eq!(adapted[0].vector, signed.vector);
+
+        // Under another user's key, none of them
+        let mut templates = load();
+        let other = RecordAuthenticator::Local(record_auth::RecordKey::from_bytes([4; 32]));
+        drop_unsigned(&storage, &other, 1000, &mut templates)
+            .await
+            .unwrap();
+        assert!(templates[&record.face_id].adapted.is_empty());
+
+        // Nothing to check against without a key service
+        let mut templates = load();
+        drop_unsigned(
+            &storage,
+            &RecordAuthenticator::Disabled,
+            1000,
+            &mut templates,
+        )
+        .await
+        .unwrap();
+        assert_eq!(templates[&record.face_id].adapted.len(), 3);
+    }
+
+    #[test]
+    fn adaptation_is_off_with_auto_enroll_improvements() {
+        let behavior = BehaviorConfig {
+            auto_enroll_improvements: false,
+            ..BehaviorConfig::default()
+        };
+        assert_eq!(AdaptationPolicy::from_config(&behavior), None);
+    }
+}
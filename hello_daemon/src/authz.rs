@@ -81,6 +81,29 @@ impl EnrollmentAuthorizer {
 /// running, action not registered — e.g. the package's `.policy` file isn't
 /// installed) denies the action rather than silently allowing it.
 async fn check_polkit_authorization(session_id: &str, action: &str) -> bool {
+    let mut subject_details = std::collections::HashMap::new();
+    subject_details.insert("session-id", Value::new(session_id));
+    check_subject_authorization(("unix-session", subject_details), action).await
+}
+
+/// Same check for another process, identified by the pid and uid its
+/// socket connection reports — how `hello-storage-key` decides whether to
+/// sign a face record (see `record_auth`). polkit resolves the process's
+/// session itself, so an authorization the daemon obtained for it moments
+/// ago (`auth_self_keep`) is reused without a second prompt.
+pub async fn check_process_authorization(pid: u32, uid: u32, action: &str) -> bool {
+    let mut subject_details = std::collections::HashMap::new();
+    subject_details.insert("pid", Value::new(pid));
+    // 0: polkit reads the start time from /proc itself
+    subject_details.insert("start-time", Value::new(0u64));
+    subject_details.insert("uid", Value::new(uid as i32));
+    check_subject_authorization(("unix-process", subject_details), action).await
+}
+
+async fn check_subject_authorization(
+    subject: (&str, std::collections::HashMap<&str, Value<'_>>),
+    action: &str,
+) -> bool {
     let connection = match Connection::system().await {
         Ok(c) => c,
         Err(e) => {
@@ -89,9 +112,6 @@ async fn check_polkit_authorization(session_id: &str, action: &str) -> bool {
         }
     };
 
-    let mut subject_details = std::collections::HashMap::new();
-    subject_details.insert("session-id", Value::new(session_id));
-    let subject = ("unix-session", subject_details);
     let details: std::collections::HashMap<&str, &str> = std::collections::HashMap::new();
     const ALLOW_USER_INTERACTION: u32 = 1;
 
@@ -0,0 +1,309 @@
+//! Passphrase-encrypted backups of a user's enrollments
+//!
+//! `linux-hello backup export` packs a user's face records and embeddings
+//! into one file that can be carried over to a reinstalled machine and
+//! loaded back with `linux-hello backup import`. The storage key (see
+//! `storage_key`) is tied to this installation's master key, so the
+//! archive is sealed under a key derived from a passphrase instead:
+//!
+//! ```text
+//! "LHFACES\0" | format (1 byte) | Argon2id m_cost, t_cost, p_cost (u32 LE each)
+//!             | salt (16 bytes) | nonce (12 bytes) | ChaCha20-Poly1305(JSON)
+//! ```
+//!
+//! Everything before the ciphertext is authenticated as associated data,
+//! so the KDF cost can't be lowered in a copied archive without the
+//! passphrase failing. The daemon decrypts archives it's handed by the
+//! user, so the costs are also capped ([`MAX_M_COST`], [`MAX_T_COST`]) —
+//! an archive can't make it spend minutes or gigabytes before failing.
+
+use crate::FaceRecord;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hello_face_core::Embedding;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const MAGIC: &[u8; 8] = b"LHFACES\0";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN + NONCE_LEN;
+
+/// Shortest passphrase [`seal`] accepts
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Highest memory cost an archive may ask for, in KiB (1 GiB)
+pub const MAX_M_COST: u32 = 1024 * 1024;
+/// Highest number of passes an archive may ask for
+pub const MAX_T_COST: u32 = 10;
+
+/// Why an archive couldn't be written or read
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Passphrase must be at least {MIN_PASSPHRASE_LEN} characters")]
+    WeakPassphrase,
+
+    #[error("Not a Linux Hello backup")]
+    NotABackup,
+
+    #[error("Backup format {0} is newer than this version supports")]
+    UnsupportedFormat(u8),
+
+    #[error("Backup key derivation parameters are out of bounds")]
+    UnreasonableCost,
+
+    #[error("Wrong passphrase, or the backup is damaged")]
+    WrongPassphrase,
+
+    #[error("Backup contents unreadable: {0}")]
+    Malformed(String),
+
+    #[error(
+        "None of the {0} face(s) in the backup were enrolled with the recognition \
+         model in use — enroll again instead"
+    )]
+    IncompatibleModel(usize),
+}
+
+/// What an archive holds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupContents {
+    /// When the archive was written (Unix seconds)
+    pub created_at: u64,
+
+    /// UID the faces were exported from; import re-assigns them to the
+    /// importing user, whose UID may differ on a new install
+    pub user_id: u32,
+
+    pub faces: Vec<BackedUpFace>,
+}
+
+/// One enrolled face with its template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackedUpFace {
+    pub record: FaceRecord,
+
+    /// Averaged embedding
+    pub embedding: Embedding,
+
+    /// Enrollment samples; absent from archives of faces enrolled before
+    /// samples were kept
+    #[serde(default)]
+    pub samples: Vec<Embedding>,
+}
+
+/// Key derivation settings stored in the archive header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfCost {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// Argon2id cost of new archives: 64 MiB, 3 passes, 1 lane
+#[cfg(not(test))]
+const DEFAULT_COST: KdfCost = KdfCost {
+    m_cost: 64 * 1024,
+    t_cost: 3,
+    p_cost: 1,
+};
+
+/// Argon2's minimum memory cost, so tests going through [`seal`] (the
+/// daemon's export/import) don't spend 64 MiB and seconds per run
+#[cfg(test)]
+const DEFAULT_COST: KdfCost = KdfCost {
+    m_cost: 8,
+    t_cost: 1,
+    p_cost: 1,
+};
+
+/// Encrypt `contents` under `passphrase`
+pub fn seal(contents: &BackupContents, passphrase: &str) -> Result<Vec<u8>, BackupError> {
+    seal_with_cost(contents, passphrase, DEFAULT_COST)
+}
+
+fn seal_with_cost(
+    contents: &BackupContents,
+    passphrase: &str,
+    cost: KdfCost,
+) -> Result<Vec<u8>, BackupError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(BackupError::WeakPassphrase);
+    }
+    let salt: [u8; SALT_LEN] = crate::security_util::random_bytes();
+    let nonce: [u8; NONCE_LEN] = crate::security_util::random_bytes();
+
+    let mut archive = Vec::with_capacity(HEADER_LEN);
+    archive.extend_from_slice(MAGIC);
+    archive.push(FORMAT_VERSION);
+    for value in [cost.m_cost, cost.t_cost, cost.p_cost] {
+        archive.extend_from_slice(&value.to_le_bytes());
+    }
+    archive.extend_from_slice(&salt);
+    archive.extend_from_slice(&nonce);
+
+    let plaintext =
+        serde_json::to_vec(contents).map_err(|e| BackupError::Malformed(e.to_string()))?;
+    let ciphertext = cipher(passphrase, &salt, cost)?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &archive,
+            },
+        )
+        .map_err(|_| BackupError::Malformed("encryption failed".to_string()))?;
+    archive.extend_from_slice(&ciphertext);
+    Ok(archive)
+}
+
+/// Decrypt an archive from [`seal`]
+pub fn open(archive: &[u8], passphrase: &str) -> Result<BackupContents, BackupError> {
+    if archive.len() < HEADER_LEN || !archive.starts_with(MAGIC) {
+        return Err(BackupError::NotABackup);
+    }
+    let format = archive[MAGIC.len()];
+    if format != FORMAT_VERSION {
+        return Err(BackupError::UnsupportedFormat(format));
+    }
+    let (header, ciphertext) = archive.split_at(HEADER_LEN);
+    let u32_at =
+        |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().expect("4 bytes"));
+    let cost = KdfCost {
+        m_cost: u32_at(MAGIC.len() + 1),
+        t_cost: u32_at(MAGIC.len() + 5),
+        p_cost: u32_at(MAGIC.len() + 9),
+    };
+    if cost.m_cost > MAX_M_COST || cost.t_cost > MAX_T_COST || cost.p_cost > 16 {
+        return Err(BackupError::UnreasonableCost);
+    }
+    let salt_at = MAGIC.len() + 13;
+    let salt = &header[salt_at..salt_at + SALT_LEN];
+    let nonce = &header[salt_at + SALT_LEN..];
+
+    let plaintext = cipher(passphrase, salt, cost)?
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| BackupError::WrongPassphrase)?;
+    serde_json::from_slice(&plaintext).map_err(|e| BackupError::Malformed(e.to_string()))
+}
+
+fn cipher(passphrase: &str, salt: &[u8], cost: KdfCost) -> Result<ChaCha20Poly1305, BackupError> {
+    let params = Params::new(cost.m_cost, cost.t_cost, cost.p_cost, Some(32))
+        .map_err(|_| BackupError::UnreasonableCost)?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| BackupError::UnreasonableCost)?;
+    Ok(ChaCha20Poly1305::new(&key.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FaceStats;
+
+    const CHEAP: KdfCost = DEFAULT_COST;
+
+    fn contents() -> BackupContents {
+        BackupContents {
+            created_at: 1_735_036_800,
+            user_id: 1000,
+            faces: vec![BackedUpFace {
+                record: FaceRecord {
+                    face_id: "face_1000_1".to_string(),
+                    user_id: 1000,
+                    quality_score: 0.9,
+                    registered_at: 1_735_036_800,
+                    context: "sudo".to_string(),
+                    label: None,
+                    notes: None,
+                    allowed_contexts: Vec::new(),
+                    needs_reenrollment: false,
+                    expires_at: None,
+                    stats: FaceStats::default(),
+                },
+                embedding: Embedding {
+                    vector: vec![0.25, -0.5],
+                    metadata: hello_face_core::EmbeddingMetadata {
+                        model: "test".to_string(),
+                        model_version: "0.1.0".to_string(),
+                        extracted_at: 42,
+                        quality_score: 0.9,
+                    },
+                },
+                samples: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let archive = seal_with_cost(&contents(), "correct horse", CHEAP).unwrap();
+        let opened = open(&archive, "correct horse").unwrap();
+
+        assert_eq!(opened.user_id, 1000);
+        assert_eq!(opened.faces.len(), 1);
+        assert_eq!(opened.faces[0].record.face_id, "face_1000_1");
+        assert_eq!(opened.faces[0].embedding.vector, vec![0.25, -0.5]);
+    }
+
+    #[test]
+    fn test_wrong_passphrase_or_altered_archive_fails() {
+        let archive = seal_with_cost(&contents(), "correct horse", CHEAP).unwrap();
+        assert!(matches!(
+            open(&archive, "wrong horse!"),
+            Err(BackupError::WrongPassphrase)
+        ));
+
+        let mut altered = archive.clone();
+        *altered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            open(&altered, "correct horse"),
+            Err(BackupError::WrongPassphrase)
+        ));
+
+        // The cost is authenticated too: a cheaper one doesn't decrypt
+        let mut cheaper = archive.clone();
+        cheaper[MAGIC.len() + 1] = 7;
+        assert!(open(&cheaper, "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_rejects_weak_passphrases_and_foreign_files() {
+        assert!(matches!(
+            seal_with_cost(&contents(), "short", CHEAP),
+            Err(BackupError::WeakPassphrase)
+        ));
+        assert!(matches!(
+            open(b"{\"faces\": []}", "correct horse"),
+            Err(BackupError::NotABackup)
+        ));
+
+        let mut newer = seal_with_cost(&contents(), "correct horse", CHEAP).unwrap();
+        newer[MAGIC.len()] = FORMAT_VERSION + 1;
+        assert!(matches!(
+            open(&newer, "correct horse"),
+            Err(BackupError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_refuses_an_unreasonable_cost_before_deriving() {
+        let mut archive = seal_with_cost(&contents(), "correct horse", CHEAP).unwrap();
+        let at = MAGIC.len() + 1;
+        archive[at..at + 4].copy_from_slice(&(MAX_M_COST + 1).to_le_bytes());
+
+        assert!(matches!(
+            open(&archive, "correct horse"),
+            Err(BackupError::UnreasonableCost)
+        ));
+    }
+}
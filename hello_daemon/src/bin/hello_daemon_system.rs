@@ -7,14 +7,16 @@
 //! `hello_daemon::pam_helper::start_system_pam_helper`). Enrollment always
 //! happens through a user's own per-user `hello-daemon` session, never here.
 
+use hello_config::Config;
 use hello_daemon::camera::CameraManager;
+use hello_daemon::lockout::{self, FailureLog};
 use hello_daemon::matcher::FaceMatcher;
 use hello_daemon::pam_helper::start_system_pam_helper;
+use hello_daemon::storage_key::{self, MasterKey};
+use std::path::Path;
 use std::sync::Arc;
 use tracing::{error, info, warn};
 
-const DEFAULT_TIMEOUT_MS: u64 = 5000;
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt()
@@ -34,10 +36,55 @@ async fn main() -> anyhow::Result<()> {
         );
     }
 
-    let camera = Arc::new(CameraManager::new(DEFAULT_TIMEOUT_MS));
-    let matcher = Arc::new(FaceMatcher::new());
+    // System file and policy only — see `Config::load_system` for why
+    // root's own ~/.config is never consulted here.
+    let loaded = Config::load_system().map_err(|e| {
+        error!("{}", e);
+        e
+    })?;
+    for source in &loaded.sources {
+        info!("Configuration: {}", source.display());
+    }
+    for unknown in &loaded.unknown_keys {
+        warn!("Configuration: {} (ignored)", unknown);
+    }
+
+    let camera = Arc::new(CameraManager::new(&loaded.config));
+    let matcher = Arc::new(FaceMatcher::new(&loaded.config.recognition));
+    let settings = Arc::new(loaded.config);
+
+    // Same master key `hello-storage-key` hands user keys out from; being
+    // root, this listener derives them directly. Without it, only
+    // unencrypted embeddings can be matched here.
+    let master_key = match MasterKey::load_or_create(Path::new(storage_key::MASTER_KEY_PATH)) {
+        Ok(key) => Some(Arc::new(key)),
+        Err(e) => {
+            error!(
+                "Storage key {} unavailable: {} (only unencrypted embeddings can be matched)",
+                storage_key::MASTER_KEY_PATH,
+                e
+            );
+            None
+        }
+    };
+
+    // Failed attempts at the login screen, kept across reboots. Without the
+    // state directory they're still counted, until this listener restarts.
+    let failures = match FailureLog::open(lockout::SYSTEM_FAILURE_LOG_PATH) {
+        Ok(log) => log,
+        Err(e) => {
+            error!(
+                "Failed-attempt log {} unavailable: {} (counting in memory)",
+                lockout::SYSTEM_FAILURE_LOG_PATH,
+                e
+            );
+            FailureLog::in_memory()?
+        }
+    };
 
-    if let Err(e) = start_system_pam_helper(camera, matcher).await {
+    if let Err(e) =
+        start_system_pam_helper(camera, matcher, settings, master_key, Arc::new(failures)).await
+    {
         error!("Failed to start the system PAM listener: {}", e);
         return Err(anyhow::anyhow!(e.to_string()));
     }
@@ -0,0 +1,56 @@
+//! Linux Hello - storage key service
+//!
+//! Started at boot as root. Holds the master key users' stored embeddings
+//! are encrypted under and hands each user's `hello-daemon` its own derived
+//! key over `/run/hello-pam/keys.socket` (see `hello_daemon::storage_key`),
+//! and signs and checks their face records over
+//! `/run/hello-pam/records.socket` (see `hello_daemon::record_auth`).
+//! Kept apart from `hello-daemon-system` on purpose: that listener is only
+//! enabled for SDDM logins, while every installation needs this one.
+
+use hello_daemon::record_auth;
+use hello_daemon::storage_key::{self, MasterKey};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    info!("Starting Linux Hello storage key service");
+    info!("Version: {}", env!("CARGO_PKG_VERSION"));
+
+    let master_key =
+        MasterKey::load_or_create(Path::new(storage_key::MASTER_KEY_PATH)).map_err(|e| {
+            error!(
+                "Storage key {} unavailable: {}",
+                storage_key::MASTER_KEY_PATH,
+                e
+            );
+            e
+        })?;
+
+    let master_key = Arc::new(master_key);
+    let socket = Path::new(storage_key::KEY_SOCKET_PATH);
+    if let Err(e) = storage_key::start_key_server(socket, Arc::clone(&master_key)).await {
+        error!("Failed to start the storage key service: {}", e);
+        return Err(anyhow::anyhow!(e.to_string()));
+    }
+    let socket = Path::new(record_auth::RECORD_SOCKET_PATH);
+    if let Err(e) = record_auth::start_record_server(socket, master_key).await {
+        error!("Failed to start the record service: {}", e);
+        return Err(anyhow::anyhow!(e.to_string()));
+    }
+    info!("✓ Storage key service ready");
+
+    tokio::signal::ctrl_c().await?;
+    info!("Stopping storage key service");
+
+    Ok(())
+}
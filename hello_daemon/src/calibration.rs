@@ -0,0 +1,440 @@
+//! Threshold calibration from recorded scores
+//!
+//! The per-context thresholds in [`FaceMatcher::new`](crate::matcher::FaceMatcher::new)
+//! and the liveness gates ([`IR_LIVENESS_GATE`], [`RGB_LIVENESS_GATE`])
+//! were set by hand on one subject. With `debug.record_scores` on,
+//! `linux-hello calibrate record` runs a labelled capture session
+//! (`FaceAuthDaemon::record_calibration`): every frame is scored against
+//! the user's faces exactly as a verification would, nothing is
+//! authenticated, and one [`ScoreSample`] per frame is appended to
+//! `calibration.jsonl` in the storage directory:
+//!
+//! ```text
+//! {"label":"impostor","context":"sudo","model":"arcface 1.0","similarity":0.31,"liveness":0.74,"liveness_source":"ir"}
+//! ```
+//!
+//! A sample is a label, a context, a model and three numbers: no user ID,
+//! no face ID, no frame and no embedding, so files from several people and
+//! machines can be pooled. `linux-hello calibrate report` turns them into
+//! FAR/FRR curves with [`ScoreSet`]:
+//!
+//! - similarity: `genuine` against `impostor` samples (someone else, live);
+//! - liveness, per source: live samples (`genuine` and `impostor`) against
+//!   `spoof` ones (a photo, screen or mask of the enrolled user).
+//!
+//! Frames of one session are strongly correlated: many sessions, subjects
+//! and lighting conditions matter more than long ones.
+
+use crate::matcher::{IR_LIVENESS_GATE, RGB_LIVENESS_GATE};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use thiserror::Error;
+
+/// File name of the recorded scores in a storage directory
+pub const SCORES_FILE: &str = "calibration.jsonl";
+
+/// Longest recording session the daemon runs
+pub const MAX_SESSION_MS: u64 = 60_000;
+
+/// Frames one session records at most
+pub const MAX_SESSION_SAMPLES: usize = 500;
+
+#[derive(Debug, Error)]
+pub enum CalibrationError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("line {line}: {source}")]
+    Parse {
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// Who was in front of the camera during a recording session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleLabel {
+    /// The enrolled user, live
+    Genuine,
+
+    /// Someone else, live, scored against the user's faces
+    Impostor,
+
+    /// A photo, screen or mask of the enrolled user
+    Spoof,
+}
+
+impl SampleLabel {
+    /// Whether a real face was in front of the camera
+    pub fn is_live(self) -> bool {
+        self != SampleLabel::Spoof
+    }
+}
+
+/// Which liveness score gated the frame, as in `match_with_liveness`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LivenessSource {
+    Ir,
+    Rgb,
+}
+
+impl LivenessSource {
+    /// The gate `match_with_liveness` applies to this source
+    pub fn gate(self) -> f32 {
+        match self {
+            LivenessSource::Ir => IR_LIVENESS_GATE,
+            LivenessSource::Rgb => RGB_LIVENESS_GATE,
+        }
+    }
+}
+
+/// One scored frame of a recording session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreSample {
+    pub label: SampleLabel,
+    pub context: String,
+
+    /// Embedding model and version: scores of different models don't mix
+    pub model: String,
+
+    /// Best similarity against the user's faces
+    pub similarity: f32,
+
+    pub liveness: f32,
+    pub liveness_source: LivenessSource,
+}
+
+impl ScoreSample {
+    /// A frame's scores, with the IR liveness when there is one and the
+    /// RGB fallback otherwise — the one `match_with_liveness` gates on
+    pub fn new(
+        label: SampleLabel,
+        context: &str,
+        model: String,
+        similarity: f32,
+        ir_liveness: Option<f32>,
+        rgb_liveness: f32,
+    ) -> Self {
+        let (liveness, liveness_source) = match ir_liveness {
+            Some(ir) => (ir, LivenessSource::Ir),
+            None => (rgb_liveness, LivenessSource::Rgb),
+        };
+        Self {
+            label,
+            context: context.to_string(),
+            model,
+            similarity,
+            liveness: liveness.clamp(0.0, 1.0),
+            liveness_source,
+        }
+    }
+}
+
+/// Append `samples` to the file at `path`, creating it (mode `0600`)
+pub fn append_samples(path: &Path, samples: &[ScoreSample]) -> Result<(), CalibrationError> {
+    let mut lines = String::new();
+    for sample in samples {
+        lines += &serde_json::to_string(sample).expect("samples always serialize");
+        lines.push('\n');
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(lines.as_bytes())?;
+    Ok(())
+}
+
+/// Every sample in the file at `path`; blank lines are skipped
+pub fn load_samples(path: &Path) -> Result<Vec<ScoreSample>, CalibrationError> {
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let mut samples = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        samples.push(
+            serde_json::from_str(&line).map_err(|source| CalibrationError::Parse {
+                line: index + 1,
+                source,
+            })?,
+        );
+    }
+    Ok(samples)
+}
+
+/// Error rates at one threshold; a score at or above it is accepted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OperatingPoint {
+    pub threshold: f32,
+
+    /// Share of impostor (or spoof) scores accepted
+    pub far: f64,
+
+    /// Share of genuine (or live) scores rejected
+    pub frr: f64,
+}
+
+/// Equal error rate: where FAR and FRR cross
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Eer {
+    pub threshold: f32,
+    pub rate: f64,
+}
+
+/// Scores that should be accepted against scores that shouldn't, sorted
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScoreSet {
+    pub genuine: Vec<f32>,
+    pub impostor: Vec<f32>,
+}
+
+impl ScoreSet {
+    pub fn new(mut genuine: Vec<f32>, mut impostor: Vec<f32>) -> Self {
+        genuine.retain(|score| !score.is_nan());
+        impostor.retain(|score| !score.is_nan());
+        genuine.sort_by(f32::total_cmp);
+        impostor.sort_by(f32::total_cmp);
+        Self { genuine, impostor }
+    }
+
+    /// Similarity scores of `samples` from `context`, or from all of them
+    pub fn similarity(samples: &[ScoreSample], context: Option<&str>) -> Self {
+        let in_context = |s: &&ScoreSample| context.is_none_or(|c| s.context == c);
+        let scores = |label| {
+            samples
+                .iter()
+                .filter(in_context)
+                .filter(|s| s.label == label)
+                .map(|s| s.similarity)
+                .collect()
+        };
+        Self::new(scores(SampleLabel::Genuine), scores(SampleLabel::Impostor))
+    }
+
+    /// Liveness scores of `samples` gated by `source`: live against spoofs
+    pub fn liveness(samples: &[ScoreSample], source: LivenessSource) -> Self {
+        let scores = |live| {
+            samples
+                .iter()
+                .filter(|s| s.liveness_source == source && s.label.is_live() == live)
+                .map(|s| s.liveness)
+                .collect()
+        };
+        Self::new(scores(true), scores(false))
+    }
+
+    /// Whether there is anything on both sides to compare
+    pub fn is_usable(&self) -> bool {
+        !self.genuine.is_empty() && !self.impostor.is_empty()
+    }
+
+    /// The error rates at `threshold`
+    pub fn at(&self, threshold: f32) -> OperatingPoint {
+        let below = |scores: &[f32]| scores.partition_point(|&score| score < threshold);
+        let rate = |count: usize, total: usize| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f64 / total as f64
+            }
+        };
+        OperatingPoint {
+            threshold,
+            far: rate(
+                self.impostor.len() - below(&self.impostor),
+                self.impostor.len(),
+            ),
+            frr: rate(below(&self.genuine), self.genuine.len()),
+        }
+    }
+
+    /// The ROC/DET curve: one point at every distinct score, by increasing
+    /// threshold, then one just above the highest where nothing is accepted
+    pub fn curve(&self) -> Vec<OperatingPoint> {
+        let mut thresholds: Vec<f32> = self.genuine.iter().chain(&self.impostor).copied().collect();
+        thresholds.sort_by(f32::total_cmp);
+        thresholds.dedup();
+        if let Some(&highest) = thresholds.last() {
+            thresholds.push(highest.next_up());
+        }
+        thresholds.into_iter().map(|t| self.at(t)).collect()
+    }
+
+    /// Where the curve's FAR and FRR are closest, `None` without scores on
+    /// both sides
+    pub fn eer(&self) -> Option<Eer> {
+        if !self.is_usable() {
+            return None;
+        }
+        let curve = self.curve();
+        // FAR only falls and FRR only rises along the curve
+        let crossing = curve.iter().position(|p| p.far <= p.frr)?;
+        let point = curve[crossing.saturating_sub(1)..=crossing]
+            .iter()
+            .min_by(|a, b| (a.far - a.frr).abs().total_cmp(&(b.far - b.frr).abs()))?;
+        Some(Eer {
+            threshold: point.threshold,
+            rate: (point.far + point.frr) / 2.0,
+        })
+    }
+
+    /// The lowest threshold whose FAR is at most `target_far`, i.e. the one
+    /// rejecting the fewest genuine scores for it
+    pub fn threshold_for_far(&self, target_far: f64) -> Option<OperatingPoint> {
+        if !self.is_usable() {
+            return None;
+        }
+        self.curve().into_iter().find(|p| p.far <= target_far)
+    }
+
+    /// A threshold for `target_far` as it would be written in config: two
+    /// decimals, the lowest keeping the error rates of
+    /// [`Self::threshold_for_far`] — anything above the next score down
+    /// has the same — and at least `floor`
+    pub fn recommend(&self, target_far: f64, floor: f32) -> Option<OperatingPoint> {
+        if !self.is_usable() {
+            return None;
+        }
+        let curve = self.curve();
+        let index = curve.iter().position(|p| p.far <= target_far)?;
+        let hundredths = match index.checked_sub(1) {
+            // The first hundredth above the highest score to reject
+            Some(below) => {
+                let below = curve[below].threshold;
+                let mut hundredths = (below * 100.0).floor() + 1.0;
+                if hundredths / 100.0 <= below {
+                    hundredths += 1.0;
+                }
+                hundredths
+            }
+            None => (curve[0].threshold * 100.0).floor(),
+        };
+        Some(self.at((hundredths / 100.0).max(floor)))
+    }
+
+    /// The lowest FAR the impostor scores can back up at 95% confidence
+    /// ("rule of three"): with none accepted out of `n`, the true rate can
+    /// still be up to about `3 / n`
+    pub fn supported_far(&self) -> f64 {
+        3.0 / self.impostor.len().max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn sample(label: SampleLabel, context: &str, similarity: f32) -> ScoreSample {
+        ScoreSample::new(label, context, "test 1".into(), similarity, Some(0.9), 0.1)
+    }
+
+    #[test]
+    fn rates_curve_and_eer_follow_the_scores() {
+        let set = ScoreSet::new(vec![0.7, 0.8, 0.6, 0.9], vec![0.1, 0.65, 0.2, 0.3]);
+        let point = set.at(0.65);
+        assert_eq!((point.far, point.frr), (0.25, 0.25));
+        assert_eq!(set.at(0.0).far, 1.0);
+
+        let curve = set.curve();
+        assert_eq!(curve.len(), 9);
+        assert_eq!((curve[0].far, curve[0].frr), (1.0, 0.0));
+        let last = curve.last().unwrap();
+        assert!(last.threshold > 0.9);
+        assert_eq!((last.far, last.frr), (0.0, 1.0));
+
+        let eer = set.eer().unwrap();
+        assert_eq!(eer.threshold, 0.65);
+        assert_eq!(eer.rate, 0.25);
+
+        // No impostor at or above 0.7, the lowest threshold that gets there
+        assert_eq!(set.threshold_for_far(0.0).unwrap().threshold, 0.7);
+        assert_eq!(set.threshold_for_far(0.25).unwrap().threshold, 0.6);
+        assert_eq!(set.supported_far(), 0.75);
+
+        assert_eq!(ScoreSet::new(vec![0.9], vec![]).eer(), None);
+    }
+
+    #[test]
+    fn recommendations_are_rounded_to_hundredths_and_floored() {
+        let set = ScoreSet::new(vec![0.62, 0.8], vec![0.3, 0.612]);
+        let point = set.recommend(0.0, 0.35).unwrap();
+        assert_eq!(point.threshold, 0.62);
+        assert_eq!((point.far, point.frr), (0.0, 0.0));
+
+        let set = ScoreSet::new(vec![0.2, 0.3], vec![0.1]);
+        assert_eq!(set.recommend(0.0, 0.35).unwrap().threshold, 0.35);
+
+        // Just above the spoof scores, not up at the next live one
+        let set = ScoreSet::new(vec![0.3005, 0.9], vec![0.1, 0.2497]);
+        let point = set.recommend(0.0, 0.0).unwrap();
+        assert_eq!(point.threshold, 0.25);
+        assert_eq!((point.far, point.frr), (0.0, 0.0));
+    }
+
+    #[test]
+    fn samples_split_by_context_label_and_liveness_source() {
+        let mut samples = vec![
+            sample(SampleLabel::Genuine, "sudo", 0.8),
+            sample(SampleLabel::Impostor, "sudo", 0.3),
+            sample(SampleLabel::Genuine, "login", 0.7),
+            sample(SampleLabel::Spoof, "login", 0.75),
+        ];
+        samples[3].liveness = 0.05;
+        samples.push(ScoreSample::new(
+            SampleLabel::Genuine,
+            "login",
+            "test 1".into(),
+            0.7,
+            None,
+            0.9,
+        ));
+
+        assert_eq!(
+            ScoreSet::similarity(&samples, Some("sudo")),
+            ScoreSet::new(vec![0.8], vec![0.3])
+        );
+        assert_eq!(ScoreSet::similarity(&samples, None).genuine.len(), 3);
+        assert_eq!(
+            ScoreSet::liveness(&samples, LivenessSource::Ir),
+            ScoreSet::new(vec![0.9, 0.9, 0.9], vec![0.05])
+        );
+        assert_eq!(
+            ScoreSet::liveness(&samples, LivenessSource::Rgb),
+            ScoreSet::new(vec![0.9], vec![])
+        );
+    }
+
+    #[test]
+    fn samples_are_appended_privately_and_read_back() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join(SCORES_FILE);
+        let first = vec![sample(SampleLabel::Genuine, "sudo", 0.8)];
+        let second = vec![
+            sample(SampleLabel::Impostor, "sudo", 0.3),
+            sample(SampleLabel::Spoof, "login", 0.5),
+        ];
+        append_samples(&path, &first).unwrap();
+        append_samples(&path, &second).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let loaded = load_samples(&path).unwrap();
+        assert_eq!(loaded, [first, second].concat());
+
+        std::fs::write(&path, "{\"label\":\"genuine\"}\n").unwrap();
+        assert!(matches!(
+            load_samples(&path),
+            Err(CalibrationError::Parse { line: 1, .. })
+        ));
+    }
+}
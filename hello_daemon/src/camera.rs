@@ -4,8 +4,9 @@
 //! and pass them to the recognition engine
 
 use crate::capture_stream::CaptureFrameEvent;
-use hello_camera::{Frame, FrameFormat};
-use hello_face_core::{Embedding, EmbeddingExtractor, FaceDetector};
+use crate::debug_dump::AttemptRecorder;
+use hello_camera::{CameraInventory, CaptureMode, Frame, FrameFormat};
+use hello_face_core::{Embedding, EmbeddingExtractor, FaceDetector, FaceRegion};
 use std::os::unix::io::AsRawFd;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -112,6 +113,9 @@ pub struct CameraManager {
     pub rgb_device: String,
     /// Path of the IR device (if detected)
     pub ir_device: Option<String>,
+    /// Requested capture mode (`[detection]` width/height/fps/format); the
+    /// closest mode the camera supports is negotiated at each capture
+    pub mode: CaptureMode,
     /// Face detector (SCRFD or fallback)
     detector: Arc<Box<dyn FaceDetector>>,
     /// Embedding extractor (ArcFace or fallback)
@@ -125,27 +129,58 @@ pub struct CameraManager {
 }
 
 impl CameraManager {
-    /// Create a camera manager by scanning available devices
-    pub fn new(default_timeout_ms: u64) -> Self {
-        let inventory = hello_camera::scan_cameras();
+    /// Create a camera manager for `config.detection`: the pinned
+    /// `camera_device` if set, otherwise whatever scanning finds.
+    /// `capture.default_timeout_ms` applies to any capture request that
+    /// doesn't carry its own timeout.
+    pub fn new(config: &hello_config::Config) -> Self {
+        let inventory = select_devices(hello_camera::scan_cameras(), &config.detection);
+        let mode = capture_mode(&config.detection);
         info!(
-            "Camera inventory: RGB={}, IR={}",
+            "Camera inventory: RGB={}, IR={}, requested mode {}",
             inventory.rgb_device,
-            inventory.ir_device.as_deref().unwrap_or("none")
+            inventory.ir_device.as_deref().unwrap_or("none"),
+            mode
         );
         let models_dir = hello_face_core::default_models_dir();
         let detector = Arc::new(hello_face_core::create_detector(&models_dir));
         let extractor = Arc::new(hello_face_core::create_extractor(&models_dir));
         Self {
-            default_timeout_ms,
+            default_timeout_ms: config.capture.default_timeout_ms,
             rgb_device: inventory.rgb_device,
             ir_device: inventory.ir_device,
+            mode,
             detector,
             extractor,
             lock_path: None,
         }
     }
 
+    /// Re-open the camera for a reloaded configuration: re-select the
+    /// devices and take the new mode and capture timeout, but keep the
+    /// already-loaded detector/extractor (and lock file override) — loading
+    /// the ONNX models again would stall the reload for nothing, since no
+    /// config key selects them.
+    pub fn reconfigured(&self, config: &hello_config::Config) -> Self {
+        let inventory = select_devices(hello_camera::scan_cameras(), &config.detection);
+        let mode = capture_mode(&config.detection);
+        info!(
+            "Camera re-opened: RGB={}, IR={}, requested mode {}",
+            inventory.rgb_device,
+            inventory.ir_device.as_deref().unwrap_or("none"),
+            mode
+        );
+        Self {
+            default_timeout_ms: config.capture.default_timeout_ms,
+            rgb_device: inventory.rgb_device,
+            ir_device: inventory.ir_device,
+            mode,
+            detector: Arc::clone(&self.detector),
+            extractor: Arc::clone(&self.extractor),
+            lock_path: self.lock_path.clone(),
+        }
+    }
+
     /// Build a `CameraManager` around injected detector/extractor fakes and
     /// an isolated lock file, bypassing `create_detector`/`create_extractor`
     /// (and the real ONNX/model-file dependency that comes with them)
@@ -163,12 +198,20 @@ impl CameraManager {
             default_timeout_ms: 1000,
             rgb_device: rgb_device.into(),
             ir_device: None,
+            mode: CaptureMode::default(),
             detector: Arc::new(detector),
             extractor: Arc::new(extractor),
             lock_path: Some(lock_path),
         }
     }
 
+    /// Whether `template` was enrolled with the embedding model this
+    /// manager extracts with, i.e. can still match — see
+    /// `EmbeddingExtractor::produced`
+    pub fn is_current_template(&self, template: &Embedding) -> bool {
+        self.extractor.produced(template)
+    }
+
     /// Resolve the lock file path to use: the test override if set, else
     /// the same `camera_lock_path()` production default as before.
     fn resolved_lock_path(&self) -> std::path::PathBuf {
@@ -212,6 +255,7 @@ impl CameraManager {
 
         let rgb_device = self.rgb_device.clone();
         let ir_device = self.ir_device.clone();
+        let mode = self.mode;
         let lock_path = self.resolved_lock_path();
 
         // Acquired once up front and shared (via Arc) with both capture
@@ -239,6 +283,7 @@ impl CameraManager {
 
             let rgb_result = hello_camera::capture_rgb_stream_v4l2(
                 &rgb_device,
+                &mode,
                 num_frames,
                 timeout,
                 |data, w, h| {
@@ -287,6 +332,7 @@ impl CameraManager {
 
                 let ir_result = hello_camera::capture_gray_stream_v4l2(
                     &ir_path,
+                    &mode,
                     num_frames,
                     timeout,
                     |data, w, h| {
@@ -359,7 +405,7 @@ impl CameraManager {
                     frame.width,
                     frame.height,
                 ) {
-                    Some((embedding, _rgb_liveness)) => embedding,
+                    Some((embedding, _rgb_liveness, _face)) => embedding,
                     // No face detected or extraction failed: empty marker
                     // (quality 0). Never use a fake embedding that would
                     // skew the comparison.
@@ -446,6 +492,9 @@ impl CameraManager {
     /// Stops as soon as `on_frame` returns `true` ("I've decided, stop") or
     /// the deadline elapses.
     ///
+    /// With a `recorder` (`[debug]` dumps on), every captured frame is
+    /// recorded on it, with its detection if any, before being scored.
+    ///
     /// Used by `verify()`'s attempt loop so the camera stays visibly
     /// engaged for the whole window instead of a fixed quick burst —
     /// `capture_frames` is kept unchanged for enrollment's fixed-sample-
@@ -453,6 +502,7 @@ impl CameraManager {
     pub async fn capture_until<F>(
         &self,
         timeout_ms: u64,
+        recorder: Option<Arc<AttemptRecorder>>,
         mut on_frame: F,
     ) -> Result<(), CameraError>
     where
@@ -473,6 +523,7 @@ impl CameraManager {
 
         let rgb_device = self.rgb_device.clone();
         let ir_device = self.ir_device.clone();
+        let mode = self.mode;
         let detector = Arc::clone(&self.detector);
         let extractor = Arc::clone(&self.extractor);
         let lock_path = self.resolved_lock_path();
@@ -517,6 +568,7 @@ impl CameraManager {
                 let mut best: Option<f32> = None;
                 let _ = hello_camera::capture_gray_stream_v4l2(
                     &ir_path,
+                    &mode,
                     IR_LIVENESS_SAMPLES,
                     2000,
                     |data, w, h| {
@@ -549,19 +601,25 @@ impl CameraManager {
         let rgb_task = tokio::task::spawn_blocking(move || {
             let _lock = rgb_lock;
             let mut frame_index: u32 = 0;
-            let result =
-                hello_camera::capture_rgb_stream_until(&rgb_device, timeout, |data, w, h| {
+            let result = hello_camera::capture_rgb_stream_until(
+                &rgb_device,
+                &mode,
+                timeout,
+                |data, w, h| {
                     frame_index += 1;
-                    if let Some((embedding, rgb_liveness)) =
-                        score_frame(&**detector, &**extractor, frame_index, &data, w, h)
-                    {
+                    let scored = score_frame(&**detector, &**extractor, frame_index, &data, w, h);
+                    if let Some(recorder) = &recorder {
+                        recorder.record_frame(frame_index, &data, w, h, scored.as_ref());
+                    }
+                    if let Some((embedding, rgb_liveness, _face)) = scored {
                         if frame_tx.blocking_send((embedding, rgb_liveness)).is_err() {
                             // Consumer is gone (decided already) — stop.
                             return true;
                         }
                     }
                     rgb_stop.load(Ordering::Acquire)
-                });
+                },
+            );
 
             if let Err(e) = result {
                 // Same fail-safe spirit as capture_frames: a capture hiccup
@@ -645,11 +703,13 @@ impl CameraManager {
         );
 
         let rgb_device = self.rgb_device.clone();
+        let mode = self.mode;
         let mut frame_num: u32 = 0;
 
         let v4l2_result = tokio::task::block_in_place(|| {
             hello_camera::capture_rgb_stream_v4l2(
                 &rgb_device,
+                &mode,
                 num_frames,
                 timeout_ms,
                 |rgb_data, width, height| {
@@ -729,7 +789,8 @@ impl CameraManager {
 /// buffer, without needing frames to flow through a real or simulated
 /// camera at all.
 ///
-/// Returns `None` if no face was detected, or detection/extraction failed —
+/// Returns the embedding, the RGB liveness score and the detection they
+/// came from, or `None` if no face was detected, or detection/extraction failed —
 /// callers treat that as "keep going, no verdict from this frame" rather
 /// than a hard error, same as before this was extracted.
 pub(crate) fn score_frame(
@@ -739,7 +800,7 @@ pub(crate) fn score_frame(
     data: &[u8],
     w: u32,
     h: u32,
-) -> Option<(Embedding, f32)> {
+) -> Option<(Embedding, f32, FaceRegion)> {
     let faces = match detector.detect(data, w, h, 3) {
         Ok(f) => f,
         Err(e) => {
@@ -761,7 +822,48 @@ pub(crate) fn score_frame(
     // hello_face_core::liveness::rgb_liveness_score. Always computed since
     // the RGB frame and detected face are already in hand at this point.
     let rgb_liveness = hello_face_core::liveness::rgb_liveness_score(data, w, h, &best_face);
-    Some((embedding, rgb_liveness))
+    Some((embedding, rgb_liveness, best_face))
+}
+
+/// Apply `[detection].camera_device` to a scan result: a pinned device
+/// replaces the scanned RGB camera (for machines with more than one — a
+/// laptop's own plus a docking station's), the IR camera still comes from
+/// the scan. A pinned path that doesn't exist is kept anyway — the capture
+/// then fails the same way an unplugged camera does — but logged, since
+/// it's usually a typo or a renumbered device.
+pub fn select_devices(
+    mut inventory: CameraInventory,
+    detection: &hello_config::DetectionConfig,
+) -> CameraInventory {
+    let Some(device) = &detection.camera_device else {
+        return inventory;
+    };
+    if !std::path::Path::new(device).exists() {
+        warn!("detection.camera_device {} does not exist", device);
+    }
+    if inventory.ir_device.as_deref() == Some(device.as_str()) {
+        warn!(
+            "detection.camera_device {} was detected as the IR camera, using it as RGB only",
+            device
+        );
+        inventory.ir_device = None;
+    }
+    inventory.rgb_device = device.clone();
+    inventory
+}
+
+/// The capture mode `[detection]` asks for
+pub fn capture_mode(detection: &hello_config::DetectionConfig) -> CaptureMode {
+    CaptureMode {
+        width: detection.width,
+        height: detection.height,
+        fps: detection.fps,
+        format: match detection.preferred_format {
+            hello_config::PixelFormat::Rgb8 => FrameFormat::Rgb8,
+            hello_config::PixelFormat::Gray8 => FrameFormat::Gray8,
+            hello_config::PixelFormat::Mjpeg => FrameFormat::MjPeg,
+        },
+    }
 }
 
 #[cfg(test)]
@@ -781,11 +883,69 @@ mod tests {
             std::env::set_var("LINUX_HELLO_MODELS_DIR", empty_models_dir.path());
         }
 
-        let camera = CameraManager::new(5000);
+        let camera = CameraManager::new(&hello_config::Config::default());
         // The scan must not panic even without /dev/video*
         assert!(!camera.rgb_device.is_empty());
     }
 
+    fn inventory() -> CameraInventory {
+        CameraInventory {
+            rgb_device: "/dev/video0".to_string(),
+            ir_device: Some("/dev/video2".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_select_devices_keeps_the_scan_without_a_pinned_device() {
+        let selected = select_devices(inventory(), &hello_config::DetectionConfig::default());
+        assert_eq!(selected.rgb_device, "/dev/video0");
+        assert_eq!(selected.ir_device.as_deref(), Some("/dev/video2"));
+    }
+
+    #[test]
+    fn test_select_devices_uses_the_pinned_camera_device() {
+        let detection = hello_config::DetectionConfig {
+            camera_device: Some("/dev/video4".to_string()),
+            ..Default::default()
+        };
+        let selected = select_devices(inventory(), &detection);
+        assert_eq!(selected.rgb_device, "/dev/video4");
+        assert_eq!(selected.ir_device.as_deref(), Some("/dev/video2"));
+
+        // Pinning the IR camera as RGB leaves no IR camera
+        let detection = hello_config::DetectionConfig {
+            camera_device: Some("/dev/video2".to_string()),
+            ..Default::default()
+        };
+        let selected = select_devices(inventory(), &detection);
+        assert_eq!(selected.rgb_device, "/dev/video2");
+        assert_eq!(selected.ir_device, None);
+    }
+
+    #[test]
+    fn test_capture_mode_follows_the_detection_settings() {
+        let detection = hello_config::DetectionConfig {
+            preferred_format: hello_config::PixelFormat::Mjpeg,
+            width: 1280,
+            height: 720,
+            fps: 15,
+            ..Default::default()
+        };
+        assert_eq!(
+            capture_mode(&detection),
+            CaptureMode {
+                width: 1280,
+                height: 720,
+                fps: 15,
+                format: FrameFormat::MjPeg,
+            }
+        );
+        assert_eq!(
+            capture_mode(&hello_config::DetectionConfig::default()),
+            CaptureMode::default()
+        );
+    }
+
     #[test]
     fn test_capture_frames_fallback() {
         // Verify that frame padding works without hardware
@@ -946,7 +1106,7 @@ mod tests {
         let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
         let calls_clone = calls.clone();
         let result = camera
-            .capture_until(200, move |_embedding, _ir, _rgb| {
+            .capture_until(200, None, move |_embedding, _ir, _rgb| {
                 calls_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
                 false
             })
@@ -972,8 +1132,9 @@ mod tests {
         let extractor = FakeExtractor::with_vector(vec![0.6, 0.8, 0.0], 0.95);
         let frame = blank_rgb_frame(64, 64);
 
-        let (embedding, rgb_liveness) =
+        let (embedding, rgb_liveness, face) =
             score_frame(&detector, &extractor, 0, &frame, 64, 64).expect("face should be found");
+        assert_eq!(face.bounding_box, default_face_region(64, 64).bounding_box);
         assert_eq!(embedding.vector, vec![0.6, 0.8, 0.0]);
         assert!((0.0..=1.0).contains(&rgb_liveness));
     }
@@ -1022,7 +1183,7 @@ mod tests {
     #[tokio::test]
     #[ignore = "touches real camera hardware; run manually with --ignored"]
     async fn test_capture_until_overlaps_ir_and_rgb_on_real_hardware() {
-        let camera = CameraManager::new(5000);
+        let camera = CameraManager::new(&hello_config::Config::default());
         if !camera.is_available() {
             eprintln!("skipping: no RGB camera detected on this machine");
             return;
@@ -1033,7 +1194,7 @@ mod tests {
         let frame_log_clone = Arc::clone(&frame_log);
 
         let result = camera
-            .capture_until(3000, move |_embedding, ir_liveness, rgb_liveness| {
+            .capture_until(3000, None, move |_embedding, ir_liveness, rgb_liveness| {
                 frame_log_clone.lock().unwrap().push((
                     start.elapsed().as_millis(),
                     ir_liveness,
@@ -0,0 +1,219 @@
+//! Reloading the configuration without restarting `hello-daemon`
+//!
+//! Three triggers share one code path ([`reload`]):
+//! - an edit to any file `Config::load` reads (system config, the user's
+//!   config, the administrator policy), noticed by [`spawn_file_watcher`];
+//! - `SIGHUP` (`systemctl --user reload hello-daemon`), see
+//!   [`spawn_sighup_handler`];
+//! - the `ReloadConfig` D-Bus method, for the config GUI to call right
+//!   after it saves.
+//!
+//! Each re-reads every layer, applies the result to the running daemon
+//! (`FaceAuthDaemon::apply_config`: thresholds swapped in the shared
+//! `FaceMatcher`, camera re-opened if `[detection]` changed) and, if
+//! anything changed, emits a `ConfigChanged` D-Bus signal carrying a
+//! [`ConfigChange`]. A file that fails to load is logged and the running
+//! configuration is kept — unlike at startup, where it's fatal, a typo
+//! saved mid-edit shouldn't take face auth down.
+//!
+//! The files are polled rather than watched with inotify, for the same
+//! reason `screenlock` polls: it's a few `stat` calls every couple of
+//! seconds, and it keeps working when an editor replaces the file (new
+//! inode) or the user's config directory doesn't exist yet.
+
+use crate::dbus::{FaceAuthInterface, OBJECT_PATH};
+use crate::{DaemonError, FaceAuthDaemon};
+use hello_config::Config;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+/// How often [`spawn_file_watcher`] checks the config files for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What a reload changed — the `ConfigChanged` signal's payload and the
+/// `ReloadConfig` method's return value (as JSON).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    /// Top-level sections (`recognition`, `detection`, ...) whose effective
+    /// values differ from before, in `config.toml` order
+    pub changed_sections: Vec<String>,
+
+    /// Whether the camera was re-opened (`[detection]` or
+    /// `capture.default_timeout_ms` changed)
+    pub camera_reopened: bool,
+}
+
+impl ConfigChange {
+    /// Nothing changed — the files were touched but say the same thing
+    pub fn is_empty(&self) -> bool {
+        self.changed_sections.is_empty()
+    }
+}
+
+/// Names of the top-level sections that differ between `old` and `new`.
+pub fn changed_sections(old: &Config, new: &Config) -> Vec<String> {
+    let sections = [
+        ("authentication", old.authentication != new.authentication),
+        ("detection", old.detection != new.detection),
+        ("recognition", old.recognition != new.recognition),
+        ("capture", old.capture != new.capture),
+        ("behavior", old.behavior != new.behavior),
+        ("advanced", old.advanced != new.advanced),
+        ("features", old.features != new.features),
+        ("backup", old.backup != new.backup),
+        ("debug", old.debug != new.debug),
+    ];
+    sections
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// Re-read every configuration layer and apply it to `daemon`. Waits for
+/// any capture in progress to finish first (it holds the read lock), so an
+/// attempt never sees half of an old configuration and half of a new one.
+pub async fn reload(daemon: &RwLock<FaceAuthDaemon>) -> Result<ConfigChange, DaemonError> {
+    let loaded = Config::load()?;
+    for unknown in &loaded.unknown_keys {
+        warn!("Configuration: {} (ignored)", unknown);
+    }
+    for overridden in &loaded.locked_overrides {
+        warn!("Configuration: {} (ignored)", overridden);
+    }
+
+    let change = daemon.write().await.apply_config(loaded);
+    if change.is_empty() {
+        info!("Configuration reloaded, nothing changed");
+    } else {
+        info!(
+            "Configuration reloaded, changed: {}",
+            change.changed_sections.join(", ")
+        );
+    }
+    Ok(change)
+}
+
+/// Emit `ConfigChanged` from the interface registered at [`OBJECT_PATH`].
+pub async fn emit_config_changed(
+    connection: &zbus::Connection,
+    change: &ConfigChange,
+) -> zbus::Result<()> {
+    let iface = connection
+        .object_server()
+        .interface::<_, FaceAuthInterface>(OBJECT_PATH)
+        .await?;
+    let change_json = serde_json::to_string(change)
+        .map_err(|e| zbus::Error::Failure(format!("ConfigChange serialization: {}", e)))?;
+    FaceAuthInterface::config_changed(iface.signal_emitter(), &change_json).await
+}
+
+/// [`reload`], then [`emit_config_changed`] if anything changed. Errors
+/// are logged, not returned — both background triggers just keep going.
+async fn reload_and_notify(
+    daemon: &RwLock<FaceAuthDaemon>,
+    connection: &zbus::Connection,
+    trigger: &str,
+) {
+    info!("Reloading configuration ({})", trigger);
+    match reload(daemon).await {
+        Ok(change) if !change.is_empty() => {
+            if let Err(e) = emit_config_changed(connection, &change).await {
+                error!("ConfigChanged emission error: {}", e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => error!(
+            "Configuration reload failed, keeping the current one: {}",
+            e
+        ),
+    }
+}
+
+/// Modification time and size of each path, `None` for a missing file —
+/// a file appearing or disappearing is a change too.
+fn fingerprint(paths: &[PathBuf]) -> Vec<Option<(SystemTime, u64)>> {
+    paths
+        .iter()
+        .map(|path| {
+            let metadata = std::fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// Poll every file `Config::load` reads and reload when any of them
+/// changes.
+pub fn spawn_file_watcher(
+    daemon: Arc<RwLock<FaceAuthDaemon>>,
+    connection: zbus::Connection,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let paths = hello_config::layer_paths();
+        let mut last = fingerprint(&paths);
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let current = fingerprint(&paths);
+            if current != last {
+                last = current;
+                reload_and_notify(&daemon, &connection, "configuration file changed").await;
+            }
+        }
+    })
+}
+
+/// Reload on every `SIGHUP`. Fails only if the signal handler can't be
+/// installed.
+pub fn spawn_sighup_handler(
+    daemon: Arc<RwLock<FaceAuthDaemon>>,
+    connection: zbus::Connection,
+) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            reload_and_notify(&daemon, &connection, "SIGHUP").await;
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_sections_lists_only_sections_that_differ() {
+        let old = Config::default();
+        assert!(changed_sections(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        new.recognition.similarity_threshold = 0.7;
+        new.detection.camera_device = Some("/dev/video2".to_string());
+        assert_eq!(
+            changed_sections(&old, &new),
+            vec!["detection", "recognition"]
+        );
+    }
+
+    #[test]
+    fn fingerprint_notices_a_file_appearing_and_changing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let paths = vec![path.clone()];
+
+        let missing = fingerprint(&paths);
+        assert_eq!(missing, vec![None]);
+
+        std::fs::write(&path, "[recognition]\n").unwrap();
+        let created = fingerprint(&paths);
+        assert_ne!(created, missing);
+
+        std::fs::write(&path, "[recognition]\nsimilarity_threshold = 0.7\n").unwrap();
+        assert_ne!(fingerprint(&paths), created);
+    }
+}
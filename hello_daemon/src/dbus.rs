@@ -2,13 +2,20 @@
 //!
 //! Wrapper that exposes the daemon's operations via D-Bus
 
-use crate::dbus_interface::{DeleteFaceRequest, RegisterFaceRequest, VerifyRequest};
+use crate::dbus_interface::{
+    DeleteFaceRequest, ExportFacesRequest, ImportFacesRequest, RecordCalibrationRequest,
+    RegisterFaceRequest, ResetAdaptationRequest, UpdateFaceRequest, VerifyRequest,
+};
 use crate::dbus_signals::StreamingSignalEmitter;
 use crate::FaceAuthDaemon;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info};
 use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+/// Object path `FaceAuthInterface` is served at on the session bus
+pub const OBJECT_PATH: &str = "/com/linuxhello/FaceAuth";
 
 /// D-Bus wrapper around the daemon
 pub struct FaceAuthInterface {
@@ -115,6 +122,102 @@ impl FaceAuthInterface {
         }
     }
 
+    /// Drop what adaptation added to one or all faces; returns how many
+    /// adapted samples were dropped
+    ///
+    /// # Arguments
+    /// * `request_json` - JSON string of ResetAdaptationRequest
+    pub async fn reset_adaptation(&self, request_json: &str) -> zbus::fdo::Result<u32> {
+        debug!("D-Bus call: reset_adaptation");
+
+        let request: ResetAdaptationRequest = parse_request(request_json)?;
+
+        let daemon = self.daemon.read().await;
+        match daemon.reset_adaptation(request).await {
+            Ok(dropped) => {
+                info!("reset_adaptation succeeded");
+                Ok(dropped as u32)
+            }
+            Err(e) => {
+                error!("reset_adaptation failed: {}", e);
+                Err(zbus::fdo::Error::Failed(e.to_string()))
+            }
+        }
+    }
+
+    /// Record labelled calibration scores for a while; returns how many
+    /// frames were recorded
+    ///
+    /// # Arguments
+    /// * `request_json` - JSON string of RecordCalibrationRequest
+    pub async fn record_calibration(&self, request_json: &str) -> zbus::fdo::Result<u32> {
+        debug!("D-Bus call: record_calibration");
+
+        let request: RecordCalibrationRequest = parse_request(request_json)?;
+
+        let daemon = self.daemon.read().await;
+        match daemon.record_calibration(request).await {
+            Ok(recorded) => {
+                info!("record_calibration succeeded");
+                Ok(recorded as u32)
+            }
+            Err(e) => {
+                error!("record_calibration failed: {}", e);
+                Err(zbus::fdo::Error::Failed(e.to_string()))
+            }
+        }
+    }
+
+    /// Change a face's label and/or notes
+    ///
+    /// # Arguments
+    /// * `request_json` - JSON string of UpdateFaceRequest
+    pub async fn update_face(&self, request_json: &str) -> zbus::fdo::Result<()> {
+        debug!("D-Bus call: update_face");
+
+        let request: UpdateFaceRequest = parse_request(request_json)?;
+
+        let daemon = self.daemon.read().await;
+        match daemon.update_face(request).await {
+            Ok(()) => {
+                info!("update_face succeeded");
+                Ok(())
+            }
+            Err(e) => {
+                error!("update_face failed: {}", e);
+                Err(zbus::fdo::Error::Failed(e.to_string()))
+            }
+        }
+    }
+
+    /// Rename a face: `update_face` with only the label
+    ///
+    /// # Arguments
+    /// * `user_id` - User UID
+    /// * `face_id` - ID of the face
+    /// * `label` - New label (empty = remove)
+    pub async fn rename_face(
+        &self,
+        user_id: u32,
+        face_id: String,
+        label: String,
+    ) -> zbus::fdo::Result<()> {
+        debug!("D-Bus call: rename_face");
+
+        let request = UpdateFaceRequest {
+            user_id,
+            face_id,
+            label: Some(label),
+            notes: None,
+            allowed_contexts: None,
+        };
+        let daemon = self.daemon.read().await;
+        daemon.update_face(request).await.map_err(|e| {
+            error!("rename_face failed: {}", e);
+            zbus::fdo::Error::Failed(e.to_string())
+        })
+    }
+
     /// Verify a user's identity
     ///
     /// # Arguments
@@ -174,6 +277,64 @@ impl FaceAuthInterface {
         }
     }
 
+    /// Export a user's faces as a passphrase-encrypted backup archive
+    ///
+    /// # Arguments
+    /// * `request_json` - JSON string of ExportFacesRequest
+    ///
+    /// # Returns
+    /// The archive bytes (see `backup`)
+    pub async fn export_faces(&self, request_json: &str) -> zbus::fdo::Result<Vec<u8>> {
+        debug!("D-Bus call: export_faces");
+
+        let request: ExportFacesRequest = parse_request(request_json)?;
+
+        let daemon = self.daemon.read().await;
+        match daemon.export_faces(request).await {
+            Ok(archive) => {
+                info!("export_faces succeeded");
+                Ok(archive)
+            }
+            Err(e) => {
+                error!("export_faces failed: {}", e);
+                Err(zbus::fdo::Error::Failed(e.to_string()))
+            }
+        }
+    }
+
+    /// Restore faces from a backup archive made by `export_faces`
+    ///
+    /// # Arguments
+    /// * `request_json` - JSON string of ImportFacesRequest
+    /// * `archive` - The archive bytes
+    ///
+    /// # Returns
+    /// JSON string of ImportFacesResponse
+    pub async fn import_faces(
+        &self,
+        request_json: &str,
+        archive: Vec<u8>,
+    ) -> zbus::fdo::Result<String> {
+        debug!("D-Bus call: import_faces");
+
+        let request: ImportFacesRequest = parse_request(request_json)?;
+
+        let daemon = self.daemon.read().await;
+        match daemon.import_faces(request, archive).await {
+            Ok(response) => {
+                info!("import_faces succeeded");
+                serde_json::to_string(&response).map_err(|e| {
+                    error!("JSON serialize error: {}", e);
+                    zbus::fdo::Error::Failed(e.to_string())
+                })
+            }
+            Err(e) => {
+                error!("import_faces failed: {}", e);
+                Err(zbus::fdo::Error::Failed(e.to_string()))
+            }
+        }
+    }
+
     /// Connection test
     pub async fn ping(&self) -> zbus::fdo::Result<String> {
         Ok("pong".to_string())
@@ -290,6 +451,63 @@ impl FaceAuthInterface {
         }
     }
 
+    /// Re-read the configuration files and apply them now, instead of
+    /// waiting for the file watcher to notice — see `config_reload`. The
+    /// config GUI calls this right after saving.
+    ///
+    /// # Returns
+    /// JSON string of `config_reload::ConfigChange`
+    ///
+    /// # D-Bus Signal Emitted
+    /// `ConfigChanged(change_json: &str)` - if anything changed
+    pub async fn reload_config(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> zbus::fdo::Result<String> {
+        debug!("D-Bus call: reload_config");
+
+        let change = crate::config_reload::reload(&self.daemon)
+            .await
+            .map_err(|e| {
+                error!("reload_config failed: {}", e);
+                zbus::fdo::Error::Failed(e.to_string())
+            })?;
+        let change_json = serde_json::to_string(&change).map_err(|e| {
+            error!("JSON serialize error: {}", e);
+            zbus::fdo::Error::Failed(e.to_string())
+        })?;
+
+        if !change.is_empty() {
+            if let Err(e) = Self::config_changed(&emitter, &change_json).await {
+                error!("ConfigChanged emission error: {}", e);
+            }
+        }
+        Ok(change_json)
+    }
+
+    /// Emitted after a reload that changed the effective configuration,
+    /// whatever triggered it (file edit, SIGHUP, `ReloadConfig`).
+    ///
+    /// # Arguments
+    /// * `change_json` - JSON string of `config_reload::ConfigChange`
+    #[zbus(signal)]
+    pub async fn config_changed(emitter: &SignalEmitter<'_>, change_json: &str)
+        -> zbus::Result<()>;
+
+    /// Emitted when an enrolled face is about to stop being accepted
+    /// because of `recognition.max_template_age_days`, or just did — see
+    /// `expiry`.
+    ///
+    /// # Arguments
+    /// * `face_id` - The face to enroll again
+    /// * `expires_at` - When it stops (or stopped) being accepted, Unix seconds
+    #[zbus(signal)]
+    pub async fn enrollment_expiring(
+        emitter: &SignalEmitter<'_>,
+        face_id: &str,
+        expires_at: u64,
+    ) -> zbus::Result<()>;
+
     #[zbus(property)]
     pub fn version(&self) -> String {
         self.version.clone()
@@ -337,6 +555,8 @@ mod tests {
         let config = DaemonConfig {
             storage_path: temp.path().to_path_buf(),
             root_mode: false,
+            settings: hello_config::Config::default(),
+            policy: hello_config::Policy::default(),
         };
         let daemon = FaceAuthDaemon::new(config).unwrap();
         (temp, FaceAuthInterface::new(daemon))
@@ -353,6 +573,8 @@ mod tests {
         let config = DaemonConfig {
             storage_path: temp.path().to_path_buf(),
             root_mode: false,
+            settings: hello_config::Config::default(),
+            policy: hello_config::Policy::default(),
         };
         let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
         (temp, FaceAuthInterface::new(daemon))
@@ -392,6 +614,8 @@ mod tests {
         let config = DaemonConfig {
             storage_path: temp.path().to_path_buf(),
             root_mode: false,
+            settings: hello_config::Config::default(),
+            policy: hello_config::Policy::default(),
         };
         let daemon = FaceAuthDaemon::new(config).unwrap();
         let iface = FaceAuthInterface::new(daemon);
@@ -438,6 +662,13 @@ mod tests {
         assert!(iface.verify("not json").await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_backup_methods_reject_malformed_json() {
+        let (_temp, iface) = test_interface();
+        assert!(iface.export_faces("not json").await.is_err());
+        assert!(iface.import_faces("not json", Vec::new()).await.is_err());
+    }
+
     // Permission-denied paths: these all return before ever touching the
     // camera/detector, so — unlike a happy-path register_face/verify call —
     // they're safe to exercise without real hardware or an ONNX model.
@@ -453,6 +684,7 @@ mod tests {
             context: "test".to_string(),
             timeout_ms: 100,
             num_samples: 1,
+            allowed_contexts: Vec::new(),
         };
         let json = serde_json::to_string(&request).unwrap();
         assert!(iface.register_face(&json).await.is_err());
@@ -545,6 +777,8 @@ mod tests {
         let config = DaemonConfig {
             storage_path: temp.path().to_path_buf(),
             root_mode: false,
+            settings: hello_config::Config::default(),
+            policy: hello_config::Policy::default(),
         };
         let daemon = FaceAuthDaemon::new_for_test(config, camera)
             .unwrap()
@@ -556,6 +790,7 @@ mod tests {
             context: "test".to_string(),
             timeout_ms: 1000,
             num_samples: 1,
+            allowed_contexts: Vec::new(),
         };
         let json = serde_json::to_string(&request).unwrap();
         assert!(iface.register_face(&json).await.is_err());
@@ -567,6 +802,8 @@ mod tests {
         let config = DaemonConfig {
             storage_path: temp.path().to_path_buf(),
             root_mode: false,
+            settings: hello_config::Config::default(),
+            policy: hello_config::Policy::default(),
         };
         let daemon = FaceAuthDaemon::new(config)
             .unwrap()
@@ -596,6 +833,7 @@ mod tests {
             context: "test".to_string(),
             timeout_ms: 1000,
             num_samples: 1,
+            allowed_contexts: Vec::new(),
         };
         let json = serde_json::to_string(&request).unwrap();
         let response_json = iface.register_face(&json).await.unwrap();
@@ -622,6 +860,7 @@ mod tests {
             context: "test".to_string(),
             timeout_ms: 1000,
             num_samples: 1,
+            allowed_contexts: Vec::new(),
         };
         let json = serde_json::to_string(&request).unwrap();
         let response_json = iface.register_face(&json).await.unwrap();
@@ -680,6 +919,7 @@ mod tests {
             context: "test".to_string(),
             timeout_ms: 1000,
             num_samples: 1,
+            allowed_contexts: Vec::new(),
         };
         iface
             .register_face(&serde_json::to_string(&register_request).unwrap())
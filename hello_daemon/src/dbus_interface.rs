@@ -3,6 +3,7 @@
 //! Interface: com.linuxhello.FaceAuth
 //! Path: /com/linuxhello/FaceAuth
 
+use crate::calibration::SampleLabel;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -24,6 +25,10 @@ pub struct RegisterFaceRequest {
 
     /// Number of frames to capture and average
     pub num_samples: u32,
+
+    /// Contexts the new face may authenticate (empty = any)
+    #[serde(default)]
+    pub allowed_contexts: Vec<String>,
 }
 
 /// Successful enrollment response
@@ -49,6 +54,85 @@ pub struct DeleteFaceRequest {
     pub face_id: Option<String>,
 }
 
+/// Request to drop what adaptation added to one or all faces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResetAdaptationRequest {
+    /// User UID
+    pub user_id: u32,
+
+    /// ID of the face to reset (None = all)
+    pub face_id: Option<String>,
+}
+
+/// Labelled capture session for threshold calibration (see `calibration`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordCalibrationRequest {
+    /// UID of the user whose faces the frames are scored against
+    pub user_id: u32,
+
+    /// Who is in front of the camera
+    pub label: SampleLabel,
+
+    /// Context the scores are recorded for
+    pub context: String,
+
+    /// How long to capture, in ms
+    pub duration_ms: u64,
+}
+
+/// Label/notes change for one face
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateFaceRequest {
+    /// User UID
+    pub user_id: u32,
+
+    /// ID of the face to change
+    pub face_id: String,
+
+    /// New label (None = unchanged, empty = remove)
+    #[serde(default)]
+    pub label: Option<String>,
+
+    /// New notes (None = unchanged, empty = remove)
+    #[serde(default)]
+    pub notes: Option<String>,
+
+    /// New set of contexts the face may authenticate (None = unchanged,
+    /// empty = any context)
+    #[serde(default)]
+    pub allowed_contexts: Option<Vec<String>>,
+}
+
+/// Backup export request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportFacesRequest {
+    /// UID whose faces are exported
+    pub user_id: u32,
+
+    /// Passphrase the archive is encrypted under
+    pub passphrase: String,
+}
+
+/// Backup import request (the archive itself travels as bytes)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFacesRequest {
+    /// UID the faces are restored for
+    pub user_id: u32,
+
+    /// Passphrase the archive was encrypted under
+    pub passphrase: String,
+}
+
+/// Backup import response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFacesResponse {
+    /// Faces written to storage
+    pub imported: usize,
+
+    /// Faces left out because they were enrolled with another model
+    pub skipped_incompatible: usize,
+}
+
 /// Verification request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VerifyRequest {
@@ -80,6 +164,17 @@ pub enum VerifyResult {
     /// No enrolled models
     NoEnrollment,
 
+    /// Every enrolled face was extracted by another embedding model (an
+    /// upgrade changed it, or the models failed to load) and can't be
+    /// compared, or is older than `recognition.max_template_age_days`
+    /// allows for the context — the user has to enroll again
+    ReEnrollmentRequired,
+
+    /// Too many failed matches in a row for this user and context (see
+    /// `lockout`): not attempted until `until` (Unix seconds), or until a
+    /// password authentication when `None`
+    LockedOut { until: Option<u64> },
+
     /// User cancelled
     Cancelled,
 
@@ -104,6 +199,19 @@ impl fmt::Display for VerifyResult {
                 write!(f, "Not recognized: {:.2} < {:.2}", best_score, threshold)
             }
             VerifyResult::NoEnrollment => write!(f, "No enrollment"),
+            VerifyResult::ReEnrollmentRequired => write!(
+                f,
+                "Re-enrollment required: enrolled faces are from another recognition model or too old"
+            ),
+            VerifyResult::LockedOut { until: Some(until) } => write!(
+                f,
+                "Locked out after too many failed attempts, try again after {}",
+                until
+            ),
+            VerifyResult::LockedOut { until: None } => write!(
+                f,
+                "Locked out after too many failed attempts, authenticate with a password"
+            ),
             VerifyResult::Cancelled => write!(f, "Cancelled"),
             VerifyResult::Error { message } => write!(f, "Error: {}", message),
         }
@@ -130,6 +238,7 @@ mod tests {
             context: "login".to_string(),
             timeout_ms: 5000,
             num_samples: 3,
+            allowed_contexts: Vec::new(),
         };
         let json = serde_json::to_string(&req).unwrap();
         let restored: RegisterFaceRequest = serde_json::from_str(&json).unwrap();
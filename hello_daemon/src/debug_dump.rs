@@ -0,0 +1,475 @@
+//! `[debug]` dumps of failed verify attempts
+//!
+//! "It never recognizes me in the morning" can't be debugged from a log
+//! line saying `NoMatch { best_score: 0.41 }`. With `debug.dump_frames`
+//! (or `dump_embeddings`) on, every verify attempt is recorded by an
+//! [`AttemptRecorder`] — per frame: the detection and its landmarks, RGB
+//! and IR liveness, the match scores, optionally the frame itself and the
+//! embedding — and a failed attempt is written out as one directory:
+//!
+//! ```text
+//! <dump_dir>/1760698800123-sudo-no_match/
+//!     attempt.json     what was detected and scored, frame by frame
+//!     frame-001.png    when dump_frames is on
+//! ```
+//!
+//! Successful attempts are dropped: they're not what anyone needs to
+//! reproduce, and keeping them would fill the directory with the user's
+//! face for nothing. Dumps are biometric data, so `dump_dir` and
+//! everything in it is created owner-only (0700/0600), and
+//! [`prune`] deletes the oldest attempts beyond `dump_max_attempts` or
+//! `dump_max_size_mb` after every write.
+
+use crate::matcher::MatchResult;
+use hello_face_core::{Embedding, FaceRegion};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// What to dump and where, from `[debug]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpSettings {
+    pub dir: PathBuf,
+    pub frames: bool,
+    pub embeddings: bool,
+    pub max_attempts: u32,
+    pub max_bytes: u64,
+}
+
+impl DumpSettings {
+    /// `None` when dumping is off (neither `dump_frames` nor
+    /// `dump_embeddings`), or no `dump_dir` is set and there's no state
+    /// directory to default to.
+    pub fn from_config(debug: &hello_config::DebugConfig) -> Option<Self> {
+        if !debug.dump_frames && !debug.dump_embeddings {
+            return None;
+        }
+        let dir = match &debug.dump_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => match dirs::state_dir() {
+                Some(state) => state.join("linux-hello").join("dumps"),
+                None => {
+                    warn!("debug dumps are on but there is no state directory; set debug.dump_dir");
+                    return None;
+                }
+            },
+        };
+        Some(Self {
+            dir,
+            frames: debug.dump_frames,
+            embeddings: debug.dump_embeddings,
+            max_attempts: debug.dump_max_attempts,
+            max_bytes: u64::from(debug.dump_max_size_mb) * 1024 * 1024,
+        })
+    }
+}
+
+/// One frame of an attempt, as written to `attempt.json`
+#[derive(Debug, Clone, Serialize)]
+struct FrameRecord {
+    index: u32,
+    timestamp_ms: u64,
+    width: u32,
+    height: u32,
+    /// Best detection, `None` when no face was found
+    face: Option<FaceRegion>,
+    rgb_liveness: Option<f32>,
+    /// Outer `None`: never matched (no face, or the attempt ended first);
+    /// inner `None`: no IR camera
+    ir_liveness: Option<Option<f32>>,
+    #[serde(rename = "match")]
+    match_result: Option<MatchRecord>,
+    embedding: Option<Vec<f32>>,
+    /// File name of the saved frame, next to `attempt.json`
+    image: Option<String>,
+    #[serde(skip)]
+    rgb: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MatchRecord {
+    face_id: Option<String>,
+    best_score: f32,
+    threshold: f32,
+    matched: bool,
+    all_scores: HashMap<String, f32>,
+}
+
+#[derive(Default)]
+struct AttemptState {
+    frames: Vec<FrameRecord>,
+    /// Indices (into `frames`) of frames with a face that haven't been
+    /// matched yet, oldest first — matches arrive in capture order
+    awaiting_match: VecDeque<usize>,
+    /// Bytes of frame data held so far
+    frame_bytes: u64,
+}
+
+/// Collects one verify attempt, frame by frame. Shared between the capture
+/// thread ([`AttemptRecorder::record_frame`]) and the matching callback
+/// ([`AttemptRecorder::record_match`]).
+pub struct AttemptRecorder {
+    settings: DumpSettings,
+    state: Mutex<AttemptState>,
+}
+
+impl AttemptRecorder {
+    pub fn new(settings: DumpSettings) -> Self {
+        Self {
+            settings,
+            state: Mutex::new(AttemptState::default()),
+        }
+    }
+
+    /// Record a captured frame and, if a face was found, its detection,
+    /// embedding and RGB liveness. Frame data beyond `max_bytes` for the
+    /// attempt isn't kept — the dump couldn't be stored anyway, and a long
+    /// attempt at a high resolution would otherwise hold hundreds of MB.
+    pub fn record_frame(
+        &self,
+        index: u32,
+        rgb: &[u8],
+        width: u32,
+        height: u32,
+        scored: Option<&(Embedding, f32, FaceRegion)>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let keep_frame =
+            self.settings.frames && state.frame_bytes + rgb.len() as u64 <= self.settings.max_bytes;
+        if keep_frame {
+            state.frame_bytes += rgb.len() as u64;
+        }
+        let record = FrameRecord {
+            index,
+            timestamp_ms: now_ms(),
+            width,
+            height,
+            face: scored.map(|(_, _, face)| face.clone()),
+            rgb_liveness: scored.map(|(_, liveness, _)| *liveness),
+            ir_liveness: None,
+            match_result: None,
+            embedding: scored
+                .filter(|_| self.settings.embeddings)
+                .map(|(embedding, _, _)| embedding.vector.clone()),
+            image: None,
+            rgb: keep_frame.then(|| rgb.to_vec()),
+        };
+        if scored.is_some() {
+            let position = state.frames.len();
+            state.awaiting_match.push_back(position);
+        }
+        state.frames.push(record);
+    }
+
+    /// Attach a match result to the oldest recorded face that doesn't have
+    /// one yet.
+    pub fn record_match(&self, ir_liveness: Option<f32>, result: &MatchResult) {
+        let mut state = self.state.lock().unwrap();
+        let Some(position) = state.awaiting_match.pop_front() else {
+            return;
+        };
+        let frame = &mut state.frames[position];
+        frame.ir_liveness = Some(ir_liveness);
+        frame.match_result = Some(MatchRecord {
+            face_id: result.face_id.clone(),
+            best_score: result.best_score,
+            threshold: result.threshold,
+            matched: result.matched,
+            all_scores: result.all_scores.clone(),
+        });
+    }
+
+    /// Write the attempt to `<dump_dir>/<time>-<context>-<outcome>/`, then
+    /// [`prune`] old attempts. Returns the directory written.
+    pub fn write(&self, user_id: u32, context: &str, outcome: &str) -> std::io::Result<PathBuf> {
+        let state = self.state.lock().unwrap();
+        ensure_private_dir(&self.settings.dir)?;
+
+        let name = format!("{}-{}-{}", now_ms(), file_safe(context), file_safe(outcome));
+        let dir = self.settings.dir.join(name);
+        std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+        let mut frames = state.frames.clone();
+        for frame in &mut frames {
+            let Some(rgb) = frame.rgb.take() else {
+                continue;
+            };
+            let file_name = format!("frame-{:03}.png", frame.index);
+            let file = create_private_file(&dir.join(&file_name))?;
+            let encoder = image::codecs::png::PngEncoder::new(std::io::BufWriter::new(file));
+            image::ImageEncoder::write_image(
+                encoder,
+                &rgb,
+                frame.width,
+                frame.height,
+                image::ExtendedColorType::Rgb8,
+            )
+            .map_err(std::io::Error::other)?;
+            frame.image = Some(file_name);
+        }
+
+        let summary = serde_json::json!({
+            "user_id": user_id,
+            "context": context,
+            "outcome": outcome,
+            "frames": frames,
+        });
+        let mut json = create_private_file(&dir.join("attempt.json"))?;
+        json.write_all(serde_json::to_string_pretty(&summary)?.as_bytes())?;
+
+        prune(
+            &self.settings.dir,
+            self.settings.max_attempts,
+            self.settings.max_bytes,
+        )?;
+        Ok(dir)
+    }
+}
+
+/// Create `dir` (and its parents) with the last component owner-only, or
+/// tighten an existing one to 0700. Refuses a symlink or a directory owned
+/// by someone else, either of which would hand the dumps to another user.
+fn ensure_private_dir(dir: &Path) -> std::io::Result<()> {
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    let metadata = std::fs::symlink_metadata(dir)?;
+    // SAFETY: geteuid() has no preconditions and cannot fail.
+    let euid = unsafe { libc::geteuid() };
+    if !metadata.is_dir() || metadata.uid() != euid {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!(
+                "{} is not a directory owned by this user, not dumping into it",
+                dir.display()
+            ),
+        ));
+    }
+    if metadata.mode() & 0o777 != 0o700 {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+fn create_private_file(path: &Path) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+/// Delete the oldest attempt directories in `dir` until at most
+/// `max_attempts` remain and they take at most `max_bytes` together —
+/// except the newest, the one just written, which is always kept.
+/// Attempt directories are named after their start time, so name order is
+/// age order.
+pub fn prune(dir: &Path, max_attempts: u32, max_bytes: u64) -> std::io::Result<()> {
+    let mut attempts = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            let size = dir_size(&entry.path())?;
+            attempts.push((entry.file_name(), entry.path(), size));
+        }
+    }
+    attempts.sort();
+
+    let mut total: u64 = attempts.iter().map(|(_, _, size)| size).sum();
+    let mut remaining = attempts.len();
+    attempts.pop();
+    for (_, path, size) in attempts {
+        if remaining <= max_attempts as usize && total <= max_bytes {
+            break;
+        }
+        std::fs::remove_dir_all(&path)?;
+        info!("Removed old debug dump {}", path.display());
+        total -= size;
+        remaining -= 1;
+    }
+    Ok(())
+}
+
+fn dir_size(dir: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// `context`/`outcome` as a file name component: anything but ASCII
+/// letters, digits, `-` and `_` becomes `_`
+fn file_safe(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::default_face_region;
+
+    fn settings(dir: &Path) -> DumpSettings {
+        DumpSettings {
+            dir: dir.join("dumps"),
+            frames: true,
+            embeddings: true,
+            max_attempts: 20,
+            max_bytes: 10 * 1024 * 1024,
+        }
+    }
+
+    fn scored() -> (Embedding, f32, FaceRegion) {
+        let embedding = Embedding {
+            vector: vec![1.0, 0.0, 0.0],
+            metadata: hello_face_core::EmbeddingMetadata {
+                model: "test".to_string(),
+                model_version: "0".to_string(),
+                extracted_at: 0,
+                quality_score: 0.9,
+            },
+        };
+        (embedding, 0.8, default_face_region(4, 4))
+    }
+
+    fn no_match(score: f32) -> MatchResult {
+        MatchResult {
+            face_id: Some("face_1".to_string()),
+            best_score: score,
+            threshold: 0.6,
+            all_scores: HashMap::from([("face_1".to_string(), score)]),
+            matched: false,
+        }
+    }
+
+    #[test]
+    fn from_config_is_off_unless_something_is_dumped() {
+        let mut debug = hello_config::DebugConfig::default();
+        assert_eq!(DumpSettings::from_config(&debug), None);
+
+        debug.dump_embeddings = true;
+        debug.dump_dir = Some("/tmp/dumps".to_string());
+        let settings = DumpSettings::from_config(&debug).unwrap();
+        assert_eq!(settings.dir, PathBuf::from("/tmp/dumps"));
+        assert!(!settings.frames);
+        assert_eq!(settings.max_bytes, 200 * 1024 * 1024);
+    }
+
+    #[test]
+    fn write_saves_frames_and_scores_owner_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let recorder = AttemptRecorder::new(settings(tmp.path()));
+        let frame = vec![128u8; 4 * 4 * 3];
+
+        recorder.record_frame(1, &frame, 4, 4, None);
+        recorder.record_frame(2, &frame, 4, 4, Some(&scored()));
+        recorder.record_match(Some(0.3), &no_match(0.41));
+
+        let dir = recorder.write(1000, "sudo", "no_match").unwrap();
+        let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.ends_with("-sudo-no_match"), "{name}");
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().mode() & 0o777;
+        assert_eq!(mode(&tmp.path().join("dumps")), 0o700);
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&dir.join("attempt.json")), 0o600);
+        assert_eq!(mode(&dir.join("frame-002.png")), 0o600);
+        assert!(image::open(dir.join("frame-001.png")).is_ok());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("attempt.json")).unwrap())
+                .unwrap();
+        let frames = json["frames"].as_array().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0]["face"].is_null());
+        assert!(frames[0]["match"].is_null());
+        assert_eq!(frames[1]["match"]["best_score"], 0.41f32 as f64);
+        assert_eq!(frames[1]["ir_liveness"], 0.3f32 as f64);
+        assert_eq!(frames[1]["embedding"].as_array().unwrap().len(), 3);
+        assert_eq!(frames[1]["image"], "frame-002.png");
+    }
+
+    #[test]
+    fn frames_beyond_the_size_cap_are_not_kept() {
+        let tmp = tempfile::tempdir().unwrap();
+        let recorder = AttemptRecorder::new(DumpSettings {
+            max_bytes: 100,
+            ..settings(tmp.path())
+        });
+        let frame = vec![0u8; 4 * 4 * 3]; // 48 bytes
+        for index in 1..=3 {
+            recorder.record_frame(index, &frame, 4, 4, None);
+        }
+
+        let dir = recorder.write(1000, "login", "no_face").unwrap();
+        assert!(dir.join("frame-002.png").exists());
+        assert!(!dir.join("frame-003.png").exists());
+    }
+
+    #[test]
+    fn prune_deletes_the_oldest_attempts_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        for (name, size) in [("100-a", 10), ("200-b", 10), ("300-c", 10)] {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            std::fs::write(dir.join("attempt.json"), vec![b'x'; size]).unwrap();
+        }
+
+        prune(tmp.path(), 2, 1000).unwrap();
+        assert!(!tmp.path().join("100-a").exists());
+        assert!(tmp.path().join("200-b").exists());
+
+        prune(tmp.path(), 10, 5).unwrap();
+        assert!(!tmp.path().join("200-b").exists());
+        assert!(tmp.path().join("300-c").exists(), "the newest is kept");
+    }
+
+    #[test]
+    fn an_existing_dump_dir_is_tightened_to_owner_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("dumps");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        ensure_private_dir(&dir).unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(ensure_private_dir(&link).is_err());
+    }
+
+    #[test]
+    fn file_safe_replaces_path_characters() {
+        assert_eq!(file_safe("../etc/x y"), "___etc_x_y");
+        assert_eq!(file_safe("sddm-greeter_2"), "sddm-greeter_2");
+    }
+}
@@ -0,0 +1,253 @@
+//! Reminding the user to enroll again before a face expires
+//!
+//! With `recognition.max_template_age_days` (or a per-context limit) set,
+//! `verify_with_storage` stops accepting a face once it is older than the
+//! context allows. So that this doesn't come as a surprise at the next
+//! `sudo`, [`spawn_expiry_reminder`] checks the user's faces when the
+//! daemon starts and twice a day after that. A face within
+//! `recognition.expiry_warning_days` of its shortest limit, or past it, is
+//! announced with an `EnrollmentExpiring` D-Bus signal (for the config GUI)
+//! and a desktop notification (org.freedesktop.Notifications).
+//!
+//! Each face is announced once per daemon run while expiring and once more
+//! when it has expired — not on every check.
+
+use crate::dbus::{FaceAuthInterface, OBJECT_PATH};
+use crate::{FaceAuthDaemon, FaceRecord, SECS_PER_DAY};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+use zbus::proxy;
+use zbus::zvariant::Value;
+
+/// How often [`spawn_expiry_reminder`] looks for expiring faces
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Proxy for org.freedesktop.Notifications (session bus)
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications",
+    gen_blocking = false
+)]
+trait Notifications {
+    /// Show a notification; returns its ID.
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+/// A face that expires soon, or has
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpiringFace {
+    pub face_id: String,
+    pub label: Option<String>,
+    /// When it stops being accepted (Unix seconds)
+    pub expires_at: u64,
+}
+
+impl ExpiringFace {
+    /// Whether it is already past its limit at `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at <= now
+    }
+
+    /// Summary and body of the desktop notification about this face
+    pub fn notification_text(&self, now: u64) -> (String, String) {
+        let name = match &self.label {
+            Some(label) => format!("\"{}\" ({})", label, self.face_id),
+            None => self.face_id.clone(),
+        };
+        if self.is_expired(now) {
+            (
+                "Face enrollment expired".to_string(),
+                format!(
+                    "Face {} is too old and no longer accepted for some logins. \
+                     Run `linux-hello enroll` to enroll it again.",
+                    name
+                ),
+            )
+        } else {
+            let days = (self.expires_at - now).div_ceil(SECS_PER_DAY);
+            (
+                "Face enrollment expiring".to_string(),
+                format!(
+                    "Face {} expires in {} day{}. Run `linux-hello enroll` to enroll it again.",
+                    name,
+                    days,
+                    if days == 1 { "" } else { "s" }
+                ),
+            )
+        }
+    }
+}
+
+/// The faces of `faces` that expire within `warning_days` of `now`, or
+/// already have, when faces are accepted for `max_age_days`
+pub fn expiring_faces(
+    faces: &[FaceRecord],
+    max_age_days: u32,
+    warning_days: u32,
+    now: u64,
+) -> Vec<ExpiringFace> {
+    let warn_from = now.saturating_add(u64::from(warning_days) * SECS_PER_DAY);
+    faces
+        .iter()
+        .map(|face| (face, face.expiry(max_age_days)))
+        .filter(|(_, expires_at)| *expires_at <= warn_from)
+        .map(|(face, expires_at)| ExpiringFace {
+            face_id: face.face_id.clone(),
+            label: face.label.clone(),
+            expires_at,
+        })
+        .collect()
+}
+
+/// Emit `EnrollmentExpiring` from the interface registered at
+/// [`OBJECT_PATH`].
+pub async fn emit_enrollment_expiring(
+    connection: &zbus::Connection,
+    face: &ExpiringFace,
+) -> zbus::Result<()> {
+    let iface = connection
+        .object_server()
+        .interface::<_, FaceAuthInterface>(OBJECT_PATH)
+        .await?;
+    FaceAuthInterface::enrollment_expiring(iface.signal_emitter(), &face.face_id, face.expires_at)
+        .await
+}
+
+/// Show the desktop notification about `face`
+async fn notify_desktop(
+    connection: &zbus::Connection,
+    face: &ExpiringFace,
+    now: u64,
+) -> zbus::Result<()> {
+    let (summary, body) = face.notification_text(now);
+    let proxy = NotificationsProxy::new(connection).await?;
+    proxy
+        .notify(
+            "Linux Hello",
+            0,
+            "user-info",
+            &summary,
+            &body,
+            &[],
+            HashMap::new(),
+            -1,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Look for `uid`'s expiring faces now and every [`CHECK_INTERVAL`], and
+/// announce each one not announced in the same state before.
+pub fn spawn_expiry_reminder(
+    daemon: Arc<RwLock<FaceAuthDaemon>>,
+    uid: u32,
+    connection: zbus::Connection,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        // face_id -> whether it had expired when last announced
+        let mut announced: HashMap<String, bool> = HashMap::new();
+        loop {
+            let now = crate::unix_now();
+            let faces = daemon.read().await.expiring_faces(uid, now);
+            match faces {
+                Ok(faces) => {
+                    debug!("{} face(s) of UID {} expiring", faces.len(), uid);
+                    for face in faces {
+                        let expired = face.is_expired(now);
+                        if announced.get(&face.face_id) == Some(&expired) {
+                            continue;
+                        }
+                        info!(
+                            "Face {} of UID {} {} at {}, reminding the user to enroll again",
+                            face.face_id,
+                            uid,
+                            if expired { "expired" } else { "expires" },
+                            face.expires_at
+                        );
+                        if let Err(e) = emit_enrollment_expiring(&connection, &face).await {
+                            error!("EnrollmentExpiring emission error: {}", e);
+                        }
+                        if let Err(e) = notify_desktop(&connection, &face, now).await {
+                            warn!("Expiry notification not shown: {}", e);
+                        }
+                        announced.insert(face.face_id, expired);
+                    }
+                }
+                Err(e) => warn!("Could not check face expiry for UID {}: {}", uid, e),
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FaceStats;
+
+    fn face(face_id: &str, registered_at: u64) -> FaceRecord {
+        FaceRecord {
+            face_id: face_id.to_string(),
+            user_id: 1000,
+            quality_score: 0.9,
+            registered_at,
+            context: "default".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
+        }
+    }
+
+    #[test]
+    fn faces_are_reported_within_the_warning_window_and_once_expired() {
+        let now = 1_000 * SECS_PER_DAY;
+        let faces = [
+            face("fresh", now - 10 * SECS_PER_DAY),
+            face("expiring", now - 80 * SECS_PER_DAY),
+            face("expired", now - 120 * SECS_PER_DAY),
+        ];
+
+        let expiring = expiring_faces(&faces, 90, 14, now);
+        let ids: Vec<&str> = expiring.iter().map(|f| f.face_id.as_str()).collect();
+        assert_eq!(ids, vec!["expiring", "expired"]);
+        assert_eq!(expiring[0].expires_at, now + 10 * SECS_PER_DAY);
+        assert!(!expiring[0].is_expired(now));
+        assert!(expiring[1].is_expired(now));
+    }
+
+    #[test]
+    fn notification_names_the_face_and_the_days_left() {
+        let now = 1_000 * SECS_PER_DAY;
+        let mut face = ExpiringFace {
+            face_id: "face_1000_1".to_string(),
+            label: Some("glasses".to_string()),
+            expires_at: now + SECS_PER_DAY / 2,
+        };
+        let (summary, body) = face.notification_text(now);
+        assert_eq!(summary, "Face enrollment expiring");
+        assert!(body.contains("\"glasses\" (face_1000_1) expires in 1 day."));
+
+        face.expires_at = now - 1;
+        let (summary, body) = face.notification_text(now);
+        assert_eq!(summary, "Face enrollment expired");
+        assert!(body.contains("linux-hello enroll"));
+    }
+}
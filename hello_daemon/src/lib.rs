@@ -6,31 +6,47 @@
 //! - Camera access
 //! - Matching and scoring
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, warn};
 
+pub mod adaptive;
 pub mod authz;
+pub mod backup;
+pub mod calibration;
 pub mod camera;
 pub mod capture_stream;
+pub mod config_reload;
 pub mod dbus;
 pub mod dbus_interface;
 pub mod dbus_signals;
+pub mod debug_dump;
+pub mod expiry;
+pub mod lockout;
 pub mod matcher;
 pub mod pam_helper;
 pub mod preview;
+pub mod record_auth;
 pub mod screenlock;
 mod security_util;
 pub mod storage;
+pub mod storage_key;
 #[cfg(test)]
 mod test_support;
 
 use authz::EnrollmentAuthorizer;
 use camera::CameraManager;
-use dbus_interface::{DeleteFaceRequest, RegisterFaceRequest, VerifyRequest, VerifyResult};
-use matcher::{FaceMatcher, MatchResult};
-use storage::FaceStorage;
+use dbus_interface::{
+    DeleteFaceRequest, ExportFacesRequest, ImportFacesRequest, ImportFacesResponse,
+    RecordCalibrationRequest, RegisterFaceRequest, ResetAdaptationRequest, UpdateFaceRequest,
+    VerifyRequest, VerifyResult,
+};
+use lockout::{FailureLog, LockoutPolicy};
+use matcher::{FaceMatcher, FaceTemplate, MatchResult};
+use record_auth::RecordAuthenticator;
+use storage::{FaceStats, FaceStorage};
 
 /// Daemon errors
 #[derive(Debug, Error)]
@@ -44,15 +60,43 @@ pub enum DaemonError {
     #[error("Access denied: {0}")]
     AccessDenied(String),
 
+    #[error("Denied by administrator policy: {0}")]
+    PolicyViolation(String),
+
+    #[error("Face authentication is disabled for context {0}")]
+    ContextDisabled(String),
+
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
     #[error("Storage failed: {0}")]
     StorageError(String),
 
+    #[error(
+        "Stored faces use layout version {found}, newer than the {supported} this \
+         daemon supports (was linux-hello downgraded?); upgrade again — the \
+         previous layout isn't kept once an upgrade succeeds"
+    )]
+    NewerStorageLayout { found: u32, supported: u32 },
+
+    #[error(
+        "No storage key ({0}): faces are neither enrolled nor verified without one; \
+         start hello-storage-key, then restart the daemon"
+    )]
+    StorageKeyUnavailable(String),
+
+    #[error("Backup: {0}")]
+    Backup(#[from] backup::BackupError),
+
     #[error("D-Bus error: {0}")]
     DbusError(String),
 
     #[error("Camera: {0}")]
     CameraError(String),
 
+    #[error("Configuration: {0}")]
+    ConfigError(#[from] hello_config::ConfigError),
+
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -68,6 +112,15 @@ pub struct DaemonConfig {
 
     /// Root mode (true) or user mode (false)
     pub root_mode: bool,
+
+    /// Settings loaded from `config.toml` (see `hello_config`) — defaults
+    /// when no file exists
+    pub settings: hello_config::Config,
+
+    /// Administrator policy the settings were loaded under (see
+    /// `hello_config::policy`) — also enforced on request parameters that
+    /// don't come from config, like `RegisterFaceRequest::num_samples`
+    pub policy: hello_config::Policy,
 }
 
 impl Default for DaemonConfig {
@@ -87,6 +140,8 @@ impl Default for DaemonConfig {
         Self {
             storage_path,
             root_mode: unsafe { libc::getuid() } == 0,
+            settings: hello_config::Config::default(),
+            policy: hello_config::Policy::default(),
         }
     }
 }
@@ -94,7 +149,7 @@ impl Default for DaemonConfig {
 /// Metadata for a registered face.
 ///
 /// Deliberately does NOT carry the embedding vector itself (that lives only
-/// in the separate `<face_id>.embedding.json` file, see
+/// in the storage's separate `embeddings` table, see
 /// `FaceStorage::save_face`/`load_face_embedding`): this struct is also what
 /// `list_faces` returns verbatim over the session D-Bus bus, which has no
 /// access-control policy of its own beyond "same UID as the daemon" — any
@@ -118,6 +173,118 @@ pub struct FaceRecord {
 
     /// Enrollment context
     pub context: String,
+
+    /// Name the user gave this enrollment ("with glasses"), set through
+    /// `update_face`; `None` until then
+    #[serde(default)]
+    pub label: Option<String>,
+
+    /// Free-form notes about the enrollment (lighting, camera, ...)
+    #[serde(default)]
+    pub notes: Option<String>,
+
+    /// Contexts this face may authenticate (`sudo`, `screenlock`, ...);
+    /// empty for any context. Enforced by `verify_with_storage`.
+    #[serde(default)]
+    pub allowed_contexts: Vec<String>,
+
+    /// Enrolled with another embedding model than the one in use, or
+    /// before face records were signed (see `record_auth`), so it can't
+    /// match anymore. Filled in by `list_faces`, never stored.
+    #[serde(default)]
+    pub needs_reenrollment: bool,
+
+    /// When this face stops being accepted in some context because of
+    /// `recognition.max_template_age_days` (Unix seconds), `None` if it
+    /// never does. Filled in by `list_faces`, never stored.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+
+    /// How often and how well it has matched (see `storage::FaceStats`).
+    /// Filled in by `list_faces`; kept in its own table.
+    #[serde(default)]
+    pub stats: FaceStats,
+}
+
+/// Seconds in a day, for the `*_days` settings
+pub const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Current time in Unix seconds
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Longest face label `update_face` accepts, in characters
+pub const MAX_FACE_LABEL_LEN: usize = 64;
+
+/// Longest face notes `update_face` accepts, in characters
+pub const MAX_FACE_NOTES_LEN: usize = 1024;
+
+/// Check a list of allowed contexts and return it sorted and without
+/// duplicates. Any name the PAM module could pass as `context=` is
+/// accepted, including custom ones no `enable_for_*` flag covers.
+fn normalize_contexts(contexts: &[String]) -> Result<Vec<String>, DaemonError> {
+    let mut normalized = Vec::with_capacity(contexts.len());
+    for context in contexts {
+        let context = context.trim();
+        let valid = !context.is_empty()
+            && context.len() <= 32
+            && context
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(DaemonError::InvalidRequest(format!(
+                "Invalid context name: {:?}",
+                context
+            )));
+        }
+        normalized.push(context.to_string());
+    }
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+/// Refuse a label or notes that are too long or carry control characters
+/// (notes may span lines) — they end up in terminals and the GUI verbatim.
+fn check_face_text(
+    field: &str,
+    text: &str,
+    max_len: usize,
+    multiline: bool,
+) -> Result<(), DaemonError> {
+    if text.chars().count() > max_len {
+        return Err(DaemonError::InvalidRequest(format!(
+            "{} is longer than {} characters",
+            field, max_len
+        )));
+    }
+    if text
+        .chars()
+        .any(|c| c.is_control() && !(multiline && c == '\n'))
+    {
+        return Err(DaemonError::InvalidRequest(format!(
+            "{} contains control characters",
+            field
+        )));
+    }
+    Ok(())
+}
+
+impl FaceRecord {
+    /// Whether this face may authenticate `context`
+    pub fn allows_context(&self, context: &str) -> bool {
+        self.allowed_contexts.is_empty() || self.allowed_contexts.iter().any(|c| c == context)
+    }
+
+    /// When this face gets older than `max_age_days` (Unix seconds)
+    pub fn expiry(&self, max_age_days: u32) -> u64 {
+        self.registered_at
+            .saturating_add(u64::from(max_age_days) * SECS_PER_DAY)
+    }
 }
 
 /// Implementation of the D-Bus service with all components
@@ -133,19 +300,37 @@ pub struct FaceAuthDaemon {
     /// the same opt-in pattern `new_with_connection`/`from_arc` already use
     /// for `FaceAuthInterface`'s signal emitter.
     enrollment_authorizer: EnrollmentAuthorizer,
+    /// Signs face records on enrollment changes and checks them before
+    /// matching — see `record_auth`. `Disabled` unless wired in with
+    /// `with_record_authenticator`.
+    record_auth: RecordAuthenticator,
+    /// Failed matches per context, next to the faces (see `lockout`)
+    failures: FailureLog,
+    /// Why `main.rs` got no storage key, if it should have had one — see
+    /// `without_storage_key`
+    storage_key_missing: Option<String>,
 }
 
 impl FaceAuthDaemon {
     pub fn new(config: DaemonConfig) -> Result<Self, DaemonError> {
+        Self::with_storage_key(config, None)
+    }
+
+    /// Same as `new()`, with embeddings sealed under `storage_key` (see
+    /// `storage_key`) — what `main.rs` uses once it has fetched the key
+    pub fn with_storage_key(
+        config: DaemonConfig,
+        storage_key: Option<storage_key::StorageKey>,
+    ) -> Result<Self, DaemonError> {
         // Create the storage
-        let storage = FaceStorage::new(&config.storage_path)
-            .map_err(|e| DaemonError::StorageError(e.to_string()))?;
+        let storage = FaceStorage::with_key(&config.storage_path, storage_key)?;
+        let failures = FailureLog::open(config.storage_path.join(lockout::FAILURE_LOG_FILE))?;
 
         // Create the camera manager
-        let camera = CameraManager::new(5000); // 5s default timeout
+        let camera = CameraManager::new(&config.settings);
 
         // Create the matcher with the configured threshold
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::new(&config.settings.recognition);
 
         info!("Daemon created with config: {:?}", config);
 
@@ -158,11 +343,14 @@ impl FaceAuthDaemon {
             enrollment_authorizer: EnrollmentAuthorizer::AllowAll,
             #[cfg(not(test))]
             enrollment_authorizer: EnrollmentAuthorizer::from_env(),
+            record_auth: RecordAuthenticator::Disabled,
+            failures,
+            storage_key_missing: None,
         })
     }
 
     /// Same as `new()`, but takes a pre-built `CameraManager` instead of
-    /// always constructing one via `CameraManager::new(5000)` — lets tests
+    /// always constructing one via `CameraManager::new` — lets tests
     /// wire in a `CameraManager::for_test()` with fake detector/extractor
     /// implementations, all the way through to `FaceAuthInterface`.
     #[cfg(test)]
@@ -170,15 +358,18 @@ impl FaceAuthDaemon {
         config: DaemonConfig,
         camera: CameraManager,
     ) -> Result<Self, DaemonError> {
-        let storage = FaceStorage::new(&config.storage_path)
-            .map_err(|e| DaemonError::StorageError(e.to_string()))?;
-        let matcher = FaceMatcher::new();
+        let storage = FaceStorage::new(&config.storage_path)?;
+        let failures = FailureLog::open(config.storage_path.join(lockout::FAILURE_LOG_FILE))?;
+        let matcher = FaceMatcher::new(&config.settings.recognition);
         Ok(Self {
             config,
             storage: Arc::new(storage),
             camera: Arc::new(camera),
             matcher: Arc::new(matcher),
             enrollment_authorizer: EnrollmentAuthorizer::AllowAll,
+            record_auth: RecordAuthenticator::Disabled,
+            failures,
+            storage_key_missing: None,
         })
     }
 
@@ -191,9 +382,62 @@ impl FaceAuthDaemon {
         self
     }
 
+    /// Authenticate face records with `record_auth` — `main.rs` wires in
+    /// the key service whenever it handed out a storage key
+    pub fn with_record_authenticator(mut self, record_auth: RecordAuthenticator) -> Self {
+        self.record_auth = record_auth;
+        self
+    }
+
+    /// Refuse to enroll and verify, since the key service didn't hand out
+    /// a key (`reason`): faces enrolled now would be stored unencrypted and
+    /// unsigned, and stored ones couldn't be told from planted ones
+    pub fn without_storage_key(mut self, reason: String) -> Self {
+        self.storage_key_missing = Some(reason);
+        self
+    }
+
+    fn require_storage_key(&self) -> Result<(), DaemonError> {
+        match &self.storage_key_missing {
+            Some(reason) => Err(DaemonError::StorageKeyUnavailable(reason.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Sign `face_id`'s record as it is stored now (see `record_auth`).
+    /// Runs after the enrollment authorizer: the key service repeats the
+    /// same polkit check before it signs.
+    async fn sign_face(&self, user_id: u32, face_id: &str) -> Result<(), DaemonError> {
+        if !self.record_auth.is_enabled() {
+            return Ok(());
+        }
+        let record = self
+            .storage
+            .list_user_faces(user_id)?
+            .into_iter()
+            .find(|face| face.face_id == face_id)
+            .ok_or_else(|| DaemonError::FaceNotFound(face_id.to_string()))?;
+        let template = self
+            .storage
+            .load_face_templates(user_id, [face_id])?
+            .remove(face_id)
+            .ok_or_else(|| DaemonError::FaceNotFound(face_id.to_string()))?;
+        let digest = record_auth::record_digest(&record, &template);
+        match self.record_auth.sign(&digest).await {
+            Ok(Some(mac)) => self.storage.set_face_mac(user_id, face_id, &mac),
+            Ok(None) => Ok(()),
+            Err(e) => Err(DaemonError::AccessDenied(format!(
+                "Face {} could not be signed: {}",
+                face_id, e
+            ))),
+        }
+    }
+
     pub async fn register_face(&self, request: RegisterFaceRequest) -> Result<String, DaemonError> {
         // Check permissions
         self.check_user_permission(request.user_id)?;
+        self.require_storage_key()?;
+        let allowed_contexts = normalize_contexts(&request.allowed_contexts)?;
 
         // A successfully enrolled face unlocks sudo (via pam_linux_hello.so),
         // so this can't be a same-UID-only decision like the check above —
@@ -214,10 +458,16 @@ impl FaceAuthDaemon {
             request.user_id, request.context
         );
 
+        // The configured sample count is a floor: the caller may ask for
+        // more samples than config (or the administrator) requires, never
+        // fewer.
+        let capture_settings = &self.config.settings.capture;
+        let num_samples = request.num_samples.max(capture_settings.enrollment_samples);
+
         // Capture frames
         let capture = self
             .camera
-            .capture_frames(request.num_samples, request.timeout_ms)
+            .capture_frames(num_samples, request.timeout_ms)
             .await
             .map_err(|e| DaemonError::CameraError(e.to_string()))?;
 
@@ -232,6 +482,21 @@ impl FaceAuthDaemon {
         if valid.is_empty() {
             return Err(DaemonError::CameraError("No face detected".to_string()));
         }
+        // Frames without a face carry no quality of their own: judge the
+        // enrollment on the frames that will actually be averaged.
+        let quality =
+            valid.iter().map(|e| e.metadata.quality_score).sum::<f32>() / valid.len() as f32;
+        if self
+            .config
+            .policy
+            .is_locked("capture.enrollment_quality_threshold")
+            && quality < capture_settings.enrollment_quality_threshold
+        {
+            return Err(DaemonError::PolicyViolation(format!(
+                "enrollment quality {:.2} is below the required {:.2}",
+                quality, capture_settings.enrollment_quality_threshold
+            )));
+        }
         let dim = valid[0].vector.len();
         let mut avg = vec![0.0f32; dim];
         for e in &valid {
@@ -251,7 +516,7 @@ impl FaceAuthDaemon {
             }
         }
         info!(
-            "Enrollment: {} frames averaged into 1 embedding",
+            "Enrollment: {} frames averaged into 1 embedding, each kept as a sample",
             valid.len()
         );
         let best = valid
@@ -263,9 +528,16 @@ impl FaceAuthDaemon {
                     .unwrap_or(std::cmp::Ordering::Equal)
             })
             .unwrap();
-        let embedding = &hello_face_core::Embedding {
-            vector: avg,
-            metadata: best.metadata.clone(),
+        // The average is kept alongside the samples it came from: the
+        // matcher scores the samples under `recognition.fusion`, and falls
+        // back to the average for `centroid` or a face without samples.
+        let template = FaceTemplate {
+            centroid: hello_face_core::Embedding {
+                vector: avg,
+                metadata: best.metadata.clone(),
+            },
+            samples: valid.iter().map(|e| (*e).clone()).collect(),
+            adapted: Vec::new(),
         };
 
         // Generate a unique ID for this face
@@ -283,12 +555,23 @@ impl FaceAuthDaemon {
             quality_score: capture.quality_score,
             registered_at: now,
             context: request.context.clone(),
+            label: None,
+            notes: None,
+            allowed_contexts,
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
         };
 
         // Save
         self.storage
-            .save_face(&record, embedding)
+            .save_face_template(&record, &template)
             .map_err(|e| DaemonError::StorageError(e.to_string()))?;
+        // An unsigned face could never match: don't leave one behind
+        if let Err(e) = self.sign_face(request.user_id, &face_id).await {
+            let _ = self.storage.delete_face(request.user_id, &face_id);
+            return Err(e);
+        }
 
         info!("Face registered: face_id={}", face_id);
 
@@ -341,29 +624,429 @@ impl FaceAuthDaemon {
         Ok(())
     }
 
+    /// Drop the adapted samples of one or all of a user's faces (see
+    /// `adaptive`), going back to the templates as enrolled. Returns how
+    /// many were dropped. Not behind the enrollment authorizer: it can only
+    /// narrow what a face matches, never widen it.
+    pub async fn reset_adaptation(
+        &self,
+        request: ResetAdaptationRequest,
+    ) -> Result<usize, DaemonError> {
+        self.check_user_permission(request.user_id)?;
+        let dropped = self
+            .storage
+            .reset_adapted_samples(request.user_id, request.face_id.as_deref())?;
+        info!(
+            "Reset adaptation for user_id={}, face_id={:?}: {} sample(s) dropped",
+            request.user_id, request.face_id, dropped
+        );
+        Ok(dropped)
+    }
+
+    /// Run a labelled capture session for threshold calibration (see
+    /// `calibration`): every frame is scored against the user's faces as a
+    /// verification for `context` would score it, nothing is
+    /// authenticated, and the scores are appended to `calibration.jsonl`
+    /// in the storage directory. Only with `debug.record_scores` on.
+    /// Returns how many frames were recorded.
+    pub async fn record_calibration(
+        &self,
+        request: RecordCalibrationRequest,
+    ) -> Result<usize, DaemonError> {
+        self.check_user_permission(request.user_id)?;
+        let settings = &self.config.settings;
+        if !settings.debug.record_scores {
+            return Err(DaemonError::InvalidRequest(
+                "Recording calibration scores is off (set debug.record_scores = true)".to_string(),
+            ));
+        }
+        let context = normalize_contexts(std::slice::from_ref(&request.context))?.remove(0);
+        if !(1..=calibration::MAX_SESSION_MS).contains(&request.duration_ms) {
+            return Err(DaemonError::InvalidRequest(format!(
+                "Recording duration must be between 1 and {} ms",
+                calibration::MAX_SESSION_MS
+            )));
+        }
+
+        // What a verification for the context would match against: faces
+        // allowed for it, from the current model, adapted samples as
+        // adaptation is set up
+        let face_ids: Vec<String> = self
+            .storage
+            .list_user_faces(request.user_id)?
+            .into_iter()
+            .filter(|face| face.allows_context(&context))
+            .map(|face| face.face_id)
+            .collect();
+        let mut templates = self
+            .storage
+            .load_face_templates(request.user_id, face_ids)?;
+        templates.retain(|_, template| self.camera.is_current_template(&template.centroid));
+        let adaptation = adaptive::AdaptationPolicy::from_config(&settings.behavior);
+        for template in templates.values_mut() {
+            match &adaptation {
+                Some(policy) => {
+                    policy.drop_drifted(&self.matcher, template);
+                }
+                None => template.adapted.clear(),
+            }
+        }
+        if templates.is_empty() {
+            return Err(DaemonError::InvalidRequest(format!(
+                "user_id={} has no face enrolled with the current model for context {}",
+                request.user_id, context
+            )));
+        }
+
+        info!(
+            "Recording {:?} calibration scores for user_id={}, context={} for {}ms",
+            request.label, request.user_id, context, request.duration_ms
+        );
+        let samples = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let samples_clone = Arc::clone(&samples);
+        let matcher = Arc::clone(&self.matcher);
+        let label = request.label;
+        self.camera
+            .capture_until(
+                request.duration_ms,
+                None,
+                move |embedding, ir_liveness, rgb_liveness| {
+                    let result = matcher.match_embedding(&embedding, &templates, &context);
+                    let model = format!(
+                        "{} {}",
+                        embedding.metadata.model, embedding.metadata.model_version
+                    );
+                    let mut samples = samples_clone.lock().unwrap();
+                    samples.push(calibration::ScoreSample::new(
+                        label,
+                        &context,
+                        model,
+                        result.best_score,
+                        ir_liveness,
+                        rgb_liveness,
+                    ));
+                    samples.len() >= calibration::MAX_SESSION_SAMPLES
+                },
+            )
+            .await
+            .map_err(|e| DaemonError::CameraError(e.to_string()))?;
+
+        let samples = std::mem::take(&mut *samples.lock().unwrap());
+        if !samples.is_empty() {
+            calibration::append_samples(
+                &self.config.storage_path.join(calibration::SCORES_FILE),
+                &samples,
+            )
+            .map_err(|e| DaemonError::StorageError(e.to_string()))?;
+        }
+        info!("Recorded {} calibration sample(s)", samples.len());
+        Ok(samples.len())
+    }
+
+    /// Rename a face, change its notes or the contexts it may
+    /// authenticate. Behind the same authorizer as `delete_face`: labels
+    /// are what the user goes by when choosing which enrollment to delete,
+    /// and lifting a context restriction widens what the face unlocks.
+    pub async fn update_face(&self, request: UpdateFaceRequest) -> Result<(), DaemonError> {
+        self.check_user_permission(request.user_id)?;
+        self.require_storage_key()?;
+        if let Some(label) = &request.label {
+            check_face_text("Label", label.trim(), MAX_FACE_LABEL_LEN, false)?;
+        }
+        if let Some(notes) = &request.notes {
+            check_face_text("Notes", notes, MAX_FACE_NOTES_LEN, true)?;
+        }
+        let allowed_contexts = request
+            .allowed_contexts
+            .as_deref()
+            .map(normalize_contexts)
+            .transpose()?;
+
+        if !self
+            .enrollment_authorizer
+            .authorize(authz::MANAGE_FACES_ACTION)
+            .await
+        {
+            return Err(DaemonError::AccessDenied(
+                "Enrollment change was not authorized".to_string(),
+            ));
+        }
+
+        info!(
+            "Updating face metadata for user_id={}, face_id={}",
+            request.user_id, request.face_id
+        );
+
+        // Labels and notes aren't covered by the record's MAC; contexts are
+        let previous = match allowed_contexts {
+            Some(_) => Some(
+                self.storage
+                    .list_user_faces(request.user_id)?
+                    .into_iter()
+                    .find(|face| face.face_id == request.face_id)
+                    .ok_or_else(|| DaemonError::FaceNotFound(request.face_id.clone()))?,
+            ),
+            None => None,
+        };
+        self.storage.update_face_metadata(
+            request.user_id,
+            &request.face_id,
+            request.label.as_deref().map(str::trim),
+            request.notes.as_deref(),
+            allowed_contexts.as_deref(),
+        )?;
+        if let Some(previous) = previous {
+            // New contexts under the old MAC would never match again: put
+            // the face back as it was, still signed
+            if let Err(e) = self.sign_face(request.user_id, &request.face_id).await {
+                self.storage.update_face_metadata(
+                    request.user_id,
+                    &request.face_id,
+                    Some(previous.label.as_deref().unwrap_or_default()),
+                    Some(previous.notes.as_deref().unwrap_or_default()),
+                    Some(&previous.allowed_contexts),
+                )?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
     pub async fn verify(&self, request: VerifyRequest) -> Result<VerifyResult, DaemonError> {
         // Check permissions
         self.check_user_permission(request.user_id)?;
+        self.require_storage_key()?;
         verify_with_storage(
             &self.storage,
             &self.camera,
             Arc::clone(&self.matcher),
+            &self.config.settings,
+            &self.record_auth,
+            &self.failures,
             &request,
         )
         .await
     }
 
+    /// Forget `user_id`'s failed matches in `context` — the PAM module
+    /// reports a password authentication that followed a failed face one
+    /// there (see `lockout`). Returns how many contexts had any.
+    pub fn clear_failures(&self, user_id: u32, context: &str) -> Result<usize, DaemonError> {
+        let cleared = self.failures.clear(user_id, Some(context))?;
+        if cleared > 0 {
+            info!(
+                "Failed face attempts of user_id={} in {} cleared after an authentication",
+                user_id, context
+            );
+        }
+        Ok(cleared)
+    }
+
+    /// `user_id`'s faces that expire within `recognition.expiry_warning_days`
+    /// of `now` or already have (see `expiry`); none without an age limit
+    pub fn expiring_faces(
+        &self,
+        user_id: u32,
+        now: u64,
+    ) -> Result<Vec<expiry::ExpiringFace>, DaemonError> {
+        let recognition = &self.config.settings.recognition;
+        let Some(max_age_days) = recognition.shortest_template_age_days() else {
+            return Ok(Vec::new());
+        };
+        let faces = self.storage.list_user_faces(user_id)?;
+        Ok(expiry::expiring_faces(
+            &faces,
+            max_age_days,
+            recognition.expiry_warning_days,
+            now,
+        ))
+    }
+
     pub async fn list_faces(&self, user_id: u32) -> Result<String, DaemonError> {
         self.check_user_permission(user_id)?;
 
-        let faces = self
+        let mut faces = self
             .storage
             .list_user_faces(user_id)
             .map_err(|e| DaemonError::StorageError(e.to_string()))?;
+        let mut stats = self.storage.load_face_stats(user_id)?;
+        let macs = if self.record_auth.is_enabled() {
+            Some(self.storage.load_face_macs(user_id)?)
+        } else {
+            None
+        };
+        for face in &mut faces {
+            face.stats = stats.remove(&face.face_id).unwrap_or_default();
+            let unsigned = macs
+                .as_ref()
+                .is_some_and(|macs| !macs.contains_key(&face.face_id));
+            // A template that can't be read is reported by verify, not here
+            face.needs_reenrollment = unsigned
+                || self
+                    .storage
+                    .load_face_embedding(user_id, &face.face_id)
+                    .is_ok_and(|embedding| !self.camera.is_current_template(&embedding));
+            face.expires_at = self
+                .config
+                .settings
+                .recognition
+                .shortest_template_age_days()
+                .map(|days| face.expiry(days));
+        }
 
         Ok(serde_json::to_string(&faces)?)
     }
 
+    /// Pack `user_id`'s faces into a passphrase-encrypted archive (see
+    /// `backup`).
+    ///
+    /// Behind the same re-authentication as enrollment changes: the
+    /// archive is as good as the raw templates to whoever chose the
+    /// passphrase, and templates never otherwise leave the daemon.
+    pub async fn export_faces(&self, request: ExportFacesRequest) -> Result<Vec<u8>, DaemonError> {
+        self.check_user_permission(request.user_id)?;
+        if !self
+            .enrollment_authorizer
+            .authorize(authz::MANAGE_FACES_ACTION)
+            .await
+        {
+            return Err(DaemonError::AccessDenied(
+                "Backup export was not authorized".to_string(),
+            ));
+        }
+
+        let records = self.storage.list_user_faces(request.user_id)?;
+        let mut templates = self
+            .storage
+            .load_face_templates(request.user_id, records.iter().map(|r| &r.face_id))?;
+        let faces = records
+            .into_iter()
+            .filter_map(|record| {
+                let template = templates.remove(&record.face_id)?;
+                Some(backup::BackedUpFace {
+                    record,
+                    embedding: template.centroid,
+                    samples: template.samples,
+                })
+            })
+            .collect::<Vec<_>>();
+        info!(
+            "Exporting {} face(s) of user_id={}",
+            faces.len(),
+            request.user_id
+        );
+
+        let contents = backup::BackupContents {
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            user_id: request.user_id,
+            faces,
+        };
+        // Key derivation is deliberately slow; keep it off the async workers
+        let archive =
+            tokio::task::spawn_blocking(move || backup::seal(&contents, &request.passphrase))
+                .await
+                .map_err(|e| DaemonError::StorageError(e.to_string()))??;
+        Ok(archive)
+    }
+
+    /// Restore faces from an archive made by [`FaceAuthDaemon::export_faces`]
+    /// into `user_id`'s enrollments, replacing faces with the same id.
+    ///
+    /// The archive is decrypted and checked first — faces enrolled with
+    /// another embedding model are left out, since they could never match
+    /// (see `VerifyResult::ReEnrollmentRequired`) — then the enrollment
+    /// re-authentication runs, and only then is anything written, in one
+    /// transaction.
+    pub async fn import_faces(
+        &self,
+        request: ImportFacesRequest,
+        archive: Vec<u8>,
+    ) -> Result<ImportFacesResponse, DaemonError> {
+        self.check_user_permission(request.user_id)?;
+        self.require_storage_key()?;
+
+        let contents =
+            tokio::task::spawn_blocking(move || backup::open(&archive, &request.passphrase))
+                .await
+                .map_err(|e| DaemonError::StorageError(e.to_string()))??;
+        let total = contents.faces.len();
+        let mut faces = Vec::with_capacity(total);
+        for face in contents.faces {
+            if !self.camera.is_current_template(&face.embedding) {
+                continue;
+            }
+            // An archive is just a file: what it says about a face gets the
+            // same checks as a label, notes or contexts set through
+            // `update_face`, and one bad face rejects the whole archive
+            let record = face.record;
+            let invalid = |e: DaemonError| {
+                DaemonError::InvalidRequest(format!(
+                    "Face {:?} in the backup: {}",
+                    record.face_id, e
+                ))
+            };
+            if let Some(label) = &record.label {
+                check_face_text("Label", label, MAX_FACE_LABEL_LEN, false).map_err(invalid)?;
+            }
+            if let Some(notes) = &record.notes {
+                check_face_text("Notes", notes, MAX_FACE_NOTES_LEN, true).map_err(invalid)?;
+            }
+            let allowed_contexts = normalize_contexts(&record.allowed_contexts).map_err(invalid)?;
+            let record = FaceRecord {
+                user_id: request.user_id,
+                allowed_contexts,
+                needs_reenrollment: false,
+                expires_at: None,
+                stats: FaceStats::default(),
+                ..record
+            };
+            let template = FaceTemplate {
+                centroid: face.embedding,
+                samples: face
+                    .samples
+                    .into_iter()
+                    .filter(|sample| self.camera.is_current_template(sample))
+                    .collect(),
+                adapted: Vec::new(),
+            };
+            faces.push((record, template));
+        }
+        if faces.is_empty() && total > 0 {
+            return Err(backup::BackupError::IncompatibleModel(total).into());
+        }
+
+        // Importing plants faces that unlock sudo, exactly like enrolling
+        if !self
+            .enrollment_authorizer
+            .authorize(authz::MANAGE_FACES_ACTION)
+            .await
+        {
+            return Err(DaemonError::AccessDenied(
+                "Backup import was not authorized".to_string(),
+            ));
+        }
+
+        self.storage.save_faces(&faces)?;
+        for (record, _) in &faces {
+            self.sign_face(request.user_id, &record.face_id).await?;
+        }
+        info!(
+            "Imported {} of {} face(s) from a backup of user_id={} into user_id={}",
+            faces.len(),
+            total,
+            contents.user_id,
+            request.user_id
+        );
+
+        Ok(ImportFacesResponse {
+            imported: faces.len(),
+            skipped_incompatible: total - faces.len(),
+        })
+    }
+
     /// Check that the current user has permission to access this UID
     fn check_user_permission(&self, target_uid: u32) -> Result<(), DaemonError> {
         let current_uid = unsafe { libc::getuid() };
@@ -388,6 +1071,45 @@ impl FaceAuthDaemon {
         &self.config
     }
 
+    /// Switch to a reloaded configuration (see `config_reload`). Takes
+    /// `&mut self` so it runs under the shared lock's write side, i.e. never
+    /// in the middle of a capture. Thresholds are swapped inside the
+    /// existing `FaceMatcher`, which the PAM helper and screen-lock watcher
+    /// reach through this same daemon; the camera is re-opened only when
+    /// `[detection]` or the default capture timeout changed.
+    ///
+    /// `behavior.logging_level` is read once, when tracing is set up, so a
+    /// change to it only takes effect on the next restart.
+    pub fn apply_config(
+        &mut self,
+        loaded: hello_config::LoadedConfig,
+    ) -> config_reload::ConfigChange {
+        let old = &self.config.settings;
+        let new = loaded.config;
+        let changed_sections = config_reload::changed_sections(old, &new);
+
+        let camera_reopened = old.detection != new.detection
+            || old.capture.default_timeout_ms != new.capture.default_timeout_ms;
+        if old.behavior.logging_level != new.behavior.logging_level {
+            warn!(
+                "behavior.logging_level changed to {:?}; takes effect after a restart",
+                new.behavior.logging_level
+            );
+        }
+
+        self.matcher.reconfigure(&new.recognition);
+        if camera_reopened {
+            self.camera = Arc::new(self.camera.reconfigured(&new));
+        }
+        self.config.settings = new;
+        self.config.policy = loaded.policy;
+
+        config_reload::ConfigChange {
+            changed_sections,
+            camera_reopened,
+        }
+    }
+
     pub fn is_camera_available(&self) -> bool {
         self.camera.is_available()
     }
@@ -411,6 +1133,8 @@ struct VerifyLoopState {
     /// `required_consecutive` — guaranteed `matched` with a `face_id`,
     /// unlike `best_result` above.
     success_result: Option<MatchResult>,
+    /// The embedding of that frame, for `adaptive`
+    success_probe: Option<hello_face_core::Embedding>,
 }
 
 /// Folds one frame's `MatchResult` into `state` and returns whether the
@@ -445,6 +1169,53 @@ fn record_frame_result(
     state.success_result.is_some()
 }
 
+/// Refuse `context` when its `authentication.enable_for_*` flag is off, so
+/// turning face auth off for one service in config.toml takes effect
+/// without editing `/etc/pam.d`. A flag the administrator policy locks off
+/// is already `false` in `settings` by the time it gets here (locks are
+/// applied when the config is loaded). Custom contexts have no flag and
+/// always pass.
+pub(crate) fn check_context_enabled(
+    settings: &hello_config::Config,
+    context: &str,
+) -> Result<(), DaemonError> {
+    if settings.authentication.is_enabled_for(context) == Some(false) {
+        info!("Face authentication is disabled for context={}", context);
+        return Err(DaemonError::ContextDisabled(context.to_string()));
+    }
+    Ok(())
+}
+
+/// Count a face that was seen and didn't match against `request`'s user
+/// and context, logging when that starts a backoff or locks face auth
+fn record_failed_match(failures: &FailureLog, policy: &LockoutPolicy, request: &VerifyRequest) {
+    let state = match failures.record_failure(request.user_id, &request.context, unix_now()) {
+        Ok(state) => state,
+        Err(e) => {
+            warn!("Could not record the failed attempt: {}", e);
+            return;
+        }
+    };
+    if policy.lock_after > 0 && state.failures == policy.lock_after {
+        warn!(
+            target: "audit",
+            "Face auth locked for user_id={}, context={} after {} failed attempts \
+             in a row; a password authentication unlocks it",
+            request.user_id,
+            request.context,
+            state.failures
+        );
+    } else if state.failures >= policy.backoff_after {
+        info!(
+            "{} failed attempts in a row for user_id={}, context={}: next one in {}s",
+            state.failures,
+            request.user_id,
+            request.context,
+            policy.backoff_secs(state.failures)
+        );
+    }
+}
+
 /// Verify a face against a given storage/camera/matcher, independent of any
 /// particular `FaceAuthDaemon` instance.
 ///
@@ -457,11 +1228,17 @@ fn record_frame_result(
 /// responsibility, not this function's: `FaceAuthDaemon::verify()` checks
 /// against the daemon process's own UID, while the system listener checks
 /// the *socket peer's* credentials instead (see `pam_helper.rs`) — the two
-/// have different trust models, so the check can't live here.
+/// have different trust models, so the check can't live here. The
+/// per-context enable check does live here, so both callers enforce it,
+/// and so does the failed-attempt throttling against `failures` (see
+/// `lockout`).
 pub async fn verify_with_storage(
     storage: &FaceStorage,
     camera: &CameraManager,
     matcher: Arc<FaceMatcher>,
+    settings: &hello_config::Config,
+    record_auth: &RecordAuthenticator,
+    failures: &FailureLog,
     request: &VerifyRequest,
 ) -> Result<VerifyResult, DaemonError> {
     info!(
@@ -469,6 +1246,21 @@ pub async fn verify_with_storage(
         request.user_id, request.context
     );
 
+    check_context_enabled(settings, &request.context)?;
+
+    // Too many failed matches in a row: don't even open the camera
+    let lockout_policy = LockoutPolicy::from_config(&settings.authentication);
+    let failure_state = failures.state(request.user_id, &request.context)?;
+    if let Some(lockout) = lockout_policy.check(&failure_state, unix_now()) {
+        info!(
+            "Face auth for user_id={}, context={} is off after {} failed attempts: {:?}",
+            request.user_id, request.context, failure_state.failures, lockout
+        );
+        return Ok(VerifyResult::LockedOut {
+            until: lockout.until(),
+        });
+    }
+
     // Load the registered faces
     let faces = storage
         .list_user_faces(request.user_id)
@@ -479,11 +1271,175 @@ pub async fn verify_with_storage(
         return Ok(VerifyResult::NoEnrollment);
     }
 
+    // Faces restricted to other contexts ("screenlock only") don't count
+    // here at all; with none left it's as if nothing were enrolled
+    let total = faces.len();
+    let mut faces: Vec<FaceRecord> = faces
+        .into_iter()
+        .filter(|face| face.allows_context(&request.context))
+        .collect();
+    if faces.is_empty() {
+        info!(
+            "None of the {} face(s) of user_id={} may authenticate context={}",
+            total, request.user_id, request.context
+        );
+        return Ok(VerifyResult::NoEnrollment);
+    }
+
+    // A face enrolled longer ago than this context allows may no longer
+    // look like the user; like one from another model, only a new
+    // enrollment helps
+    if let Some(days) = settings.recognition.max_template_age_days(&request.context) {
+        let now = unix_now();
+        let (faces_in_date, expired): (Vec<FaceRecord>, Vec<FaceRecord>) =
+            faces.into_iter().partition(|face| face.expiry(days) > now);
+        if !expired.is_empty() {
+            info!(
+                "{} face(s) of user_id={} are older than {} days and not accepted for context={}: {:?}",
+                expired.len(),
+                request.user_id,
+                days,
+                request.context,
+                expired.iter().map(|f| &f.face_id).collect::<Vec<_>>()
+            );
+        }
+        if faces_in_date.is_empty() {
+            return Ok(VerifyResult::ReEnrollmentRequired);
+        }
+        faces = faces_in_date;
+    }
+
     // Load the stored embeddings
-    let stored_embeddings = storage
-        .load_face_embeddings(request.user_id, faces.iter().map(|f| &f.face_id))
+    let mut stored_embeddings = storage
+        .load_face_templates(request.user_id, faces.iter().map(|f| &f.face_id))
         .map_err(|e| DaemonError::StorageError(e.to_string()))?;
+
+    // A face whose MAC doesn't check out was written by something other
+    // than an authorized enrollment change: never match against it. One
+    // without any was enrolled before records were signed — or planted
+    // without one — and has to be enrolled again either way.
+    let macs = if record_auth.is_enabled() {
+        storage.load_face_macs(request.user_id)?
+    } else {
+        HashMap::new()
+    };
+    if record_auth.is_enabled() {
+        let mut unsigned = 0;
+        let mut unchecked = None;
+        for face in &faces {
+            let Some(template) = stored_embeddings.get(&face.face_id) else {
+                continue;
+            };
+            let Some(mac) = macs.get(&face.face_id) else {
+                info!(
+                    "Face {} of user_id={} isn't signed (enrolled before face records were); \
+                     it has to be enrolled again",
+                    face.face_id, request.user_id
+                );
+                unsigned += 1;
+                stored_embeddings.remove(&face.face_id);
+                continue;
+            };
+            let digest = record_auth::record_digest(face, template);
+            let checked = match record_auth.check(&digest, Some(mac)).await {
+                // Signed before its registration time was covered: moved
+                // over to the current digest if that time is still its own
+                Ok(false) => match record_auth.resign(face, template, mac).await {
+                    Ok(Some(resigned)) => {
+                        if let Err(e) =
+                            storage.set_face_mac(request.user_id, &face.face_id, &resigned)
+                        {
+                            warn!(
+                                "Could not store the new MAC of face {}: {}",
+                                face.face_id, e
+                            );
+                        }
+                        Ok(true)
+                    }
+                    Ok(None) => Ok(false),
+                    Err(e) => Err(e),
+                },
+                checked => checked,
+            };
+            match checked {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!(
+                        target: "audit",
+                        "Face {} of user_id={} failed its integrity check and is ignored \
+                         (altered or added outside an authorized enrollment)",
+                        face.face_id,
+                        request.user_id
+                    );
+                    stored_embeddings.remove(&face.face_id);
+                }
+                Err(e) => {
+                    warn!("Could not check face {}: {}", face.face_id, e);
+                    stored_embeddings.remove(&face.face_id);
+                    unchecked = Some(e);
+                }
+            }
+        }
+        if stored_embeddings.is_empty() {
+            if let Some(e) = unchecked {
+                return Err(DaemonError::StorageError(format!(
+                    "Face records could not be checked: {e}"
+                )));
+            }
+            if unsigned > 0 {
+                return Ok(VerifyResult::ReEnrollmentRequired);
+            }
+            return Ok(VerifyResult::NoEnrollment);
+        }
+
+        // Adapted samples count only with the MAC `hello-storage-key` gave
+        // them as they were folded in
+        adaptive::drop_unsigned(
+            storage,
+            record_auth,
+            request.user_id,
+            &mut stored_embeddings,
+        )
+        .await?;
+    }
+
+    // Templates from another embedding model — enrolled before a model
+    // upgrade, or while the stub fallback was loaded — can't be compared
+    // with what the camera extracts now
+    let (mut stored_embeddings, stale): (HashMap<_, _>, HashMap<_, _>) = stored_embeddings
+        .into_iter()
+        .partition(|(_, template)| camera.is_current_template(&template.centroid));
+    if !stale.is_empty() {
+        warn!(
+            "{} enrolled face(s) of user_id={} are from another embedding model and skipped: {:?}",
+            stale.len(),
+            request.user_id,
+            stale.keys().collect::<Vec<_>>()
+        );
+    }
+    if stored_embeddings.is_empty() {
+        return Ok(VerifyResult::ReEnrollmentRequired);
+    }
+
+    // What adaptation added is only used while it's turned on, and within
+    // its drift cap (see `adaptive`)
+    let adaptation = adaptive::AdaptationPolicy::from_config(&settings.behavior);
+    for (face_id, template) in &mut stored_embeddings {
+        match &adaptation {
+            Some(policy) => {
+                let dropped = policy.drop_drifted(&matcher, template);
+                if dropped > 0 {
+                    warn!(
+                        "{} adapted sample(s) of face {} are past the drift cap and ignored",
+                        dropped, face_id
+                    );
+                }
+            }
+            None => template.adapted.clear(),
+        }
+    }
     let stored_embeddings = Arc::new(stored_embeddings);
+    let adapt_matcher = Arc::clone(&matcher);
 
     // Camera stays engaged (no on/off blink) and keeps trying for the whole
     // request.timeout_ms window instead of a fixed quick burst — gives the
@@ -497,14 +1453,18 @@ pub async fn verify_with_storage(
     const REQUIRED_CONSECUTIVE_MATCHES: u32 = 2;
 
     let state = Arc::new(std::sync::Mutex::new(VerifyLoopState::default()));
+    let recorder = debug_dump::DumpSettings::from_config(&settings.debug)
+        .map(|dump| Arc::new(debug_dump::AttemptRecorder::new(dump)));
 
     let context = request.context.clone();
     let state_clone = Arc::clone(&state);
     let stored_clone = Arc::clone(&stored_embeddings);
+    let recorder_clone = recorder.clone();
 
     camera
         .capture_until(
             request.timeout_ms,
+            recorder.clone(),
             move |embedding, ir_liveness, rgb_liveness| {
                 let result = matcher.match_with_liveness(
                     &embedding,
@@ -513,38 +1473,102 @@ pub async fn verify_with_storage(
                     ir_liveness,
                     rgb_liveness,
                 );
+                if let Some(recorder) = &recorder_clone {
+                    recorder.record_match(ir_liveness, &result);
+                }
                 let mut s = state_clone.lock().unwrap();
-                record_frame_result(&mut s, result, REQUIRED_CONSECUTIVE_MATCHES)
+                let done = record_frame_result(&mut s, result, REQUIRED_CONSECUTIVE_MATCHES);
+                if done && s.success_probe.is_none() {
+                    s.success_probe = Some(embedding);
+                }
+                done
             },
         )
         .await
         .map_err(|e| DaemonError::CameraError(e.to_string()))?;
 
-    let final_state = state.lock().unwrap();
+    // Out of the mutex: adapting may wait on the record service
+    let final_state = std::mem::take(&mut *state.lock().unwrap());
 
     if let Some(success) = &final_state.success_result {
         info!(
             "Face recognized after {} consecutive matching frames (score={:.3})",
             REQUIRED_CONSECUTIVE_MATCHES, success.best_score
         );
+        let face_id = success.face_id.clone().unwrap_or_default();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        // Statistics only: a failure to record one never fails the login
+        if let Err(e) = storage.record_match(request.user_id, &face_id, now, success.best_score) {
+            warn!("Could not record the match for {}: {}", face_id, e);
+        }
+        if failure_state.failures > 0 {
+            if let Err(e) = failures.clear(request.user_id, Some(&request.context)) {
+                warn!("Could not clear the failed attempts: {}", e);
+            }
+        }
+        if let (Some(policy), Some(probe), Some(template), Some(record)) = (
+            &adaptation,
+            &final_state.success_probe,
+            stored_embeddings.get(&face_id),
+            faces.iter().find(|face| face.face_id == face_id),
+        ) {
+            adaptive::fold_in(
+                storage,
+                record_auth,
+                policy,
+                &adapt_matcher,
+                record,
+                macs.get(&face_id).map(Vec::as_slice),
+                template,
+                probe,
+                &request.context,
+                success.best_score,
+                success.threshold,
+                now,
+            )
+            .await;
+        }
         return Ok(VerifyResult::Success {
-            face_id: success.face_id.clone().unwrap_or_default(),
+            face_id,
             similarity_score: success.best_score,
         });
     }
 
-    if !final_state.any_face_detected {
+    let result = if !final_state.any_face_detected {
         info!("No face detected within the verification window");
-        return Ok(VerifyResult::NoFaceDetected);
+        VerifyResult::NoFaceDetected
+    } else {
+        match &final_state.best_result {
+            Some(best) if best.best_score > 0.0 => VerifyResult::NoMatch {
+                best_score: best.best_score,
+                threshold: best.threshold,
+            },
+            _ => VerifyResult::NoFaceDetected,
+        }
+    };
+
+    if let VerifyResult::NoMatch { .. } = result {
+        record_failed_match(failures, &lockout_policy, request);
     }
 
-    match &final_state.best_result {
-        Some(best) if best.best_score > 0.0 => Ok(VerifyResult::NoMatch {
-            best_score: best.best_score,
-            threshold: best.threshold,
-        }),
-        _ => Ok(VerifyResult::NoFaceDetected),
+    if let Some(recorder) = recorder {
+        let outcome = match result {
+            VerifyResult::NoMatch { .. } => "no_match",
+            _ => "no_face",
+        };
+        // Written in the background: encoding a few dozen PNGs shouldn't
+        // delay the PAM module's fallback to the password prompt.
+        let (user_id, context) = (request.user_id, request.context.clone());
+        tokio::task::spawn_blocking(move || match recorder.write(user_id, &context, outcome) {
+            Ok(dir) => info!("Failed attempt dumped to {}", dir.display()),
+            Err(e) => warn!("Debug dump failed: {}", e),
+        });
     }
+
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -565,6 +1589,12 @@ mod tests {
             quality_score: 0.95,
             registered_at: 0,
             context: "login".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
         };
         let json = serde_json::to_string(&record).unwrap();
         let restored: FaceRecord = serde_json::from_str(&json).unwrap();
@@ -658,9 +1688,27 @@ mod tests {
         DaemonConfig {
             storage_path,
             root_mode: false,
+            settings: hello_config::Config::default(),
+            policy: hello_config::Policy::default(),
         }
     }
 
+    /// `test_config`, under an administrator policy parsed from `policy`
+    /// whose enforced values are also applied to `settings` by `apply`.
+    fn test_config_with_policy(
+        storage_path: std::path::PathBuf,
+        policy: &str,
+        apply: impl FnOnce(&mut hello_config::Config),
+    ) -> DaemonConfig {
+        let (policy, unknown) =
+            hello_config::Policy::parse(std::path::Path::new("policy.toml"), policy).unwrap();
+        assert!(unknown.is_empty(), "{unknown:?}");
+        let mut config = test_config(storage_path);
+        apply(&mut config.settings);
+        config.policy = policy;
+        config
+    }
+
     /// A `CameraManager` pointed at a device path that can't exist, with its
     /// own private lock file — matches camera::tests's own helper, but
     /// that one is private to camera.rs's test module.
@@ -702,6 +1750,7 @@ mod tests {
                 context: "test".to_string(),
                 timeout_ms: 1000,
                 num_samples: 1,
+                allowed_contexts: Vec::new(),
             })
             .await
             .unwrap();
@@ -723,9 +1772,9 @@ mod tests {
             FakeDetector::always_detects(default_face_region(640, 480)),
             FakeExtractor::sequence(vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]], 0.8),
         );
-        let daemon =
-            FaceAuthDaemon::new_for_test(test_config(storage_dir.path().to_path_buf()), camera)
-                .unwrap();
+        let mut config = test_config(storage_dir.path().to_path_buf());
+        config.settings.capture.enrollment_samples = 2;
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
 
         let uid = my_uid();
         let response_json = daemon
@@ -734,6 +1783,7 @@ mod tests {
                 context: "test".to_string(),
                 timeout_ms: 1000,
                 num_samples: 2,
+                allowed_contexts: Vec::new(),
             })
             .await
             .unwrap();
@@ -747,6 +1797,17 @@ mod tests {
         assert!((embedding.vector[0] - expected).abs() < 1e-4);
         assert!((embedding.vector[1] - expected).abs() < 1e-4);
         assert!(embedding.vector[2].abs() < 1e-5);
+
+        // Both samples are kept as captured, next to the average
+        let templates = storage
+            .load_face_templates(uid, [&response.face_id])
+            .unwrap();
+        let samples: Vec<_> = templates[&response.face_id]
+            .samples
+            .iter()
+            .map(|s| s.vector.clone())
+            .collect();
+        assert_eq!(samples, vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]]);
     }
 
     #[tokio::test]
@@ -766,6 +1827,7 @@ mod tests {
                 context: "test".to_string(),
                 timeout_ms: 1000,
                 num_samples: 2,
+                allowed_contexts: Vec::new(),
             })
             .await;
 
@@ -774,7 +1836,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_verify_returns_no_enrollment_when_nothing_registered() {
+    async fn test_without_a_storage_key_faces_are_neither_enrolled_nor_verified() {
         let storage_dir = tempfile::TempDir::new().unwrap();
         let (_cam_dir, camera) = test_camera(
             FakeDetector::always_detects(default_face_region(640, 480)),
@@ -782,28 +1844,39 @@ mod tests {
         );
         let daemon =
             FaceAuthDaemon::new_for_test(test_config(storage_dir.path().to_path_buf()), camera)
-                .unwrap();
+                .unwrap()
+                .without_storage_key("keys.socket unreachable".to_string());
 
-        let result = daemon
+        let enrolled = daemon
+            .register_face(RegisterFaceRequest {
+                user_id: my_uid(),
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await;
+        assert!(matches!(
+            enrolled,
+            Err(DaemonError::StorageKeyUnavailable(_))
+        ));
+        assert!(daemon.storage.list_user_faces(my_uid()).unwrap().is_empty());
+
+        let verified = daemon
             .verify(VerifyRequest {
                 user_id: my_uid(),
                 context: "test".to_string(),
                 timeout_ms: 100,
             })
-            .await
-            .unwrap();
-
-        assert!(matches!(result, VerifyResult::NoEnrollment));
+            .await;
+        assert!(matches!(
+            verified,
+            Err(DaemonError::StorageKeyUnavailable(_))
+        ));
     }
 
     #[tokio::test]
-    async fn test_verify_returns_no_face_detected_after_enrollment_when_camera_yields_nothing() {
-        // capture_until (verify's path) never invokes its callback at all
-        // when the device can't be opened, unlike capture_frames
-        // (register_face's path) which pads with stub frames — see
-        // camera::tests::test_capture_until_with_unavailable_device_never_invokes_callback.
-        // So a fresh enrollment followed by verify against the same fake
-        // camera deterministically lands on NoFaceDetected.
+    async fn test_verify_returns_no_enrollment_when_nothing_registered() {
         let storage_dir = tempfile::TempDir::new().unwrap();
         let (_cam_dir, camera) = test_camera(
             FakeDetector::always_detects(default_face_region(640, 480)),
@@ -812,17 +1885,945 @@ mod tests {
         let daemon =
             FaceAuthDaemon::new_for_test(test_config(storage_dir.path().to_path_buf()), camera)
                 .unwrap();
+
+        let result = daemon
+            .verify(VerifyRequest {
+                user_id: my_uid(),
+                context: "test".to_string(),
+                timeout_ms: 100,
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(result, VerifyResult::NoEnrollment));
+    }
+
+    #[tokio::test]
+    async fn test_update_face_labels_a_face_behind_the_authorizer() {
+        let uid = my_uid();
+        let (_dirs, daemon) = backup_test_daemon();
+        let face_id = serde_json::from_str::<dbus_interface::RegisterFaceResponse>(
+            &daemon
+                .register_face(RegisterFaceRequest {
+                    user_id: uid,
+                    context: "test".to_string(),
+                    timeout_ms: 1000,
+                    num_samples: 1,
+                    allowed_contexts: Vec::new(),
+                })
+                .await
+                .unwrap(),
+        )
+        .unwrap()
+        .face_id;
+        let request = |label: &str| UpdateFaceRequest {
+            user_id: uid,
+            face_id: face_id.clone(),
+            label: Some(label.to_string()),
+            notes: Some("office lighting\nlaptop camera".to_string()),
+            allowed_contexts: None,
+        };
+
+        daemon
+            .update_face(request("  with glasses "))
+            .await
+            .unwrap();
+        let faces: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert_eq!(faces[0].label.as_deref(), Some("with glasses"));
+        assert_eq!(
+            faces[0].notes.as_deref(),
+            Some("office lighting\nlaptop camera")
+        );
+
+        assert!(matches!(
+            daemon.update_face(request("tab\there")).await,
+            Err(DaemonError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            daemon
+                .update_face(request(&"x".repeat(MAX_FACE_LABEL_LEN + 1)))
+                .await,
+            Err(DaemonError::InvalidRequest(_))
+        ));
+
+        let daemon = daemon.with_enrollment_authorizer(EnrollmentAuthorizer::DenyAll);
+        assert!(matches!(
+            daemon.update_face(request("someone else")).await,
+            Err(DaemonError::AccessDenied(_))
+        ));
+        let faces: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert_eq!(faces[0].label.as_deref(), Some("with glasses"));
+    }
+
+    /// A daemon on fresh storage whose camera always sees one face; the
+    /// returned directories must outlive it
+    fn backup_test_daemon() -> ((tempfile::TempDir, tempfile::TempDir), FaceAuthDaemon) {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let daemon =
+            FaceAuthDaemon::new_for_test(test_config(storage_dir.path().to_path_buf()), camera)
+                .unwrap();
+        ((storage_dir, cam_dir), daemon)
+    }
+
+    fn backed_up_face(face_id: &str, model: &str) -> backup::BackedUpFace {
+        backup::BackedUpFace {
+            record: FaceRecord {
+                face_id: face_id.to_string(),
+                user_id: 4242,
+                quality_score: 0.9,
+                registered_at: 0,
+                context: "test".to_string(),
+                label: None,
+                notes: None,
+                allowed_contexts: Vec::new(),
+                needs_reenrollment: true,
+                expires_at: None,
+                stats: FaceStats::default(),
+            },
+            embedding: hello_face_core::Embedding {
+                vector: vec![1.0, 0.0, 0.0],
+                metadata: hello_face_core::EmbeddingMetadata {
+                    model: model.to_string(),
+                    model_version: "test".to_string(),
+                    extracted_at: 0,
+                    quality_score: 0.9,
+                },
+            },
+            samples: Vec::new(),
+        }
+    }
+
+    fn archive_of(faces: Vec<backup::BackedUpFace>) -> Vec<u8> {
+        let contents = backup::BackupContents {
+            created_at: 0,
+            user_id: 4242,
+            faces,
+        };
+        backup::seal(&contents, "correct horse").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exported_faces_import_into_a_fresh_install() {
+        let uid = my_uid();
+        let (_dir, daemon) = backup_test_daemon();
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let archive = daemon
+            .export_faces(ExportFacesRequest {
+                user_id: uid,
+                passphrase: "correct horse".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let (_other_dir, fresh) = backup_test_daemon();
+        let response = fresh
+            .import_faces(
+                ImportFacesRequest {
+                    user_id: uid,
+                    passphrase: "correct horse".to_string(),
+                },
+                archive,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.imported, 1);
+        assert_eq!(response.skipped_incompatible, 0);
+
+        let before: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        let after: Vec<FaceRecord> =
+            serde_json::from_str(&fresh.list_faces(uid).await.unwrap()).unwrap();
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].face_id, before[0].face_id);
+        assert_eq!(
+            fresh
+                .storage
+                .load_face_embedding(uid, &after[0].face_id)
+                .unwrap()
+                .vector,
+            daemon
+                .storage
+                .load_face_embedding(uid, &before[0].face_id)
+                .unwrap()
+                .vector
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_reassigns_faces_and_skips_other_models() {
+        let uid = my_uid();
+        let (_dir, daemon) = backup_test_daemon();
+        let archive = archive_of(vec![
+            backed_up_face("face_current", "fake"),
+            backed_up_face("face_stale", "histogram"),
+        ]);
+
+        let response = daemon
+            .import_faces(
+                ImportFacesRequest {
+                    user_id: uid,
+                    passphrase: "correct horse".to_string(),
+                },
+                archive,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.imported, 1);
+        assert_eq!(response.skipped_incompatible, 1);
+
+        let faces: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert_eq!(faces.len(), 1);
+        assert_eq!(faces[0].face_id, "face_current");
+        assert_eq!(faces[0].user_id, uid);
+        assert!(!faces[0].needs_reenrollment);
+
+        // Nothing usable at all is an error rather than an empty success
+        let stale_only = archive_of(vec![backed_up_face("face_stale", "histogram")]);
+        let result = daemon
+            .import_faces(
+                ImportFacesRequest {
+                    user_id: uid,
+                    passphrase: "correct horse".to_string(),
+                },
+                stale_only,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(DaemonError::Backup(backup::BackupError::IncompatibleModel(
+                1
+            )))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_import_checks_what_the_archive_says_about_each_face() {
+        let uid = my_uid();
+        let (_dir, daemon) = backup_test_daemon();
+        let import = |faces| {
+            daemon.import_faces(
+                ImportFacesRequest {
+                    user_id: uid,
+                    passphrase: "correct horse".to_string(),
+                },
+                archive_of(faces),
+            )
+        };
+
+        let mut bad_label = backed_up_face("face_label", "fake");
+        bad_label.record.label = Some("\x1b[2Jgone".to_string());
+        let mut long_notes = backed_up_face("face_notes", "fake");
+        long_notes.record.notes = Some("x".repeat(MAX_FACE_NOTES_LEN + 1));
+        let mut bad_context = backed_up_face("face_context", "fake");
+        bad_context.record.allowed_contexts = vec!["sudo; rm".to_string()];
+        for face in [bad_label, long_notes, bad_context] {
+            let result = import(vec![backed_up_face("face_fine", "fake"), face]).await;
+            assert!(
+                matches!(result, Err(DaemonError::InvalidRequest(_))),
+                "{result:?}"
+            );
+        }
+        assert!(daemon.storage.list_user_faces(uid).unwrap().is_empty());
+
+        // Contexts are normalized, samples of another model dropped
+        let mut face = backed_up_face("face_ok", "fake");
+        face.record.allowed_contexts = vec![" sudo".to_string(), "login".to_string()];
+        let stale_sample = backed_up_face("unused", "histogram").embedding;
+        face.samples = vec![face.embedding.clone(), stale_sample];
+        import(vec![face]).await.unwrap();
+        let faces = daemon.storage.list_user_faces(uid).unwrap();
+        assert_eq!(faces[0].allowed_contexts, vec!["login", "sudo"]);
+        let templates = daemon
+            .storage
+            .load_face_templates(uid, ["face_ok"])
+            .unwrap();
+        assert_eq!(templates["face_ok"].samples.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_backup_needs_authorization_and_the_right_passphrase() {
+        let uid = my_uid();
+        let (_dir, daemon) = backup_test_daemon();
+        let daemon = daemon.with_enrollment_authorizer(EnrollmentAuthorizer::DenyAll);
+        let request = || ImportFacesRequest {
+            user_id: uid,
+            passphrase: "correct horse".to_string(),
+        };
+
+        let denied = daemon
+            .import_faces(
+                request(),
+                archive_of(vec![backed_up_face("face_a", "fake")]),
+            )
+            .await;
+        assert!(matches!(denied, Err(DaemonError::AccessDenied(_))));
+        assert!(daemon.storage.list_user_faces(uid).unwrap().is_empty());
+
+        let export = daemon
+            .export_faces(ExportFacesRequest {
+                user_id: uid,
+                passphrase: "correct horse".to_string(),
+            })
+            .await;
+        assert!(matches!(export, Err(DaemonError::AccessDenied(_))));
+
+        let wrong = daemon
+            .import_faces(
+                ImportFacesRequest {
+                    passphrase: "wrong horse!".to_string(),
+                    ..request()
+                },
+                archive_of(vec![backed_up_face("face_a", "fake")]),
+            )
+            .await;
+        assert!(matches!(
+            wrong,
+            Err(DaemonError::Backup(backup::BackupError::WrongPassphrase))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_faces_from_another_model_require_reenrollment() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let uid = my_uid();
+        // Enrolled by another model, before the daemon existed
+        storage::FaceStorage::new(storage_dir.path())
+            .unwrap()
+            .save_face(
+                &FaceRecord {
+                    face_id: "face_old".to_string(),
+                    user_id: uid,
+                    quality_score: 0.9,
+                    registered_at: 0,
+                    context: "test".to_string(),
+                    label: None,
+                    notes: None,
+                    allowed_contexts: Vec::new(),
+                    needs_reenrollment: false,
+                    expires_at: None,
+                    stats: FaceStats::default(),
+                },
+                &hello_face_core::Embedding {
+                    vector: vec![1.0, 0.0, 0.0],
+                    metadata: hello_face_core::EmbeddingMetadata {
+                        model: "histogram".to_string(),
+                        model_version: "0.1".to_string(),
+                        extracted_at: 0,
+                        quality_score: 0.9,
+                    },
+                },
+            )
+            .unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let daemon =
+            FaceAuthDaemon::new_for_test(test_config(storage_dir.path().to_path_buf()), camera)
+                .unwrap();
+
+        let result = daemon
+            .verify(VerifyRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 100,
+            })
+            .await
+            .unwrap();
+        assert!(
+            matches!(result, VerifyResult::ReEnrollmentRequired),
+            "{result}"
+        );
+
+        let faces: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert!(faces[0].needs_reenrollment);
+
+        // A face enrolled with the current model clears the way again
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let faces: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert_eq!(
+            faces.iter().filter(|f| f.needs_reenrollment).count(),
+            1,
+            "only the old face is flagged"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_returns_no_face_detected_after_enrollment_when_camera_yields_nothing() {
+        // capture_until (verify's path) never invokes its callback at all
+        // when the device can't be opened, unlike capture_frames
+        // (register_face's path) which pads with stub frames — see
+        // camera::tests::test_capture_until_with_unavailable_device_never_invokes_callback.
+        // So a fresh enrollment followed by verify against the same fake
+        // camera deterministically lands on NoFaceDetected.
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let daemon =
+            FaceAuthDaemon::new_for_test(test_config(storage_dir.path().to_path_buf()), camera)
+                .unwrap();
+        let uid = my_uid();
+
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let result = daemon
+            .verify(VerifyRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 100,
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(result, VerifyResult::NoFaceDetected));
+    }
+
+    #[tokio::test]
+    async fn test_verify_only_uses_faces_allowed_for_the_context() {
+        let uid = my_uid();
+        let (_dirs, daemon) = backup_test_daemon();
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "screenlock".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: vec!["screenlock".to_string(), "screenlock".to_string()],
+            })
+            .await
+            .unwrap();
+        let verify = |context: &str| {
+            daemon.verify(VerifyRequest {
+                user_id: uid,
+                context: context.to_string(),
+                timeout_ms: 100,
+            })
+        };
+
+        // A screenlock-only face is never even tried for sudo...
+        assert!(matches!(
+            verify("sudo").await.unwrap(),
+            VerifyResult::NoEnrollment
+        ));
+        // ...but is for screenlock (the fake camera then sees nothing, see
+        // the test above)
+        assert!(matches!(
+            verify("screenlock").await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+
+        let faces: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert_eq!(faces[0].allowed_contexts, vec!["screenlock"]);
+
+        // Lifting the restriction lets it authenticate anything again
+        daemon
+            .update_face(UpdateFaceRequest {
+                user_id: uid,
+                face_id: faces[0].face_id.clone(),
+                label: None,
+                notes: None,
+                allowed_contexts: Some(Vec::new()),
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            verify("sudo").await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_backs_off_then_locks_until_an_authentication() {
+        let uid = my_uid();
+        let (_dirs, daemon) = backup_test_daemon();
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let verify = |context: &str| {
+            daemon.verify(VerifyRequest {
+                user_id: uid,
+                context: context.to_string(),
+                timeout_ms: 100,
+            })
+        };
+        let fail = |times| {
+            for _ in 0..times {
+                daemon
+                    .failures
+                    .record_failure(uid, "sudo", unix_now())
+                    .unwrap();
+            }
+        };
+
+        // Nobody in front of the camera isn't a failed attempt
+        assert!(matches!(
+            verify("sudo").await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+        assert_eq!(daemon.failures.state(uid, "sudo").unwrap().failures, 0);
+
+        // Three failed matches: wait before the next, in that context only
+        fail(3);
+        match verify("sudo").await.unwrap() {
+            VerifyResult::LockedOut { until: Some(until) } => assert!(until > unix_now()),
+            other => panic!("expected a backoff, got {other:?}"),
+        }
+        assert!(matches!(
+            verify("screenlock").await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+
+        // Ten: only an authentication brings it back
+        fail(7);
+        assert!(matches!(
+            verify("sudo").await.unwrap(),
+            VerifyResult::LockedOut { until: None }
+        ));
+        assert_eq!(daemon.clear_failures(uid, "screenlock").unwrap(), 0);
+        assert!(matches!(
+            verify("sudo").await.unwrap(),
+            VerifyResult::LockedOut { until: None }
+        ));
+        assert_eq!(daemon.clear_failures(uid, "sudo").unwrap(), 1);
+        assert!(matches!(
+            verify("sudo").await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_ignores_faces_older_than_the_context_allows() {
+        let uid = my_uid();
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let mut config = test_config(storage_dir.path().to_path_buf());
+        config.settings.recognition.max_template_age_days = 30;
+        config.settings.recognition.contexts.insert(
+            "screenlock".to_string(),
+            hello_config::ContextConfig {
+                similarity_threshold: None,
+                require_confirmation: false,
+                max_template_age_days: Some(0),
+            },
+        );
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+        let verify = |context: &str| {
+            daemon.verify(VerifyRequest {
+                user_id: uid,
+                context: context.to_string(),
+                timeout_ms: 100,
+            })
+        };
+
+        // The same template, once enrolled 40 days ago
+        let response: dbus_interface::RegisterFaceResponse = serde_json::from_str(
+            &daemon
+                .register_face(RegisterFaceRequest {
+                    user_id: uid,
+                    context: "test".to_string(),
+                    timeout_ms: 1000,
+                    num_samples: 1,
+                    allowed_contexts: Vec::new(),
+                })
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let mut record = daemon.storage.list_user_faces(uid).unwrap().remove(0);
+        let template = daemon
+            .storage
+            .load_face_templates(uid, [&record.face_id])
+            .unwrap()
+            .remove(&record.face_id)
+            .unwrap();
+        daemon.storage.delete_face(uid, &record.face_id).unwrap();
+        record.registered_at = response.registered_at - 40 * SECS_PER_DAY;
+        daemon
+            .storage
+            .save_face_template(&record, &template)
+            .unwrap();
+
+        // Too old for sudo, and nothing else is enrolled
+        assert!(matches!(
+            verify("sudo").await.unwrap(),
+            VerifyResult::ReEnrollmentRequired
+        ));
+        // screenlock has no limit, so it's tried (the fake camera then sees
+        // nothing, see above)
+        assert!(matches!(
+            verify("screenlock").await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+
+        let faces: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert_eq!(
+            faces[0].expires_at,
+            Some(response.registered_at - 10 * SECS_PER_DAY)
+        );
+        let expiring = daemon.expiring_faces(uid, unix_now()).unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert!(expiring[0].is_expired(unix_now()));
+    }
+
+    #[tokio::test]
+    async fn test_list_faces_reports_match_statistics() {
+        let uid = my_uid();
+        let (_dirs, daemon) = backup_test_daemon();
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let list = || async {
+            serde_json::from_str::<Vec<FaceRecord>>(&daemon.list_faces(uid).await.unwrap()).unwrap()
+        };
+        let face_id = list().await[0].face_id.clone();
+        assert_eq!(list().await[0].stats, FaceStats::default());
+
+        // What verify_with_storage records on each success
+        daemon
+            .storage
+            .record_match(uid, &face_id, 100, 0.8)
+            .unwrap();
+        daemon
+            .storage
+            .record_match(uid, &face_id, 200, 0.7)
+            .unwrap();
+
+        let stats = &list().await[0].stats;
+        assert_eq!(stats.match_count, 2);
+        assert_eq!(stats.last_matched_at, Some(200));
+        assert_eq!(stats.recent_scores, vec![0.8, 0.7]);
+    }
+
+    #[tokio::test]
+    async fn test_reset_adaptation_drops_the_adapted_samples() {
+        let uid = my_uid();
+        let (_dirs, daemon) = backup_test_daemon();
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let list = || async {
+            serde_json::from_str::<Vec<FaceRecord>>(&daemon.list_faces(uid).await.unwrap()).unwrap()
+        };
+        let face_id = list().await[0].face_id.clone();
+        let probe = daemon.storage.load_face_embedding(uid, &face_id).unwrap();
+        daemon
+            .storage
+            .record_match(uid, &face_id, 100, 0.9)
+            .unwrap();
+        daemon
+            .storage
+            .add_adapted_sample(uid, &face_id, &probe, None, 100, 5)
+            .unwrap();
+        assert_eq!(list().await[0].stats.adapted_samples, 1);
+
+        let request = ResetAdaptationRequest {
+            user_id: uid,
+            face_id: Some(face_id.clone()),
+        };
+        assert_eq!(daemon.reset_adaptation(request).await.unwrap(), 1);
+        assert_eq!(list().await[0].stats.adapted_samples, 0);
+    }
+
+    #[tokio::test]
+    async fn test_record_calibration_needs_the_opt_in_and_an_enrolled_face() {
+        let uid = my_uid();
+        let (_dirs, mut daemon) = backup_test_daemon();
+        let request = |duration_ms| RecordCalibrationRequest {
+            user_id: uid,
+            label: calibration::SampleLabel::Genuine,
+            context: "sudo".to_string(),
+            duration_ms,
+        };
+        let refused = |result: Result<usize, DaemonError>, what: &str| match result {
+            Err(DaemonError::InvalidRequest(message)) => assert!(message.contains(what)),
+            other => panic!("expected {what:?} to be refused, got {other:?}"),
+        };
+
+        refused(
+            daemon.record_calibration(request(100)).await,
+            "debug.record_scores",
+        );
+        daemon.config.settings.debug.record_scores = true;
+        refused(daemon.record_calibration(request(100)).await, "no face");
+        refused(
+            daemon.record_calibration(request(600_000)).await,
+            "duration",
+        );
+
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+        // The test camera never sees anyone during a continuous capture
+        assert_eq!(daemon.record_calibration(request(100)).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_register_face_rejects_a_malformed_context_name() {
+        let (_dirs, daemon) = backup_test_daemon();
+        let result = daemon
+            .register_face(RegisterFaceRequest {
+                user_id: my_uid(),
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: vec!["sudo; rm".to_string()],
+            })
+            .await;
+
+        assert!(matches!(result, Err(DaemonError::InvalidRequest(_))));
+        assert!(daemon.storage.list_user_faces(my_uid()).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_verify_ignores_faces_written_or_altered_outside_enrollment() {
+        let uid = my_uid();
+        let (_dirs, daemon) = backup_test_daemon();
+        let daemon = daemon.with_record_authenticator(RecordAuthenticator::Local(
+            record_auth::RecordKey::from_bytes([9; 32]),
+        ));
+        let verify = || {
+            daemon.verify(VerifyRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 100,
+            })
+        };
+
+        // Planted straight into the database, or enrolled before records
+        // were signed: no MAC, never matched, enrolled again
+        daemon
+            .storage
+            .save_face(
+                &FaceRecord {
+                    face_id: format!("face_{}_1", uid),
+                    user_id: uid,
+                    quality_score: 0.9,
+                    registered_at: 0,
+                    context: "test".to_string(),
+                    label: None,
+                    notes: None,
+                    allowed_contexts: Vec::new(),
+                    needs_reenrollment: false,
+                    expires_at: None,
+                    stats: FaceStats::default(),
+                },
+                &hello_face_core::Embedding {
+                    vector: vec![1.0, 0.0, 0.0],
+                    metadata: hello_face_core::EmbeddingMetadata {
+                        model: "fake".to_string(),
+                        model_version: "test".to_string(),
+                        extracted_at: 0,
+                        quality_score: 0.9,
+                    },
+                },
+            )
+            .unwrap();
+        assert!(matches!(
+            verify().await.unwrap(),
+            VerifyResult::ReEnrollmentRequired
+        ));
+        let listed: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert!(listed[0].needs_reenrollment);
+
+        // Enrolled through the daemon: signed, and tried (the fake camera
+        // then sees nothing)
+        daemon
+            .storage
+            .delete_face(uid, &format!("face_{}_1", uid))
+            .unwrap();
+        let response: dbus_interface::RegisterFaceResponse = serde_json::from_str(
+            &daemon
+                .register_face(RegisterFaceRequest {
+                    user_id: uid,
+                    context: "test".to_string(),
+                    timeout_ms: 1000,
+                    num_samples: 1,
+                    allowed_contexts: vec!["screenlock".to_string()],
+                })
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let verify_screenlock = || {
+            daemon.verify(VerifyRequest {
+                user_id: uid,
+                context: "screenlock".to_string(),
+                timeout_ms: 100,
+            })
+        };
+        assert!(matches!(
+            verify_screenlock().await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+        let listed: Vec<FaceRecord> =
+            serde_json::from_str(&daemon.list_faces(uid).await.unwrap()).unwrap();
+        assert!(!listed[0].needs_reenrollment);
+
+        // Widening its contexts behind the daemon's back breaks the MAC
+        let any_context: &[String] = &[];
+        daemon
+            .storage
+            .update_face_metadata(uid, &response.face_id, None, None, Some(any_context))
+            .unwrap();
+        assert!(matches!(
+            verify_screenlock().await.unwrap(),
+            VerifyResult::NoEnrollment
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_update_face_is_undone_when_it_cannot_be_signed() {
         let uid = my_uid();
+        let (dirs, daemon) = backup_test_daemon();
+        let key = || RecordAuthenticator::Local(record_auth::RecordKey::from_bytes([9; 32]));
+        let daemon = daemon.with_record_authenticator(key());
+        let response: dbus_interface::RegisterFaceResponse = serde_json::from_str(
+            &daemon
+                .register_face(RegisterFaceRequest {
+                    user_id: uid,
+                    context: "test".to_string(),
+                    timeout_ms: 1000,
+                    num_samples: 1,
+                    allowed_contexts: vec!["screenlock".to_string()],
+                })
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+
+        // The record service is down: nothing can be signed
+        let daemon = daemon.with_record_authenticator(RecordAuthenticator::Service {
+            socket_path: dirs.0.path().join("no-records.socket"),
+            server_uid: 0,
+        });
+        let result = daemon
+            .update_face(UpdateFaceRequest {
+                user_id: uid,
+                face_id: response.face_id.clone(),
+                label: Some("Glasses".to_string()),
+                notes: None,
+                allowed_contexts: Some(vec!["sudo".to_string()]),
+            })
+            .await;
+        assert!(matches!(result, Err(DaemonError::AccessDenied(_))));
+
+        let face = &daemon.storage.list_user_faces(uid).unwrap()[0];
+        assert_eq!(face.allowed_contexts, vec!["screenlock".to_string()]);
+        assert_eq!(face.label, None);
+
+        // ...and still passes its integrity check
+        let daemon = daemon.with_record_authenticator(key());
+        assert!(matches!(
+            daemon
+                .verify(VerifyRequest {
+                    user_id: uid,
+                    context: "screenlock".to_string(),
+                    timeout_ms: 100,
+                })
+                .await
+                .unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+    }
 
+    #[tokio::test]
+    async fn test_verify_fails_when_face_records_cannot_be_checked() {
+        let uid = my_uid();
+        let (dirs, daemon) = backup_test_daemon();
         daemon
             .register_face(RegisterFaceRequest {
                 user_id: uid,
                 context: "test".to_string(),
                 timeout_ms: 1000,
                 num_samples: 1,
+                allowed_contexts: Vec::new(),
             })
             .await
             .unwrap();
+        let face_id = daemon.storage.list_user_faces(uid).unwrap()[0]
+            .face_id
+            .clone();
+        daemon
+            .storage
+            .set_face_mac(uid, &face_id, &[1; 32])
+            .unwrap();
+        // The record service isn't running: not a tampered face
+        let daemon = daemon.with_record_authenticator(RecordAuthenticator::Service {
+            socket_path: dirs.0.path().join("no-records.socket"),
+            server_uid: 0,
+        });
 
         let result = daemon
             .verify(VerifyRequest {
@@ -830,10 +2831,342 @@ mod tests {
                 context: "test".to_string(),
                 timeout_ms: 100,
             })
+            .await;
+        assert!(
+            matches!(&result, Err(DaemonError::StorageError(message)) if message.contains("could not be checked")),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_ignores_a_face_whose_registration_time_was_reset() {
+        let uid = my_uid();
+        let (_dirs, daemon) = backup_test_daemon();
+        let key = record_auth::RecordKey::from_bytes([9; 32]);
+        let daemon = daemon.with_record_authenticator(RecordAuthenticator::Local(key.clone()));
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
             .await
             .unwrap();
+        let verify = || {
+            daemon.verify(VerifyRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 100,
+            })
+        };
+        let mut record = daemon.storage.list_user_faces(uid).unwrap().remove(0);
+        let template = daemon
+            .storage
+            .load_face_templates(uid, [&record.face_id])
+            .unwrap()
+            .remove(&record.face_id)
+            .unwrap();
+        let rewrite = |record: &FaceRecord, mac: &[u8]| {
+            daemon.storage.delete_face(uid, &record.face_id).unwrap();
+            daemon
+                .storage
+                .save_face_template(record, &template)
+                .unwrap();
+            daemon
+                .storage
+                .set_face_mac(uid, &record.face_id, mac)
+                .unwrap();
+        };
 
-        assert!(matches!(result, VerifyResult::NoFaceDetected));
+        // Signed before the registration time was covered: moved over to
+        // the current digest on the first verify
+        let legacy = key.mac(&record_auth::legacy_record_digest(&record, &template));
+        rewrite(&record, &legacy);
+        assert!(matches!(
+            verify().await.unwrap(),
+            VerifyResult::NoFaceDetected
+        ));
+        let mac = daemon.storage.load_face_macs(uid).unwrap()[&record.face_id].clone();
+        assert_eq!(
+            mac,
+            key.mac(&record_auth::record_digest(&record, &template))
+        );
+
+        // Made younger behind the MAC's back, to get past
+        // max_template_age_days: no longer matched, whichever MAC it has
+        record.registered_at += 40 * SECS_PER_DAY;
+        for mac in [mac.as_slice(), &legacy] {
+            rewrite(&record, mac);
+            assert!(matches!(
+                verify().await.unwrap(),
+                VerifyResult::NoEnrollment
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_refuses_a_context_the_policy_locks_off() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let config = test_config_with_policy(
+            storage_dir.path().to_path_buf(),
+            "[authentication]\nenable_for_sudo = false\n",
+            |settings| settings.authentication.enable_for_sudo = false,
+        );
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+
+        let request = |context: &str| VerifyRequest {
+            user_id: my_uid(),
+            context: context.to_string(),
+            timeout_ms: 100,
+        };
+        let err = daemon.verify(request("sudo")).await.unwrap_err();
+        assert!(matches!(err, DaemonError::ContextDisabled(_)), "{err}");
+
+        // Other contexts are unaffected (nothing enrolled here, so the
+        // request gets as far as the storage lookup).
+        assert!(matches!(
+            daemon.verify(request("login")).await.unwrap(),
+            VerifyResult::NoEnrollment
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_refuses_a_disabled_context_before_touching_storage() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let mut config = test_config(storage_dir.path().to_path_buf());
+        config.settings.authentication.enable_for_polkit = false;
+        config.settings.authentication.enable_for_screenlock = false;
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+
+        for context in ["polkit", "screenlock"] {
+            let err = daemon
+                .verify(VerifyRequest {
+                    user_id: my_uid(),
+                    context: context.to_string(),
+                    timeout_ms: 100,
+                })
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&err, DaemonError::ContextDisabled(c) if c == context),
+                "{err}"
+            );
+        }
+
+        // A custom context has no flag to check
+        assert!(matches!(
+            daemon
+                .verify(VerifyRequest {
+                    user_id: my_uid(),
+                    context: "kiosk".to_string(),
+                    timeout_ms: 100,
+                })
+                .await
+                .unwrap(),
+            VerifyResult::NoEnrollment
+        ));
+    }
+
+    fn loaded(config: hello_config::Config) -> hello_config::LoadedConfig {
+        hello_config::LoadedConfig {
+            config,
+            sources: Vec::new(),
+            unknown_keys: Vec::new(),
+            policy: hello_config::Policy::default(),
+            locked_overrides: Vec::new(),
+            origins: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_takes_effect_without_a_restart() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let config = test_config(storage_dir.path().to_path_buf());
+        let mut daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+        let matcher = Arc::clone(&daemon.matcher);
+        let rgb_device = daemon.camera_manager().rgb_device.clone();
+
+        let mut settings = hello_config::Config::default();
+        settings.recognition.similarity_threshold = 0.7;
+        settings.authentication.enable_for_sudo = false;
+        let change = daemon.apply_config(loaded(settings));
+
+        assert_eq!(
+            change.changed_sections,
+            vec!["authentication", "recognition"]
+        );
+        assert!(!change.camera_reopened);
+        // Same matcher everyone else holds, new thresholds
+        assert_eq!(matcher.get_threshold("unknown"), 0.7);
+        assert_eq!(daemon.camera_manager().rgb_device, rgb_device);
+        let err = daemon
+            .verify(VerifyRequest {
+                user_id: my_uid(),
+                context: "sudo".to_string(),
+                timeout_ms: 100,
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DaemonError::ContextDisabled(_)), "{err}");
+
+        // Loading the same thing again changes nothing
+        let settings = daemon.config().settings.clone();
+        assert!(daemon.apply_config(loaded(settings)).is_empty());
+    }
+
+    #[test]
+    fn test_apply_config_reopens_the_camera_when_detection_changes() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let config = test_config(storage_dir.path().to_path_buf());
+        let mut daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+        let before = Arc::clone(&daemon.camera);
+
+        let mut settings = hello_config::Config::default();
+        settings.detection.camera_device = Some("/dev/video2".to_string());
+        let change = daemon.apply_config(loaded(settings));
+
+        assert_eq!(change.changed_sections, vec!["detection"]);
+        assert!(change.camera_reopened);
+        assert!(!Arc::ptr_eq(&before, &daemon.camera));
+    }
+
+    #[tokio::test]
+    async fn test_register_face_raises_num_samples_to_the_configured_minimum() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::sequence(vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]], 0.8),
+        );
+        // From the user's config.toml, not locked by any policy
+        let mut config = test_config(storage_dir.path().to_path_buf());
+        config.settings.capture.enrollment_samples = 2;
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+
+        let uid = my_uid();
+        let response_json = daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let response: dbus_interface::RegisterFaceResponse =
+            serde_json::from_str(&response_json).unwrap();
+
+        // Both samples were averaged despite the request asking for one
+        let storage = storage::FaceStorage::new(storage_dir.path()).unwrap();
+        let embedding = storage.load_face_embedding(uid, &response.face_id).unwrap();
+        assert!(embedding.vector[1] > 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_register_face_rejects_quality_below_a_locked_threshold() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.8),
+        );
+        let config = test_config_with_policy(
+            storage_dir.path().to_path_buf(),
+            "locked = [\"capture.enrollment_quality_threshold\"]\n",
+            |_| {},
+        );
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+
+        let uid = my_uid();
+        let err = daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DaemonError::PolicyViolation(_)), "{err}");
+
+        let storage = storage::FaceStorage::new(storage_dir.path()).unwrap();
+        assert!(storage.list_user_faces(uid).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_register_face_ignores_an_unlocked_quality_threshold() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::always_detects(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.8),
+        );
+        let mut config = test_config(storage_dir.path().to_path_buf());
+        config.settings.capture.enrollment_quality_threshold = 0.95;
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+
+        let uid = my_uid();
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 1,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let storage = storage::FaceStorage::new(storage_dir.path()).unwrap();
+        assert_eq!(storage.list_user_faces(uid).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_locked_quality_threshold_ignores_frames_without_a_face() {
+        let storage_dir = tempfile::TempDir::new().unwrap();
+        // Every other frame has no face; the ones that do are good enough
+        let (_cam_dir, camera) = test_camera(
+            FakeDetector::alternating(default_face_region(640, 480)),
+            FakeExtractor::with_vector(vec![1.0, 0.0, 0.0], 0.9),
+        );
+        let config = test_config_with_policy(
+            storage_dir.path().to_path_buf(),
+            "locked = [\"capture.enrollment_quality_threshold\"]\n",
+            |_| {},
+        );
+        let daemon = FaceAuthDaemon::new_for_test(config, camera).unwrap();
+
+        let uid = my_uid();
+        daemon
+            .register_face(RegisterFaceRequest {
+                user_id: uid,
+                context: "test".to_string(),
+                timeout_ms: 1000,
+                num_samples: 4,
+                allowed_contexts: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let storage = storage::FaceStorage::new(storage_dir.path()).unwrap();
+        assert_eq!(storage.list_user_faces(uid).unwrap().len(), 1);
     }
 
     /// Chains score_frame -> matcher::match_with_liveness -> record_frame_result
@@ -853,18 +3186,19 @@ mod tests {
             hello_face_core::Embedding {
                 vector: vec![1.0, 0.0, 0.0],
                 metadata: hello_face_core::EmbeddingMetadata {
-                    model: "test".to_string(),
+                    model: "fake".to_string(),
                     model_version: "test".to_string(),
                     extracted_at: 0,
                     quality_score: 0.9,
                 },
-            },
+            }
+            .into(),
         );
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
         let mut state = VerifyLoopState::default();
 
         for _ in 0..2 {
-            let (embedding, rgb_liveness) =
+            let (embedding, rgb_liveness, _face) =
                 camera::score_frame(&detector, &extractor, 0, &frame, 640, 480).unwrap();
             let result =
                 matcher.match_with_liveness(&embedding, &stored, "test", None, rgb_liveness);
@@ -890,17 +3224,18 @@ mod tests {
             hello_face_core::Embedding {
                 vector: vec![1.0, 0.0, 0.0],
                 metadata: hello_face_core::EmbeddingMetadata {
-                    model: "test".to_string(),
+                    model: "fake".to_string(),
                     model_version: "test".to_string(),
                     extracted_at: 0,
                     quality_score: 0.9,
                 },
-            },
+            }
+            .into(),
         );
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
         let mut state = VerifyLoopState::default();
 
-        let (embedding, rgb_liveness) =
+        let (embedding, rgb_liveness, _face) =
             camera::score_frame(&detector, &extractor, 0, &frame, 640, 480).unwrap();
         let result = matcher.match_with_liveness(&embedding, &stored, "test", None, rgb_liveness);
         record_frame_result(&mut state, result, 2);
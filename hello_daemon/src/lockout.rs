@@ -0,0 +1,279 @@
+//! Throttling failed verifications
+//!
+//! Nothing used to stop a caller from asking for another capture after a
+//! failed one, which is how a presentation attack (a photo, a screen, a
+//! mask) gets hundreds of tries. [`verify_with_storage`](crate::verify_with_storage)
+//! now counts failed matches per user and per context in a [`FailureLog`]
+//! and checks them against a [`LockoutPolicy`] before touching the camera:
+//!
+//! - after `authentication.backoff_after_failures` failures in a row, each
+//!   attempt has to wait `backoff_base_secs`, doubling with every further
+//!   failure up to `backoff_max_secs`;
+//! - after `authentication.lockout_after_failures`, face auth stays off for
+//!   the context until a password authentication.
+//!
+//! Only a face that was seen and didn't match counts: nobody in front of
+//! the camera (a screen locking on its own) doesn't. A match clears the
+//! count for its context. So does a password: a PAM stack that succeeded
+//! after the module's own attempt didn't, reported through `pam_sm_setcred`
+//! by a root process (`PamHelperAction::Authenticated` in `pam_helper`).
+//!
+//! The log is a small database of its own rather than a table of
+//! `faces.db`: the SDDM listener only ever opens a user's faces read-only,
+//! so it keeps its counts under `/var/lib/linux-hello/` instead. The user's
+//! daemon keeps them next to their faces, where a process running as that
+//! user can reset them — the lockout is against someone at the camera, not
+//! against code already running in the session.
+
+use crate::storage::{create_owner_only_db_file, db_error};
+use crate::DaemonError;
+use hello_config::AuthenticationConfig;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+/// File name of the log in a storage directory
+pub const FAILURE_LOG_FILE: &str = "lockout.db";
+
+/// Where the SDDM listener keeps its log
+pub const SYSTEM_FAILURE_LOG_PATH: &str = "/var/lib/linux-hello/lockout.db";
+
+/// Failed matches in a row for one user and context
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FailureState {
+    pub failures: u32,
+
+    /// When the last one happened (Unix seconds)
+    pub last_failure_at: Option<u64>,
+}
+
+/// Why face auth isn't attempted right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lockout {
+    /// Backing off until then (Unix seconds)
+    Until(u64),
+
+    /// Locked until a password authentication
+    UntilPassword,
+}
+
+impl Lockout {
+    /// The end of the backoff, `None` for a lockout
+    pub fn until(self) -> Option<u64> {
+        match self {
+            Lockout::Until(until) => Some(until),
+            Lockout::UntilPassword => None,
+        }
+    }
+}
+
+/// The limits from `[authentication]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub backoff_after: u32,
+    pub backoff_base_secs: u64,
+    pub backoff_max_secs: u64,
+
+    /// 0 never locks
+    pub lock_after: u32,
+}
+
+impl LockoutPolicy {
+    pub fn from_config(authentication: &AuthenticationConfig) -> Self {
+        Self {
+            backoff_after: authentication.backoff_after_failures,
+            backoff_base_secs: u64::from(authentication.backoff_base_secs),
+            backoff_max_secs: u64::from(authentication.backoff_max_secs),
+            lock_after: authentication.lockout_after_failures,
+        }
+    }
+
+    /// How long to wait after `failures` failures in a row
+    pub fn backoff_secs(&self, failures: u32) -> u64 {
+        if failures < self.backoff_after {
+            return 0;
+        }
+        let doublings = (failures - self.backoff_after).min(32);
+        self.backoff_base_secs
+            .saturating_mul(1 << doublings)
+            .min(self.backoff_max_secs)
+    }
+
+    /// Whether `state` keeps face auth off at `now`
+    pub fn check(&self, state: &FailureState, now: u64) -> Option<Lockout> {
+        if self.lock_after > 0 && state.failures >= self.lock_after {
+            return Some(Lockout::UntilPassword);
+        }
+        let until = state
+            .last_failure_at?
+            .saturating_add(self.backoff_secs(state.failures));
+        (now < until).then_some(Lockout::Until(until))
+    }
+}
+
+/// Failed matches per user and context, kept across restarts
+pub struct FailureLog {
+    conn: Mutex<Connection>,
+}
+
+impl FailureLog {
+    /// Open the log at `path`, creating it (mode `0600`) if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DaemonError> {
+        let path = path.as_ref();
+        create_owner_only_db_file(path)?;
+        Self::init(Connection::open(path).map_err(db_error)?)
+    }
+
+    /// A log that lives as long as the process — for tests, and for the
+    /// SDDM listener when its state directory isn't writable
+    pub fn in_memory() -> Result<Self, DaemonError> {
+        Self::init(Connection::open_in_memory().map_err(db_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, DaemonError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS verify_failures (
+                 user_id INTEGER NOT NULL,
+                 context TEXT NOT NULL,
+                 failures INTEGER NOT NULL,
+                 last_failure_at INTEGER NOT NULL,
+                 PRIMARY KEY (user_id, context)
+             )",
+        )
+        .map_err(db_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// `user_id`'s failures in a row for `context`
+    pub fn state(&self, user_id: u32, context: &str) -> Result<FailureState, DaemonError> {
+        let state = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT failures, last_failure_at FROM verify_failures
+                 WHERE user_id = ?1 AND context = ?2",
+                params![user_id, context],
+                |row| {
+                    Ok(FailureState {
+                        failures: row.get(0)?,
+                        last_failure_at: Some(row.get(1)?),
+                    })
+                },
+            )
+            .optional()
+            .map_err(db_error)?;
+        Ok(state.unwrap_or_default())
+    }
+
+    /// Count one more failure for `user_id` in `context`, at `at`
+    pub fn record_failure(
+        &self,
+        user_id: u32,
+        context: &str,
+        at: u64,
+    ) -> Result<FailureState, DaemonError> {
+        self.conn
+            .lock()
+            .query_row(
+                "INSERT INTO verify_failures (user_id, context, failures, last_failure_at)
+                 VALUES (?1, ?2, 1, ?3)
+                 ON CONFLICT (user_id, context) DO UPDATE
+                 SET failures = failures + 1, last_failure_at = excluded.last_failure_at
+                 RETURNING failures, last_failure_at",
+                params![user_id, context, at],
+                |row| {
+                    Ok(FailureState {
+                        failures: row.get(0)?,
+                        last_failure_at: Some(row.get(1)?),
+                    })
+                },
+            )
+            .map_err(db_error)
+    }
+
+    /// Forget `user_id`'s failures in `context`, or in every context.
+    /// Returns how many contexts had any.
+    pub fn clear(&self, user_id: u32, context: Option<&str>) -> Result<usize, DaemonError> {
+        self.conn
+            .lock()
+            .execute(
+                "DELETE FROM verify_failures
+                 WHERE user_id = ?1 AND (?2 IS NULL OR context = ?2)",
+                params![user_id, context],
+            )
+            .map_err(db_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn policy() -> LockoutPolicy {
+        LockoutPolicy::from_config(&AuthenticationConfig::default())
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_its_cap_then_locks() {
+        let policy = policy();
+        assert_eq!(policy.backoff_secs(2), 0);
+        assert_eq!(policy.backoff_secs(3), 30);
+        assert_eq!(policy.backoff_secs(4), 60);
+        assert_eq!(policy.backoff_secs(9), 900);
+        assert_eq!(policy.backoff_secs(u32::MAX), 900);
+
+        let state = |failures| FailureState {
+            failures,
+            last_failure_at: Some(1_000),
+        };
+        assert_eq!(policy.check(&FailureState::default(), 1_000), None);
+        assert_eq!(policy.check(&state(2), 1_000), None);
+        assert_eq!(policy.check(&state(3), 1_010), Some(Lockout::Until(1_030)));
+        assert_eq!(policy.check(&state(3), 1_030), None);
+        assert_eq!(
+            policy.check(&state(10), 100_000),
+            Some(Lockout::UntilPassword)
+        );
+
+        let never_locks = LockoutPolicy {
+            lock_after: 0,
+            ..policy
+        };
+        assert_eq!(
+            never_locks.check(&state(50), 1_000),
+            Some(Lockout::Until(1_900))
+        );
+    }
+
+    #[test]
+    fn failures_are_counted_per_user_and_context_and_kept() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join(FAILURE_LOG_FILE);
+        let log = FailureLog::open(&path).unwrap();
+        log.record_failure(1000, "sudo", 10).unwrap();
+        let state = log.record_failure(1000, "sudo", 20).unwrap();
+        assert_eq!(
+            state,
+            FailureState {
+                failures: 2,
+                last_failure_at: Some(20)
+            }
+        );
+        log.record_failure(1000, "screenlock", 30).unwrap();
+        log.record_failure(2000, "sudo", 40).unwrap();
+        drop(log);
+
+        let log = FailureLog::open(&path).unwrap();
+        assert_eq!(log.state(1000, "sudo").unwrap().failures, 2);
+        assert_eq!(log.state(1000, "login").unwrap(), FailureState::default());
+
+        assert_eq!(log.clear(1000, Some("sudo")).unwrap(), 1);
+        assert_eq!(log.state(1000, "screenlock").unwrap().failures, 1);
+        assert_eq!(log.clear(1000, None).unwrap(), 1);
+        assert_eq!(log.state(1000, "screenlock").unwrap().failures, 0);
+        assert_eq!(log.state(2000, "sudo").unwrap().failures, 1);
+    }
+}
@@ -3,7 +3,9 @@
 //! Launches the D-Bus service for face management
 
 use clap::Parser;
-use hello_daemon::{dbus::FaceAuthInterface, DaemonConfig, FaceAuthDaemon};
+use hello_config::Config;
+use hello_daemon::dbus::{FaceAuthInterface, OBJECT_PATH};
+use hello_daemon::{config_reload, expiry, DaemonConfig, FaceAuthDaemon};
 use std::path::PathBuf;
 use tracing::{error, info, warn};
 
@@ -24,8 +26,17 @@ struct Args {
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
+    // Loaded before tracing is initialized so `behavior.logging_level` can
+    // pick the default log level; anything worth reporting about the load
+    // itself is logged right after.
+    let loaded = Config::load();
+
     // Initialize tracing
-    let level = if args.debug { "debug" } else { "info" };
+    let level = match (&loaded, args.debug) {
+        (_, true) => "debug",
+        (Ok(loaded), false) => loaded.config.behavior.logging_level.as_str(),
+        (Err(_), false) => "info",
+    };
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -36,16 +47,56 @@ async fn main() -> anyhow::Result<()> {
     info!("Starting Linux Hello daemon");
     info!("Version: {}", env!("CARGO_PKG_VERSION"));
 
+    // A config file that exists but is broken stops startup rather than
+    // silently running on defaults the admin/user didn't ask for (e.g. a
+    // typo'd, looser threshold) — PAM falls back to the password meanwhile.
+    let loaded = loaded.map_err(|e| {
+        error!("{}", e);
+        e
+    })?;
+    for source in &loaded.sources {
+        info!("Configuration: {}", source.display());
+    }
+    for unknown in &loaded.unknown_keys {
+        warn!("Configuration: {} (ignored)", unknown);
+    }
+    for overridden in &loaded.locked_overrides {
+        warn!("Configuration: {} (ignored)", overridden);
+    }
+
     // Create the daemon config
-    let mut config = DaemonConfig::default();
+    let mut config = DaemonConfig {
+        settings: loaded.config,
+        policy: loaded.policy,
+        ..DaemonConfig::default()
+    };
     if let Some(path) = args.storage_path {
         config.storage_path = path;
     }
 
     info!("Storage: {}", config.storage_path.display());
 
-    // Create the daemon
-    let daemon = FaceAuthDaemon::new(config)?;
+    // Embeddings are sealed, and face records signed, under keys only
+    // hello-storage-key (root) holds. Without them, faces would be stored
+    // in the clear and planted ones taken at face value, so the daemon
+    // then refuses to enroll or verify — PAM falls back to the password.
+    let daemon = match hello_daemon::storage_key::wait_for_user_key(
+        std::path::Path::new(hello_daemon::storage_key::KEY_SOCKET_PATH),
+        0,
+    )
+    .await
+    {
+        Ok(key) => FaceAuthDaemon::with_storage_key(config, Some(key))?
+            .with_record_authenticator(hello_daemon::record_auth::RecordAuthenticator::service()),
+        Err(e) => {
+            error!(
+                "Storage key unavailable: {} — face enrollment and verification are \
+                 refused (is hello-storage-key running?)",
+                e
+            );
+            FaceAuthDaemon::new(config)?.without_storage_key(e)
+        }
+    };
     let storage_path = daemon.config().storage_path.to_string_lossy().into_owned();
 
     if daemon.config().root_mode {
@@ -130,7 +181,7 @@ async fn main() -> anyhow::Result<()> {
         e
     })?;
 
-    let iface = FaceAuthInterface::from_arc(daemon_arc, storage_path);
+    let iface = FaceAuthInterface::from_arc(daemon_arc.clone(), storage_path);
 
     connection
         .request_name("com.linuxhello.FaceAuth")
@@ -142,7 +193,7 @@ async fn main() -> anyhow::Result<()> {
 
     connection
         .object_server()
-        .at("/com/linuxhello/FaceAuth", iface)
+        .at(OBJECT_PATH, iface)
         .await
         .map_err(|e| {
             error!("D-Bus object registration error: {}", e);
@@ -150,9 +201,21 @@ async fn main() -> anyhow::Result<()> {
         })?;
 
     info!("✓ D-Bus service registered: com.linuxhello.FaceAuth");
-    info!("  Interface: /com/linuxhello/FaceAuth");
-    info!("  Methods: register_face, verify, delete_face, list_faces, ping");
-    info!("  Signals: CaptureProgress, CaptureCompleted, CaptureError");
+    info!("  Interface: {}", OBJECT_PATH);
+    info!("  Methods: register_face, verify, delete_face, list_faces, update_face, rename_face, reset_adaptation, record_calibration, export_faces, import_faces, ping, reload_config");
+    info!("  Signals: CaptureProgress, CaptureCompleted, CaptureError, ConfigChanged, EnrollmentExpiring");
+
+    // Started after registration: a reload emits ConfigChanged, and an
+    // expiring face EnrollmentExpiring, from the interface registered above.
+    config_reload::spawn_file_watcher(daemon_arc.clone(), connection.clone());
+    expiry::spawn_expiry_reminder(daemon_arc.clone(), uid, connection.clone());
+    match config_reload::spawn_sighup_handler(daemon_arc, connection.clone()) {
+        Ok(_) => info!("✓ Configuration reloads on file change and SIGHUP"),
+        Err(e) => warn!(
+            "SIGHUP handler not installed: {} (configuration still reloads on file change)",
+            e
+        ),
+    }
 
     // Keep the daemon running indefinitely
     info!("Daemon ready. Press Ctrl+C to stop.");
@@ -2,9 +2,11 @@
 //!
 //! Computes similarities, applies thresholds, etc.
 
+use hello_config::{FusionRule, RecognitionConfig, MIN_SIMILARITY_THRESHOLD};
 use hello_face_core::Embedding;
+use parking_lot::RwLock;
 use std::collections::HashMap;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// Result of a comparison
 #[derive(Debug, Clone)]
@@ -25,57 +27,166 @@ pub struct MatchResult {
     pub matched: bool,
 }
 
+/// One enrolled face as the matcher sees it: the averaged embedding plus
+/// the samples it was averaged from. Faces enrolled before samples were
+/// kept have none, and are scored on `centroid` whatever the fusion rule.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FaceTemplate {
+    /// Average of the samples, L2-normalized
+    pub centroid: Embedding,
+
+    /// Each enrollment sample, in capture order
+    pub samples: Vec<Embedding>,
+
+    /// Probes of confident verifications folded in since enrollment,
+    /// oldest first (see `adaptive`). Scored alongside `samples` (or the
+    /// centroid of a face without any), except by [`FusionRule::Centroid`].
+    pub adapted: Vec<Embedding>,
+}
+
+impl From<Embedding> for FaceTemplate {
+    fn from(centroid: Embedding) -> Self {
+        Self {
+            centroid,
+            samples: Vec::new(),
+            adapted: Vec::new(),
+        }
+    }
+}
+
 /// Face matching manager
+///
+/// Shared (`Arc`) between the session daemon's D-Bus interface, the PAM
+/// helper and the screen-lock watcher, so a configuration reload swaps the
+/// thresholds in place (see [`FaceMatcher::reconfigure`]) rather than
+/// building a new matcher every holder would have to be told about.
 pub struct FaceMatcher {
+    thresholds: RwLock<Thresholds>,
+}
+
+/// The thresholds one `[recognition]` configuration resolves to
+struct Thresholds {
     /// Default similarity threshold
     default_threshold: f32,
 
     /// Thresholds per context
     context_thresholds: HashMap<String, f32>,
+
+    /// How the scores of a face's samples combine into its score
+    fusion: FusionRule,
+
+    /// Samples averaged by `FusionRule::TopKMean`
+    fusion_top_k: usize,
 }
 
 impl Default for FaceMatcher {
     fn default() -> Self {
-        Self::new()
+        Self::new(&RecognitionConfig::default())
     }
 }
 
-impl FaceMatcher {
-    /// Create a new matcher. Thresholds are fixed (see `context_thresholds`
-    /// below) — this crate previously accepted a `default_threshold`
-    /// parameter here, but every caller's value was silently discarded; see
-    /// the removed `--similarity-threshold` CLI flag/`DaemonConfig` field.
-    pub fn new() -> Self {
-        let default_threshold = 0.58;
-        let mut context_thresholds = HashMap::new();
-
-        // Stricter thresholds for sensitive contexts
-        context_thresholds.insert("login".to_string(), 0.60);
-        context_thresholds.insert("sudo".to_string(), 0.62);
-        context_thresholds.insert("polkit".to_string(), 0.60);
-        context_thresholds.insert("sddm".to_string(), 0.60);
-        context_thresholds.insert("screenlock".to_string(), 0.55);
-        context_thresholds.insert("test".to_string(), 0.50);
+/// Liveness gates of [`FaceMatcher::match_with_liveness`], independent of
+/// the recognition threshold. `linux-hello calibrate report` measures how
+/// recorded live and spoof scores fall around them (see `calibration`).
+///
+/// IR: 0.20 is calibrated to accept low-signal IR cameras while blocking
+/// photos (near-zero texture → score < 0.10).
+pub const IR_LIVENESS_GATE: f32 = 0.20;
+
+/// RGB fallback: see `hello_face_core::liveness::rgb_liveness_score` for
+/// the real-hardware calibration this is based on (one subject, one phone,
+/// three sessions) — live scored 1.000 every time, a phone-screen replay
+/// scored 0.0-0.453. 0.55 sits with a wide margin below every live sample
+/// observed and above every spoof sample observed; still a much weaker
+/// guarantee than the IR gate, since it rests on far less validation.
+pub const RGB_LIVENESS_GATE: f32 = 0.55;
+
+/// Per-context thresholds used when `[recognition.contexts]` doesn't name
+/// the context itself — stricter for contexts that grant more. A
+/// `similarity_threshold` raised above its default (what a policy does to
+/// tighten every context) raises them with it.
+const BUILTIN_CONTEXT_THRESHOLDS: [(&str, f32); 6] = [
+    ("login", 0.60),
+    ("sudo", 0.62),
+    ("polkit", 0.60),
+    ("sddm", 0.60),
+    ("screenlock", 0.55),
+    ("test", 0.50),
+];
+
+impl Thresholds {
+    fn from_config(config: &RecognitionConfig) -> Self {
+        let default_threshold = floor_threshold("default", config.similarity_threshold);
+        let raised = (config.similarity_threshold
+            > RecognitionConfig::default().similarity_threshold)
+            .then_some(default_threshold);
+
+        let mut context_thresholds: HashMap<String, f32> = BUILTIN_CONTEXT_THRESHOLDS
+            .iter()
+            .map(|(name, threshold)| {
+                (
+                    name.to_string(),
+                    raised.map_or(*threshold, |raised| threshold.max(raised)),
+                )
+            })
+            .collect();
+        for (name, context) in &config.contexts {
+            if let Some(threshold) = context.similarity_threshold {
+                context_thresholds.insert(name.clone(), floor_threshold(name, threshold));
+            }
+        }
 
         Self {
             default_threshold,
             context_thresholds,
+            fusion: config.fusion,
+            fusion_top_k: config.fusion_top_k.max(1) as usize,
+        }
+    }
+}
+
+impl FaceMatcher {
+    /// Create a new matcher from the `[recognition]` configuration.
+    ///
+    /// A context's threshold comes from, in order: its own
+    /// `[recognition.contexts]` entry (any name, built-in or not), then the
+    /// built-in table above — no lower than a raised
+    /// `recognition.similarity_threshold` — then that threshold itself. Every
+    /// value is clamped up to `MIN_SIMILARITY_THRESHOLD` — `Config::validate`
+    /// already rejects lower ones, this just keeps the floor from depending
+    /// on every caller having validated first.
+    pub fn new(config: &RecognitionConfig) -> Self {
+        Self {
+            thresholds: RwLock::new(Thresholds::from_config(config)),
         }
     }
 
+    /// Replace every threshold with the ones `config` resolves to, same
+    /// rules as [`FaceMatcher::new`]. An attempt already past
+    /// `get_threshold` keeps the threshold it read; the next one sees the
+    /// new values.
+    pub fn reconfigure(&self, config: &RecognitionConfig) {
+        *self.thresholds.write() = Thresholds::from_config(config);
+    }
+
     /// Get the threshold for a context
     pub fn get_threshold(&self, context: &str) -> f32 {
-        self.context_thresholds
+        let thresholds = self.thresholds.read();
+        thresholds
+            .context_thresholds
             .get(context)
             .copied()
-            .unwrap_or(self.default_threshold)
+            .unwrap_or(thresholds.default_threshold)
     }
 
-    /// Compare a probe embedding against several stored embeddings
+    /// Compare a probe embedding against several enrolled faces
+    ///
+    /// A face's score is its samples' scores combined by
+    /// `recognition.fusion` (see [`FaceMatcher::face_score`]).
     ///
     /// # Arguments
     /// * `probe` - The embedding to verify
-    /// * `stored` - HashMap of face_id -> embedding
+    /// * `stored` - HashMap of face_id -> template
     /// * `context` - The authentication context
     ///
     /// # Returns
@@ -83,10 +194,14 @@ impl FaceMatcher {
     pub fn match_embedding(
         &self,
         probe: &Embedding,
-        stored: &HashMap<String, Embedding>,
+        stored: &HashMap<String, FaceTemplate>,
         context: &str,
     ) -> MatchResult {
         let threshold = self.get_threshold(context);
+        let (fusion, top_k) = {
+            let thresholds = self.thresholds.read();
+            (thresholds.fusion, thresholds.fusion_top_k)
+        };
 
         info!(
             "Matching probe vs {} stored faces, context={}, threshold={:.2}",
@@ -99,8 +214,25 @@ impl FaceMatcher {
         let mut best_face_id = None;
         let mut all_scores = HashMap::new();
 
-        for (face_id, stored_emb) in stored {
-            let score = self.cosine_similarity(&probe.vector, &stored_emb.vector);
+        for (face_id, template) in stored {
+            let stored_emb = &template.centroid;
+            // Enrolled under another model: its score would be noise. The
+            // caller checks for a gallery left with nothing comparable
+            // (see `verify_with_storage`); this is the backstop.
+            if !probe.is_comparable_with(stored_emb) {
+                debug!(
+                    "Face {} skipped: enrolled with {} {} ({} dims), probe is {} {} ({} dims)",
+                    face_id,
+                    stored_emb.metadata.model,
+                    stored_emb.metadata.model_version,
+                    stored_emb.vector.len(),
+                    probe.metadata.model,
+                    probe.metadata.model_version,
+                    probe.vector.len()
+                );
+                continue;
+            }
+            let score = self.face_score(probe, template, fusion, top_k);
             all_scores.insert(face_id.clone(), score);
 
             debug!("Face {} score: {:.4}", face_id, score);
@@ -146,32 +278,16 @@ impl FaceMatcher {
     pub fn match_with_liveness(
         &self,
         probe: &Embedding,
-        stored: &HashMap<String, Embedding>,
+        stored: &HashMap<String, FaceTemplate>,
         context: &str,
         ir_liveness: Option<f32>,
         rgb_liveness: f32,
     ) -> MatchResult {
-        // Liveness check thresholds, independent of the recognition
-        // threshold.
-        //
-        // LIVENESS_GATE (IR): 0.20 is calibrated to accept low-signal IR
-        // cameras while blocking photos (near-zero texture → score < 0.10).
-        //
-        // RGB_LIVENESS_GATE: see `hello_face_core::liveness::rgb_liveness_score`
-        // for the real-hardware calibration this is based on (one subject,
-        // one phone, three sessions) — live scored 1.000 every time, a
-        // phone-screen replay scored 0.0-0.453. 0.55 sits with a wide
-        // margin below every live sample observed and above every spoof
-        // sample observed; still a much weaker guarantee than the IR gate,
-        // since it rests on far less validation.
-        const LIVENESS_GATE: f32 = 0.20;
-        const RGB_LIVENESS_GATE: f32 = 0.55;
-
         // First compute the best RGB score
         let rgb_result = self.match_embedding(probe, stored, context);
 
         let (liveness, gate, source) = match ir_liveness {
-            Some(ir) => (ir.clamp(0.0, 1.0), LIVENESS_GATE, "IR"),
+            Some(ir) => (ir.clamp(0.0, 1.0), IR_LIVENESS_GATE, "IR"),
             None => (
                 rgb_liveness.clamp(0.0, 1.0),
                 RGB_LIVENESS_GATE,
@@ -204,8 +320,53 @@ impl FaceMatcher {
         rgb_result
     }
 
+    /// Score `probe` against one face under `fusion`:
+    ///
+    /// - `Max`: its best-matching sample — most forgiving, one lucky sample
+    ///   is enough
+    /// - `TopKMean`: the mean of its `top_k` best samples (all of them if it
+    ///   has fewer), which tolerates pose changes without resting on a
+    ///   single sample
+    /// - `Centroid`: the averaged embedding alone, as before samples were
+    ///   kept
+    ///
+    /// The samples are the enrollment's (its centroid if it kept none) and
+    /// the adapted ones. Samples from another model than the probe's are
+    /// ignored; with none left, the centroid is scored.
+    fn face_score(
+        &self,
+        probe: &Embedding,
+        template: &FaceTemplate,
+        fusion: FusionRule,
+        top_k: usize,
+    ) -> f32 {
+        let enrolled = if template.samples.is_empty() {
+            std::slice::from_ref(&template.centroid)
+        } else {
+            &template.samples
+        };
+        let mut sample_scores: Vec<f32> = enrolled
+            .iter()
+            .chain(&template.adapted)
+            .filter(|sample| probe.is_comparable_with(sample))
+            .map(|sample| self.cosine_similarity(&probe.vector, &sample.vector))
+            .collect();
+        if fusion == FusionRule::Centroid || sample_scores.is_empty() {
+            return self.cosine_similarity(&probe.vector, &template.centroid.vector);
+        }
+
+        sample_scores.sort_by(|a, b| b.total_cmp(a));
+        match fusion {
+            FusionRule::Max => sample_scores[0],
+            _ => {
+                let best = &sample_scores[..top_k.min(sample_scores.len())];
+                best.iter().sum::<f32>() / best.len() as f32
+            }
+        }
+    }
+
     /// Compute the cosine similarity between two vectors
-    fn cosine_similarity(&self, a: &[f32], b: &[f32]) -> f32 {
+    pub fn cosine_similarity(&self, a: &[f32], b: &[f32]) -> f32 {
         if a.is_empty() || b.is_empty() {
             return 0.0;
         }
@@ -233,13 +394,27 @@ impl FaceMatcher {
     }
 }
 
+/// Clamp a configured threshold up to `MIN_SIMILARITY_THRESHOLD`, logging
+/// whenever that actually changes anything.
+fn floor_threshold(context: &str, threshold: f32) -> f32 {
+    if threshold < MIN_SIMILARITY_THRESHOLD || threshold.is_nan() {
+        warn!(
+            "Similarity threshold {} for context {} is below the {:.2} floor, using the floor",
+            threshold, context, MIN_SIMILARITY_THRESHOLD
+        );
+        MIN_SIMILARITY_THRESHOLD
+    } else {
+        threshold
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_cosine_similarity() {
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
 
         // Identical vectors = 1.0
         let v1 = vec![1.0, 0.0, 0.0];
@@ -256,7 +431,7 @@ mod tests {
 
     #[test]
     fn test_match_embedding() {
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
 
         let probe = Embedding {
             vector: vec![1.0, 0.0, 0.0, 0.0, 0.0],
@@ -268,7 +443,7 @@ mod tests {
             },
         };
 
-        let mut stored = HashMap::new();
+        let mut stored: HashMap<String, FaceTemplate> = HashMap::new();
         stored.insert(
             "face_1".to_string(),
             Embedding {
@@ -279,7 +454,8 @@ mod tests {
                     extracted_at: 0,
                     quality_score: 0.9,
                 },
-            },
+            }
+            .into(),
         );
         stored.insert(
             "face_2".to_string(),
@@ -291,7 +467,8 @@ mod tests {
                     extracted_at: 0,
                     quality_score: 0.9,
                 },
-            },
+            }
+            .into(),
         );
 
         let result = matcher.match_embedding(&probe, &stored, "test");
@@ -300,16 +477,227 @@ mod tests {
         assert!(result.matched);
     }
 
+    #[test]
+    fn test_match_embedding_skips_templates_of_another_model() {
+        let matcher = FaceMatcher::default();
+        let embedding = |model: &str, vector: Vec<f32>| Embedding {
+            vector,
+            metadata: hello_face_core::EmbeddingMetadata {
+                model: model.to_string(),
+                model_version: "0.1.0".to_string(),
+                extracted_at: 0,
+                quality_score: 0.9,
+            },
+        };
+        let probe = embedding("arcface", vec![1.0, 0.0, 0.0]);
+
+        let mut stored: HashMap<String, FaceTemplate> = HashMap::new();
+        // Identical vectors, but from another model or of another dimension
+        stored.insert(
+            "old_model".to_string(),
+            embedding("histogram", vec![1.0, 0.0, 0.0]).into(),
+        );
+        stored.insert(
+            "short".to_string(),
+            embedding("arcface", vec![1.0, 0.0]).into(),
+        );
+
+        let result = matcher.match_embedding(&probe, &stored, "test");
+        assert!(!result.matched);
+        assert!(result.all_scores.is_empty());
+
+        stored.insert(
+            "current".to_string(),
+            embedding("arcface", vec![1.0, 0.0, 0.0]).into(),
+        );
+        let result = matcher.match_embedding(&probe, &stored, "test");
+        assert_eq!(result.face_id.as_deref(), Some("current"));
+        assert_eq!(result.all_scores.len(), 1);
+    }
+
+    #[test]
+    fn test_fusion_rules_combine_sample_scores() {
+        let embedding = |vector: Vec<f32>| Embedding {
+            vector,
+            metadata: hello_face_core::EmbeddingMetadata {
+                model: "test".to_string(),
+                model_version: "0.1.0".to_string(),
+                extracted_at: 0,
+                quality_score: 0.9,
+            },
+        };
+        let probe = embedding(vec![1.0, 0.0]);
+        // Sample scores 1.0, 0.6 and 0.0; the centroid scores 0.8
+        let mut stored = HashMap::new();
+        stored.insert(
+            "face_1".to_string(),
+            FaceTemplate {
+                centroid: embedding(vec![0.8, 0.6]),
+                samples: vec![
+                    embedding(vec![1.0, 0.0]),
+                    embedding(vec![0.6, 0.8]),
+                    embedding(vec![0.0, 1.0]),
+                ],
+                adapted: Vec::new(),
+            },
+        );
+        let score =
+            |stored: &HashMap<String, FaceTemplate>, fusion: FusionRule, fusion_top_k: u32| {
+                let matcher = FaceMatcher::new(&RecognitionConfig {
+                    fusion,
+                    fusion_top_k,
+                    ..RecognitionConfig::default()
+                });
+                matcher.match_embedding(&probe, stored, "test").all_scores["face_1"]
+            };
+
+        assert!((score(&stored, FusionRule::Max, 2) - 1.0).abs() < 0.001);
+        assert!((score(&stored, FusionRule::TopKMean, 2) - 0.8).abs() < 0.001);
+        assert!((score(&stored, FusionRule::TopKMean, 10) - 1.6 / 3.0).abs() < 0.001);
+        assert!((score(&stored, FusionRule::Centroid, 2) - 0.8).abs() < 0.001);
+
+        // A face with no samples is scored on its centroid under any rule
+        stored.get_mut("face_1").unwrap().samples.clear();
+        assert!((score(&stored, FusionRule::Max, 2) - 0.8).abs() < 0.001);
+
+        // ...and on it along with what adaptation added, except by the
+        // centroid rule
+        stored
+            .get_mut("face_1")
+            .unwrap()
+            .adapted
+            .push(embedding(vec![1.0, 0.0]));
+        assert!((score(&stored, FusionRule::Max, 2) - 1.0).abs() < 0.001);
+        assert!((score(&stored, FusionRule::TopKMean, 2) - 0.9).abs() < 0.001);
+        assert!((score(&stored, FusionRule::Centroid, 2) - 0.8).abs() < 0.001);
+    }
+
     #[test]
     fn test_context_thresholds() {
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
 
         assert_eq!(matcher.get_threshold("login"), 0.60);
         assert_eq!(matcher.get_threshold("sudo"), 0.62);
         assert_eq!(matcher.get_threshold("unknown"), 0.58); // default
     }
 
-    fn matching_probe_and_stored() -> (Embedding, HashMap<String, Embedding>) {
+    #[test]
+    fn test_policy_threshold_raises_every_builtin_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = dir.path().join("policy.toml");
+        std::fs::write(&policy, "[recognition]\nsimilarity_threshold = 0.65\n").unwrap();
+        let loaded =
+            hello_config::Config::load_layered(&dir.path().join("config.toml"), None, &policy)
+                .unwrap();
+
+        let matcher = FaceMatcher::new(&loaded.config.recognition);
+        for (context, builtin) in BUILTIN_CONTEXT_THRESHOLDS {
+            let threshold = matcher.get_threshold(context);
+            assert!(threshold >= 0.65, "{context}: {threshold}");
+            assert!(threshold >= builtin, "{context}: {threshold}");
+        }
+        assert_eq!(matcher.get_threshold("unknown"), 0.65);
+
+        // A lowered one leaves them alone
+        let matcher = FaceMatcher::new(&RecognitionConfig {
+            similarity_threshold: 0.52,
+            ..RecognitionConfig::default()
+        });
+        assert_eq!(matcher.get_threshold("screenlock"), 0.55);
+        assert_eq!(matcher.get_threshold("sudo"), 0.62);
+    }
+
+    #[test]
+    fn test_configured_similarity_threshold_is_the_fallback() {
+        let config = RecognitionConfig {
+            similarity_threshold: 0.66,
+            ..RecognitionConfig::default()
+        };
+        let matcher = FaceMatcher::new(&config);
+
+        assert_eq!(matcher.get_threshold("unknown"), 0.66);
+        // Raised above the default, it's a floor under the built-in ones
+        assert_eq!(matcher.get_threshold("sudo"), 0.66);
+    }
+
+    fn recognition_config_with_contexts(contexts: &[(&str, f32)]) -> RecognitionConfig {
+        RecognitionConfig {
+            contexts: contexts
+                .iter()
+                .map(|(name, threshold)| {
+                    (
+                        name.to_string(),
+                        hello_config::ContextConfig {
+                            similarity_threshold: Some(*threshold),
+                            require_confirmation: false,
+                            max_template_age_days: None,
+                        },
+                    )
+                })
+                .collect(),
+            ..RecognitionConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_configured_contexts_override_builtins_and_add_new_names() {
+        let matcher = FaceMatcher::new(&recognition_config_with_contexts(&[
+            ("sudo", 0.75),
+            ("screenlock", 0.52),
+            ("kiosk", 0.9),
+        ]));
+
+        assert_eq!(matcher.get_threshold("sudo"), 0.75);
+        assert_eq!(matcher.get_threshold("screenlock"), 0.52);
+        assert_eq!(matcher.get_threshold("kiosk"), 0.9);
+        // Untouched built-ins and unknown names are unaffected.
+        assert_eq!(matcher.get_threshold("login"), 0.60);
+        assert_eq!(matcher.get_threshold("unknown"), 0.58);
+    }
+
+    #[test]
+    fn test_thresholds_below_the_floor_are_clamped() {
+        let mut config = recognition_config_with_contexts(&[("screenlock", 0.1)]);
+        config.similarity_threshold = 0.2;
+        let matcher = FaceMatcher::new(&config);
+
+        assert_eq!(
+            matcher.get_threshold("screenlock"),
+            MIN_SIMILARITY_THRESHOLD
+        );
+        assert_eq!(matcher.get_threshold("unknown"), MIN_SIMILARITY_THRESHOLD);
+    }
+
+    #[test]
+    fn test_reconfigure_replaces_every_threshold_in_place() {
+        let matcher = FaceMatcher::new(&recognition_config_with_contexts(&[("kiosk", 0.9)]));
+
+        let mut config = recognition_config_with_contexts(&[("sudo", 0.8)]);
+        config.similarity_threshold = 0.7;
+        matcher.reconfigure(&config);
+
+        assert_eq!(matcher.get_threshold("sudo"), 0.8);
+        // A context dropped from the new config falls back to the new
+        // default, not its old value.
+        assert_eq!(matcher.get_threshold("kiosk"), 0.7);
+        assert_eq!(matcher.get_threshold("login"), 0.7);
+    }
+
+    #[test]
+    fn test_match_result_reports_the_effective_context_threshold() {
+        let matcher = FaceMatcher::new(&recognition_config_with_contexts(&[("kiosk", 0.9)]));
+        let (mut probe, stored) = matching_probe_and_stored();
+        // cosine([0.8, 0.6, 0], [1, 0, 0]) = 0.8: would pass the 0.58
+        // default, must fail kiosk's own 0.9.
+        probe.vector = vec![0.8, 0.6, 0.0];
+
+        let result = matcher.match_with_liveness(&probe, &stored, "kiosk", Some(0.9), 1.0);
+
+        assert!(!result.matched);
+        assert_eq!(result.threshold, 0.9);
+    }
+
+    fn matching_probe_and_stored() -> (Embedding, HashMap<String, FaceTemplate>) {
         let probe = Embedding {
             vector: vec![1.0, 0.0, 0.0],
             metadata: hello_face_core::EmbeddingMetadata {
@@ -330,14 +718,15 @@ mod tests {
                     extracted_at: 0,
                     quality_score: 0.9,
                 },
-            },
+            }
+            .into(),
         );
         (probe, stored)
     }
 
     #[test]
     fn test_match_with_liveness_ir_gate_rejects_below_threshold() {
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
         let (probe, stored) = matching_probe_and_stored();
 
         // A recognizable face, but IR liveness reads as a flat photo
@@ -351,7 +740,7 @@ mod tests {
 
     #[test]
     fn test_match_with_liveness_ir_gate_accepts_above_threshold() {
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
         let (probe, stored) = matching_probe_and_stored();
 
         let result = matcher.match_with_liveness(&probe, &stored, "test", Some(0.9), 1.0);
@@ -362,7 +751,7 @@ mod tests {
 
     #[test]
     fn test_match_with_liveness_falls_back_to_rgb_gate_without_ir() {
-        let matcher = FaceMatcher::new();
+        let matcher = FaceMatcher::default();
         let (probe, stored) = matching_probe_and_stored();
 
         // No IR camera (None): a strong RGB match must still be rejected
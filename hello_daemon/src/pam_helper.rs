@@ -10,7 +10,10 @@
 //! EAGAIN on read() on the PAM side.
 
 use crate::dbus_interface::{VerifyRequest, VerifyResult};
+use crate::lockout::FailureLog;
+use crate::record_auth::RecordAuthenticator;
 use crate::storage::FaceStorage;
+use crate::storage_key::MasterKey;
 use crate::verify_with_storage;
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -25,6 +28,23 @@ pub struct PamHelperRequest {
     pub user_id: u32,
     pub context: String,
     pub timeout_ms: u64,
+    /// Missing from modules older than the failed-attempt throttling:
+    /// those only ever asked to verify
+    #[serde(default)]
+    pub action: PamHelperAction,
+}
+
+/// What the PAM module asks for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PamHelperAction {
+    /// Capture and match a face
+    #[default]
+    Verify,
+    /// The PAM stack authenticated the user (`pam_sm_setcred`) after the
+    /// face didn't: forget their failed attempts in `context` (see
+    /// `lockout`). Only taken from root, which the PAM stacks of `login`,
+    /// `sudo` or a display manager run as.
+    Authenticated,
 }
 
 /// Helper response
@@ -37,8 +57,26 @@ pub enum PamHelperResponse {
     Failure {
         reason: String,
     },
+    /// Face auth is turned off for this context (`enable_for_*` in
+    /// config.toml) — the PAM module answers `PAM_IGNORE`, so the stack
+    /// moves on to the password as if the module weren't configured,
+    /// rather than counting a failed attempt.
+    ContextDisabled {
+        context: String,
+    },
+    /// The user has no face that could match in this context: none
+    /// enrolled, none allowed for it, or none passing its integrity check
+    /// (see `record_auth`). Final — `pam_linux_hello` doesn't run another
+    /// round for it.
+    NoEnrollment,
+    /// Answer to [`PamHelperAction::Authenticated`]
+    FailuresCleared,
 }
 
+/// Failure reason while failed attempts keep face auth off (`pam_linux_hello`
+/// tells the user to use their password)
+pub const LOCKED_OUT: &str = "Locked out";
+
 /// Start the PAM socket listener (async tokio)
 pub async fn start_pam_helper(
     uid: u32,
@@ -146,18 +184,45 @@ fn verify_outcome_to_response(
             face_id,
             similarity_score,
         },
+        // Not worth another round: no capture can match until the user
+        // enrolls again (`pam_linux_hello` tells them so)
+        Ok(Ok(VerifyResult::ReEnrollmentRequired)) => PamHelperResponse::Failure {
+            reason: "Re-enrollment required".to_string(),
+        },
+        // Neither is another round, until the backoff ends or a password
+        // clears it
+        Ok(Ok(VerifyResult::LockedOut { .. })) => PamHelperResponse::Failure {
+            reason: LOCKED_OUT.to_string(),
+        },
+        Ok(Ok(VerifyResult::NoEnrollment)) => PamHelperResponse::NoEnrollment,
         Ok(Ok(_)) => PamHelperResponse::Failure {
             reason: "Face not recognized".to_string(),
         },
-        Ok(Err(e)) => PamHelperResponse::Failure {
-            reason: e.to_string(),
-        },
+        Ok(Err(e)) => error_to_response(e),
         Err(_) => PamHelperResponse::Failure {
             reason: "Timeout".to_string(),
         },
     }
 }
 
+/// A daemon error as a helper response — `ContextDisabled` keeps its own
+/// variant so `pam_linux_hello` can tell it apart from a failed match.
+fn error_to_response(error: crate::DaemonError) -> PamHelperResponse {
+    match error {
+        crate::DaemonError::ContextDisabled(context) => {
+            PamHelperResponse::ContextDisabled { context }
+        }
+        e => PamHelperResponse::Failure {
+            reason: e.to_string(),
+        },
+    }
+}
+
+/// Whether a peer may send [`PamHelperAction::Authenticated`]
+fn may_report_authentication(peer_uid: Option<u32>) -> bool {
+    peer_uid == Some(0)
+}
+
 /// Process an incoming PAM connection
 async fn handle_pam_request(
     mut stream: tokio::net::UnixStream,
@@ -204,6 +269,30 @@ async fn handle_pam_request(
         }
     }
 
+    if req.action == PamHelperAction::Authenticated {
+        // Anyone else — the user themselves included — could otherwise
+        // reset the count between two tries with a photo
+        if !may_report_authentication(peer_uid) {
+            error!(
+                "PAM helper: authentication report refused — peer uid={:?}",
+                peer_uid
+            );
+            return reject(stream, "Unauthorized: only root reports authentications").await;
+        }
+        let response = match daemon
+            .read()
+            .await
+            .clear_failures(req.user_id, &req.context)
+        {
+            Ok(_) => PamHelperResponse::FailuresCleared,
+            Err(e) => error_to_response(e),
+        };
+        let response_json = serde_json::to_string(&response)?;
+        stream.write_all(response_json.as_bytes()).await?;
+        stream.shutdown().await?;
+        return Ok(());
+    }
+
     let verify_req = VerifyRequest {
         user_id: req.user_id,
         context: req.context,
@@ -283,12 +372,21 @@ fn resolve_home_dir(uid: u32) -> Option<std::path::PathBuf> {
 /// Start the system-wide PAM socket listener for the SDDM context.
 ///
 /// `camera`/`matcher` are long-lived and shared across requests (built once
-/// by `hello-daemon-system`'s `main()`); `storage` is resolved fresh per
+/// by `hello-daemon-system`'s `main()`, from the same `settings` passed
+/// here for the per-context enable check); `storage` is resolved fresh per
 /// request from the target user's own home directory, via
 /// [`FaceStorage::open_read_only`] — never the side-effecting `FaceStorage::new`.
+/// `master_key` derives the keys that user's sealed embeddings open with
+/// and its face records are authenticated under (see `record_auth`);
+/// without it only unencrypted ones can be matched, unchecked. `failures`
+/// counts failed matches at the login screen — kept by this listener, as
+/// the user's own storage is only ever read here (see `lockout`).
 pub async fn start_system_pam_helper(
     camera: Arc<crate::camera::CameraManager>,
     matcher: Arc<crate::matcher::FaceMatcher>,
+    settings: Arc<hello_config::Config>,
+    master_key: Option<Arc<MasterKey>>,
+    failures: Arc<FailureLog>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let socket_path = system_socket_path();
     let _ = fs::remove_file(&socket_path);
@@ -327,8 +425,15 @@ pub async fn start_system_pam_helper(
 
                     let camera = camera.clone();
                     let matcher = matcher.clone();
+                    let settings = settings.clone();
+                    let master_key = master_key.clone();
+                    let failures = failures.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = handle_system_pam_request(stream, camera, matcher).await {
+                        if let Err(e) = handle_system_pam_request(
+                            stream, camera, matcher, settings, master_key, failures,
+                        )
+                        .await
+                        {
                             error!("PAM system helper error: {}", e);
                         }
                     });
@@ -353,8 +458,18 @@ pub async fn start_system_pam_helper(
 async fn compute_verify_response(
     camera: &crate::camera::CameraManager,
     matcher: Arc<crate::matcher::FaceMatcher>,
+    settings: &hello_config::Config,
+    master_key: Option<&MasterKey>,
+    failures: &FailureLog,
     req: &PamHelperRequest,
 ) -> PamHelperResponse {
+    // Before the home-directory lookup: whether `sddm` is enabled is a
+    // machine-wide setting, so answering it first reveals nothing about
+    // the user, and a disabled context shouldn't read from anyone's home.
+    if let Err(e) = crate::check_context_enabled(settings, &req.context) {
+        return error_to_response(e);
+    }
+
     match resolve_home_dir(req.user_id) {
         None => {
             debug!(
@@ -367,10 +482,12 @@ async fn compute_verify_response(
         }
         Some(home) => {
             let base_path = home.join(".local/share/linux-hello");
-            match FaceStorage::open_read_only(&base_path) {
-                Ok(None) => PamHelperResponse::Failure {
-                    reason: "No enrollment".to_string(),
-                },
+            let key = master_key.map(|master| master.user_key(req.user_id));
+            let record_auth = master_key.map_or(RecordAuthenticator::Disabled, |master| {
+                RecordAuthenticator::Local(master.record_key(req.user_id))
+            });
+            match FaceStorage::open_read_only(&base_path, key) {
+                Ok(None) => PamHelperResponse::NoEnrollment,
                 Ok(Some(storage)) => {
                     let verify_req = VerifyRequest {
                         user_id: req.user_id,
@@ -380,7 +497,15 @@ async fn compute_verify_response(
                     let timeout = std::time::Duration::from_millis(verify_req.timeout_ms + 1000);
                     let result = tokio::time::timeout(
                         timeout,
-                        verify_with_storage(&storage, camera, matcher, &verify_req),
+                        verify_with_storage(
+                            &storage,
+                            camera,
+                            matcher,
+                            settings,
+                            &record_auth,
+                            failures,
+                            &verify_req,
+                        ),
                     )
                     .await;
 
@@ -428,6 +553,9 @@ async fn handle_system_pam_request(
     mut stream: tokio::net::UnixStream,
     camera: Arc<crate::camera::CameraManager>,
     matcher: Arc<crate::matcher::FaceMatcher>,
+    settings: Arc<hello_config::Config>,
+    master_key: Option<Arc<MasterKey>>,
+    failures: Arc<FailureLog>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let start = std::time::Instant::now();
 
@@ -459,8 +587,25 @@ async fn handle_system_pam_request(
     debug!("PAM system helper received: {}", request_json);
     let req: PamHelperRequest = serde_json::from_str(&request_json)?;
 
-    let response = compute_verify_response(&camera, matcher, &req).await;
-    let response = respond_with_floor(start, &req, response).await;
+    // Only root reaches this socket, so only a PAM stack that has just
+    // authenticated the user at the login screen sends this
+    let response = if req.action == PamHelperAction::Authenticated {
+        match failures.clear(req.user_id, Some(&req.context)) {
+            Ok(_) => PamHelperResponse::FailuresCleared,
+            Err(e) => error_to_response(e),
+        }
+    } else {
+        let response = compute_verify_response(
+            &camera,
+            matcher,
+            &settings,
+            master_key.as_deref(),
+            &failures,
+            &req,
+        )
+        .await;
+        respond_with_floor(start, &req, response).await
+    };
 
     let response_json = serde_json::to_string(&response)?;
     stream.write_all(response_json.as_bytes()).await?;
@@ -511,16 +656,25 @@ mod tests {
             user_id: 4_294_967_000,
             context: "sddm".to_string(),
             timeout_ms,
+            action: PamHelperAction::Verify,
         }
     }
 
     #[tokio::test]
     async fn test_compute_verify_response_unknown_user_is_a_fast_failure() {
-        let camera = crate::camera::CameraManager::new(1000);
-        let matcher = Arc::new(crate::matcher::FaceMatcher::new());
+        let camera = crate::camera::CameraManager::new(&hello_config::Config::default());
+        let matcher = Arc::new(crate::matcher::FaceMatcher::default());
         let req = unknown_user_req(5000);
 
-        let response = compute_verify_response(&camera, matcher, &req).await;
+        let response = compute_verify_response(
+            &camera,
+            matcher,
+            &hello_config::Config::default(),
+            None,
+            &FailureLog::in_memory().unwrap(),
+            &req,
+        )
+        .await;
 
         assert!(matches!(
             response,
@@ -528,6 +682,31 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_compute_verify_response_disabled_context_is_reported_as_such() {
+        let camera = crate::camera::CameraManager::new(&hello_config::Config::default());
+        let matcher = Arc::new(crate::matcher::FaceMatcher::default());
+        let mut settings = hello_config::Config::default();
+        settings.authentication.enable_for_sddm = false;
+        let req = unknown_user_req(5000);
+
+        let response = compute_verify_response(
+            &camera,
+            matcher,
+            &settings,
+            None,
+            &FailureLog::in_memory().unwrap(),
+            &req,
+        )
+        .await;
+
+        // Checked before the user lookup, so even an unknown user gets it
+        assert!(matches!(
+            response,
+            PamHelperResponse::ContextDisabled { context } if context == "sddm"
+        ));
+    }
+
     /// Regression test for the timing side-channel described in
     /// docs/PAM_MODULE.md: without `respond_with_floor`, this branch returns
     /// in microseconds — a local attacker at the greeter could distinguish
@@ -535,12 +714,20 @@ mod tests {
     /// caller-supplied `timeout_ms` closes that gap.
     #[tokio::test]
     async fn test_respond_with_floor_pads_a_fast_failure_up_to_timeout_ms() {
-        let camera = crate::camera::CameraManager::new(1000);
-        let matcher = Arc::new(crate::matcher::FaceMatcher::new());
+        let camera = crate::camera::CameraManager::new(&hello_config::Config::default());
+        let matcher = Arc::new(crate::matcher::FaceMatcher::default());
         let req = unknown_user_req(150);
 
         let start = std::time::Instant::now();
-        let response = compute_verify_response(&camera, matcher, &req).await;
+        let response = compute_verify_response(
+            &camera,
+            matcher,
+            &hello_config::Config::default(),
+            None,
+            &FailureLog::in_memory().unwrap(),
+            &req,
+        )
+        .await;
         let response = respond_with_floor(start, &req, response).await;
 
         assert!(matches!(response, PamHelperResponse::Failure { .. }));
@@ -619,6 +806,16 @@ mod tests {
         tempfile::TempDir,
         tempfile::TempDir,
         Arc<tokio::sync::RwLock<crate::FaceAuthDaemon>>,
+    ) {
+        test_daemon_with_settings(hello_config::Config::default())
+    }
+
+    fn test_daemon_with_settings(
+        settings: hello_config::Config,
+    ) -> (
+        tempfile::TempDir,
+        tempfile::TempDir,
+        Arc<tokio::sync::RwLock<crate::FaceAuthDaemon>>,
     ) {
         let storage_dir = tempfile::TempDir::new().unwrap();
         let cam_dir = tempfile::TempDir::new().unwrap();
@@ -642,6 +839,8 @@ mod tests {
         let config = crate::DaemonConfig {
             storage_path: storage_dir.path().to_path_buf(),
             root_mode: false,
+            settings,
+            policy: hello_config::Policy::default(),
         };
         let daemon = crate::FaceAuthDaemon::new_for_test(config, camera).unwrap();
         (
@@ -662,7 +861,7 @@ mod tests {
         let response: PamHelperResponse = serde_json::from_slice(&buf).unwrap();
         match response {
             PamHelperResponse::Failure { reason } => assert_eq!(reason, "some reason"),
-            PamHelperResponse::Success { .. } => panic!("expected Failure"),
+            other => panic!("expected Failure, got {other:?}"),
         }
     }
 
@@ -694,6 +893,7 @@ mod tests {
             user_id: not_my_uid(),
             context: "test".to_string(),
             timeout_ms: 100,
+            action: PamHelperAction::Verify,
         };
         b.write_all(serde_json::to_string(&req).unwrap().as_bytes())
             .await
@@ -707,7 +907,7 @@ mod tests {
         let response: PamHelperResponse = serde_json::from_slice(&buf).unwrap();
         match response {
             PamHelperResponse::Failure { reason } => assert!(reason.contains("Unauthorized")),
-            PamHelperResponse::Success { .. } => panic!("expected Failure"),
+            other => panic!("expected Failure, got {other:?}"),
         }
     }
 
@@ -720,6 +920,7 @@ mod tests {
             user_id: my_uid(),
             context: "test".to_string(),
             timeout_ms: 100,
+            action: PamHelperAction::Verify,
         };
         b.write_all(serde_json::to_string(&req).unwrap().as_bytes())
             .await
@@ -731,9 +932,114 @@ mod tests {
         let mut buf = Vec::new();
         b.read_to_end(&mut buf).await.unwrap();
         let response: PamHelperResponse = serde_json::from_slice(&buf).unwrap();
-        match response {
-            PamHelperResponse::Failure { reason } => assert_eq!(reason, "Face not recognized"),
-            PamHelperResponse::Success { .. } => panic!("expected Failure: nothing is enrolled"),
+        assert!(
+            matches!(response, PamHelperResponse::NoEnrollment),
+            "nothing is enrolled, got {response:?}"
+        );
+    }
+
+    #[test]
+    fn test_only_a_failed_match_is_reported_as_not_recognized() {
+        let response = |result| verify_outcome_to_response(Ok(Ok(result)));
+        assert!(matches!(
+            response(VerifyResult::NoEnrollment),
+            PamHelperResponse::NoEnrollment
+        ));
+        for result in [
+            VerifyResult::NoFaceDetected,
+            VerifyResult::NoMatch {
+                best_score: 0.3,
+                threshold: 0.6,
+            },
+        ] {
+            assert!(matches!(
+                response(result),
+                PamHelperResponse::Failure { reason } if reason == "Face not recognized"
+            ));
         }
     }
+
+    #[test]
+    fn test_only_root_reports_authentications() {
+        assert!(may_report_authentication(Some(0)));
+        assert!(!may_report_authentication(Some(1000)));
+        assert!(!may_report_authentication(Some(65534)));
+        assert!(!may_report_authentication(None));
+    }
+
+    #[tokio::test]
+    async fn test_handle_pam_request_clears_failures_after_an_authentication() {
+        let (_storage_dir, _cam_dir, daemon) = test_daemon();
+        let (a, mut b) = tokio::net::UnixStream::pair().unwrap();
+        for context in ["sudo", "screenlock"] {
+            daemon
+                .read()
+                .await
+                .failures
+                .record_failure(my_uid(), context, 1)
+                .unwrap();
+        }
+
+        // Older modules don't send an action: that's a verify
+        let req: PamHelperRequest =
+            serde_json::from_str(r#"{"user_id":1000,"context":"sudo","timeout_ms":100}"#).unwrap();
+        assert_eq!(req.action, PamHelperAction::Verify);
+
+        let req = PamHelperRequest {
+            user_id: my_uid(),
+            context: "sudo".to_string(),
+            timeout_ms: 0,
+            action: PamHelperAction::Authenticated,
+        };
+        b.write_all(serde_json::to_string(&req).unwrap().as_bytes())
+            .await
+            .unwrap();
+        b.shutdown().await.unwrap();
+
+        handle_pam_request(a, daemon.clone()).await.unwrap();
+
+        let mut buf = Vec::new();
+        b.read_to_end(&mut buf).await.unwrap();
+        let response: PamHelperResponse = serde_json::from_slice(&buf).unwrap();
+        if my_uid() == 0 {
+            assert!(matches!(response, PamHelperResponse::FailuresCleared));
+        } else {
+            assert!(matches!(response, PamHelperResponse::Failure { .. }));
+            return;
+        }
+
+        // Only in the context the PAM stack was for
+        let failures = &daemon.read().await.failures;
+        assert_eq!(failures.state(my_uid(), "sudo").unwrap().failures, 0);
+        assert_eq!(failures.state(my_uid(), "screenlock").unwrap().failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_handle_pam_request_reports_a_disabled_context() {
+        let mut settings = hello_config::Config::default();
+        settings.authentication.enable_for_sudo = false;
+        let (_storage_dir, _cam_dir, daemon) = test_daemon_with_settings(settings);
+        let (a, mut b) = tokio::net::UnixStream::pair().unwrap();
+
+        let req = PamHelperRequest {
+            user_id: my_uid(),
+            context: "sudo".to_string(),
+            timeout_ms: 100,
+            action: PamHelperAction::Verify,
+        };
+        b.write_all(serde_json::to_string(&req).unwrap().as_bytes())
+            .await
+            .unwrap();
+        b.shutdown().await.unwrap();
+
+        handle_pam_request(a, daemon).await.unwrap();
+
+        let mut buf = Vec::new();
+        b.read_to_end(&mut buf).await.unwrap();
+        let response: PamHelperResponse = serde_json::from_slice(&buf).unwrap();
+        assert!(matches!(
+            response,
+            PamHelperResponse::ContextDisabled { context } if context == "sudo"
+        ));
+    }
 }
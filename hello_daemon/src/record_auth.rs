@@ -0,0 +1,926 @@
+//! Tamper detection for stored face records
+//!
+//! Embedding vectors are sealed (`storage_key`), but under a key the user's
+//! own daemon is handed — so any process running as the user can fetch it
+//! too, and write a face of its choosing straight into `faces.db` without
+//! ever passing the re-authentication `authz` puts in front of the D-Bus
+//! methods. Each face record is therefore also authenticated with an
+//! HMAC-SHA256 under a second per-user key that never leaves root:
+//!
+//! - The key is derived from the storage master key under its own label
+//!   ([`MasterKey::record_key`](crate::storage_key::MasterKey::record_key)).
+//! - `hello-storage-key` serves [`RECORD_SOCKET_PATH`]. It signs a record
+//!   digest only for a process polkit authorizes for
+//!   `com.linuxhello.manage-faces` — the check the daemon itself passed
+//!   moments before, which polkit keeps for the session (`auth_self_keep`)
+//!   so nobody is asked twice — and checks a MAC for anyone, always under
+//!   the key of the connecting uid.
+//! - The SDDM listener, root already, derives the key directly.
+//!
+//! The MAC covers what decides who a face unlocks for, and until when:
+//! owner, face id, registration time (which `max_template_age_days` counts
+//! from), allowed contexts, and every stored vector with its model.
+//! Labels, notes and statistics are left out, so editing them needs no new
+//! signature. `verify_with_storage` refuses to match against a face whose
+//! MAC is missing or wrong, and logs it on the `audit` target.
+//!
+//! Faces signed before the registration time was covered carry a MAC of
+//! the older digest. The service re-signs one without polkit when its
+//! registration time is still the one its id was minted with
+//! (`face_<uid>_<registered_at>`, see [`RecordAuthenticator::resign`]) —
+//! the only one that old MAC vouches for — and `verify_with_storage`
+//! stores the new MAC the first time it meets such a face.
+//!
+//! Adapted samples (see `adaptive`) are folded in during a login, where
+//! nobody can be asked to re-authenticate, so each gets a MAC of its own
+//! ([`adapted_digest`]). The service signs one without polkit, but only
+//! for a face whose MAC checks out, and only once the probe passes the
+//! margin and drift checks against that signed enrollment, under the
+//! system configuration's limits. One without a valid MAC is never scored.
+
+use crate::adaptive::AdaptationPolicy;
+use crate::matcher::{FaceMatcher, FaceTemplate};
+use crate::storage_key::MasterKey;
+use crate::FaceRecord;
+use hello_face_core::Embedding;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, error, info, warn};
+
+/// Socket `hello-storage-key` signs and checks face records on
+pub const RECORD_SOCKET_PATH: &str = "/run/hello-pam/records.socket";
+
+const KEY_LEN: usize = 32;
+pub const DIGEST_LEN: usize = 32;
+const MAC_LEN: usize = 32;
+const DIGEST_LABEL: &[u8] = b"linux-hello face record v2";
+/// [`legacy_record_digest`]'s, before `registered_at` was covered
+const LEGACY_DIGEST_LABEL: &[u8] = b"linux-hello face record v1";
+const ADAPTED_DIGEST_LABEL: &[u8] = b"linux-hello adapted sample v1";
+
+/// Request: `OP_SIGN || digest`. Reply: the MAC, or the connection closed
+/// without one if the caller isn't authorized.
+const OP_SIGN: u8 = 1;
+/// Request: `OP_CHECK || digest || mac`. Reply: one byte, 1 if it matches.
+const OP_CHECK: u8 = 2;
+/// Request: `OP_SIGN_ADAPTED || length (u32 LE) || AdaptationRequest as
+/// JSON`. Reply: the MAC, or the connection closed without one if the
+/// sample doesn't pass the service's own checks.
+const OP_SIGN_ADAPTED: u8 = 3;
+
+/// Request: `OP_RESIGN || length (u32 LE) || ResignRequest as JSON`.
+/// Reply: 1 and the MAC of the current digest, or 0 and as many zeros if
+/// the face isn't one [`RecordAuthenticator::resign`] applies to.
+const OP_RESIGN: u8 = 4;
+
+/// Largest JSON request (`OP_SIGN_ADAPTED`, `OP_RESIGN`) read
+const MAX_JSON_REQUEST: usize = 1 << 20;
+
+/// How long a signing request may wait for polkit — long enough for the
+/// user to answer a password prompt if the kept authorization has expired
+const SIGN_TIMEOUT: Duration = Duration::from_secs(120);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One user's record authentication key
+#[derive(Clone)]
+pub struct RecordKey([u8; KEY_LEN]);
+
+impl std::fmt::Debug for RecordKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RecordKey(..)")
+    }
+}
+
+impl RecordKey {
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn as_bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+
+    /// MAC of a record digest
+    pub fn mac(&self, digest: &[u8; DIGEST_LEN]) -> [u8; MAC_LEN] {
+        let mut mac = self.hmac();
+        mac.update(digest);
+        mac.finalize().into_bytes().into()
+    }
+
+    /// Whether `mac` is [`RecordKey::mac`] of `digest`, compared in
+    /// constant time
+    pub fn verify(&self, digest: &[u8; DIGEST_LEN], mac: &[u8]) -> bool {
+        let mut expected = self.hmac();
+        expected.update(digest);
+        expected.verify_slice(mac).is_ok()
+    }
+
+    fn hmac(&self) -> Hmac<Sha256> {
+        <Hmac<Sha256> as hmac::KeyInit>::new_from_slice(&self.0)
+            .expect("HMAC accepts keys of any length")
+    }
+}
+
+fn hash_field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+fn hash_embedding(hasher: &mut Sha256, embedding: &Embedding) {
+    hash_field(hasher, embedding.metadata.model.as_bytes());
+    hash_field(hasher, embedding.metadata.model_version.as_bytes());
+    let vector: Vec<u8> = embedding
+        .vector
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .collect();
+    hash_field(hasher, &vector);
+}
+
+/// What a face's MAC is computed over: see the module docs for which
+/// fields are covered
+pub fn record_digest(record: &FaceRecord, template: &FaceTemplate) -> [u8; DIGEST_LEN] {
+    digest_fields(DIGEST_LABEL, Some(record.registered_at), record, template)
+}
+
+/// What a face's MAC was computed over before it covered `registered_at`
+pub(crate) fn legacy_record_digest(
+    record: &FaceRecord,
+    template: &FaceTemplate,
+) -> [u8; DIGEST_LEN] {
+    digest_fields(LEGACY_DIGEST_LABEL, None, record, template)
+}
+
+fn digest_fields(
+    label: &[u8],
+    registered_at: Option<u64>,
+    record: &FaceRecord,
+    template: &FaceTemplate,
+) -> [u8; DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(label);
+    hasher.update(record.user_id.to_le_bytes());
+    hash_field(&mut hasher, record.face_id.as_bytes());
+    if let Some(registered_at) = registered_at {
+        hasher.update(registered_at.to_le_bytes());
+    }
+    let mut contexts = record.allowed_contexts.clone();
+    contexts.sort();
+    hasher.update((contexts.len() as u64).to_le_bytes());
+    for context in &contexts {
+        hash_field(&mut hasher, context.as_bytes());
+    }
+    hash_embedding(&mut hasher, &template.centroid);
+    hasher.update((template.samples.len() as u64).to_le_bytes());
+    for sample in &template.samples {
+        hash_embedding(&mut hasher, sample);
+    }
+    hasher.finalize().into()
+}
+
+/// What an adapted sample's MAC is computed over: the sample, bound to the
+/// owner, the face and the enrolled centroid it was folded into — it can't
+/// be moved to another face, nor outlive a new enrollment of this one
+pub fn adapted_digest(
+    user_id: u32,
+    face_id: &str,
+    centroid: &Embedding,
+    sample: &Embedding,
+) -> [u8; DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(ADAPTED_DIGEST_LABEL);
+    hasher.update(user_id.to_le_bytes());
+    hash_field(&mut hasher, face_id.as_bytes());
+    hash_embedding(&mut hasher, centroid);
+    hash_embedding(&mut hasher, sample);
+    hasher.finalize().into()
+}
+
+/// The registration time in a face id `register_face` minted
+/// (`face_<uid>_<registered_at>`)
+fn registered_at_in_id(face_id: &str) -> Option<u64> {
+    face_id
+        .strip_prefix("face_")?
+        .rsplit('_')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// The MAC of `record`'s current digest under `uid`'s `key`, if `mac`
+/// authenticates its legacy one and `registered_at` is still the time its
+/// id was minted at — the only registration time the old MAC vouches for
+fn resign_legacy(
+    key: &RecordKey,
+    uid: u32,
+    record: &FaceRecord,
+    template: &FaceTemplate,
+    mac: &[u8],
+) -> Option<[u8; MAC_LEN]> {
+    let legacy = record.user_id == uid
+        && registered_at_in_id(&record.face_id) == Some(record.registered_at)
+        && key.verify(&legacy_record_digest(record, template), mac);
+    legacy.then(|| key.mac(&record_digest(record, template)))
+}
+
+/// A face signed under the legacy digest, to re-sign
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ResignRequest {
+    record: FaceRecord,
+    template: FaceTemplate,
+    mac: Vec<u8>,
+}
+
+/// What the service checks an adapted sample against before signing it:
+/// the face as signed, with its MAC, and the probe with the context it
+/// matched for
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AdaptationRequest {
+    record: FaceRecord,
+    template: FaceTemplate,
+    mac: Vec<u8>,
+    context: String,
+    probe: Embedding,
+}
+
+/// Why the service wouldn't sign an adapted sample
+#[derive(Debug)]
+enum AdaptationRefused {
+    /// The face doesn't match its MAC: not as the user enrolled it
+    Integrity,
+    /// The probe doesn't clear the checks of `adaptive`
+    Policy(String),
+}
+
+/// The MAC of `request.probe` as an adapted sample of `request.record`
+/// under `uid`'s `key`, if the face checks out against its MAC and the
+/// probe clears the margin and drift checks of `adaptive` under
+/// `settings` — scored here, against the signed enrollment, not taken from
+/// the daemon asking
+fn sign_adaptation(
+    key: &RecordKey,
+    uid: u32,
+    request: AdaptationRequest,
+    settings: &hello_config::Config,
+) -> Result<[u8; MAC_LEN], AdaptationRefused> {
+    let AdaptationRequest {
+        record,
+        mut template,
+        mac,
+        context,
+        probe,
+    } = request;
+    template.adapted.clear();
+    if record.user_id != uid || !key.verify(&record_digest(&record, &template), &mac) {
+        return Err(AdaptationRefused::Integrity);
+    }
+    if !record.allows_context(&context) {
+        return Err(AdaptationRefused::Policy(format!(
+            "face {} may not authenticate context {}",
+            record.face_id, context
+        )));
+    }
+    let matcher = FaceMatcher::new(&settings.recognition);
+    let gallery = HashMap::from([(record.face_id.clone(), template.clone())]);
+    let scored = matcher.match_embedding(&probe, &gallery, &context);
+    AdaptationPolicy::limits(&settings.behavior)
+        .check(
+            &matcher,
+            &template,
+            &probe,
+            scored.best_score,
+            scored.threshold,
+            None,
+            0,
+        )
+        .map_err(|skipped| AdaptationRefused::Policy(format!("{:?}", skipped)))?;
+    Ok(key.mac(&adapted_digest(
+        uid,
+        &record.face_id,
+        &template.centroid,
+        &probe,
+    )))
+}
+
+/// How a daemon signs and checks face records
+#[derive(Debug)]
+pub enum RecordAuthenticator {
+    /// Records aren't authenticated: there's no key service to hold the
+    /// key — the same condition under which embeddings are stored
+    /// unencrypted
+    Disabled,
+    /// Through `hello-storage-key` on `socket_path`, trusted only when
+    /// served by `server_uid` (the user's own daemon)
+    Service {
+        socket_path: PathBuf,
+        server_uid: u32,
+    },
+    /// With the key itself (the SDDM listener, which never signs; tests)
+    Local(RecordKey),
+}
+
+impl RecordAuthenticator {
+    /// The production setup of a user's daemon
+    pub fn service() -> Self {
+        Self::Service {
+            socket_path: PathBuf::from(RECORD_SOCKET_PATH),
+            server_uid: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    /// MAC for `digest`; `None` when records aren't authenticated
+    pub async fn sign(&self, digest: &[u8; DIGEST_LEN]) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Self::Disabled => Ok(None),
+            Self::Local(key) => Ok(Some(key.mac(digest).to_vec())),
+            Self::Service {
+                socket_path,
+                server_uid,
+            } => {
+                let mut request = vec![OP_SIGN];
+                request.extend_from_slice(digest);
+                let reply =
+                    exchange(socket_path, *server_uid, &request, MAC_LEN, SIGN_TIMEOUT).await?;
+                Ok(Some(reply))
+            }
+        }
+    }
+
+    /// Whether `mac` (`None`: the face was never signed) authenticates
+    /// `digest`. Always true when records aren't authenticated.
+    pub async fn check(
+        &self,
+        digest: &[u8; DIGEST_LEN],
+        mac: Option<&[u8]>,
+    ) -> Result<bool, String> {
+        if !self.is_enabled() {
+            return Ok(true);
+        }
+        let Some(mac) = mac.filter(|mac| mac.len() == MAC_LEN) else {
+            return Ok(false);
+        };
+        match self {
+            Self::Disabled => Ok(true),
+            Self::Local(key) => Ok(key.verify(digest, mac)),
+            Self::Service {
+                socket_path,
+                server_uid,
+            } => {
+                let mut request = vec![OP_CHECK];
+                request.extend_from_slice(digest);
+                request.extend_from_slice(mac);
+                let reply = exchange(socket_path, *server_uid, &request, 1, CHECK_TIMEOUT).await?;
+                Ok(reply == [1])
+            }
+        }
+    }
+
+    /// MAC of `record`'s current digest, for a face whose `mac` was made
+    /// over the legacy one (before `registered_at` was covered) while its
+    /// registration time is still the one in its id. `None` when `mac`
+    /// isn't such a MAC, or records aren't authenticated.
+    pub async fn resign(
+        &self,
+        record: &FaceRecord,
+        template: &FaceTemplate,
+        mac: &[u8],
+    ) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Self::Disabled => Ok(None),
+            Self::Local(key) => {
+                Ok(resign_legacy(key, record.user_id, record, template, mac)
+                    .map(|mac| mac.to_vec()))
+            }
+            Self::Service {
+                socket_path,
+                server_uid,
+            } => {
+                let request = json_request(
+                    OP_RESIGN,
+                    &ResignRequest {
+                        record: record.clone(),
+                        template: FaceTemplate {
+                            adapted: Vec::new(),
+                            ..template.clone()
+                        },
+                        mac: mac.to_vec(),
+                    },
+                )?;
+                let reply = exchange(
+                    socket_path,
+                    *server_uid,
+                    &request,
+                    1 + MAC_LEN,
+                    CHECK_TIMEOUT,
+                )
+                .await?;
+                Ok((reply[0] == 1).then(|| reply[1..].to_vec()))
+            }
+        }
+    }
+
+    /// MAC for `probe` as a new adapted sample of `record`, which the
+    /// service checks against the enrollment `template` and `mac` sign
+    /// (see the module docs); `None` when records aren't authenticated.
+    /// `Local` signs without those checks, as it signs without polkit.
+    pub async fn sign_adapted(
+        &self,
+        record: &FaceRecord,
+        template: &FaceTemplate,
+        mac: Option<&[u8]>,
+        context: &str,
+        probe: &Embedding,
+    ) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Self::Disabled => Ok(None),
+            Self::Local(key) => Ok(Some(
+                key.mac(&adapted_digest(
+                    record.user_id,
+                    &record.face_id,
+                    &template.centroid,
+                    probe,
+                ))
+                .to_vec(),
+            )),
+            Self::Service {
+                socket_path,
+                server_uid,
+            } => {
+                let mac = mac.ok_or_else(|| format!("face {} isn't signed", record.face_id))?;
+                let request = json_request(
+                    OP_SIGN_ADAPTED,
+                    &AdaptationRequest {
+                        record: record.clone(),
+                        template: FaceTemplate {
+                            adapted: Vec::new(),
+                            ..template.clone()
+                        },
+                        mac: mac.to_vec(),
+                        context: context.to_string(),
+                        probe: probe.clone(),
+                    },
+                )?;
+                let reply =
+                    exchange(socket_path, *server_uid, &request, MAC_LEN, CHECK_TIMEOUT).await?;
+                Ok(Some(reply))
+            }
+        }
+    }
+}
+
+/// `op`, then `payload` as length-prefixed JSON
+fn json_request(op: u8, payload: &impl serde::Serialize) -> Result<Vec<u8>, String> {
+    let payload = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let mut request = vec![op];
+    request.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    request.extend_from_slice(&payload);
+    Ok(request)
+}
+
+/// Send `request` to the service on `socket_path` and read a `reply_len`
+/// byte answer, trusting only a server running as `server_uid`
+async fn exchange(
+    socket_path: &Path,
+    server_uid: u32,
+    request: &[u8],
+    reply_len: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    let exchange = async {
+        let mut stream = UnixStream::connect(socket_path)
+            .await
+            .map_err(|e| format!("{} unreachable: {}", socket_path.display(), e))?;
+        match stream.peer_cred().map(|c| c.uid()) {
+            Ok(uid) if uid == server_uid => {}
+            Ok(uid) => {
+                return Err(format!(
+                    "Refusing {}: served by uid {}, expected {}",
+                    socket_path.display(),
+                    uid,
+                    server_uid
+                ))
+            }
+            Err(e) => return Err(format!("{}: {}", socket_path.display(), e)),
+        }
+        stream
+            .write_all(request)
+            .await
+            .map_err(|e| format!("Writing to {}: {}", socket_path.display(), e))?;
+        let mut reply = vec![0u8; reply_len];
+        stream.read_exact(&mut reply).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                "the record service refused the request".to_string()
+            } else {
+                format!("Reading from {}: {}", socket_path.display(), e)
+            }
+        })?;
+        Ok(reply)
+    };
+    tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| format!("{} did not answer", socket_path.display()))?
+}
+
+/// Sign and check face records on `socket_path` (`hello-storage-key`
+/// only). The socket is `0666` like the key socket: what a caller gets is
+/// always computed under its own uid's key.
+pub async fn start_record_server(
+    socket_path: &Path,
+    master: Arc<MasterKey>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _ = std::fs::remove_file(socket_path);
+    let listener = UnixListener::bind(socket_path)?;
+    std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o666))?;
+    info!("Record service listening on {}", socket_path.display());
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let master = Arc::clone(&master);
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_record_request(stream, &master).await {
+                            debug!("Record request dropped: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Record service accept error: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn serve_record_request(mut stream: UnixStream, master: &MasterKey) -> std::io::Result<()> {
+    let cred = stream.peer_cred()?;
+    let uid = cred.uid();
+    let key = master.record_key(uid);
+
+    let mut op = [0u8; 1];
+    read_request(&mut stream, &mut op).await?;
+    let mut digest = [0u8; DIGEST_LEN];
+    if matches!(op[0], OP_SIGN | OP_CHECK) {
+        read_request(&mut stream, &mut digest).await?;
+    }
+
+    match op[0] {
+        OP_SIGN => {
+            let authorized = match cred.pid().and_then(|pid| u32::try_from(pid).ok()) {
+                Some(pid) => {
+                    crate::authz::check_process_authorization(
+                        pid,
+                        uid,
+                        crate::authz::MANAGE_FACES_ACTION,
+                    )
+                    .await
+                }
+                None => false,
+            };
+            if authorized {
+                stream.write_all(&key.mac(&digest)).await?;
+                debug!("Face record signed for uid={}", uid);
+            } else {
+                warn!(
+                    target: "audit",
+                    "Refused to sign a face record for uid={} (pid {:?}): not authorized",
+                    uid,
+                    cred.pid()
+                );
+            }
+        }
+        OP_CHECK => {
+            let mut mac = [0u8; MAC_LEN];
+            read_request(&mut stream, &mut mac).await?;
+            stream
+                .write_all(&[u8::from(key.verify(&digest, &mac))])
+                .await?;
+        }
+        OP_RESIGN => {
+            let Some(request) = read_json_request::<ResignRequest>(&mut stream, uid).await? else {
+                return Ok(());
+            };
+            let mut reply = [0u8; 1 + MAC_LEN];
+            if let Some(mac) =
+                resign_legacy(&key, uid, &request.record, &request.template, &request.mac)
+            {
+                reply[0] = 1;
+                reply[1..].copy_from_slice(&mac);
+                info!(
+                    "Face {} of uid={} re-signed to cover its registration time",
+                    request.record.face_id, uid
+                );
+            }
+            stream.write_all(&reply).await?;
+        }
+        OP_SIGN_ADAPTED => {
+            let Some(request) = read_json_request::<AdaptationRequest>(&mut stream, uid).await?
+            else {
+                return Ok(());
+            };
+            // The system's limits, never the user's own config.toml
+            let settings = match hello_config::Config::load_system() {
+                Ok(loaded) => loaded.config,
+                Err(e) => {
+                    error!("Refusing to sign adapted samples: {}", e);
+                    return Ok(());
+                }
+            };
+            let face_id = request.record.face_id.clone();
+            match sign_adaptation(&key, uid, request, &settings) {
+                Ok(mac) => {
+                    stream.write_all(&mac).await?;
+                    debug!("Adapted sample of face {} signed for uid={}", face_id, uid);
+                }
+                Err(AdaptationRefused::Integrity) => warn!(
+                    target: "audit",
+                    "Refused to sign an adapted sample of face {} for uid={}: the face failed \
+                     its integrity check",
+                    face_id,
+                    uid
+                ),
+                Err(AdaptationRefused::Policy(reason)) => info!(
+                    "Refused to sign an adapted sample of face {} for uid={}: {}",
+                    face_id, uid, reason
+                ),
+            }
+        }
+        other => debug!("Unknown record request {} from uid={}", other, uid),
+    }
+    Ok(())
+}
+
+/// Read the length-prefixed JSON of a request; `None` if it's oversized
+/// or malformed
+async fn read_json_request<T: serde::de::DeserializeOwned>(
+    stream: &mut UnixStream,
+    uid: u32,
+) -> std::io::Result<Option<T>> {
+    let mut len = [0u8; 4];
+    read_request(stream, &mut len).await?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_JSON_REQUEST {
+        debug!("Oversized record request from uid={}", uid);
+        return Ok(None);
+    }
+    let mut payload = vec![0u8; len];
+    read_request(stream, &mut payload).await?;
+    match serde_json::from_slice(&payload) {
+        Ok(request) => Ok(Some(request)),
+        Err(e) => {
+            debug!("Malformed record request from uid={}: {}", uid, e);
+            Ok(None)
+        }
+    }
+}
+
+/// Read exactly `buf.len()` bytes of a request, within [`CHECK_TIMEOUT`]
+async fn read_request(stream: &mut UnixStream, buf: &mut [u8]) -> std::io::Result<()> {
+    tokio::time::timeout(CHECK_TIMEOUT, stream.read_exact(buf))
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FaceStats;
+    use tempfile::TempDir;
+
+    fn face(contexts: &[&str], vector: Vec<f32>) -> (FaceRecord, FaceTemplate) {
+        let record = FaceRecord {
+            face_id: "face_1000_1".to_string(),
+            user_id: 1000,
+            quality_score: 0.9,
+            registered_at: 0,
+            context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: contexts.iter().map(|c| c.to_string()).collect(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
+        };
+        let template = FaceTemplate::from(Embedding {
+            vector,
+            metadata: hello_face_core::EmbeddingMetadata {
+                model: "fake".to_string(),
+                model_version: "test".to_string(),
+                extracted_at: 0,
+                quality_score: 0.9,
+            },
+        });
+        (record, template)
+    }
+
+    #[test]
+    fn test_digest_covers_owner_contexts_and_vectors_not_labels() {
+        let (record, template) = face(&["sudo"], vec![1.0, 0.0]);
+        let digest = record_digest(&record, &template);
+
+        let mut other_owner = record.clone();
+        other_owner.user_id = 1001;
+        assert_ne!(record_digest(&other_owner, &template), digest);
+
+        // What the template age is counted from
+        let mut registered_later = record.clone();
+        registered_later.registered_at += 1;
+        assert_ne!(record_digest(&registered_later, &template), digest);
+
+        let (any_context, _) = face(&[], vec![1.0, 0.0]);
+        assert_ne!(record_digest(&any_context, &template), digest);
+
+        let (_, other_vector) = face(&["sudo"], vec![0.0, 1.0]);
+        assert_ne!(record_digest(&record, &other_vector), digest);
+
+        let mut with_sample = template.clone();
+        with_sample.samples.push(template.centroid.clone());
+        assert_ne!(record_digest(&record, &with_sample), digest);
+
+        let mut relabeled = record.clone();
+        relabeled.label = Some("Glasses".to_string());
+        relabeled.notes = Some("Office".to_string());
+        assert_eq!(record_digest(&relabeled, &template), digest);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_macs_are_resigned_only_with_their_own_registration_time() {
+        let key = RecordKey::from_bytes([4; KEY_LEN]);
+        let auth = RecordAuthenticator::Local(key.clone());
+        let (mut record, template) = face(&[], vec![1.0, 0.0]);
+        record.face_id = "face_1000_1700000000".to_string();
+        record.registered_at = 1_700_000_000;
+        let legacy = key.mac(&legacy_record_digest(&record, &template));
+
+        let mac = auth.resign(&record, &template, &legacy).await.unwrap();
+        let mac = mac.expect("a legacy MAC is moved over");
+        assert!(auth
+            .check(&record_digest(&record, &template), Some(&mac))
+            .await
+            .unwrap());
+
+        // Reset since it was signed: the old MAC doesn't vouch for that
+        let mut reset = record.clone();
+        reset.registered_at = 1_800_000_000;
+        assert_eq!(auth.resign(&reset, &template, &legacy).await, Ok(None));
+        // Not a legacy MAC, or not of this face
+        assert_eq!(auth.resign(&record, &template, &mac).await, Ok(None));
+        let (_, other) = face(&[], vec![0.0, 1.0]);
+        assert_eq!(auth.resign(&record, &other, &legacy).await, Ok(None));
+        // No registration time to go by in its id
+        let mut renamed = record.clone();
+        renamed.face_id = "face_1000_imported".to_string();
+        let renamed_legacy = key.mac(&legacy_record_digest(&renamed, &template));
+        assert_eq!(
+            auth.resign(&renamed, &template, &renamed_legacy).await,
+            Ok(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_authenticator_signs_and_checks() {
+        let (record, template) = face(&[], vec![1.0, 0.0]);
+        let digest = record_digest(&record, &template);
+        let auth = RecordAuthenticator::Local(RecordKey::from_bytes([4; KEY_LEN]));
+
+        let mac = auth.sign(&digest).await.unwrap().unwrap();
+        assert!(auth.check(&digest, Some(&mac)).await.unwrap());
+        assert!(!auth.check(&digest, None).await.unwrap());
+        assert!(!auth.check(&[0; DIGEST_LEN], Some(&mac)).await.unwrap());
+        let other = RecordAuthenticator::Local(RecordKey::from_bytes([5; KEY_LEN]));
+        assert!(!other.check(&digest, Some(&mac)).await.unwrap());
+
+        // Nothing to check against without a key service
+        assert!(RecordAuthenticator::Disabled
+            .check(&digest, None)
+            .await
+            .unwrap());
+        assert_eq!(RecordAuthenticator::Disabled.sign(&digest).await, Ok(None));
+    }
+
+    #[test]
+    fn test_adapted_samples_are_signed_only_near_the_signed_enrollment() {
+        let key = RecordKey::from_bytes([4; KEY_LEN]);
+        let (record, template) = face(&[], vec![1.0, 0.0]);
+        let mac = key.mac(&record_digest(&record, &template)).to_vec();
+        let probe = |vector: Vec<f32>| face(&[], vector).1.centroid;
+        let request = |mac: &[u8], probe: Embedding| AdaptationRequest {
+            record: record.clone(),
+            template: template.clone(),
+            mac: mac.to_vec(),
+            context: "test".to_string(),
+            probe,
+        };
+        let settings = hello_config::Config::default();
+
+        // 0.95 similar: well past the "test" threshold and the drift cap
+        let near = probe(vec![0.95, 0.312]);
+        let sample_mac = sign_adaptation(&key, 1000, request(&mac, near.clone()), &settings)
+            .expect("a confident probe is signed");
+        let digest = adapted_digest(1000, &record.face_id, &template.centroid, &near);
+        assert!(key.verify(&digest, &sample_mac));
+        // ...bound to the face it was folded into
+        assert!(!key.verify(
+            &adapted_digest(1000, "face_1000_2", &template.centroid, &near),
+            &sample_mac
+        ));
+
+        // Not for a face that isn't as signed, or someone else's
+        assert!(matches!(
+            sign_adaptation(&key, 1000, request(&[0; MAC_LEN], near.clone()), &settings),
+            Err(AdaptationRefused::Integrity)
+        ));
+        assert!(matches!(
+            sign_adaptation(&key, 1001, request(&mac, near), &settings),
+            Err(AdaptationRefused::Integrity)
+        ));
+
+        // Nor past the drift cap (0.6 similar), whatever the daemon scored
+        assert!(matches!(
+            sign_adaptation(&key, 1000, request(&mac, probe(vec![0.6, 0.8])), &settings),
+            Err(AdaptationRefused::Policy(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_record_service_checks_but_signs_only_when_authorized() {
+        let temp = TempDir::new().unwrap();
+        let socket = temp.path().join("records.socket");
+        let master = Arc::new(MasterKey::load_or_create(&temp.path().join("storage.key")).unwrap());
+        start_record_server(&socket, Arc::clone(&master))
+            .await
+            .unwrap();
+
+        let uid = unsafe { libc::getuid() };
+        let auth = RecordAuthenticator::Service {
+            socket_path: socket.clone(),
+            server_uid: uid,
+        };
+        let digest = [7; DIGEST_LEN];
+        let mac = master.record_key(uid).mac(&digest);
+        assert!(auth.check(&digest, Some(&mac)).await.unwrap());
+        assert!(!auth.check(&[8; DIGEST_LEN], Some(&mac)).await.unwrap());
+
+        // No polkit authority to grant the signature here: fails closed
+        assert!(auth.sign(&digest).await.is_err());
+
+        // An adapted sample needs no polkit, only a face that checks out
+        let (mut record, template) = face(&[], vec![1.0, 0.0]);
+        record.user_id = uid;
+        let face_mac = master
+            .record_key(uid)
+            .mac(&record_digest(&record, &template));
+        let probe = template.centroid.clone();
+        let sample_mac = auth
+            .sign_adapted(&record, &template, Some(&face_mac), "test", &probe)
+            .await
+            .unwrap()
+            .unwrap();
+        let digest = adapted_digest(uid, &record.face_id, &template.centroid, &probe);
+        assert!(master.record_key(uid).verify(&digest, &sample_mac));
+        assert!(auth
+            .sign_adapted(&record, &template, Some(&[0; MAC_LEN]), "test", &probe)
+            .await
+            .is_err());
+
+        // Nor does moving a legacy MAC over to the current digest
+        record.face_id = format!("face_{}_{}", uid, record.registered_at);
+        let legacy = master
+            .record_key(uid)
+            .mac(&legacy_record_digest(&record, &template));
+        let resigned = auth.resign(&record, &template, &legacy).await.unwrap();
+        assert_eq!(
+            resigned,
+            Some(
+                master
+                    .record_key(uid)
+                    .mac(&record_digest(&record, &template))
+                    .to_vec()
+            )
+        );
+        assert_eq!(auth.resign(&record, &template, &face_mac).await, Ok(None));
+
+        // A server running as anyone but the expected uid isn't trusted
+        let impostor = RecordAuthenticator::Service {
+            socket_path: socket,
+            server_uid: uid.wrapping_add(1),
+        };
+        assert!(impostor.check(&digest, Some(&mac)).await.is_err());
+    }
+}
@@ -226,6 +226,23 @@ async fn try_face_unlock(
     session_id: &str,
     status: SharedScreenlockStatus,
 ) {
+    // Checked up front, not left to verify() below: no point waiting for
+    // the lock screen to render (or claiming the camera) just to be told no.
+    if !daemon
+        .read()
+        .await
+        .config()
+        .settings
+        .authentication
+        .enable_for_screenlock
+    {
+        info!("Face authentication is disabled for context=screenlock");
+        let mut s = status.lock().unwrap();
+        s.state = ScreenlockState::Idle;
+        s.message = "disabled".to_string();
+        return;
+    }
+
     // Give the lock screen time to fully render.
     tokio::time::sleep(Duration::from_millis(1200)).await;
 
@@ -10,12 +10,18 @@ use std::path::Path;
 /// directly from `/dev/urandom` (`read_exact`, not `fs::read` — the latter
 /// would block forever on a character device that never returns EOF).
 pub(crate) fn generate_token() -> String {
+    let buf: [u8; 32] = random_bytes();
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `N` bytes from `/dev/urandom` (keys, nonces, tokens)
+pub(crate) fn random_bytes<const N: usize>() -> [u8; N] {
     use std::io::Read;
-    let mut buf = [0u8; 32];
+    let mut buf = [0u8; N];
     std::fs::File::open("/dev/urandom")
         .and_then(|mut f| f.read_exact(&mut buf))
-        .expect("Unable to read /dev/urandom for a security token");
-    buf.iter().map(|b| format!("{:02x}", b)).collect()
+        .expect("Unable to read /dev/urandom");
+    buf
 }
 
 /// Compares two strings in time that doesn't depend on *where* they first
@@ -1,14 +1,179 @@
 //! Persistent storage management for face embeddings
 //!
-//! - SQLite for metadata (user_id, face_id, quality_score, registered_at)
-//! - JSON files for embeddings (for flexibility)
-
-use crate::security_util::write_owner_only_file;
+//! One SQLite database, `faces.db`, under the storage directory holds face
+//! records, their embeddings and per-face statistics; an enrollment is
+//! written in a single transaction, so a face is never listed without its
+//! embedding.
+//!
+//! The layout is versioned: `layout_version` next to the database names
+//! the [`LAYOUT_VERSION`] it was last written by, and [`FaceStorage::new`]
+//! brings older layouts up to date through [`MIGRATIONS`], after copying
+//! what's there to `snapshots/` — restored automatically if a migration
+//! fails, and deleted once it and sealing (below) have succeeded: it holds
+//! the faces as they were, unencrypted and including any deleted later. A
+//! layout newer than this daemon knows is refused with
+//! [`DaemonError::NewerStorageLayout`] rather than guessed at. Layout 0 is the pre-database one: a
+//! `<face_id>.meta.json` / `<face_id>.embedding.json` pair per face under
+//! `users/<uid>/`, imported by the first migration.
+//!
+//! Opened with a [`StorageKey`], embedding vectors are sealed before they
+//! are written and opened again on load (see `storage_key`); rows written
+//! while no key was available are sealed the next time one is.
+//!
+//! Every open for writing also checks the stored data is consistent: a face
+//! with no embedding, a row of a per-face table whose face is gone, or one
+//! half of a legacy JSON pair — left by a crash, a disk filling up, or a
+//! hand edit — is moved to `quarantine/` instead of failing every load for
+//! its user.
+
+use crate::matcher::FaceTemplate;
+use crate::storage_key::StorageKey;
 use crate::{DaemonError, FaceRecord};
-use hello_face_core::Embedding;
-use std::os::unix::fs::PermissionsExt;
+use hello_face_core::{Embedding, EmbeddingMetadata};
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Transaction};
+use std::collections::HashMap;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::{Path, PathBuf};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Database file name under the storage directory
+const DB_FILE: &str = "faces.db";
+
+/// File holding the layout version, as a decimal number
+const LAYOUT_FILE: &str = "layout_version";
+
+/// Where the pre-migration snapshot goes
+const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Where inconsistent data found on open is moved
+const QUARANTINE_DIR: &str = "quarantine";
+
+/// Tables holding one face's data, keyed by `face_id` and cascading from
+/// `faces`
+const FACE_TABLES: &[&str] = &[
+    "embeddings",
+    "embedding_samples",
+    "adapted_samples",
+    "face_stats",
+    "face_scores",
+    "face_contexts",
+];
+
+/// Layout version this daemon reads and writes; the last entry of
+/// [`MIGRATIONS`]
+pub const LAYOUT_VERSION: u32 = 9;
+
+/// One step of the storage layout history
+struct Migration {
+    /// Layout version once this step has run
+    to: u32,
+
+    /// What the step changes, for the log
+    description: &'static str,
+
+    /// Must be safe to run again on a layout it already (partly) upgraded:
+    /// the version file is only written once every step has succeeded, so
+    /// a daemon killed mid-migration starts over from the old version.
+    run: fn(&mut Connection, &MigrationContext) -> Result<(), DaemonError>,
+}
+
+struct MigrationContext<'a> {
+    base_path: &'a Path,
+
+    /// `false` when migrating an in-memory copy for a read-only open:
+    /// nothing on disk may change
+    writable: bool,
+}
+
+/// Every layout change, oldest first. Append only — a released step is
+/// never edited, since installs out there have already run it.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        to: 1,
+        description: "move per-face JSON files into faces.db",
+        run: migrate_json_to_database,
+    },
+    Migration {
+        to: 2,
+        description: "flag encrypted embeddings",
+        run: migrate_add_sealed_flag,
+    },
+    Migration {
+        to: 3,
+        description: "add face labels and notes",
+        run: migrate_add_face_labels,
+    },
+    Migration {
+        to: 4,
+        description: "restrict faces to contexts",
+        run: migrate_add_face_contexts,
+    },
+    Migration {
+        to: 5,
+        description: "keep each enrollment sample",
+        run: migrate_add_embedding_samples,
+    },
+    Migration {
+        to: 6,
+        description: "authenticate face records",
+        run: migrate_add_face_macs,
+    },
+    Migration {
+        to: 7,
+        description: "keep recent match scores",
+        run: migrate_add_face_scores,
+    },
+    Migration {
+        to: 8,
+        description: "keep adapted samples",
+        run: migrate_add_adapted_samples,
+    },
+    Migration {
+        to: 9,
+        description: "authenticate adapted samples",
+        run: migrate_add_adapted_macs,
+    },
+];
+
+/// Adapted sample MACs per face id, in sample order (`None`: unsigned)
+pub type AdaptedMacs = HashMap<String, Vec<Option<Vec<u8>>>>;
+
+/// How many of a face's most recent match scores [`FaceStats`] keeps
+pub const RECENT_SCORES: usize = 20;
+
+/// Fewest recent scores [`FaceStats::score_trend`] is computed from
+const MIN_TREND_SCORES: usize = 4;
+
+/// A [`FaceStats::score_trend`] this far below zero or further counts as
+/// scores drifting down
+pub const DECLINING_SCORE_TREND: f32 = -0.03;
+
+/// Tables as of layout 1, created by the first migration. Later changes
+/// are migrations of their own, never edits here.
+const SCHEMA_V1: &str = "
+    CREATE TABLE IF NOT EXISTS faces (
+        face_id TEXT PRIMARY KEY,
+        user_id INTEGER NOT NULL,
+        quality_score REAL NOT NULL,
+        registered_at INTEGER NOT NULL,
+        context TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS faces_by_user ON faces(user_id);
+    CREATE TABLE IF NOT EXISTS embeddings (
+        face_id TEXT PRIMARY KEY REFERENCES faces(face_id) ON DELETE CASCADE,
+        model TEXT NOT NULL,
+        model_version TEXT NOT NULL,
+        extracted_at INTEGER NOT NULL,
+        quality_score REAL NOT NULL,
+        vector BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS face_stats (
+        face_id TEXT PRIMARY KEY REFERENCES faces(face_id) ON DELETE CASCADE,
+        match_count INTEGER NOT NULL DEFAULT 0,
+        last_matched_at INTEGER
+    );
+";
 
 /// Restrict a directory to owner-only access (`0700`).
 ///
@@ -22,19 +187,94 @@ fn harden_dir(path: &Path) -> Result<(), DaemonError> {
         .map_err(|e| DaemonError::StorageError(format!("chmod 700 on {}: {}", path.display(), e)))
 }
 
+pub(crate) fn db_error(e: rusqlite::Error) -> DaemonError {
+    DaemonError::StorageError(format!("Database: {}", e))
+}
+
+/// Usage statistics kept per enrolled face
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FaceStats {
+    /// Successful verifications this face matched
+    pub match_count: u64,
+
+    /// When it last matched (Unix seconds)
+    pub last_matched_at: Option<u64>,
+
+    /// Similarity of its last [`RECENT_SCORES`] matches, oldest first
+    #[serde(default)]
+    pub recent_scores: Vec<f32>,
+
+    /// [`FaceStats::is_declining`] when loaded, for clients that only see
+    /// the JSON (the config GUI)
+    #[serde(default)]
+    pub declining: bool,
+
+    /// Samples adaptation has added to it (see `adaptive`)
+    #[serde(default)]
+    pub adapted_samples: usize,
+}
+
+impl FaceStats {
+    /// Mean of `recent_scores`, `None` without any
+    pub fn mean_score(&self) -> Option<f32> {
+        mean(&self.recent_scores)
+    }
+
+    /// Lowest of `recent_scores`
+    pub fn min_score(&self) -> Option<f32> {
+        self.recent_scores.iter().copied().reduce(f32::min)
+    }
+
+    /// How the newer half of `recent_scores` compares with the older half
+    /// (difference of their means): negative when the face matches less
+    /// and less well. `None` with fewer than a handful of scores.
+    pub fn score_trend(&self) -> Option<f32> {
+        if self.recent_scores.len() < MIN_TREND_SCORES {
+            return None;
+        }
+        let (older, newer) = self.recent_scores.split_at(self.recent_scores.len() / 2);
+        Some(mean(newer)? - mean(older)?)
+    }
+
+    /// Whether scores are drifting down enough that enrolling the face
+    /// again is worth it
+    pub fn is_declining(&self) -> bool {
+        self.score_trend()
+            .is_some_and(|trend| trend <= DECLINING_SCORE_TREND)
+    }
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+}
+
 /// Face storage manager
 pub struct FaceStorage {
-    /// Root storage directory
-    base_path: PathBuf,
+    /// Open database. `Connection` isn't `Sync`; the daemon shares one
+    /// `FaceStorage` across its D-Bus and PAM socket tasks.
+    conn: Mutex<Connection>,
+
+    /// Opened by [`FaceStorage::open_read_only`]: statistics aren't updated
+    read_only: bool,
 
-    /// Path to the SQLite DB
-    #[allow(dead_code)]
-    db_path: PathBuf,
+    /// Seals embedding vectors; without one they're stored in the clear
+    key: Option<StorageKey>,
 }
 
 impl FaceStorage {
-    /// Create a new storage manager
+    /// Create a new storage manager, creating the directory and database
+    /// if needed and importing any legacy per-face JSON files. Embeddings
+    /// are stored unencrypted — see [`FaceStorage::with_key`].
     pub fn new(base_path: impl AsRef<Path>) -> Result<Self, DaemonError> {
+        Self::with_key(base_path, None)
+    }
+
+    /// Same as [`FaceStorage::new`], sealing embeddings with `key` —
+    /// including any stored in the clear before.
+    pub fn with_key(
+        base_path: impl AsRef<Path>,
+        key: Option<StorageKey>,
+    ) -> Result<Self, DaemonError> {
         let base_path = base_path.as_ref().to_path_buf();
 
         // Create the directory structure
@@ -42,14 +282,68 @@ impl FaceStorage {
             .map_err(|e| DaemonError::StorageError(format!("Directory creation failed: {}", e)))?;
         harden_dir(&base_path)?;
 
-        let db_path = base_path.join("faces.db");
+        let from = read_layout_version(&base_path)?;
+        check_layout_supported(from)?;
+        let snapshot = if from < LAYOUT_VERSION && has_stored_data(&base_path) {
+            Some(take_snapshot(&base_path, from)?)
+        } else {
+            None
+        };
 
-        let storage = Self { base_path, db_path };
+        let db_path = base_path.join(DB_FILE);
+        create_owner_only_db_file(&db_path)?;
+        let mut conn = Connection::open(&db_path).map_err(db_error)?;
+        init_connection(&conn)?;
+
+        if from < LAYOUT_VERSION {
+            let context = MigrationContext {
+                base_path: &base_path,
+                writable: true,
+            };
+            let migrated = run_migrations(&mut conn, from, &context)
+                .and_then(|()| write_layout_version(&base_path, LAYOUT_VERSION));
+            if let Err(e) = migrated {
+                drop(conn);
+                if let Some(snapshot) = &snapshot {
+                    restore_snapshot(&base_path, snapshot)?;
+                    warn!("Storage migration failed, restored {}", snapshot.display());
+                }
+                return Err(e);
+            }
+            info!(
+                "Storage layout upgraded from version {} to {}",
+                from, LAYOUT_VERSION
+            );
+        }
 
-        // Initialize the DB if it doesn't exist
-        storage.init_db()?;
+        let quarantined = quarantine_inconsistent_data(&mut conn, &base_path)?;
+        if quarantined > 0 {
+            warn!(
+                "Moved {} inconsistent face record(s) or file(s) to {}",
+                quarantined,
+                base_path.join(QUARANTINE_DIR).display()
+            );
+        }
 
-        Ok(storage)
+        if let Some(key) = &key {
+            let sealed = seal_plaintext_rows(&mut conn, key)?;
+            if sealed > 0 {
+                // Rewrite the file so no page from before, in the clear,
+                // survives anywhere in it
+                conn.execute_batch("VACUUM").map_err(db_error)?;
+                info!("Encrypted {} previously unencrypted embedding(s)", sealed);
+            }
+        }
+        // Also catches one kept by a daemon from before snapshots were
+        // deleted
+        remove_snapshots(&base_path)?;
+        info!("Storage initialized at: {}", base_path.display());
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            read_only: false,
+            key,
+        })
     }
 
     /// Open an existing storage directory without creating anything.
@@ -60,55 +354,89 @@ impl FaceStorage {
     /// not-yet-authenticated user's home directory and must never create
     /// directories there as a side effect of a failed or in-progress login
     /// attempt.
-    pub fn open_read_only(base_path: impl AsRef<Path>) -> Result<Option<Self>, DaemonError> {
+    ///
+    /// The database is opened read-only and without following a symlink.
+    /// A layout older than [`LAYOUT_VERSION`] — the user's daemon hasn't
+    /// run since the upgrade — is copied into an in-memory database and
+    /// migrated there, leaving the files as they are. `key` opens sealed
+    /// embeddings.
+    pub fn open_read_only(
+        base_path: impl AsRef<Path>,
+        key: Option<StorageKey>,
+    ) -> Result<Option<Self>, DaemonError> {
         let base_path = base_path.as_ref().to_path_buf();
         if !base_path.is_dir() {
             return Ok(None);
         }
-        let db_path = base_path.join("faces.db");
-        Ok(Some(Self { base_path, db_path }))
-    }
-
-    /// Initialize the SQLite structure
-    fn init_db(&self) -> Result<(), DaemonError> {
-        // Create the embeddings directory
-        let embeddings_dir = self.base_path.join("embeddings");
-        std::fs::create_dir_all(&embeddings_dir)
-            .map_err(|e| DaemonError::StorageError(format!("Embeddings dir creation: {}", e)))?;
-        harden_dir(&embeddings_dir)?;
-
-        // For now we use JSON files
-        // Migration to SQLite will happen later with sqlx async
-        info!("Storage initialized at: {}", self.base_path.display());
-
-        Ok(())
+        let version = read_layout_version(&base_path)?;
+        check_layout_supported(version)?;
+
+        let db_path = base_path.join(DB_FILE);
+        let on_disk = if db_path.symlink_metadata().is_ok() {
+            Some(
+                Connection::open_with_flags(
+                    &db_path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | OpenFlags::SQLITE_OPEN_NOFOLLOW
+                        | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )
+                .map_err(db_error)?,
+            )
+        } else {
+            None
+        };
+        let conn = match on_disk {
+            Some(conn) if version == LAYOUT_VERSION => conn,
+            on_disk => {
+                let mut conn = Connection::open_in_memory().map_err(db_error)?;
+                if let Some(on_disk) = on_disk {
+                    rusqlite::backup::Backup::new(&on_disk, &mut conn)
+                        .and_then(|backup| {
+                            backup.run_to_completion(1024, std::time::Duration::ZERO, None)
+                        })
+                        .map_err(db_error)?;
+                }
+                init_connection(&conn)?;
+                let context = MigrationContext {
+                    base_path: &base_path,
+                    writable: false,
+                };
+                run_migrations(&mut conn, version, &context)?;
+                conn
+            }
+        };
+        Ok(Some(Self {
+            conn: Mutex::new(conn),
+            read_only: true,
+            key,
+        }))
     }
 
-    /// Save a newly registered face
+    /// Save a newly registered face with no individual samples
     pub fn save_face(&self, record: &FaceRecord, embedding: &Embedding) -> Result<(), DaemonError> {
-        // Check permissions of the user directory
-        let user_dir = self.user_dir(record.user_id)?;
-        std::fs::create_dir_all(&user_dir)
-            .map_err(|e| DaemonError::StorageError(format!("User dir creation: {}", e)))?;
-        harden_dir(&user_dir)?;
-
-        // Save metadata to a JSON file
-        let metadata_path = face_path(&user_dir, &record.face_id, ".meta.json")?;
-        let metadata_json =
-            serde_json::to_string_pretty(&record).map_err(DaemonError::JsonError)?;
-
-        write_owner_only_file(&metadata_path, &metadata_json).map_err(|e| {
-            DaemonError::StorageError(format!("write {}: {}", metadata_path.display(), e))
-        })?;
+        self.save_face_template(
+            record,
+            &FaceTemplate {
+                centroid: embedding.clone(),
+                samples: Vec::new(),
+                adapted: Vec::new(),
+            },
+        )
+    }
 
-        // Save the embedding
-        let embedding_path = face_path(&user_dir, &record.face_id, ".embedding.json")?;
-        let embedding_json =
-            serde_json::to_string_pretty(&embedding).map_err(DaemonError::JsonError)?;
+    /// Save a newly registered face: its averaged embedding and every
+    /// sample it was averaged from
+    pub fn save_face_template(
+        &self,
+        record: &FaceRecord,
+        template: &FaceTemplate,
+    ) -> Result<(), DaemonError> {
+        check_face_id(&record.face_id)?;
 
-        write_owner_only_file(&embedding_path, &embedding_json).map_err(|e| {
-            DaemonError::StorageError(format!("write {}: {}", embedding_path.display(), e))
-        })?;
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        insert_face(&tx, self.key.as_ref(), record, template)?;
+        tx.commit().map_err(db_error)?;
 
         debug!(
             "Face saved: user_id={}, face_id={}",
@@ -118,111 +446,223 @@ impl FaceStorage {
         Ok(())
     }
 
+    /// Save several faces in one transaction: all of them or none (used to
+    /// restore a backup). A face_id already enrolled for another user is
+    /// refused rather than taken over.
+    pub fn save_faces(&self, faces: &[(FaceRecord, FaceTemplate)]) -> Result<(), DaemonError> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        for (record, template) in faces {
+            check_face_id(&record.face_id)?;
+            let owner: Option<u32> = tx
+                .query_row(
+                    "SELECT user_id FROM faces WHERE face_id = ?1",
+                    [&record.face_id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db_error)?;
+            if owner.is_some_and(|owner| owner != record.user_id) {
+                return Err(DaemonError::AccessDenied(format!(
+                    "Face {} belongs to another user",
+                    record.face_id
+                )));
+            }
+            insert_face(&tx, self.key.as_ref(), record, template)?;
+        }
+        tx.commit().map_err(db_error)?;
+
+        debug!("{} face(s) saved", faces.len());
+
+        Ok(())
+    }
+
     /// Load an embedding by face_id
     pub fn load_face_embedding(
         &self,
         user_id: u32,
         face_id: &str,
     ) -> Result<Embedding, DaemonError> {
-        let user_dir = self.user_dir(user_id)?;
-        Self::load_face_embedding_from_dir(&user_dir, face_id)
+        let conn = self.conn.lock();
+        load_embedding(&conn, self.key.as_ref(), user_id, face_id)
     }
 
-    /// Load several embeddings for the same user in one call — resolves
-    /// (and `canonicalize`s) `user_dir` once up front instead of once per
-    /// `face_id`, unlike calling `load_face_embedding` in a loop. Used by
-    /// `verify_with_storage`, which otherwise re-resolved the identical
-    /// directory once per registered face on every single verify() attempt.
-    pub fn load_face_embeddings(
+    /// Load the averaged embedding, the samples and the adapted samples of
+    /// several faces of the same user, under one lock of the database. Used by
+    /// `verify_with_storage` on every verify() attempt.
+    pub fn load_face_templates(
         &self,
         user_id: u32,
         face_ids: impl IntoIterator<Item = impl AsRef<str>>,
-    ) -> Result<std::collections::HashMap<String, Embedding>, DaemonError> {
-        let user_dir = self.user_dir(user_id)?;
+    ) -> Result<HashMap<String, FaceTemplate>, DaemonError> {
+        let conn = self.conn.lock();
         face_ids
             .into_iter()
             .map(|face_id| {
                 let face_id = face_id.as_ref();
-                let embedding = Self::load_face_embedding_from_dir(&user_dir, face_id)?;
-                Ok((face_id.to_string(), embedding))
+                let centroid = load_embedding(&conn, self.key.as_ref(), user_id, face_id)?;
+                let samples = load_samples(&conn, self.key.as_ref(), user_id, face_id, &centroid)?;
+                let adapted =
+                    load_adapted_samples(&conn, self.key.as_ref(), user_id, face_id, &centroid)?;
+                Ok((
+                    face_id.to_string(),
+                    FaceTemplate {
+                        centroid,
+                        samples,
+                        adapted,
+                    },
+                ))
             })
             .collect()
     }
 
-    fn load_face_embedding_from_dir(
-        user_dir: &Path,
-        face_id: &str,
-    ) -> Result<Embedding, DaemonError> {
-        let embedding_path = face_path(user_dir, face_id, ".embedding.json")?;
-
-        let content = std::fs::read_to_string(&embedding_path)
-            .map_err(|e| DaemonError::StorageError(format!("Embedding read: {}", e)))?;
-
-        let embedding: hello_face_core::Embedding =
-            serde_json::from_str(&content).map_err(DaemonError::JsonError)?;
+    /// Store the MAC `record_auth` signed for a face's current contents.
+    /// Saving the face again clears it. A no-op on storage opened
+    /// read-only.
+    pub fn set_face_mac(&self, user_id: u32, face_id: &str, mac: &[u8]) -> Result<(), DaemonError> {
+        if self.read_only {
+            return Ok(());
+        }
+        check_face_id(face_id)?;
+        let updated = self
+            .conn
+            .lock()
+            .execute(
+                "UPDATE faces SET mac = ?3 WHERE user_id = ?1 AND face_id = ?2",
+                params![user_id, face_id, mac],
+            )
+            .map_err(db_error)?;
+        if updated == 0 {
+            return Err(DaemonError::FaceNotFound(face_id.to_string()));
+        }
+        Ok(())
+    }
 
-        Ok(embedding)
+    /// The MAC of every signed face of `user_id`; unsigned faces are absent
+    pub fn load_face_macs(&self, user_id: u32) -> Result<HashMap<String, Vec<u8>>, DaemonError> {
+        let conn = self.conn.lock();
+        let mut statement = conn
+            .prepare_cached("SELECT face_id, mac FROM faces WHERE user_id = ?1 AND mac IS NOT NULL")
+            .map_err(db_error)?;
+        let macs = statement
+            .query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_error)?
+            .collect::<Result<HashMap<_, _>, _>>()
+            .map_err(db_error)?;
+        Ok(macs)
     }
 
-    /// List all faces of a user
+    /// List all faces of a user, oldest enrollment first
     pub fn list_user_faces(&self, user_id: u32) -> Result<Vec<FaceRecord>, DaemonError> {
-        let user_dir = self.user_dir(user_id)?;
-
-        if !user_dir.exists() {
-            return Ok(Vec::new());
-        }
-
-        let mut faces = Vec::new();
-
-        for entry in std::fs::read_dir(&user_dir)
-            .map_err(|e| DaemonError::StorageError(format!("User dir read: {}", e)))?
+        let conn = self.conn.lock();
+        let mut statement = conn
+            .prepare_cached(
+                "SELECT face_id, user_id, quality_score, registered_at, context, label, notes
+                 FROM faces f
+                 WHERE user_id = ?1
+                     AND EXISTS (SELECT 1 FROM embeddings e WHERE e.face_id = f.face_id)
+                 ORDER BY registered_at, face_id",
+            )
+            .map_err(db_error)?;
+        let faces = statement
+            .query_map([user_id], |row| {
+                Ok(FaceRecord {
+                    face_id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    quality_score: row.get(2)?,
+                    registered_at: row.get(3)?,
+                    context: row.get(4)?,
+                    label: row.get(5)?,
+                    notes: row.get(6)?,
+                    allowed_contexts: Vec::new(),
+                    needs_reenrollment: false,
+                    expires_at: None,
+                    stats: FaceStats::default(),
+                })
+            })
+            .map_err(db_error)?
+            .collect::<Result<Vec<FaceRecord>, _>>()
+            .map_err(db_error)?;
+
+        let mut statement = conn
+            .prepare_cached(
+                "SELECT c.face_id, c.context
+                 FROM face_contexts c JOIN faces f ON f.face_id = c.face_id
+                 WHERE f.user_id = ?1 ORDER BY c.context",
+            )
+            .map_err(db_error)?;
+        let mut contexts: HashMap<String, Vec<String>> = HashMap::new();
+        for row in statement
+            .query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_error)?
         {
-            let entry =
-                entry.map_err(|e| DaemonError::StorageError(format!("Dir entry: {}", e)))?;
-            let path = entry.path();
-
-            // Look for .meta.json files
-            if path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .map(|n| n.ends_with(".meta.json"))
-                .unwrap_or(false)
-            {
-                let content = std::fs::read_to_string(&path)
-                    .map_err(|e| DaemonError::StorageError(format!("Meta read: {}", e)))?;
-
-                let record: FaceRecord =
-                    serde_json::from_str(&content).map_err(DaemonError::JsonError)?;
+            let (face_id, context) = row.map_err(db_error)?;
+            contexts.entry(face_id).or_default().push(context);
+        }
+        Ok(faces
+            .into_iter()
+            .map(|face| FaceRecord {
+                allowed_contexts: contexts.remove(&face.face_id).unwrap_or_default(),
+                ..face
+            })
+            .collect())
+    }
 
-                faces.push(record);
-            }
+    /// Change a face's label, notes and allowed contexts; `None` leaves
+    /// that field as it is, an empty string clears a label or notes, and
+    /// no contexts lets the face authenticate any context.
+    pub fn update_face_metadata(
+        &self,
+        user_id: u32,
+        face_id: &str,
+        label: Option<&str>,
+        notes: Option<&str>,
+        allowed_contexts: Option<&[String]>,
+    ) -> Result<(), DaemonError> {
+        check_face_id(face_id)?;
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        // NULL keeps the column (COALESCE), '' clears it (NULLIF)
+        let updated = tx
+            .execute(
+                "UPDATE faces SET label = NULLIF(COALESCE(?3, label), ''),
+                     notes = NULLIF(COALESCE(?4, notes), '')
+                 WHERE user_id = ?1 AND face_id = ?2",
+                params![user_id, face_id, label, notes],
+            )
+            .map_err(db_error)?;
+        if updated == 0 {
+            return Err(DaemonError::FaceNotFound(face_id.to_string()));
         }
+        if let Some(contexts) = allowed_contexts {
+            write_face_contexts(&tx, face_id, contexts)?;
+        }
+        tx.commit().map_err(db_error)?;
+
+        debug!(
+            "Face metadata updated: user_id={}, face_id={}",
+            user_id, face_id
+        );
 
-        Ok(faces)
+        Ok(())
     }
 
-    /// Delete a face
+    /// Delete a face. Its embedding and statistics go with it.
     pub fn delete_face(&self, user_id: u32, face_id: &str) -> Result<(), DaemonError> {
         // face_id is attacker-controlled (it comes straight from the D-Bus
-        // DeleteFace request body) and, unlike user_id (a plain u32), needs
-        // validating before being joined onto a path — user_dir() guards
-        // against user_id escaping base_path, but a face_id like
-        // "../../../../etc/cron.d/x" would still escape *this* user's own
-        // directory once appended below. face_path() does that check.
-        let user_dir = self.user_dir(user_id)?;
-
-        let meta_path = face_path(&user_dir, face_id, ".meta.json")?;
-        let emb_path = face_path(&user_dir, face_id, ".embedding.json")?;
-
-        if meta_path.exists() {
-            std::fs::remove_file(&meta_path)
-                .map_err(|e| DaemonError::StorageError(format!("Meta deletion: {}", e)))?;
-        }
-
-        if emb_path.exists() {
-            std::fs::remove_file(&emb_path)
-                .map_err(|e| DaemonError::StorageError(format!("Embedding deletion: {}", e)))?;
-        }
+        // DeleteFace request body). The query is parameterized, but the
+        // same narrow format check as at enrollment keeps anything that
+        // could never have been stored from reaching the database at all.
+        check_face_id(face_id)?;
+
+        self.conn
+            .lock()
+            .execute(
+                "DELETE FROM faces WHERE user_id = ?1 AND face_id = ?2",
+                params![user_id, face_id],
+            )
+            .map_err(db_error)?;
 
         debug!("Face deleted: user_id={}, face_id={}", user_id, face_id);
 
@@ -231,184 +671,1549 @@ impl FaceStorage {
 
     /// Delete all faces of a user
     pub fn delete_all_faces(&self, user_id: u32) -> Result<(), DaemonError> {
-        let user_dir = self.user_dir(user_id)?;
-
-        if user_dir.exists() {
-            std::fs::remove_dir_all(&user_dir)
-                .map_err(|e| DaemonError::StorageError(format!("User dir deletion: {}", e)))?;
-        }
+        self.conn
+            .lock()
+            .execute("DELETE FROM faces WHERE user_id = ?1", [user_id])
+            .map_err(db_error)?;
 
         debug!("All faces deleted for user_id={}", user_id);
 
         Ok(())
     }
 
-    /// Get the user's directory
-    fn user_dir(&self, user_id: u32) -> Result<PathBuf, DaemonError> {
-        let user_dir = self.base_path.join(format!("users/{}", user_id));
+    /// Count a successful verification against `face_id` with similarity
+    /// `score`, keeping the last [`RECENT_SCORES`] scores. A no-op on
+    /// storage opened read-only.
+    pub fn record_match(
+        &self,
+        user_id: u32,
+        face_id: &str,
+        at: u64,
+        score: f32,
+    ) -> Result<(), DaemonError> {
+        if self.read_only {
+            return Ok(());
+        }
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        let updated = tx
+            .execute(
+                "UPDATE face_stats SET match_count = match_count + 1, last_matched_at = ?3
+                 WHERE face_id = ?2
+                   AND face_id IN (SELECT face_id FROM faces WHERE user_id = ?1)",
+                params![user_id, face_id, at],
+            )
+            .map_err(db_error)?;
+        if updated > 0 {
+            tx.execute(
+                "INSERT INTO face_scores (face_id, matched_at, score) VALUES (?1, ?2, ?3)",
+                params![face_id, at, score],
+            )
+            .map_err(db_error)?;
+            tx.execute(
+                "DELETE FROM face_scores WHERE face_id = ?1 AND rowid NOT IN
+                   (SELECT rowid FROM face_scores WHERE face_id = ?1
+                    ORDER BY rowid DESC LIMIT ?2)",
+                params![face_id, RECENT_SCORES as i64],
+            )
+            .map_err(db_error)?;
+        }
+        tx.commit().map_err(db_error)
+    }
 
-        // Verify we don't escape base_path (security)
-        // Use a simpler approach: check that the normalized path starts with base_path
-        let normalized_user = user_dir.canonicalize().ok();
-        let normalized_base = self.base_path.canonicalize().ok();
+    /// Statistics for each of `user_id`'s faces, by face ID
+    pub fn load_face_stats(&self, user_id: u32) -> Result<HashMap<String, FaceStats>, DaemonError> {
+        let conn = self.conn.lock();
+        let mut statement = conn
+            .prepare(
+                "SELECT s.face_id, s.match_count, s.last_matched_at
+                 FROM face_stats s JOIN faces f ON f.face_id = s.face_id
+                 WHERE f.user_id = ?1",
+            )
+            .map_err(db_error)?;
+        let mut stats = HashMap::new();
+        for row in statement
+            .query_map([user_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    FaceStats {
+                        match_count: row.get(1)?,
+                        last_matched_at: row.get(2)?,
+                        recent_scores: Vec::new(),
+                        declining: false,
+                        adapted_samples: 0,
+                    },
+                ))
+            })
+            .map_err(db_error)?
+        {
+            let (face_id, mut face_stats) = row.map_err(db_error)?;
+            face_stats.recent_scores = load_recent_scores(&conn, &face_id)?;
+            face_stats.declining = face_stats.is_declining();
+            face_stats.adapted_samples = count_adapted_samples(&conn, &face_id)?;
+            stats.insert(face_id, face_stats);
+        }
+        Ok(stats)
+    }
 
-        match (normalized_user, normalized_base) {
-            (Some(user), Some(base)) => {
-                if !user.starts_with(&base) {
-                    return Err(DaemonError::AccessDenied(format!(
-                        "Path traversal attempt for user_id={}",
-                        user_id
-                    )));
-                }
-            }
-            _ => {
-                // If the path doesn't exist yet, do a simple check
-                // Verify that "../" is not in the path
-                let user_str = user_dir.to_string_lossy();
-                if user_str.contains("..") {
-                    return Err(DaemonError::AccessDenied(format!(
-                        "Path traversal attempt for user_id={}",
-                        user_id
-                    )));
-                }
-            }
+    /// Statistics for one face, `None` if the user has no such face
+    pub fn face_stats(
+        &self,
+        user_id: u32,
+        face_id: &str,
+    ) -> Result<Option<FaceStats>, DaemonError> {
+        let conn = self.conn.lock();
+        let stats = conn
+            .query_row(
+                "SELECT s.match_count, s.last_matched_at
+                 FROM face_stats s JOIN faces f ON f.face_id = s.face_id
+                 WHERE f.user_id = ?1 AND f.face_id = ?2",
+                params![user_id, face_id],
+                |row| {
+                    Ok(FaceStats {
+                        match_count: row.get(0)?,
+                        last_matched_at: row.get(1)?,
+                        recent_scores: Vec::new(),
+                        declining: false,
+                        adapted_samples: 0,
+                    })
+                },
+            )
+            .optional()
+            .map_err(db_error)?;
+        stats
+            .map(|mut stats| {
+                stats.recent_scores = load_recent_scores(&conn, face_id)?;
+                stats.declining = stats.is_declining();
+                stats.adapted_samples = count_adapted_samples(&conn, face_id)?;
+                Ok(stats)
+            })
+            .transpose()
+    }
+
+    /// Add `sample` to `face_id`'s adapted samples, added at `at` with the
+    /// `mac` `record_auth` signed for it, and drop the oldest beyond
+    /// `keep`. A no-op on storage opened read-only.
+    pub fn add_adapted_sample(
+        &self,
+        user_id: u32,
+        face_id: &str,
+        sample: &Embedding,
+        mac: Option<&[u8]>,
+        at: u64,
+        keep: usize,
+    ) -> Result<(), DaemonError> {
+        if self.read_only {
+            return Ok(());
+        }
+        check_face_id(face_id)?;
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(db_error)?;
+        let owned: bool = tx
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM faces WHERE user_id = ?1 AND face_id = ?2",
+                params![user_id, face_id],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        if !owned {
+            return Err(DaemonError::FaceNotFound(face_id.to_string()));
         }
+        let seq: u64 = tx
+            .query_row(
+                "SELECT COALESCE(MAX(seq) + 1, 0) FROM adapted_samples WHERE face_id = ?1",
+                [face_id],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        let mut vector = vector_to_blob(&sample.vector);
+        if let Some(key) = &self.key {
+            vector = key.seal(&vector, &adapted_aad(user_id, face_id, seq));
+        }
+        tx.execute(
+            "INSERT INTO adapted_samples
+                 (face_id, seq, added_at, extracted_at, quality_score, vector, sealed, mac)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                face_id,
+                seq,
+                at,
+                sample.metadata.extracted_at,
+                sample.metadata.quality_score,
+                vector,
+                self.key.is_some(),
+                mac
+            ],
+        )
+        .map_err(db_error)?;
+        tx.execute(
+            "DELETE FROM adapted_samples WHERE face_id = ?1 AND seq <= ?2",
+            params![face_id, seq as i64 - keep as i64],
+        )
+        .map_err(db_error)?;
+        tx.commit().map_err(db_error)
+    }
+
+    /// The MACs of every adapted sample of `user_id`'s faces, per face in
+    /// the order [`FaceStorage::load_face_templates`] returns the samples;
+    /// `None` for one stored without
+    pub fn load_adapted_macs(&self, user_id: u32) -> Result<AdaptedMacs, DaemonError> {
+        let conn = self.conn.lock();
+        let mut statement = conn
+            .prepare_cached(
+                "SELECT a.face_id, a.mac FROM adapted_samples a
+                 JOIN faces f ON f.face_id = a.face_id
+                 WHERE f.user_id = ?1 ORDER BY a.face_id, a.seq",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([user_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<Vec<u8>>>(1)?))
+            })
+            .map_err(db_error)?;
+        let mut macs = AdaptedMacs::new();
+        for row in rows {
+            let (face_id, mac) = row.map_err(db_error)?;
+            macs.entry(face_id).or_default().push(mac);
+        }
+        Ok(macs)
+    }
+
+    /// When a sample was last added to `face_id`'s adapted samples, `None`
+    /// if it has none
+    pub fn last_adapted_at(&self, user_id: u32, face_id: &str) -> Result<Option<u64>, DaemonError> {
+        self.conn
+            .lock()
+            .query_row(
+                "SELECT MAX(a.added_at) FROM adapted_samples a
+                 JOIN faces f ON f.face_id = a.face_id
+                 WHERE f.user_id = ?1 AND f.face_id = ?2",
+                params![user_id, face_id],
+                |row| row.get(0),
+            )
+            .map_err(db_error)
+    }
 
-        Ok(user_dir)
+    /// Drop the adapted samples of `face_id`, or of all of `user_id`'s
+    /// faces, going back to the templates as enrolled. Returns how many
+    /// were dropped.
+    pub fn reset_adapted_samples(
+        &self,
+        user_id: u32,
+        face_id: Option<&str>,
+    ) -> Result<usize, DaemonError> {
+        if let Some(face_id) = face_id {
+            check_face_id(face_id)?;
+        }
+        self.conn
+            .lock()
+            .execute(
+                "DELETE FROM adapted_samples WHERE face_id IN
+                   (SELECT face_id FROM faces WHERE user_id = ?1 AND (?2 IS NULL OR face_id = ?2))",
+                params![user_id, face_id],
+            )
+            .map_err(db_error)
     }
 }
 
-/// Whether `face_id` is safe to join onto a filesystem path. Real face_ids
-/// are always `face_<uid>_<timestamp>` (see `register_face`), so this is
-/// deliberately narrow — alphanumeric, `_`, and `-` only, non-empty — rather
-/// than trying to blocklist `/`/`..`/etc, which is easy to get wrong.
-fn is_safe_face_id(face_id: &str) -> bool {
-    !face_id.is_empty()
-        && face_id
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+/// `face_id`'s kept match scores, oldest first
+fn load_recent_scores(conn: &Connection, face_id: &str) -> Result<Vec<f32>, DaemonError> {
+    let mut statement = conn
+        .prepare("SELECT score FROM face_scores WHERE face_id = ?1 ORDER BY rowid")
+        .map_err(db_error)?;
+    let scores = statement
+        .query_map([face_id], |row| row.get::<_, f64>(0))
+        .map_err(db_error)?
+        .map(|score| score.map(|score| score as f32))
+        .collect::<Result<_, _>>()
+        .map_err(db_error)?;
+    Ok(scores)
 }
 
-/// Joins `face_id` onto `user_dir` with the given filename suffix (e.g.
-/// `.meta.json`), rejecting an unsafe `face_id` — the shared path-joining
-/// layer every method that touches a face's files goes through, so the
-/// `is_safe_face_id` check can't be missed by a future caller the way it
-/// almost was here: only `delete_face` validated its (D-Bus-attacker-
-/// controlled) `face_id` before this, while `save_face`/
-/// `load_face_embedding_from_dir` built the identical kind of path with no
-/// check of their own, safe only because their current callers happen to
-/// always supply a machine-generated `face_id`.
-fn face_path(user_dir: &Path, face_id: &str, suffix: &str) -> Result<PathBuf, DaemonError> {
-    if !is_safe_face_id(face_id) {
-        return Err(DaemonError::AccessDenied(format!(
-            "Invalid face_id: {}",
-            face_id
-        )));
-    }
-    Ok(user_dir.join(format!("{}{}", face_id, suffix)))
+/// How many adapted samples `face_id` has
+fn count_adapted_samples(conn: &Connection, face_id: &str) -> Result<usize, DaemonError> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM adapted_samples WHERE face_id = ?1",
+        [face_id],
+        |row| row.get(0),
+    )
+    .map_err(db_error)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+/// Create `path` at mode `0600` if it doesn't exist, and reset the mode if
+/// it does — SQLite would otherwise create it per the umask. Its rollback
+/// journal copies the database file's mode.
+pub(crate) fn create_owner_only_db_file(path: &Path) -> Result<(), DaemonError> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path)
+        .and_then(|_| std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)))
+        .map_err(|e| DaemonError::StorageError(format!("create {}: {}", path.display(), e)))
+}
 
-    #[test]
-    fn test_storage_init() {
-        let temp = TempDir::new().unwrap();
-        let _ = FaceStorage::new(temp.path()).unwrap();
+/// Per-connection settings
+fn init_connection(conn: &Connection) -> Result<(), DaemonError> {
+    conn.pragma_update(None, "foreign_keys", true)
+        .map_err(db_error)?;
+    // Deleted and overwritten rows (a face deleted, a vector sealed in
+    // place) are zeroed instead of left readable in free pages
+    conn.pragma_update(None, "secure_delete", true)
+        .map_err(db_error)
+}
 
-        assert!(temp.path().join("embeddings").exists());
+/// The layout version at `base_path`. Without a version file, that's 1 if
+/// there's a database (written before layouts were versioned) and 0
+/// otherwise — the JSON layout, or nothing at all yet.
+///
+/// The SDDM listener reads this as root from a user's home, so the file is
+/// opened without following a symlink or blocking on a FIFO, must be a
+/// regular file owned by whoever owns `base_path`, and only its first few
+/// bytes are read.
+fn read_layout_version(base_path: &Path) -> Result<u32, DaemonError> {
+    use std::io::Read;
+    use std::os::unix::fs::MetadataExt;
+
+    let path = base_path.join(LAYOUT_FILE);
+    let error =
+        |e: &dyn std::fmt::Display| DaemonError::StorageError(format!("{}: {}", path.display(), e));
+    let file = match std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(&path)
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(u32::from(
+                base_path.join(DB_FILE).symlink_metadata().is_ok(),
+            ))
+        }
+        Err(e) if e.raw_os_error() == Some(libc::ELOOP) => return Err(error(&"is a symlink")),
+        Err(e) => return Err(error(&e)),
+    };
+    let metadata = file.metadata().map_err(|e| error(&e))?;
+    if !metadata.is_file() {
+        return Err(error(&"not a regular file"));
+    }
+    let owner = base_path.metadata().map_err(|e| error(&e))?.uid();
+    if metadata.uid() != owner {
+        return Err(error(&format!(
+            "owned by UID {} instead of {}",
+            metadata.uid(),
+            owner
+        )));
     }
+    let mut contents = String::new();
+    file.take(16)
+        .read_to_string(&mut contents)
+        .map_err(|e| error(&e))?;
+    contents
+        .trim()
+        .parse()
+        .map_err(|_| error(&format!("not a layout version: {:?}", contents.trim())))
+}
 
-    #[test]
-    fn test_save_and_load_face() {
-        let temp = TempDir::new().unwrap();
-        let storage = FaceStorage::new(temp.path()).unwrap();
+fn check_layout_supported(version: u32) -> Result<(), DaemonError> {
+    if version > LAYOUT_VERSION {
+        return Err(DaemonError::NewerStorageLayout {
+            found: version,
+            supported: LAYOUT_VERSION,
+        });
+    }
+    Ok(())
+}
 
-        let record = FaceRecord {
-            face_id: "test_face_1".to_string(),
-            user_id: 1000,
-            quality_score: 0.95,
-            registered_at: 0,
-            context: "test".to_string(),
-        };
+/// Replace the version file in one rename, so it's never seen half-written
+fn write_layout_version(base_path: &Path, version: u32) -> Result<(), DaemonError> {
+    use std::io::Write;
+
+    let path = base_path.join(LAYOUT_FILE);
+    let temp = base_path.join(format!("{}.tmp", LAYOUT_FILE));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp)
+        .and_then(|mut file| {
+            writeln!(file, "{}", version)?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&temp, &path))
+        .map_err(|e| DaemonError::StorageError(format!("write {}: {}", path.display(), e)))
+}
 
-        let embedding = Embedding {
-            vector: vec![0.1, 0.2, 0.3],
-            metadata: hello_face_core::EmbeddingMetadata {
-                model: "test".to_string(),
-                model_version: "0.1.0".to_string(),
-                extracted_at: 0,
-                quality_score: 0.95,
-            },
-        };
+/// Run every migration past `from`, in order
+fn run_migrations(
+    conn: &mut Connection,
+    from: u32,
+    context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    for migration in MIGRATIONS.iter().filter(|m| m.to > from) {
+        debug!(
+            "Storage migration to layout {}: {}",
+            migration.to, migration.description
+        );
+        (migration.run)(conn, context).map_err(|e| {
+            DaemonError::StorageError(format!(
+                "Migration to layout {} ({}) failed: {}",
+                migration.to, migration.description, e
+            ))
+        })?;
+    }
+    Ok(())
+}
 
-        storage.save_face(&record, &embedding).unwrap();
+fn migrate_json_to_database(
+    conn: &mut Connection,
+    context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    conn.execute_batch(SCHEMA_V1).map_err(db_error)?;
+    let imported = import_legacy_json(conn, context.base_path, context.writable)?;
+    if imported > 0 {
+        info!(
+            "Imported {} face(s) from the legacy JSON files into {}",
+            imported, DB_FILE
+        );
+    }
+    Ok(())
+}
 
-        let loaded = storage.load_face_embedding(1000, "test_face_1").unwrap();
-        assert_eq!(loaded.vector.len(), 3);
+fn migrate_add_sealed_flag(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    // Databases written just before layouts were versioned have it already
+    let has_sealed: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('embeddings') WHERE name = 'sealed'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_error)?;
+    if !has_sealed {
+        conn.execute_batch("ALTER TABLE embeddings ADD COLUMN sealed INTEGER NOT NULL DEFAULT 0")
+            .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+fn migrate_add_face_labels(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    for column in ["label", "notes"] {
+        let exists: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('faces') WHERE name = ?1",
+                [column],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        if !exists {
+            conn.execute_batch(&format!("ALTER TABLE faces ADD COLUMN {} TEXT", column))
+                .map_err(db_error)?;
+        }
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_list_faces() {
-        let temp = TempDir::new().unwrap();
-        let storage = FaceStorage::new(temp.path()).unwrap();
+fn migrate_add_face_contexts(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    // No rows for a face: it may authenticate any context
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS face_contexts (
+            face_id TEXT NOT NULL REFERENCES faces(face_id) ON DELETE CASCADE,
+            context TEXT NOT NULL,
+            PRIMARY KEY (face_id, context)
+        )",
+    )
+    .map_err(db_error)
+}
 
-        let record1 = FaceRecord {
-            face_id: "face_1".to_string(),
-            user_id: 1000,
-            quality_score: 0.95,
-            registered_at: 0,
-            context: "test".to_string(),
-        };
+fn migrate_add_embedding_samples(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    // Model and version are the face's, in `embeddings`. Faces enrolled
+    // before this have no samples and are matched on their average alone.
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS embedding_samples (
+            face_id TEXT NOT NULL REFERENCES faces(face_id) ON DELETE CASCADE,
+            sample_index INTEGER NOT NULL,
+            extracted_at INTEGER NOT NULL,
+            quality_score REAL NOT NULL,
+            vector BLOB NOT NULL,
+            sealed INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (face_id, sample_index)
+        )",
+    )
+    .map_err(db_error)
+}
 
-        let record2 = FaceRecord {
-            face_id: "face_2".to_string(),
-            user_id: 1000,
-            quality_score: 0.92,
-            registered_at: 0,
-            context: "test".to_string(),
-        };
+fn migrate_add_face_macs(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    // Existing faces stay without one: only an enrollment change the user
+    // re-authenticates for may sign a record (see `record_auth`)
+    let exists: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('faces') WHERE name = 'mac'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_error)?;
+    if !exists {
+        conn.execute_batch("ALTER TABLE faces ADD COLUMN mac BLOB")
+            .map_err(db_error)?;
+    }
+    Ok(())
+}
 
-        let embedding = Embedding {
-            vector: vec![0.1, 0.2, 0.3],
-            metadata: hello_face_core::EmbeddingMetadata {
-                model: "test".to_string(),
-                model_version: "0.1.0".to_string(),
-                extracted_at: 0,
-                quality_score: 0.95,
-            },
-        };
+fn migrate_add_face_scores(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS face_scores (
+            face_id TEXT NOT NULL REFERENCES faces(face_id) ON DELETE CASCADE,
+            matched_at INTEGER NOT NULL,
+            score REAL NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS face_scores_by_face ON face_scores(face_id);",
+    )
+    .map_err(db_error)
+}
 
-        storage.save_face(&record1, &embedding).unwrap();
-        storage.save_face(&record2, &embedding).unwrap();
+fn migrate_add_adapted_samples(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS adapted_samples (
+            face_id TEXT NOT NULL REFERENCES faces(face_id) ON DELETE CASCADE,
+            seq INTEGER NOT NULL,
+            added_at INTEGER NOT NULL,
+            extracted_at INTEGER NOT NULL,
+            quality_score REAL NOT NULL,
+            vector BLOB NOT NULL,
+            sealed INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (face_id, seq)
+        )",
+    )
+    .map_err(db_error)
+}
 
-        let faces = storage.list_user_faces(1000).unwrap();
-        assert_eq!(faces.len(), 2);
+fn migrate_add_adapted_macs(
+    conn: &mut Connection,
+    _context: &MigrationContext,
+) -> Result<(), DaemonError> {
+    // Samples adapted so far stay without one, and unused: only
+    // `hello-storage-key` may sign one, as it is folded in
+    let exists: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('adapted_samples') WHERE name = 'mac'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_error)?;
+    if !exists {
+        conn.execute_batch("ALTER TABLE adapted_samples ADD COLUMN mac BLOB")
+            .map_err(db_error)?;
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_open_read_only_missing_dir_returns_none_and_creates_nothing() {
-        // This is the property the SDDM system listener depends on: checking
-        // an arbitrary, not-yet-authenticated user's storage must never
-        // create directories in their home as a side effect.
-        let temp = TempDir::new().unwrap();
-        let missing = temp.path().join("never-enrolled-user");
-
-        let result = FaceStorage::open_read_only(&missing).unwrap();
+fn has_stored_data(base_path: &Path) -> bool {
+    base_path.join(DB_FILE).symlink_metadata().is_ok() || base_path.join("users").is_dir()
+}
 
-        assert!(result.is_none());
-        assert!(
-            !missing.exists(),
-            "open_read_only must not create the directory"
-        );
+/// The files a migration may touch: the database with its journal, and the
+/// legacy JSON tree
+const SNAPSHOT_ENTRIES: &[&str] = &[DB_FILE, "faces.db-journal", "users"];
+
+/// Copy what's stored under `base_path` to `snapshots/layout-v<from>/`,
+/// replacing any older snapshot — they hold biometric data too, so only
+/// the one taken before the latest migration is kept. Returns its path.
+fn take_snapshot(base_path: &Path, from: u32) -> Result<PathBuf, DaemonError> {
+    let snapshots = base_path.join(SNAPSHOT_DIR);
+    if snapshots.symlink_metadata().is_ok() {
+        std::fs::remove_dir_all(&snapshots).map_err(|e| {
+            DaemonError::StorageError(format!("remove {}: {}", snapshots.display(), e))
+        })?;
     }
+    let snapshot = snapshots.join(format!("layout-v{}", from));
+    std::fs::create_dir_all(&snapshot)
+        .map_err(|e| DaemonError::StorageError(format!("create {}: {}", snapshot.display(), e)))?;
+    harden_dir(&snapshots)?;
+    harden_dir(&snapshot)?;
+    for entry in SNAPSHOT_ENTRIES {
+        copy_tree(&base_path.join(entry), &snapshot.join(entry))?;
+    }
+    info!("Storage snapshot before migrating: {}", snapshot.display());
+    Ok(snapshot)
+}
 
-    #[test]
-    fn test_open_read_only_existing_dir_can_list_faces() {
-        let temp = TempDir::new().unwrap();
-        // Set up with the side-effecting constructor once, as enrollment
+/// Move whatever can't belong to a complete face to `quarantine/`:
+///
+/// - a face with no embedding (with whatever else it has),
+/// - rows of a per-face table whose face doesn't exist,
+/// - a legacy `.meta.json` without its `.embedding.json`, or the reverse.
+///
+/// Database rows are written out as JSON (one file per face, synced before
+/// the rows are deleted, in one transaction), legacy files are renamed.
+/// Nothing is deleted outright: the quarantine is for a human to look at.
+/// Returns how many faces and files were moved.
+fn quarantine_inconsistent_data(
+    conn: &mut Connection,
+    base_path: &Path,
+) -> Result<usize, DaemonError> {
+    let quarantine = base_path.join(QUARANTINE_DIR);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let tx = conn.transaction().map_err(db_error)?;
+    let mut orphans: Vec<(String, &str)> = Vec::new();
+    {
+        let mut statement = tx
+            .prepare(
+                "SELECT face_id FROM faces f
+                 WHERE NOT EXISTS (SELECT 1 FROM embeddings e WHERE e.face_id = f.face_id)",
+            )
+            .map_err(db_error)?;
+        for face_id in statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(db_error)?
+        {
+            orphans.push((face_id.map_err(db_error)?, "face without an embedding"));
+        }
+        for table in FACE_TABLES {
+            let mut statement = tx
+                .prepare(&format!(
+                    "SELECT DISTINCT face_id FROM {} t
+                     WHERE NOT EXISTS (SELECT 1 FROM faces f WHERE f.face_id = t.face_id)",
+                    table
+                ))
+                .map_err(db_error)?;
+            for face_id in statement
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(db_error)?
+            {
+                let face_id = face_id.map_err(db_error)?;
+                if !orphans.iter().any(|(id, _)| *id == face_id) {
+                    orphans.push((face_id, "rows of a face that doesn't exist"));
+                }
+            }
+        }
+    }
+
+    for (index, (face_id, reason)) in orphans.iter().enumerate() {
+        let mut rows = serde_json::Map::new();
+        for table in std::iter::once(&"faces").chain(FACE_TABLES) {
+            rows.insert(table.to_string(), dump_face_rows(&tx, table, face_id)?);
+        }
+        let dump = serde_json::json!({
+            "face_id": face_id,
+            "reason": reason,
+            "quarantined_at": now,
+            "rows": rows,
+        });
+        let contents = serde_json::to_vec_pretty(&dump)
+            .map_err(|e| DaemonError::StorageError(e.to_string()))?;
+        write_quarantined(
+            &quarantine,
+            &format!("db-{}-{}.json", now, index),
+            &contents,
+        )?;
+        warn!("Quarantined face {}: {}", face_id, reason);
+
+        for table in FACE_TABLES.iter().chain(std::iter::once(&"faces")) {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE face_id = ?1", table),
+                [face_id],
+            )
+            .map_err(db_error)?;
+        }
+    }
+    tx.commit().map_err(db_error)?;
+
+    let moved_files = quarantine_legacy_halves(base_path, &quarantine)?;
+    Ok(orphans.len() + moved_files)
+}
+
+/// `table`'s rows for `face_id`, as JSON objects (blobs hex-encoded)
+fn dump_face_rows(
+    tx: &Transaction<'_>,
+    table: &str,
+    face_id: &str,
+) -> Result<serde_json::Value, DaemonError> {
+    use rusqlite::types::ValueRef;
+
+    let mut statement = tx
+        .prepare(&format!("SELECT * FROM {} WHERE face_id = ?1", table))
+        .map_err(db_error)?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let mut rows = statement.query([face_id]).map_err(db_error)?;
+    let mut dumped = Vec::new();
+    while let Some(row) = rows.next().map_err(db_error)? {
+        let mut object = serde_json::Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            let value = match row.get_ref(i).map_err(db_error)? {
+                ValueRef::Null => serde_json::Value::Null,
+                ValueRef::Integer(n) => n.into(),
+                ValueRef::Real(x) => x.into(),
+                ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+                ValueRef::Blob(bytes) => bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>()
+                    .into(),
+            };
+            object.insert(column.clone(), value);
+        }
+        dumped.push(serde_json::Value::Object(object));
+    }
+    Ok(dumped.into())
+}
+
+/// Move unpaired legacy JSON files from `users/<uid>/` to
+/// `quarantine/legacy-<uid>-<file>`; complete pairs the import refused
+/// (see `import_legacy_json`) stay where they are
+fn quarantine_legacy_halves(base_path: &Path, quarantine: &Path) -> Result<usize, DaemonError> {
+    const HALVES: [(&str, &str); 2] = [
+        (".meta.json", ".embedding.json"),
+        (".embedding.json", ".meta.json"),
+    ];
+
+    let Ok(user_dirs) = std::fs::read_dir(base_path.join("users")) else {
+        return Ok(0);
+    };
+    let mut moved = 0;
+    for user_entry in user_dirs.flatten() {
+        let user_path = user_entry.path();
+        let Some(uid) = user_entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let Ok(entries) = std::fs::read_dir(&user_path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            let unpaired = HALVES.iter().any(|(suffix, other)| {
+                name.strip_suffix(suffix)
+                    .is_some_and(|face_id| !user_path.join(format!("{face_id}{other}")).exists())
+            });
+            if !unpaired {
+                continue;
+            }
+            std::fs::create_dir_all(quarantine).map_err(|e| {
+                DaemonError::StorageError(format!("create {}: {}", quarantine.display(), e))
+            })?;
+            harden_dir(quarantine)?;
+            let target = quarantine.join(format!("legacy-{}-{}", uid, name));
+            std::fs::rename(entry.path(), &target).map_err(|e| {
+                DaemonError::StorageError(format!("move {}: {}", entry.path().display(), e))
+            })?;
+            warn!(
+                "Quarantined {}: the other half of the face is missing",
+                entry.path().display()
+            );
+            moved += 1;
+        }
+    }
+    Ok(moved)
+}
+
+/// Write `contents` to `dir/name` durably: temporary file, fsync, rename,
+/// fsync of the directory
+fn write_quarantined(dir: &Path, name: &str, contents: &[u8]) -> Result<(), DaemonError> {
+    use std::io::Write;
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| DaemonError::StorageError(format!("create {}: {}", dir.display(), e)))?;
+    harden_dir(dir)?;
+    let path = dir.join(name);
+    let temp = dir.join(format!("{}.tmp", name));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&temp, &path))
+        .and_then(|()| std::fs::File::open(dir)?.sync_all())
+        .map_err(|e| DaemonError::StorageError(format!("write {}: {}", path.display(), e)))
+}
+
+/// Delete `snapshots/`, once what it could restore has been migrated
+fn remove_snapshots(base_path: &Path) -> Result<(), DaemonError> {
+    let snapshots = base_path.join(SNAPSHOT_DIR);
+    if snapshots.symlink_metadata().is_err() {
+        return Ok(());
+    }
+    std::fs::remove_dir_all(&snapshots)
+        .map_err(|e| DaemonError::StorageError(format!("remove {}: {}", snapshots.display(), e)))?;
+    info!("Pre-migration snapshot deleted: {}", snapshots.display());
+    Ok(())
+}
+
+/// Put a snapshot's files back in place of the (partly) migrated ones
+fn restore_snapshot(base_path: &Path, snapshot: &Path) -> Result<(), DaemonError> {
+    for entry in SNAPSHOT_ENTRIES {
+        let target = base_path.join(entry);
+        let removed = match target.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&target),
+            Ok(_) => std::fs::remove_file(&target),
+            Err(_) => Ok(()),
+        };
+        removed.map_err(|e| {
+            DaemonError::StorageError(format!("remove {}: {}", target.display(), e))
+        })?;
+        copy_tree(&snapshot.join(entry), &target)?;
+    }
+    Ok(())
+}
+
+/// Copy a file or directory tree, skipping symlinks; nothing if `from`
+/// doesn't exist
+fn copy_tree(from: &Path, to: &Path) -> Result<(), DaemonError> {
+    let io = |e: std::io::Error| {
+        DaemonError::StorageError(format!(
+            "copy {} to {}: {}",
+            from.display(),
+            to.display(),
+            e
+        ))
+    };
+    let Ok(metadata) = from.symlink_metadata() else {
+        return Ok(());
+    };
+    if metadata.is_file() {
+        std::fs::copy(from, to).map_err(io)?;
+    } else if metadata.is_dir() {
+        std::fs::create_dir_all(to).map_err(io)?;
+        harden_dir(to)?;
+        for entry in std::fs::read_dir(from).map_err(io)?.flatten() {
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Associated data a face's sealed vector is bound to
+fn seal_aad(user_id: u32, face_id: &str) -> Vec<u8> {
+    format!("{}:{}", user_id, face_id).into_bytes()
+}
+
+/// Associated data one enrollment sample's sealed vector is bound to
+fn sample_aad(user_id: u32, face_id: &str, index: usize) -> Vec<u8> {
+    format!("{}:{}:sample{}", user_id, face_id, index).into_bytes()
+}
+
+/// Associated data one adapted sample's sealed vector is bound to
+fn adapted_aad(user_id: u32, face_id: &str, seq: u64) -> Vec<u8> {
+    format!("{}:{}:adapted{}", user_id, face_id, seq).into_bytes()
+}
+
+/// Seal every vector still stored in the clear, in one transaction.
+/// Returns how many were sealed.
+fn seal_plaintext_rows(conn: &mut Connection, key: &StorageKey) -> Result<usize, DaemonError> {
+    let tx = conn.transaction().map_err(db_error)?;
+    let rows = {
+        let mut statement = tx
+            .prepare(
+                "SELECT f.user_id, f.face_id, e.vector
+                 FROM embeddings e JOIN faces f ON f.face_id = e.face_id
+                 WHERE e.sealed = 0",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Vec<u8>>(2)?,
+                ))
+            })
+            .map_err(db_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+        rows
+    };
+    for (user_id, face_id, vector) in &rows {
+        tx.execute(
+            "UPDATE embeddings SET vector = ?2, sealed = 1 WHERE face_id = ?1",
+            params![face_id, key.seal(vector, &seal_aad(*user_id, face_id))],
+        )
+        .map_err(db_error)?;
+    }
+    let samples = {
+        let mut statement = tx
+            .prepare(
+                "SELECT f.user_id, f.face_id, s.sample_index, s.vector
+                 FROM embedding_samples s JOIN faces f ON f.face_id = s.face_id
+                 WHERE s.sealed = 0",
+            )
+            .map_err(db_error)?;
+        let samples = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, usize>(2)?,
+                    row.get::<_, Vec<u8>>(3)?,
+                ))
+            })
+            .map_err(db_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+        samples
+    };
+    for (user_id, face_id, index, vector) in &samples {
+        tx.execute(
+            "UPDATE embedding_samples SET vector = ?3, sealed = 1
+             WHERE face_id = ?1 AND sample_index = ?2",
+            params![
+                face_id,
+                index,
+                key.seal(vector, &sample_aad(*user_id, face_id, *index))
+            ],
+        )
+        .map_err(db_error)?;
+    }
+    let adapted = {
+        let mut statement = tx
+            .prepare(
+                "SELECT f.user_id, f.face_id, a.seq, a.vector
+                 FROM adapted_samples a JOIN faces f ON f.face_id = a.face_id
+                 WHERE a.sealed = 0",
+            )
+            .map_err(db_error)?;
+        let adapted = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, Vec<u8>>(3)?,
+                ))
+            })
+            .map_err(db_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+        adapted
+    };
+    for (user_id, face_id, seq, vector) in &adapted {
+        tx.execute(
+            "UPDATE adapted_samples SET vector = ?3, sealed = 1 WHERE face_id = ?1 AND seq = ?2",
+            params![
+                face_id,
+                seq,
+                key.seal(vector, &adapted_aad(*user_id, face_id, *seq))
+            ],
+        )
+        .map_err(db_error)?;
+    }
+    tx.commit().map_err(db_error)?;
+    Ok(rows.len() + samples.len() + adapted.len())
+}
+
+/// Replace the contexts `face_id` is restricted to
+fn write_face_contexts(
+    tx: &Transaction<'_>,
+    face_id: &str,
+    contexts: &[String],
+) -> Result<(), DaemonError> {
+    tx.execute("DELETE FROM face_contexts WHERE face_id = ?1", [face_id])
+        .map_err(db_error)?;
+    for context in contexts {
+        tx.execute(
+            "INSERT OR IGNORE INTO face_contexts (face_id, context) VALUES (?1, ?2)",
+            params![face_id, context],
+        )
+        .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+/// Insert or replace one face and its embedding inside `tx`. Statistics of
+/// a face saved again under the same id are kept.
+fn insert_face(
+    tx: &Transaction<'_>,
+    key: Option<&StorageKey>,
+    record: &FaceRecord,
+    template: &FaceTemplate,
+) -> Result<(), DaemonError> {
+    let embedding = &template.centroid;
+    let mut vector = vector_to_blob(&embedding.vector);
+    if let Some(key) = key {
+        vector = key.seal(&vector, &seal_aad(record.user_id, &record.face_id));
+    }
+    tx.execute(
+        "INSERT INTO faces (face_id, user_id, quality_score, registered_at, context, label, notes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(face_id) DO UPDATE SET user_id = ?2, quality_score = ?3,
+             registered_at = ?4, context = ?5, label = ?6, notes = ?7, mac = NULL",
+        params![
+            record.face_id,
+            record.user_id,
+            record.quality_score,
+            record.registered_at,
+            record.context,
+            record.label,
+            record.notes
+        ],
+    )
+    .map_err(db_error)?;
+    tx.execute(
+        "INSERT OR REPLACE INTO embeddings
+             (face_id, model, model_version, extracted_at, quality_score, vector, sealed)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            record.face_id,
+            embedding.metadata.model,
+            embedding.metadata.model_version,
+            embedding.metadata.extracted_at,
+            embedding.metadata.quality_score,
+            vector,
+            key.is_some()
+        ],
+    )
+    .map_err(db_error)?;
+    tx.execute(
+        "INSERT OR IGNORE INTO face_stats (face_id) VALUES (?1)",
+        [&record.face_id],
+    )
+    .map_err(db_error)?;
+    write_face_contexts(tx, &record.face_id, &record.allowed_contexts)?;
+    tx.execute(
+        "DELETE FROM embedding_samples WHERE face_id = ?1",
+        [&record.face_id],
+    )
+    .map_err(db_error)?;
+    // What was adapted to the old template doesn't belong to a new one
+    tx.execute(
+        "DELETE FROM adapted_samples WHERE face_id = ?1",
+        [&record.face_id],
+    )
+    .map_err(db_error)?;
+    for (index, sample) in template.samples.iter().enumerate() {
+        let mut vector = vector_to_blob(&sample.vector);
+        if let Some(key) = key {
+            vector = key.seal(&vector, &sample_aad(record.user_id, &record.face_id, index));
+        }
+        tx.execute(
+            "INSERT INTO embedding_samples
+                 (face_id, sample_index, extracted_at, quality_score, vector, sealed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                record.face_id,
+                index,
+                sample.metadata.extracted_at,
+                sample.metadata.quality_score,
+                vector,
+                key.is_some()
+            ],
+        )
+        .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+fn load_embedding(
+    conn: &Connection,
+    key: Option<&StorageKey>,
+    user_id: u32,
+    face_id: &str,
+) -> Result<Embedding, DaemonError> {
+    check_face_id(face_id)?;
+    let row = conn
+        .prepare_cached(
+            "SELECT e.model, e.model_version, e.extracted_at, e.quality_score, e.vector,
+                    e.sealed
+             FROM embeddings e JOIN faces f ON f.face_id = e.face_id
+             WHERE f.user_id = ?1 AND f.face_id = ?2",
+        )
+        .and_then(|mut statement| {
+            statement
+                .query_row(params![user_id, face_id], |row| {
+                    Ok((
+                        EmbeddingMetadata {
+                            model: row.get(0)?,
+                            model_version: row.get(1)?,
+                            extracted_at: row.get(2)?,
+                            quality_score: row.get(3)?,
+                        },
+                        row.get::<_, Vec<u8>>(4)?,
+                        row.get::<_, bool>(5)?,
+                    ))
+                })
+                .optional()
+        })
+        .map_err(db_error)?;
+    let (metadata, blob, sealed) = row.ok_or_else(|| {
+        DaemonError::StorageError(format!("No embedding stored for face {}", face_id))
+    })?;
+    Ok(Embedding {
+        vector: open_vector(
+            key,
+            blob,
+            sealed,
+            &seal_aad(user_id, face_id),
+            &format!("face {}", face_id),
+        )?,
+        metadata,
+    })
+}
+
+/// The samples stored for `face_id`, in enrollment order; they share the
+/// model of its averaged embedding `centroid`
+fn load_samples(
+    conn: &Connection,
+    key: Option<&StorageKey>,
+    user_id: u32,
+    face_id: &str,
+    centroid: &Embedding,
+) -> Result<Vec<Embedding>, DaemonError> {
+    let mut statement = conn
+        .prepare_cached(
+            "SELECT s.sample_index, s.extracted_at, s.quality_score, s.vector, s.sealed
+             FROM embedding_samples s JOIN faces f ON f.face_id = s.face_id
+             WHERE f.user_id = ?1 AND f.face_id = ?2 ORDER BY s.sample_index",
+        )
+        .map_err(db_error)?;
+    let rows = statement
+        .query_map(params![user_id, face_id], |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, f32>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })
+        .map_err(db_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_error)?;
+    rows.into_iter()
+        .map(|(index, extracted_at, quality_score, blob, sealed)| {
+            Ok(Embedding {
+                vector: open_vector(
+                    key,
+                    blob,
+                    sealed,
+                    &sample_aad(user_id, face_id, index),
+                    &format!("sample {} of face {}", index, face_id),
+                )?,
+                metadata: EmbeddingMetadata {
+                    extracted_at,
+                    quality_score,
+                    ..centroid.metadata.clone()
+                },
+            })
+        })
+        .collect()
+}
+
+/// The adapted samples stored for `face_id`, oldest first; like its
+/// enrollment samples, they share the model of `centroid`
+fn load_adapted_samples(
+    conn: &Connection,
+    key: Option<&StorageKey>,
+    user_id: u32,
+    face_id: &str,
+    centroid: &Embedding,
+) -> Result<Vec<Embedding>, DaemonError> {
+    let mut statement = conn
+        .prepare_cached(
+            "SELECT a.seq, a.extracted_at, a.quality_score, a.vector, a.sealed
+             FROM adapted_samples a JOIN faces f ON f.face_id = a.face_id
+             WHERE f.user_id = ?1 AND f.face_id = ?2 ORDER BY a.seq",
+        )
+        .map_err(db_error)?;
+    let rows = statement
+        .query_map(params![user_id, face_id], |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, f32>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })
+        .map_err(db_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_error)?;
+    rows.into_iter()
+        .map(|(seq, extracted_at, quality_score, blob, sealed)| {
+            Ok(Embedding {
+                vector: open_vector(
+                    key,
+                    blob,
+                    sealed,
+                    &adapted_aad(user_id, face_id, seq),
+                    &format!("adapted sample {} of face {}", seq, face_id),
+                )?,
+                metadata: EmbeddingMetadata {
+                    extracted_at,
+                    quality_score,
+                    ..centroid.metadata.clone()
+                },
+            })
+        })
+        .collect()
+}
+
+/// Decrypt a stored vector if it's sealed, and decode it; `what` names it
+/// in errors
+fn open_vector(
+    key: Option<&StorageKey>,
+    mut blob: Vec<u8>,
+    sealed: bool,
+    aad: &[u8],
+    what: &str,
+) -> Result<Vec<f32>, DaemonError> {
+    if sealed {
+        let key = key.ok_or_else(|| {
+            DaemonError::StorageError(format!(
+                "Embedding for {} is encrypted and no storage key is available",
+                what
+            ))
+        })?;
+        blob = key.open(&blob, aad).ok_or_else(|| {
+            DaemonError::StorageError(format!(
+                "Embedding for {} could not be decrypted (wrong key or altered)",
+                what
+            ))
+        })?;
+    }
+    blob_to_vector(&blob)
+        .ok_or_else(|| DaemonError::StorageError(format!("Corrupt embedding for {}", what)))
+}
+
+/// Embedding vectors are stored as little-endian `f32`s
+fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn blob_to_vector(blob: &[u8]) -> Option<Vec<f32>> {
+    if !blob.len().is_multiple_of(4) {
+        return None;
+    }
+    Some(
+        blob.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
+/// Import the pre-database layout — `users/<uid>/<face_id>.meta.json` plus
+/// `<face_id>.embedding.json` — into `conn`, in one transaction. Returns how
+/// many faces were imported.
+///
+/// A pair is only imported whole: a metadata file without its embedding
+/// (or one that doesn't parse, or whose `user_id` isn't the directory it
+/// sits in) is skipped with a warning and left on disk. With
+/// `remove_imported`, the imported files are deleted once the transaction
+/// has committed, and user directories left empty go with them; that's
+/// what makes the import one-time.
+fn import_legacy_json(
+    conn: &mut Connection,
+    base_path: &Path,
+    remove_imported: bool,
+) -> Result<usize, DaemonError> {
+    let users_dir = base_path.join("users");
+    let Ok(user_dirs) = std::fs::read_dir(&users_dir) else {
+        return Ok(0);
+    };
+
+    let tx = conn.transaction().map_err(db_error)?;
+    let mut imported_files = Vec::new();
+    let mut user_paths = Vec::new();
+    let mut count = 0;
+
+    for user_entry in user_dirs.flatten() {
+        let user_path = user_entry.path();
+        let Some(dir_uid) = user_entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(entries) = std::fs::read_dir(&user_path) else {
+            continue;
+        };
+        user_paths.push(user_path.clone());
+
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(face_id) = name.to_str().and_then(|n| n.strip_suffix(".meta.json")) else {
+                continue;
+            };
+            let meta_path = entry.path();
+            let embedding_path = user_path.join(format!("{}.embedding.json", face_id));
+            match read_legacy_pair(face_id, dir_uid, &meta_path, &embedding_path) {
+                Ok((record, embedding)) => {
+                    insert_legacy_face(&tx, &record, &embedding)?;
+                    imported_files.push(meta_path);
+                    imported_files.push(embedding_path);
+                    count += 1;
+                }
+                Err(reason) => warn!(
+                    "Legacy face file {} not imported: {}",
+                    meta_path.display(),
+                    reason
+                ),
+            }
+        }
+    }
+
+    tx.commit().map_err(db_error)?;
+
+    if remove_imported {
+        for path in &imported_files {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("Could not remove imported {}: {}", path.display(), e);
+            }
+        }
+        // Only succeeds on directories that are now empty
+        for path in &user_paths {
+            let _ = std::fs::remove_dir(path);
+        }
+        let _ = std::fs::remove_dir(&users_dir);
+    }
+
+    Ok(count)
+}
+
+/// Insert one imported face with layout 1's columns — the migration
+/// mustn't depend on how later layouts write faces
+fn insert_legacy_face(
+    tx: &Transaction<'_>,
+    record: &FaceRecord,
+    embedding: &Embedding,
+) -> Result<(), DaemonError> {
+    tx.execute(
+        "INSERT OR REPLACE INTO faces (face_id, user_id, quality_score, registered_at, context)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            record.face_id,
+            record.user_id,
+            record.quality_score,
+            record.registered_at,
+            record.context
+        ],
+    )
+    .map_err(db_error)?;
+    tx.execute(
+        "INSERT OR REPLACE INTO embeddings
+             (face_id, model, model_version, extracted_at, quality_score, vector)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            record.face_id,
+            embedding.metadata.model,
+            embedding.metadata.model_version,
+            embedding.metadata.extracted_at,
+            embedding.metadata.quality_score,
+            vector_to_blob(&embedding.vector)
+        ],
+    )
+    .map_err(db_error)?;
+    tx.execute(
+        "INSERT OR IGNORE INTO face_stats (face_id) VALUES (?1)",
+        [&record.face_id],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+fn read_legacy_pair(
+    face_id: &str,
+    dir_uid: u32,
+    meta_path: &Path,
+    embedding_path: &Path,
+) -> Result<(FaceRecord, Embedding), String> {
+    if !is_safe_face_id(face_id) {
+        return Err("invalid face_id".to_string());
+    }
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    let record: FaceRecord = serde_json::from_str(&read(meta_path)?).map_err(|e| e.to_string())?;
+    let embedding: Embedding =
+        serde_json::from_str(&read(embedding_path)?).map_err(|e| e.to_string())?;
+    if record.face_id != face_id || record.user_id != dir_uid {
+        return Err(format!(
+            "record is for face {} of user {}",
+            record.face_id, record.user_id
+        ));
+    }
+    Ok((record, embedding))
+}
+
+/// Whether `face_id` is well-formed. Real face_ids are always
+/// `face_<uid>_<timestamp>` (see `register_face`), so this is deliberately
+/// narrow — alphanumeric, `_`, and `-` only, non-empty. They still end up in
+/// file names (the legacy layout, debug dumps), so the check stays even
+/// though the database itself doesn't need it.
+fn is_safe_face_id(face_id: &str) -> bool {
+    !face_id.is_empty()
+        && face_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Rejects an unsafe `face_id` — every method that takes one goes through
+/// this, so the check can't be missed by a future caller.
+fn check_face_id(face_id: &str) -> Result<(), DaemonError> {
+    if !is_safe_face_id(face_id) {
+        return Err(DaemonError::AccessDenied(format!(
+            "Invalid face_id: {}",
+            face_id
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_storage_init() {
+        let temp = TempDir::new().unwrap();
+        let _ = FaceStorage::new(temp.path()).unwrap();
+
+        assert!(temp.path().join(DB_FILE).exists());
+    }
+
+    #[test]
+    fn test_save_and_load_face() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+
+        let record = FaceRecord {
+            face_id: "test_face_1".to_string(),
+            user_id: 1000,
+            quality_score: 0.95,
+            registered_at: 0,
+            context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
+        };
+
+        let embedding = Embedding {
+            vector: vec![0.1, 0.2, 0.3],
+            metadata: hello_face_core::EmbeddingMetadata {
+                model: "test".to_string(),
+                model_version: "0.1.0".to_string(),
+                extracted_at: 0,
+                quality_score: 0.95,
+            },
+        };
+
+        storage.save_face(&record, &embedding).unwrap();
+
+        let loaded = storage.load_face_embedding(1000, "test_face_1").unwrap();
+        assert_eq!(loaded.vector.len(), 3);
+    }
+
+    #[test]
+    fn test_list_faces() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+
+        let record1 = FaceRecord {
+            face_id: "face_1".to_string(),
+            user_id: 1000,
+            quality_score: 0.95,
+            registered_at: 0,
+            context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
+        };
+
+        let record2 = FaceRecord {
+            face_id: "face_2".to_string(),
+            user_id: 1000,
+            quality_score: 0.92,
+            registered_at: 0,
+            context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
+        };
+
+        let embedding = Embedding {
+            vector: vec![0.1, 0.2, 0.3],
+            metadata: hello_face_core::EmbeddingMetadata {
+                model: "test".to_string(),
+                model_version: "0.1.0".to_string(),
+                extracted_at: 0,
+                quality_score: 0.95,
+            },
+        };
+
+        storage.save_face(&record1, &embedding).unwrap();
+        storage.save_face(&record2, &embedding).unwrap();
+
+        let faces = storage.list_user_faces(1000).unwrap();
+        assert_eq!(faces.len(), 2);
+    }
+
+    #[test]
+    fn test_open_read_only_missing_dir_returns_none_and_creates_nothing() {
+        // This is the property the SDDM system listener depends on: checking
+        // an arbitrary, not-yet-authenticated user's storage must never
+        // create directories in their home as a side effect.
+        let temp = TempDir::new().unwrap();
+        let missing = temp.path().join("never-enrolled-user");
+
+        let result = FaceStorage::open_read_only(&missing, None).unwrap();
+
+        assert!(result.is_none());
+        assert!(
+            !missing.exists(),
+            "open_read_only must not create the directory"
+        );
+    }
+
+    #[test]
+    fn test_open_read_only_existing_dir_can_list_faces() {
+        let temp = TempDir::new().unwrap();
+        // Set up with the side-effecting constructor once, as enrollment
         // would have already done.
         let storage = FaceStorage::new(temp.path()).unwrap();
         let record = FaceRecord {
@@ -417,6 +2222,12 @@ mod tests {
             quality_score: 0.9,
             registered_at: 0,
             context: "sddm".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
         };
         let embedding = Embedding {
             vector: vec![0.1, 0.2],
@@ -431,7 +2242,7 @@ mod tests {
         drop(storage);
 
         // Now re-open read-only, as the system listener would per-request.
-        let reopened = FaceStorage::open_read_only(temp.path())
+        let reopened = FaceStorage::open_read_only(temp.path(), None)
             .unwrap()
             .expect("directory exists, should return Some");
         let faces = reopened.list_user_faces(1000).unwrap();
@@ -461,6 +2272,12 @@ mod tests {
             quality_score: 0.95,
             registered_at: 0,
             context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
         };
         let embedding = Embedding {
             vector: vec![0.1, 0.2, 0.3],
@@ -523,6 +2340,12 @@ mod tests {
             quality_score: 0.95,
             registered_at: 0,
             context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
         };
         let embedding = Embedding {
             vector: vec![0.1, 0.2, 0.3],
@@ -542,7 +2365,7 @@ mod tests {
 
     /// Regression test: biometric data must never be left readable by other
     /// local users, regardless of the process umask. Without the explicit
-    /// `harden_dir`/`write_owner_only_file` calls, these would end up at
+    /// `harden_dir`/`create_owner_only_db_file` calls, these would end up at
     /// whatever the umask allows (0755/0644 under systemd's default 0022).
     #[test]
     fn test_save_face_restricts_directory_and_file_permissions() {
@@ -555,6 +2378,12 @@ mod tests {
             quality_score: 0.95,
             registered_at: 0,
             context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
         };
         let embedding = Embedding {
             vector: vec![0.1, 0.2, 0.3],
@@ -570,23 +2399,1010 @@ mod tests {
         let mode = |p: &std::path::Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
 
         assert_eq!(mode(temp.path()), 0o700, "base storage dir");
-        assert_eq!(
-            mode(&temp.path().join("embeddings")),
-            0o700,
-            "embeddings dir"
-        );
+        assert_eq!(mode(&temp.path().join(DB_FILE)), 0o600, "database file");
+    }
 
-        let user_dir = temp.path().join("users/1000");
-        assert_eq!(mode(&user_dir), 0o700, "per-user dir");
-        assert_eq!(
-            mode(&user_dir.join("face_1000_1735036800.meta.json")),
-            0o600,
-            "metadata file"
-        );
-        assert_eq!(
-            mode(&user_dir.join("face_1000_1735036800.embedding.json")),
-            0o600,
-            "embedding file"
-        );
+    #[test]
+    fn test_existing_database_file_is_reset_to_owner_only() {
+        let temp = TempDir::new().unwrap();
+        let db_path = temp.path().join(DB_FILE);
+        drop(FaceStorage::new(temp.path()).unwrap());
+        std::fs::set_permissions(&db_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let _ = FaceStorage::new(temp.path()).unwrap();
+
+        let mode = std::fs::metadata(&db_path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    fn record(face_id: &str, user_id: u32) -> FaceRecord {
+        FaceRecord {
+            face_id: face_id.to_string(),
+            user_id,
+            quality_score: 0.9,
+            registered_at: 1_735_036_800,
+            context: "test".to_string(),
+            label: None,
+            notes: None,
+            allowed_contexts: Vec::new(),
+            needs_reenrollment: false,
+            expires_at: None,
+            stats: FaceStats::default(),
+        }
+    }
+
+    fn embedding(vector: &[f32]) -> Embedding {
+        Embedding {
+            vector: vector.to_vec(),
+            metadata: hello_face_core::EmbeddingMetadata {
+                model: "test".to_string(),
+                model_version: "0.1.0".to_string(),
+                extracted_at: 42,
+                quality_score: 0.9,
+            },
+        }
+    }
+
+    /// Lay out one face the way the pre-database storage did
+    fn write_legacy_face(base: &Path, record: &FaceRecord, embedding: &Embedding) -> PathBuf {
+        let user_dir = base.join(format!("users/{}", record.user_id));
+        std::fs::create_dir_all(&user_dir).unwrap();
+        std::fs::write(
+            user_dir.join(format!("{}.meta.json", record.face_id)),
+            serde_json::to_string_pretty(record).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            user_dir.join(format!("{}.embedding.json", record.face_id)),
+            serde_json::to_string_pretty(embedding).unwrap(),
+        )
+        .unwrap();
+        user_dir
+    }
+
+    #[test]
+    fn test_faces_and_embeddings_survive_reopening() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.25, -0.5, 1.0]))
+            .unwrap();
+        drop(storage);
+
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        let loaded = storage.load_face_embedding(1000, "face_1").unwrap();
+        assert_eq!(loaded.vector, vec![0.25, -0.5, 1.0]);
+        assert_eq!(loaded.metadata.model, "test");
+        assert_eq!(loaded.metadata.extracted_at, 42);
+        assert_eq!(storage.list_user_faces(1000).unwrap()[0].context, "test");
+    }
+
+    #[test]
+    fn test_faces_are_scoped_to_their_user() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_a", 1000), &embedding(&[0.1]))
+            .unwrap();
+        storage
+            .save_face(&record("face_b", 1001), &embedding(&[0.2]))
+            .unwrap();
+
+        assert_eq!(storage.list_user_faces(1000).unwrap().len(), 1);
+        assert!(storage.load_face_embedding(1000, "face_b").is_err());
+
+        // Neither deleting someone else's face id nor all of one user's
+        // faces touches the other user
+        storage.delete_face(1000, "face_b").unwrap();
+        storage.delete_all_faces(1000).unwrap();
+        assert!(storage.list_user_faces(1000).unwrap().is_empty());
+        assert!(storage.load_face_embedding(1001, "face_b").is_ok());
+    }
+
+    #[test]
+    fn test_delete_face_removes_its_embedding_and_stats() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.1]))
+            .unwrap();
+        storage.delete_face(1000, "face_1").unwrap();
+
+        let conn = storage.conn.lock();
+        let rows: u32 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM embeddings) + (SELECT COUNT(*) FROM face_stats)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+
+    #[test]
+    fn test_record_match_counts_successes() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.1]))
+            .unwrap();
+        assert_eq!(
+            storage.face_stats(1000, "face_1").unwrap(),
+            Some(FaceStats::default())
+        );
+
+        storage.record_match(1000, "face_1", 100, 0.7).unwrap();
+        storage.record_match(1000, "face_1", 200, 0.8).unwrap();
+        // Another user's id for the same face changes nothing
+        storage.record_match(1001, "face_1", 300, 0.9).unwrap();
+
+        let stats = storage.face_stats(1000, "face_1").unwrap().unwrap();
+        assert_eq!(stats.match_count, 2);
+        assert_eq!(stats.last_matched_at, Some(200));
+        assert_eq!(stats.recent_scores, vec![0.7, 0.8]);
+        assert_eq!(storage.face_stats(1001, "face_1").unwrap(), None);
+
+        // Re-saving the face keeps its history
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.2]))
+            .unwrap();
+        assert_eq!(
+            storage
+                .face_stats(1000, "face_1")
+                .unwrap()
+                .unwrap()
+                .match_count,
+            2
+        );
+    }
+
+    #[test]
+    fn test_only_the_most_recent_scores_are_kept() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.1]))
+            .unwrap();
+        storage
+            .save_face(&record("face_2", 1000), &embedding(&[0.2]))
+            .unwrap();
+
+        // Scores sliding from 0.9 down to 0.6 over 30 matches
+        for i in 0..30u64 {
+            let score = 0.9 - 0.01 * i as f32;
+            storage.record_match(1000, "face_1", i, score).unwrap();
+        }
+        storage.record_match(1000, "face_2", 5, 0.8).unwrap();
+
+        let stats = storage.load_face_stats(1000).unwrap();
+        let face_1 = &stats["face_1"];
+        assert_eq!(face_1.match_count, 30);
+        assert_eq!(face_1.recent_scores.len(), RECENT_SCORES);
+        assert!((face_1.recent_scores[0] - 0.8).abs() < 1e-6);
+        assert!((face_1.min_score().unwrap() - 0.61).abs() < 1e-6);
+        assert!((face_1.score_trend().unwrap() + 0.1).abs() < 1e-5);
+        assert!(face_1.declining);
+
+        let face_2 = &stats["face_2"];
+        assert_eq!(face_2.recent_scores, vec![0.8]);
+        assert_eq!(face_2.score_trend(), None);
+        assert!(!face_2.declining);
+    }
+
+    #[test]
+    fn test_legacy_json_files_are_imported_once_and_removed() {
+        let temp = TempDir::new().unwrap();
+        let user_dir = write_legacy_face(
+            temp.path(),
+            &record("face_1000_1", 1000),
+            &embedding(&[0.3, 0.4]),
+        );
+
+        let storage = FaceStorage::new(temp.path()).unwrap();
+
+        let faces = storage.list_user_faces(1000).unwrap();
+        assert_eq!(faces.len(), 1);
+        assert_eq!(faces[0].face_id, "face_1000_1");
+        assert_eq!(
+            storage
+                .load_face_embedding(1000, "face_1000_1")
+                .unwrap()
+                .vector,
+            vec![0.3, 0.4]
+        );
+        assert!(!user_dir.exists(), "imported files are removed");
+        assert!(!temp.path().join("users").exists());
+        assert!(
+            !temp.path().join(SNAPSHOT_DIR).exists(),
+            "and not kept in a snapshot either"
+        );
+
+        // Nothing left to import the second time
+        drop(storage);
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        assert_eq!(storage.list_user_faces(1000).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_legacy_import_quarantines_halves_and_leaves_mismatched_pairs_alone() {
+        let temp = TempDir::new().unwrap();
+        let user_dir = write_legacy_face(temp.path(), &record("face_ok", 1000), &embedding(&[0.1]));
+        // Metadata whose embedding file is missing
+        write_legacy_face(
+            temp.path(),
+            &record("face_orphan", 1000),
+            &embedding(&[0.1]),
+        );
+        std::fs::remove_file(user_dir.join("face_orphan.embedding.json")).unwrap();
+        // A record claiming another user, filed under 1000
+        let misfiled = record("face_other", 1001);
+        std::fs::write(
+            user_dir.join("face_other.meta.json"),
+            serde_json::to_string(&misfiled).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            user_dir.join("face_other.embedding.json"),
+            serde_json::to_string(&embedding(&[0.1])).unwrap(),
+        )
+        .unwrap();
+
+        let storage = FaceStorage::new(temp.path()).unwrap();
+
+        let faces = storage.list_user_faces(1000).unwrap();
+        assert_eq!(faces.len(), 1);
+        assert_eq!(faces[0].face_id, "face_ok");
+        assert!(storage.list_user_faces(1001).unwrap().is_empty());
+        assert!(!user_dir.join("face_orphan.meta.json").exists());
+        assert!(temp
+            .path()
+            .join("quarantine/legacy-1000-face_orphan.meta.json")
+            .exists());
+        assert!(user_dir.join("face_other.meta.json").exists());
+        assert!(!user_dir.join("face_ok.meta.json").exists());
+    }
+
+    #[test]
+    fn test_open_read_only_reads_a_legacy_layout_without_touching_it() {
+        // The SDDM listener may see a user whose daemon hasn't migrated yet
+        let temp = TempDir::new().unwrap();
+        let user_dir = write_legacy_face(temp.path(), &record("face_1", 1000), &embedding(&[0.5]));
+
+        let storage = FaceStorage::open_read_only(temp.path(), None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(storage.list_user_faces(1000).unwrap().len(), 1);
+        assert_eq!(
+            storage.load_face_embedding(1000, "face_1").unwrap().vector,
+            vec![0.5]
+        );
+        storage.record_match(1000, "face_1", 1, 0.7).unwrap();
+        assert!(user_dir.join("face_1.meta.json").exists());
+        assert!(!temp.path().join(DB_FILE).exists());
+    }
+
+    #[test]
+    fn test_open_read_only_refuses_a_symlinked_database() {
+        let temp = TempDir::new().unwrap();
+        let elsewhere = TempDir::new().unwrap();
+        drop(FaceStorage::new(elsewhere.path()).unwrap());
+        let storage_dir = temp.path().join("storage");
+        std::fs::create_dir(&storage_dir).unwrap();
+        std::os::unix::fs::symlink(elsewhere.path().join(DB_FILE), storage_dir.join(DB_FILE))
+            .unwrap();
+
+        assert!(FaceStorage::open_read_only(&storage_dir, None).is_err());
+    }
+
+    fn stored_vector(storage: &FaceStorage, face_id: &str) -> (Vec<u8>, bool) {
+        storage
+            .conn
+            .lock()
+            .query_row(
+                "SELECT vector, sealed FROM embeddings WHERE face_id = ?1",
+                [face_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_embeddings_are_sealed_with_a_key() {
+        let temp = TempDir::new().unwrap();
+        let key = StorageKey::from_bytes([5; 32]);
+        let storage = FaceStorage::with_key(temp.path(), Some(key.clone())).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.25, 0.5]))
+            .unwrap();
+
+        let (blob, sealed) = stored_vector(&storage, "face_1");
+        assert!(sealed);
+        assert_ne!(blob, vector_to_blob(&[0.25, 0.5]));
+        assert_eq!(
+            storage.load_face_embedding(1000, "face_1").unwrap().vector,
+            vec![0.25, 0.5]
+        );
+        drop(storage);
+
+        // The SDDM listener's read-only view needs the same key
+        let with_key = FaceStorage::open_read_only(temp.path(), Some(key))
+            .unwrap()
+            .unwrap();
+        assert!(with_key.load_face_embedding(1000, "face_1").is_ok());
+        let without_key = FaceStorage::open_read_only(temp.path(), None)
+            .unwrap()
+            .unwrap();
+        assert!(without_key.load_face_embedding(1000, "face_1").is_err());
+        let wrong_key =
+            FaceStorage::open_read_only(temp.path(), Some(StorageKey::from_bytes([6; 32])))
+                .unwrap()
+                .unwrap();
+        assert!(wrong_key.load_face_embedding(1000, "face_1").is_err());
+    }
+
+    #[test]
+    fn test_a_sealed_vector_moved_to_another_face_does_not_open() {
+        let temp = TempDir::new().unwrap();
+        let storage =
+            FaceStorage::with_key(temp.path(), Some(StorageKey::from_bytes([5; 32]))).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.1]))
+            .unwrap();
+        storage
+            .save_face(&record("face_2", 1000), &embedding(&[0.9]))
+            .unwrap();
+
+        storage
+            .conn
+            .lock()
+            .execute(
+                "UPDATE embeddings SET vector = (SELECT vector FROM embeddings WHERE face_id = 'face_1')
+                 WHERE face_id = 'face_2'",
+                [],
+            )
+            .unwrap();
+
+        assert!(storage.load_face_embedding(1000, "face_2").is_err());
+    }
+
+    #[test]
+    fn test_unencrypted_embeddings_are_sealed_once_a_key_is_available() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        // Real-sized vectors, several faces with samples: sealing moves
+        // rows between pages, which is what leaves old copies behind
+        let vector = |seed: usize| -> Vec<f32> {
+            (0..128)
+                .map(|i| ((i * 7 + seed * 13) % 97) as f32 / 97.0 - 0.5)
+                .collect()
+        };
+        let mut plaintext = Vec::new();
+        for face in 0..4 {
+            let centroid = vector(face * 10);
+            let samples: Vec<Vec<f32>> = (1..4).map(|n| vector(face * 10 + n)).collect();
+            let sample_refs: Vec<&[f32]> = samples.iter().map(Vec::as_slice).collect();
+            storage
+                .save_face_template(
+                    &record(&format!("face_{face}"), 1000),
+                    &template(&centroid, &sample_refs),
+                )
+                .unwrap();
+            plaintext.push(centroid);
+            plaintext.extend(samples);
+        }
+        assert!(!stored_vector(&storage, "face_1").1);
+        drop(storage);
+
+        let storage =
+            FaceStorage::with_key(temp.path(), Some(StorageKey::from_bytes([5; 32]))).unwrap();
+        assert!(stored_vector(&storage, "face_1").1);
+        assert_eq!(
+            storage.load_face_embedding(1000, "face_1").unwrap().vector,
+            plaintext[4]
+        );
+        drop(storage);
+
+        // Not in a free page or a leftover journal either
+        for vector in &plaintext {
+            assert!(files_containing(temp.path(), &vector_to_blob(vector)).is_empty());
+        }
+    }
+
+    /// Every file under `dir` whose bytes contain `needle`
+    fn files_containing(dir: &Path, needle: &[u8]) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                found.extend(files_containing(&path, needle));
+            } else if std::fs::read(&path)
+                .unwrap()
+                .windows(needle.len())
+                .any(|window| window == needle)
+            {
+                found.push(path);
+            }
+        }
+        found
+    }
+
+    fn template(centroid: &[f32], samples: &[&[f32]]) -> FaceTemplate {
+        FaceTemplate {
+            centroid: embedding(centroid),
+            samples: samples.iter().map(|vector| embedding(vector)).collect(),
+            adapted: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_samples_are_kept_sealed_and_replaced_with_their_face() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face_template(
+                &record("face_1", 1000),
+                &template(&[0.5], &[&[0.25], &[0.75]]),
+            )
+            .unwrap();
+        storage
+            .save_face(&record("face_2", 1000), &embedding(&[0.1]))
+            .unwrap();
+        drop(storage);
+
+        // Samples written in the clear are sealed with the rest
+        let storage =
+            FaceStorage::with_key(temp.path(), Some(StorageKey::from_bytes([5; 32]))).unwrap();
+        let unsealed: u32 = storage
+            .conn
+            .lock()
+            .query_row(
+                "SELECT COUNT(*) FROM embedding_samples WHERE sealed = 0",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(unsealed, 0);
+
+        let templates = storage
+            .load_face_templates(1000, ["face_1", "face_2"])
+            .unwrap();
+        let samples: Vec<_> = templates["face_1"]
+            .samples
+            .iter()
+            .map(|s| s.vector.clone())
+            .collect();
+        assert_eq!(samples, vec![vec![0.25], vec![0.75]]);
+        assert_eq!(templates["face_1"].centroid.vector, vec![0.5]);
+        assert!(templates["face_2"].samples.is_empty());
+
+        // Saving a face again replaces its samples; deleting it drops them
+        storage
+            .save_face_template(&record("face_1", 1000), &template(&[0.5], &[&[0.5]]))
+            .unwrap();
+        let templates = storage.load_face_templates(1000, ["face_1"]).unwrap();
+        assert_eq!(templates["face_1"].samples.len(), 1);
+        storage.delete_face(1000, "face_1").unwrap();
+        let remaining: u32 = storage
+            .conn
+            .lock()
+            .query_row("SELECT COUNT(*) FROM embedding_samples", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn test_adapted_samples_are_capped_sealed_and_reset() {
+        let temp = TempDir::new().unwrap();
+        let storage =
+            FaceStorage::with_key(temp.path(), Some(StorageKey::from_bytes([5; 32]))).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[1.0]))
+            .unwrap();
+        storage
+            .save_face(&record("face_2", 1000), &embedding(&[1.0]))
+            .unwrap();
+        assert_eq!(storage.last_adapted_at(1000, "face_1").unwrap(), None);
+
+        for (at, value) in [(10, 0.1), (20, 0.2), (30, 0.3)] {
+            storage
+                .add_adapted_sample(
+                    1000,
+                    "face_1",
+                    &embedding(&[value]),
+                    Some(&[at]),
+                    at.into(),
+                    2,
+                )
+                .unwrap();
+        }
+        storage
+            .add_adapted_sample(1000, "face_2", &embedding(&[0.9]), None, 40, 2)
+            .unwrap();
+        assert!(matches!(
+            storage.add_adapted_sample(2000, "face_1", &embedding(&[0.9]), None, 50, 2),
+            Err(DaemonError::FaceNotFound(_))
+        ));
+        assert_eq!(storage.last_adapted_at(1000, "face_1").unwrap(), Some(30));
+        assert_eq!(storage.last_adapted_at(2000, "face_1").unwrap(), None);
+
+        let unsealed: u32 = storage
+            .conn
+            .lock()
+            .query_row(
+                "SELECT COUNT(*) FROM adapted_samples WHERE sealed = 0",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(unsealed, 0);
+
+        // Only the newest two are kept
+        let templates = storage
+            .load_face_templates(1000, ["face_1", "face_2"])
+            .unwrap();
+        let adapted: Vec<_> = templates["face_1"]
+            .adapted
+            .iter()
+            .map(|s| s.vector.clone())
+            .collect();
+        assert_eq!(adapted, vec![vec![0.2], vec![0.3]]);
+        // Their MACs come in the same order
+        let macs = storage.load_adapted_macs(1000).unwrap();
+        assert_eq!(macs["face_1"], vec![Some(vec![20]), Some(vec![30])]);
+        assert_eq!(macs["face_2"], vec![None]);
+        assert!(storage.load_adapted_macs(2000).unwrap().is_empty());
+
+        // Another user can't reset them; the owner can, one face or all
+        assert_eq!(storage.reset_adapted_samples(2000, None).unwrap(), 0);
+        assert_eq!(
+            storage.reset_adapted_samples(1000, Some("face_1")).unwrap(),
+            2
+        );
+        assert_eq!(storage.reset_adapted_samples(1000, None).unwrap(), 1);
+        let templates = storage
+            .load_face_templates(1000, ["face_1", "face_2"])
+            .unwrap();
+        assert!(templates.values().all(|t| t.adapted.is_empty()));
+
+        // Enrolling a face again starts it over
+        storage
+            .add_adapted_sample(1000, "face_1", &embedding(&[0.5]), None, 60, 2)
+            .unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[1.0]))
+            .unwrap();
+        assert_eq!(storage.last_adapted_at(1000, "face_1").unwrap(), None);
+    }
+
+    /// A database as written before layouts were versioned: layout 1, no
+    /// `sealed` column, no version file
+    fn write_unversioned_database(base: &Path) -> PathBuf {
+        let db_path = base.join(DB_FILE);
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(SCHEMA_V1).unwrap();
+        conn.execute(
+            "INSERT INTO faces VALUES ('face_1', 1000, 0.9, 0, 'test')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO embeddings VALUES ('face_1', 'test', '0.1.0', 0, 0.9, ?1)",
+            [vector_to_blob(&[0.5, 0.25])],
+        )
+        .unwrap();
+        db_path
+    }
+
+    fn has_sealed_column(db_path: &Path) -> bool {
+        Connection::open(db_path)
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('embeddings') WHERE name = 'sealed'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_migrations_end_at_the_current_layout() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.to as usize, i + 1, "{}", migration.description);
+        }
+        assert_eq!(MIGRATIONS.last().unwrap().to, LAYOUT_VERSION);
+    }
+
+    #[test]
+    fn test_fresh_storage_records_the_current_layout() {
+        let temp = TempDir::new().unwrap();
+        drop(FaceStorage::new(temp.path()).unwrap());
+
+        assert_eq!(read_layout_version(temp.path()).unwrap(), LAYOUT_VERSION);
+        assert!(
+            !temp.path().join(SNAPSHOT_DIR).exists(),
+            "nothing to snapshot"
+        );
+    }
+
+    #[test]
+    fn test_unversioned_database_is_migrated_and_its_snapshot_deleted() {
+        let temp = TempDir::new().unwrap();
+        let db_path = write_unversioned_database(temp.path());
+
+        let storage = FaceStorage::new(temp.path()).unwrap();
+
+        assert_eq!(
+            storage.load_face_embedding(1000, "face_1").unwrap().vector,
+            vec![0.5, 0.25]
+        );
+        assert_eq!(read_layout_version(temp.path()).unwrap(), LAYOUT_VERSION);
+        assert!(has_sealed_column(&db_path));
+        assert!(!temp.path().join(SNAPSHOT_DIR).exists());
+    }
+
+    #[test]
+    fn test_layout_version_must_be_a_regular_file() {
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("elsewhere");
+        std::fs::write(&target, "1").unwrap();
+        std::os::unix::fs::symlink(&target, temp.path().join(LAYOUT_FILE)).unwrap();
+        assert!(read_layout_version(temp.path()).is_err(), "symlink");
+
+        std::fs::remove_file(temp.path().join(LAYOUT_FILE)).unwrap();
+        std::fs::create_dir(temp.path().join(LAYOUT_FILE)).unwrap();
+        assert!(read_layout_version(temp.path()).is_err(), "directory");
+
+        std::fs::remove_dir(temp.path().join(LAYOUT_FILE)).unwrap();
+        let fifo =
+            std::ffi::CString::new(temp.path().join(LAYOUT_FILE).as_os_str().as_encoded_bytes())
+                .unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        assert!(
+            read_layout_version(temp.path()).is_err(),
+            "FIFO, without hanging"
+        );
+
+        std::fs::remove_file(temp.path().join(LAYOUT_FILE)).unwrap();
+        std::fs::write(
+            temp.path().join(LAYOUT_FILE),
+            format!("{}\n", LAYOUT_VERSION),
+        )
+        .unwrap();
+        assert_eq!(read_layout_version(temp.path()).unwrap(), LAYOUT_VERSION);
+    }
+
+    #[test]
+    fn test_no_plaintext_embedding_is_left_after_upgrading() {
+        let temp = TempDir::new().unwrap();
+        write_unversioned_database(temp.path());
+        // What an earlier daemon left behind
+        let stale = temp.path().join("snapshots/layout-v0");
+        std::fs::create_dir_all(&stale).unwrap();
+        std::fs::copy(temp.path().join(DB_FILE), stale.join(DB_FILE)).unwrap();
+
+        let storage =
+            FaceStorage::with_key(temp.path(), Some(StorageKey::from_bytes([5; 32]))).unwrap();
+        assert!(stored_vector(&storage, "face_1").1);
+        drop(storage);
+
+        assert_eq!(
+            files_containing(temp.path(), &vector_to_blob(&[0.5, 0.25])),
+            Vec::<PathBuf>::new()
+        );
+    }
+
+    #[test]
+    fn test_failed_migration_restores_the_snapshot() {
+        let temp = TempDir::new().unwrap();
+        let db_path = temp.path().join(DB_FILE);
+        // Layout 1 by the missing version file, but without the embeddings
+        // table the second migration alters
+        Connection::open(&db_path)
+            .unwrap()
+            .execute_batch("CREATE TABLE faces (face_id TEXT PRIMARY KEY)")
+            .unwrap();
+        let before = std::fs::read(&db_path).unwrap();
+
+        assert!(FaceStorage::new(temp.path()).is_err());
+
+        assert_eq!(std::fs::read(&db_path).unwrap(), before);
+        assert!(!temp.path().join(LAYOUT_FILE).exists());
+    }
+
+    #[test]
+    fn test_newer_layout_is_refused() {
+        let temp = TempDir::new().unwrap();
+        drop(FaceStorage::new(temp.path()).unwrap());
+        let newer = LAYOUT_VERSION + 1;
+        std::fs::write(temp.path().join(LAYOUT_FILE), format!("{}\n", newer)).unwrap();
+
+        let err = FaceStorage::new(temp.path()).err().unwrap();
+        assert!(
+            matches!(err, DaemonError::NewerStorageLayout { found, supported }
+                if found == newer && supported == LAYOUT_VERSION),
+            "{err}"
+        );
+        let err = FaceStorage::open_read_only(temp.path(), None)
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, DaemonError::NewerStorageLayout { .. }),
+            "{err}"
+        );
+        assert_eq!(read_layout_version(temp.path()).unwrap(), newer);
+    }
+
+    #[test]
+    fn test_open_read_only_migrates_an_old_layout_in_memory() {
+        let temp = TempDir::new().unwrap();
+        let db_path = write_unversioned_database(temp.path());
+        let before = std::fs::read(&db_path).unwrap();
+
+        let storage = FaceStorage::open_read_only(temp.path(), None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            storage.load_face_embedding(1000, "face_1").unwrap().vector,
+            vec![0.5, 0.25]
+        );
+        assert_eq!(std::fs::read(&db_path).unwrap(), before);
+        assert!(!temp.path().join(LAYOUT_FILE).exists());
+        assert!(!temp.path().join(SNAPSHOT_DIR).exists());
+    }
+
+    #[test]
+    fn test_face_label_and_notes_are_updated_kept_and_cleared() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[1.0]))
+            .unwrap();
+
+        storage
+            .update_face_metadata(1000, "face_1", Some("with glasses"), Some("office"), None)
+            .unwrap();
+        // None leaves a field alone, "" clears it
+        storage
+            .update_face_metadata(1000, "face_1", None, Some(""), None)
+            .unwrap();
+
+        let face = &storage.list_user_faces(1000).unwrap()[0];
+        assert_eq!(face.label.as_deref(), Some("with glasses"));
+        assert_eq!(face.notes, None);
+
+        // Another user's face is as good as missing
+        assert!(matches!(
+            storage.update_face_metadata(1001, "face_1", Some("mine"), None, None),
+            Err(DaemonError::FaceNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_allowed_contexts_are_stored_replaced_and_cleared() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        let mut restricted = record("face_1", 1000);
+        restricted.allowed_contexts = vec!["screenlock".to_string()];
+        storage.save_face(&restricted, &embedding(&[1.0])).unwrap();
+        storage
+            .save_face(&record("face_2", 1000), &embedding(&[0.5]))
+            .unwrap();
+
+        let faces = storage.list_user_faces(1000).unwrap();
+        assert_eq!(faces[0].allowed_contexts, vec!["screenlock"]);
+        assert!(faces[1].allowed_contexts.is_empty());
+
+        let contexts = ["login".to_string(), "sudo".to_string()];
+        storage
+            .update_face_metadata(1000, "face_1", None, None, Some(&contexts))
+            .unwrap();
+        assert_eq!(
+            storage.list_user_faces(1000).unwrap()[0].allowed_contexts,
+            vec!["login", "sudo"]
+        );
+
+        storage
+            .update_face_metadata(1000, "face_1", None, None, Some(&[]))
+            .unwrap();
+        assert!(storage.list_user_faces(1000).unwrap()[0]
+            .allowed_contexts
+            .is_empty());
+
+        // Deleting the face takes its restrictions with it
+        storage
+            .update_face_metadata(1000, "face_1", None, None, Some(&contexts))
+            .unwrap();
+        storage.delete_face(1000, "face_1").unwrap();
+        let remaining: u32 = storage
+            .conn
+            .lock()
+            .query_row("SELECT COUNT(*) FROM face_contexts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn test_save_faces_is_all_or_nothing_and_keeps_other_users_faces() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_taken", 1001), &embedding(&[1.0]))
+            .unwrap();
+
+        let result = storage.save_faces(&[
+            (record("face_new", 1000), embedding(&[0.5]).into()),
+            (record("face_taken", 1000), embedding(&[0.25]).into()),
+        ]);
+
+        assert!(matches!(result, Err(DaemonError::AccessDenied(_))));
+        assert!(storage.list_user_faces(1000).unwrap().is_empty());
+        assert_eq!(
+            storage
+                .load_face_embedding(1001, "face_taken")
+                .unwrap()
+                .vector,
+            vec![1.0]
+        );
+
+        storage
+            .save_faces(&[
+                (record("face_a", 1000), embedding(&[0.5]).into()),
+                (record("face_b", 1000), embedding(&[0.25]).into()),
+            ])
+            .unwrap();
+        assert_eq!(storage.list_user_faces(1000).unwrap().len(), 2);
+    }
+
+    /// Make every insert into `table` fail, as a crash or a full disk
+    /// would halfway through a write
+    fn fail_inserts_into(storage: &FaceStorage, table: &str) {
+        storage
+            .conn
+            .lock()
+            .execute_batch(&format!(
+                "CREATE TRIGGER fail_{table} BEFORE INSERT ON {table}
+                 BEGIN SELECT RAISE(ABORT, 'injected fault'); END"
+            ))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_an_enrollment_failing_halfway_leaves_nothing_behind() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+
+        fail_inserts_into(&storage, "embeddings");
+        assert!(storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.5]))
+            .is_err());
+        assert!(storage.list_user_faces(1000).unwrap().is_empty());
+
+        storage
+            .conn
+            .lock()
+            .execute_batch("DROP TRIGGER fail_embeddings")
+            .unwrap();
+        storage
+            .save_face(&record("face_1", 1000), &embedding(&[0.5]))
+            .unwrap();
+
+        // Re-saving an enrolled face fails on its last table: the earlier
+        // version is kept whole
+        fail_inserts_into(&storage, "embedding_samples");
+        let mut relabeled = record("face_1", 1000);
+        relabeled.label = Some("new".to_string());
+        let result = storage.save_face_template(
+            &relabeled,
+            &FaceTemplate {
+                centroid: embedding(&[0.25]),
+                samples: vec![embedding(&[0.25])],
+                adapted: Vec::new(),
+            },
+        );
+        assert!(result.is_err());
+        let faces = storage.list_user_faces(1000).unwrap();
+        assert_eq!(faces.len(), 1);
+        assert_eq!(faces[0].label, None);
+        assert_eq!(
+            storage.load_face_embedding(1000, "face_1").unwrap().vector,
+            vec![0.5]
+        );
+    }
+
+    #[test]
+    fn test_a_backup_restore_failing_halfway_leaves_nothing_behind() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        fail_inserts_into(&storage, "face_contexts");
+
+        let mut restricted = record("face_b", 1000);
+        restricted.allowed_contexts = vec!["sudo".to_string()];
+        let result = storage.save_faces(&[
+            (record("face_a", 1000), embedding(&[0.5]).into()),
+            (restricted, embedding(&[0.25]).into()),
+        ]);
+
+        assert!(result.is_err());
+        assert!(storage.list_user_faces(1000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_inconsistent_rows_are_quarantined_on_open() {
+        let temp = TempDir::new().unwrap();
+        let storage = FaceStorage::new(temp.path()).unwrap();
+        storage
+            .save_face(&record("face_ok", 1000), &embedding(&[0.5]))
+            .unwrap();
+        storage
+            .save_face(&record("face_half", 1000), &embedding(&[0.25]))
+            .unwrap();
+        {
+            // What an interrupted write without a transaction would leave:
+            // a face without its embedding, and rows of a face that's gone
+            let conn = storage.conn.lock();
+            conn.execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                 DELETE FROM embeddings WHERE face_id = 'face_half';
+                 INSERT INTO face_stats (face_id, match_count) VALUES ('face_gone', 3);",
+            )
+            .unwrap();
+        }
+        // Listed without its embedding, a half face fails every load
+        assert_eq!(storage.list_user_faces(1000).unwrap().len(), 1);
+        drop(storage);
+
+        let storage = FaceStorage::new(temp.path()).unwrap();
+
+        let faces = storage.list_user_faces(1000).unwrap();
+        assert_eq!(faces.len(), 1);
+        assert!(storage
+            .load_face_templates(1000, ["face_ok"])
+            .unwrap()
+            .contains_key("face_ok"));
+        let remaining: u32 = storage
+            .conn
+            .lock()
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM faces) + (SELECT COUNT(*) FROM face_stats)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(remaining, 2);
+
+        let mut quarantined: Vec<serde_json::Value> =
+            std::fs::read_dir(temp.path().join(QUARANTINE_DIR))
+                .unwrap()
+                .map(|entry| {
+                    serde_json::from_slice(&std::fs::read(entry.unwrap().path()).unwrap()).unwrap()
+                })
+                .collect();
+        quarantined.sort_by_key(|dump| dump["face_id"].as_str().unwrap().to_string());
+        assert_eq!(quarantined.len(), 2);
+        assert_eq!(quarantined[0]["face_id"], "face_gone");
+        assert_eq!(quarantined[0]["rows"]["face_stats"][0]["match_count"], 3);
+        assert_eq!(quarantined[1]["face_id"], "face_half");
+        assert_eq!(quarantined[1]["rows"]["faces"][0]["user_id"], 1000);
+
+        // Nothing left to quarantine the next time
+        drop(storage);
+        FaceStorage::new(temp.path()).unwrap();
+        assert_eq!(
+            std::fs::read_dir(temp.path().join(QUARANTINE_DIR))
+                .unwrap()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_vector_blob_round_trip() {
+        let vector = vec![0.0, -1.5, f32::MIN_POSITIVE, 3.25];
+        assert_eq!(blob_to_vector(&vector_to_blob(&vector)), Some(vector));
+        assert_eq!(blob_to_vector(&[0, 1, 2]), None);
     }
 }
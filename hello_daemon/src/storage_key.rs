@@ -0,0 +1,374 @@
+//! Keys for encrypting stored embeddings at rest
+//!
+//! A copy of someone's home directory — a backup, a stolen disk — shouldn't
+//! hand out reusable biometric templates. Embedding vectors in `faces.db`
+//! are therefore sealed with ChaCha20-Poly1305 under a per-user key that is
+//! never written under the home directory:
+//!
+//! - `hello-storage-key` (root, always enabled) keeps a random 32-byte
+//!   master key in [`MASTER_KEY_PATH`], creating it on first start.
+//! - Each user's key is derived from it as
+//!   `HMAC-SHA256(master, "linux-hello storage key v1" || uid)`.
+//! - The user's own `hello-daemon` gets its key over [`KEY_SOCKET_PATH`]:
+//!   the key service answers every connection with the key for the
+//!   connecting process's uid (`SO_PEERCRED`), so no user can obtain
+//!   anyone else's. The SDDM listener, already root, derives keys directly.
+//!
+//! Sealed values are `nonce (12 bytes) || ciphertext || tag`, with the
+//! owning uid and face id as associated data, so a sealed vector copied
+//! onto another face's row fails to open.
+
+use crate::record_auth::RecordKey;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, error, info};
+
+/// Master key file, readable by root only
+pub const MASTER_KEY_PATH: &str = "/var/lib/linux-hello/storage.key";
+
+/// Socket `hello-storage-key` hands out per-user keys on
+pub const KEY_SOCKET_PATH: &str = "/run/hello-pam/keys.socket";
+
+/// Delay before the first retry of [`wait_for_user_key`], doubled after
+/// each failed one: about 15 s in all, for the key service to come up
+const FIRST_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
+const KEY_FETCH_ATTEMPTS: u32 = 7;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const DERIVATION_LABEL: &[u8] = b"linux-hello storage key v1";
+const RECORD_DERIVATION_LABEL: &[u8] = b"linux-hello record key v1";
+
+/// One user's embedding encryption key
+#[derive(Clone)]
+pub struct StorageKey([u8; KEY_LEN]);
+
+impl std::fmt::Debug for StorageKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StorageKey(..)")
+    }
+}
+
+impl StorageKey {
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// Encrypt `plaintext`, bound to `aad`
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+        let nonce: [u8; NONCE_LEN] = crate::security_util::random_bytes();
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .expect("ChaCha20-Poly1305 encryption of an in-memory buffer cannot fail");
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// Decrypt a value from [`StorageKey::seal`]; `None` if it was sealed
+    /// under another key or `aad`, or has been altered
+    pub fn open(&self, sealed: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .ok()
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+/// The per-installation secret user keys are derived from
+pub struct MasterKey([u8; KEY_LEN]);
+
+impl MasterKey {
+    /// Read the key at `path`, or create it (mode `0600`) if there is none.
+    ///
+    /// An existing file is refused unless it is a regular file (not a
+    /// symlink) owned by this process's user and not readable by anyone
+    /// else — a key others can read protects nothing.
+    pub fn load_or_create(path: &Path) -> std::io::Result<Self> {
+        use std::io::{Error, ErrorKind, Read, Write};
+
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                let euid = unsafe { libc::geteuid() };
+                if !metadata.file_type().is_file()
+                    || metadata.uid() != euid
+                    || metadata.permissions().mode() & 0o077 != 0
+                {
+                    return Err(Error::new(
+                        ErrorKind::PermissionDenied,
+                        format!(
+                            "{} must be a regular file owned by uid {} with mode 0600",
+                            path.display(),
+                            euid
+                        ),
+                    ));
+                }
+                let mut key = [0u8; KEY_LEN];
+                let mut file = std::fs::File::open(path)?;
+                file.read_exact(&mut key)?;
+                Ok(Self(key))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let key: [u8; KEY_LEN] = crate::security_util::random_bytes();
+                let mut file = match std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(path)
+                {
+                    Ok(file) => file,
+                    // The other root service (`hello-storage-key` or
+                    // `hello-daemon-system`) starting at the same moment
+                    // won the race; use the key it just wrote
+                    Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                        return Self::load_or_create(path)
+                    }
+                    Err(e) => return Err(e),
+                };
+                file.write_all(&key)?;
+                file.sync_all()?;
+                info!("Created storage master key {}", path.display());
+                Ok(Self(key))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The key for `uid`'s embeddings
+    pub fn user_key(&self, uid: u32) -> StorageKey {
+        StorageKey(self.derive(DERIVATION_LABEL, uid))
+    }
+
+    /// The key `uid`'s face records are authenticated under. Unlike
+    /// [`MasterKey::user_key`], never handed out: see `record_auth`.
+    pub fn record_key(&self, uid: u32) -> RecordKey {
+        RecordKey::from_bytes(self.derive(RECORD_DERIVATION_LABEL, uid))
+    }
+
+    fn derive(&self, label: &[u8], uid: u32) -> [u8; KEY_LEN] {
+        let mut mac = <Hmac<Sha256> as hmac::KeyInit>::new_from_slice(&self.0)
+            .expect("HMAC accepts keys of any length");
+        mac.update(label);
+        mac.update(&uid.to_le_bytes());
+        mac.finalize().into_bytes().into()
+    }
+}
+
+/// Serve per-user keys on `socket_path` (`hello-storage-key` only).
+///
+/// The socket is `0666` like the per-user PAM sockets: anyone may connect,
+/// and each connection gets exactly 32 bytes — the key for its own peer
+/// uid — and is closed. There is no request to parse.
+pub async fn start_key_server(
+    socket_path: &Path,
+    master: Arc<MasterKey>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _ = std::fs::remove_file(socket_path);
+    let listener = UnixListener::bind(socket_path)?;
+    std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o666))?;
+    info!("Storage key service listening on {}", socket_path.display());
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((mut stream, _)) => {
+                    let Some(uid) = stream.peer_cred().ok().map(|c| c.uid()) else {
+                        continue;
+                    };
+                    let key = master.user_key(uid);
+                    tokio::spawn(async move {
+                        match stream.write_all(&key.0).await {
+                            Ok(()) => debug!("Storage key handed out to uid={}", uid),
+                            Err(e) => debug!("Storage key for uid={} not sent: {}", uid, e),
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Storage key service accept error: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Ask the key service on `socket_path` for this process's key, trusting
+/// only a server running as `server_uid` (root in production) — anyone can
+/// bind a socket in `/run/hello-pam/` before the real service does.
+pub async fn fetch_user_key(socket_path: &Path, server_uid: u32) -> Result<StorageKey, String> {
+    let exchange = async {
+        let mut stream = UnixStream::connect(socket_path)
+            .await
+            .map_err(|e| format!("{} unreachable: {}", socket_path.display(), e))?;
+        match stream.peer_cred().map(|c| c.uid()) {
+            Ok(uid) if uid == server_uid => {}
+            Ok(uid) => {
+                return Err(format!(
+                    "Refusing key from {}: served by uid {}, expected {}",
+                    socket_path.display(),
+                    uid,
+                    server_uid
+                ))
+            }
+            Err(e) => return Err(format!("{}: {}", socket_path.display(), e)),
+        }
+        let mut key = [0u8; KEY_LEN];
+        stream
+            .read_exact(&mut key)
+            .await
+            .map_err(|e| format!("Reading the key from {}: {}", socket_path.display(), e))?;
+        Ok(StorageKey(key))
+    };
+    tokio::time::timeout(std::time::Duration::from_secs(2), exchange)
+        .await
+        .map_err(|_| format!("{} did not answer", socket_path.display()))?
+}
+
+/// [`fetch_user_key`], retried with backoff — a user daemon can start
+/// before the key service does. The last error if it never answers.
+pub async fn wait_for_user_key(socket_path: &Path, server_uid: u32) -> Result<StorageKey, String> {
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match fetch_user_key(socket_path, server_uid).await {
+            Ok(key) => return Ok(key),
+            Err(e) if attempt == KEY_FETCH_ATTEMPTS => return Err(e),
+            Err(e) => debug!("Storage key (attempt {}): {}", attempt, e),
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_seal_round_trip_and_binding() {
+        let key = StorageKey::from_bytes([7; KEY_LEN]);
+        let sealed = key.seal(b"embedding bytes", b"1000:face_1");
+
+        assert_eq!(
+            key.open(&sealed, b"1000:face_1").as_deref(),
+            Some(&b"embedding bytes"[..])
+        );
+        // Moved to another face, opened with another key, or altered
+        assert_eq!(key.open(&sealed, b"1000:face_2"), None);
+        assert_eq!(
+            StorageKey::from_bytes([8; KEY_LEN]).open(&sealed, b"1000:face_1"),
+            None
+        );
+        let mut altered = sealed.clone();
+        *altered.last_mut().unwrap() ^= 1;
+        assert_eq!(key.open(&altered, b"1000:face_1"), None);
+        assert_eq!(key.open(&sealed[..4], b"1000:face_1"), None);
+
+        // Fresh nonce each time
+        assert_ne!(sealed, key.seal(b"embedding bytes", b"1000:face_1"));
+    }
+
+    #[test]
+    fn test_user_keys_are_stable_and_distinct() {
+        let master = MasterKey([1; KEY_LEN]);
+        assert_eq!(master.user_key(1000).0, master.user_key(1000).0);
+        assert_ne!(master.user_key(1000).0, master.user_key(1001).0);
+        assert_ne!(
+            master.user_key(1000).0,
+            MasterKey([2; KEY_LEN]).user_key(1000).0
+        ); // Record keys are a separate derivation
+        assert_ne!(master.record_key(1000).as_bytes(), &master.user_key(1000).0);
+    }
+
+    #[test]
+    fn test_master_key_is_created_once_at_0600() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("storage.key");
+
+        let created = MasterKey::load_or_create(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+
+        let loaded = MasterKey::load_or_create(&path).unwrap();
+        assert_eq!(created.0, loaded.0);
+    }
+
+    #[test]
+    fn test_master_key_readable_by_others_is_refused() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("storage.key");
+        MasterKey::load_or_create(&path).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert!(MasterKey::load_or_create(&path).is_err());
+
+        let link = temp.path().join("link.key");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        assert!(MasterKey::load_or_create(&link).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_key_service_hands_out_the_callers_own_key() {
+        let temp = TempDir::new().unwrap();
+        let socket = temp.path().join("keys.socket");
+        let master = Arc::new(MasterKey([3; KEY_LEN]));
+        start_key_server(&socket, master.clone()).await.unwrap();
+
+        let uid = unsafe { libc::getuid() };
+        let key = fetch_user_key(&socket, uid).await.unwrap();
+        assert_eq!(key.0, master.user_key(uid).0);
+
+        // A server running as anyone but the expected uid isn't trusted
+        assert!(fetch_user_key(&socket, uid.wrapping_add(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_key_is_waited_for_until_the_service_is_up() {
+        let temp = TempDir::new().unwrap();
+        let socket = temp.path().join("keys.socket");
+        let master = Arc::new(MasterKey([4; KEY_LEN]));
+        let uid = unsafe { libc::getuid() };
+
+        let waiting = tokio::spawn({
+            let socket = socket.clone();
+            async move { wait_for_user_key(&socket, uid).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        start_key_server(&socket, master.clone()).await.unwrap();
+
+        let key = waiting.await.unwrap().unwrap();
+        assert_eq!(key.0, master.user_key(uid).0);
+    }
+}
@@ -157,7 +157,7 @@ impl EmbeddingExtractor for FakeExtractor {
     }
 
     fn model_name(&self) -> &str {
-        "fake-extractor"
+        "fake"
     }
 
     fn model_version(&self) -> &str {
@@ -161,7 +161,9 @@ impl EmbeddingExtractor for ArcFaceExtractor {
     }
 
     fn model_version(&self) -> &str {
-        "insightface-onnx-zoo"
+        // What `extract` stamps on embeddings, and so on every stored
+        // template — changing it invalidates existing enrollments
+        "0.1.0"
     }
 
     fn embedding_dimension(&self) -> usize {
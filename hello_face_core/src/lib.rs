@@ -41,6 +41,18 @@ pub struct Embedding {
     pub metadata: EmbeddingMetadata,
 }
 
+impl Embedding {
+    /// Whether a similarity score between `self` and `other` means
+    /// anything: both extracted by the same model and version, into
+    /// vectors of the same dimension. Embeddings of different models live
+    /// in unrelated spaces — even a high score between them is noise.
+    pub fn is_comparable_with(&self, other: &Embedding) -> bool {
+        self.metadata.model == other.metadata.model
+            && self.metadata.model_version == other.metadata.model_version
+            && self.vector.len() == other.vector.len()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingMetadata {
     /// Model used for extraction (e.g. "arcface_mobilenet", "facenet")
@@ -132,6 +144,16 @@ pub trait EmbeddingExtractor: Send + Sync {
 
     /// Expected vector dimension
     fn embedding_dimension(&self) -> usize;
+
+    /// Whether `template` was extracted by this model, i.e. can be compared
+    /// with what [`extract`](EmbeddingExtractor::extract) returns now. The
+    /// name and version it reports must therefore match the metadata its
+    /// embeddings carry.
+    fn produced(&self, template: &Embedding) -> bool {
+        template.metadata.model == self.model_name()
+            && template.metadata.model_version == self.model_version()
+            && template.vector.len() == self.embedding_dimension()
+    }
 }
 
 /// Simple histogram-based implementation for prototyping
@@ -202,7 +224,7 @@ pub mod simple_implementation {
         }
 
         fn model_name(&self) -> &str {
-            "SimpleHistogram"
+            "histogram"
         }
 
         fn model_version(&self) -> &str {
@@ -435,6 +457,45 @@ mod factory_tests {
         let ext = create_extractor(tmp);
         assert!(ext.embedding_dimension() > 0);
     }
+
+    #[test]
+    fn test_extractors_recognize_their_own_embeddings() {
+        let region = FaceRegion {
+            bounding_box: (0, 0, 32, 32),
+            confidence: 0.9,
+            landmarks: vec![],
+        };
+        let frame = vec![128u8; 64 * 64 * 3];
+        let histogram = simple_implementation::SimpleEmbedder;
+        let fallback = arcface_extractor::ArcFaceFallback;
+        let extractors: [&dyn EmbeddingExtractor; 2] = [&histogram, &fallback];
+
+        for extractor in extractors {
+            let embedding = extractor.extract(&region, &frame, 64, 64, 3).unwrap();
+            assert!(extractor.produced(&embedding), "{}", extractor.model_name());
+        }
+        let histogram_embedding = histogram.extract(&region, &frame, 64, 64, 3).unwrap();
+        assert!(!fallback.produced(&histogram_embedding));
+    }
+
+    #[test]
+    fn test_embeddings_of_different_models_are_not_comparable() {
+        let embedding = |model: &str, version: &str, dimension: usize| Embedding {
+            vector: vec![0.5; dimension],
+            metadata: EmbeddingMetadata {
+                model: model.to_string(),
+                model_version: version.to_string(),
+                extracted_at: 0,
+                quality_score: 0.9,
+            },
+        };
+        let current = embedding("arcface-w600k-mbf", "0.1.0", 512);
+
+        assert!(current.is_comparable_with(&embedding("arcface-w600k-mbf", "0.1.0", 512)));
+        assert!(!current.is_comparable_with(&embedding("arcface-fallback", "stub-0.1", 512)));
+        assert!(!current.is_comparable_with(&embedding("arcface-w600k-mbf", "0.2.0", 512)));
+        assert!(!current.is_comparable_with(&embedding("arcface-w600k-mbf", "0.1.0", 128)));
+    }
 }
 
 #[cfg(test)]
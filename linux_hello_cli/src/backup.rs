@@ -0,0 +1,208 @@
+//! `linux-hello backup` — carry enrollments over to another install
+//!
+//! The daemon does the work (`ExportFaces`/`ImportFaces`): only it holds
+//! the key the stored embeddings are encrypted under. What travels over
+//! D-Bus and lands on disk is the passphrase-encrypted archive from
+//! `hello_daemon::backup`, never a bare template. Both directions go
+//! through the same re-authentication prompt as enrolling a face.
+
+use std::io::{BufRead, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::Subcommand;
+use hello_daemon::backup::MIN_PASSPHRASE_LEN;
+use hello_daemon::dbus_interface::{ExportFacesRequest, ImportFacesRequest, ImportFacesResponse};
+use tracing::info;
+use zbus::Connection;
+
+use super::{daemon_call_error, daemon_proxy};
+
+#[derive(Subcommand, Debug)]
+pub enum BackupAction {
+    /// Write a user's enrolled faces to an encrypted archive
+    Export {
+        /// User UID
+        user_id: u32,
+
+        /// Archive to create (mode 0600; an existing file is not overwritten)
+        file: PathBuf,
+
+        /// Read the passphrase from the first line of this file instead of
+        /// asking for it
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+
+    /// Restore faces from an archive made by `backup export`
+    Import {
+        /// User UID the faces are restored for
+        user_id: u32,
+
+        /// Archive to read
+        file: PathBuf,
+
+        /// Read the passphrase from the first line of this file instead of
+        /// asking for it
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+}
+
+pub async fn run(action: BackupAction) -> anyhow::Result<()> {
+    match action {
+        BackupAction::Export {
+            user_id,
+            file,
+            passphrase_file,
+        } => command_export(user_id, &file, passphrase_file.as_deref()).await,
+        BackupAction::Import {
+            user_id,
+            file,
+            passphrase_file,
+        } => command_import(user_id, &file, passphrase_file.as_deref()).await,
+    }
+}
+
+async fn command_export(
+    user_id: u32,
+    file: &Path,
+    passphrase_file: Option<&Path>,
+) -> anyhow::Result<()> {
+    info!(
+        "Exporting the faces of UID {} to {}",
+        user_id,
+        file.display()
+    );
+
+    let passphrase = match passphrase_file {
+        Some(path) => read_passphrase_file(path)?,
+        None => {
+            let passphrase = prompt_passphrase("Backup passphrase: ")?;
+            if prompt_passphrase("Repeat passphrase: ")? != passphrase {
+                bail!("Passphrases don't match");
+            }
+            passphrase
+        }
+    };
+    // The daemon checks too; failing here saves a polkit prompt
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        bail!("Passphrase must be at least {MIN_PASSPHRASE_LEN} characters");
+    }
+
+    // Create the file before asking the daemon, so a bad path fails
+    // before the authentication prompt rather than after
+    let mut out = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(file)
+        .with_context(|| format!("Creating {}", file.display()))?;
+
+    let conn = Connection::session().await?;
+    let proxy = daemon_proxy(&conn).await?;
+    let request_json = serde_json::to_string(&ExportFacesRequest {
+        user_id,
+        passphrase,
+    })?;
+    let archive: Vec<u8> = match proxy.call("ExportFaces", &(request_json,)).await {
+        Ok(archive) => archive,
+        Err(e) => {
+            drop(out);
+            let _ = std::fs::remove_file(file);
+            return Err(daemon_call_error("ExportFaces", e));
+        }
+    };
+
+    out.write_all(&archive)
+        .and_then(|()| out.sync_all())
+        .with_context(|| format!("Writing {}", file.display()))?;
+
+    println!("✓ Faces of UID {} exported to {}", user_id, file.display());
+    println!("Keep the passphrase: without it the archive can't be restored.");
+
+    Ok(())
+}
+
+async fn command_import(
+    user_id: u32,
+    file: &Path,
+    passphrase_file: Option<&Path>,
+) -> anyhow::Result<()> {
+    info!(
+        "Importing faces for UID {} from {}",
+        user_id,
+        file.display()
+    );
+
+    let archive = std::fs::read(file).with_context(|| format!("Reading {}", file.display()))?;
+    let passphrase = match passphrase_file {
+        Some(path) => read_passphrase_file(path)?,
+        None => prompt_passphrase("Backup passphrase: ")?,
+    };
+
+    let conn = Connection::session().await?;
+    let proxy = daemon_proxy(&conn).await?;
+    let request_json = serde_json::to_string(&ImportFacesRequest {
+        user_id,
+        passphrase,
+    })?;
+    let response_json: String = proxy
+        .call("ImportFaces", &(request_json, archive))
+        .await
+        .map_err(|e| daemon_call_error("ImportFaces", e))?;
+    let response: ImportFacesResponse = serde_json::from_str(&response_json)?;
+
+    println!(
+        "✓ {} face(s) imported for UID {}",
+        response.imported, user_id
+    );
+    if response.skipped_incompatible > 0 {
+        println!(
+            "{} face(s) skipped: enrolled with another recognition model, enroll again instead",
+            response.skipped_incompatible
+        );
+    }
+
+    Ok(())
+}
+
+fn read_passphrase_file(path: &Path) -> anyhow::Result<String> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    Ok(contents.lines().next().unwrap_or_default().to_string())
+}
+
+/// Ask for a passphrase on the controlling terminal with echo turned off
+fn prompt_passphrase(prompt: &str) -> anyhow::Result<String> {
+    use std::os::fd::AsRawFd;
+
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .context("No terminal to ask for the passphrase on (use --passphrase-file)")?;
+    let fd = tty.as_raw_fd();
+
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        bail!(
+            "Reading terminal settings: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    let mut silent = saved;
+    silent.c_lflag &= !libc::ECHO;
+    silent.c_lflag |= libc::ECHONL;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &silent) };
+
+    let mut line = String::new();
+    let result = (&tty)
+        .write_all(prompt.as_bytes())
+        .and_then(|()| std::io::BufReader::new(&tty).read_line(&mut line));
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+    result.context("Reading the passphrase")?;
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
@@ -0,0 +1,398 @@
+//! `linux-hello calibrate` — error rates and thresholds from real scores
+//!
+//! `record` asks the daemon for a labelled capture session
+//! (`RecordCalibration`, only with `debug.record_scores` on); `report`
+//! reads what was recorded (see `hello_daemon::calibration`) and prints,
+//! per context, the FAR/FRR points of the similarity curve (ROC and DET
+//! plots are both drawn from them), the equal error rate and the lowest
+//! threshold keeping the false-accept rate under a target — then the same
+//! for the liveness gates.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::{Subcommand, ValueEnum};
+use hello_config::MIN_SIMILARITY_THRESHOLD;
+use hello_daemon::calibration::{
+    self, LivenessSource, OperatingPoint, SampleLabel, ScoreSample, ScoreSet,
+};
+use hello_daemon::dbus_interface::RecordCalibrationRequest;
+use hello_daemon::matcher::FaceMatcher;
+use tracing::info;
+use zbus::Connection;
+
+use super::{daemon_call_error, daemon_proxy};
+
+#[derive(Subcommand, Debug)]
+pub enum CalibrateAction {
+    /// Score frames against a user's faces and save the scores, labelled
+    Record {
+        /// User UID whose faces the frames are scored against
+        user_id: u32,
+
+        /// Who is in front of the camera
+        #[arg(short, long, value_enum)]
+        label: Label,
+
+        /// Context the scores are for
+        #[arg(short, long, default_value = "test")]
+        context: String,
+
+        /// Recording duration in seconds
+        #[arg(short, long, default_value = "10")]
+        duration: u64,
+    },
+
+    /// FAR/FRR curves, EER and recommended thresholds from recorded scores
+    Report {
+        /// Score files to pool (default: the daemon's calibration.jsonl)
+        files: Vec<PathBuf>,
+
+        /// False-accept rate the recommended thresholds are for
+        #[arg(long, default_value = "0.001")]
+        target_far: f64,
+
+        /// Only use scores of this embedding model ("<model> <version>")
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Also write every point of every curve to this CSV file
+        #[arg(long)]
+        csv: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Label {
+    /// The enrolled user
+    Genuine,
+
+    /// Someone else
+    Impostor,
+
+    /// A photo, screen or mask of the enrolled user
+    Spoof,
+}
+
+impl From<Label> for SampleLabel {
+    fn from(label: Label) -> Self {
+        match label {
+            Label::Genuine => SampleLabel::Genuine,
+            Label::Impostor => SampleLabel::Impostor,
+            Label::Spoof => SampleLabel::Spoof,
+        }
+    }
+}
+
+pub async fn run(action: CalibrateAction) -> anyhow::Result<()> {
+    match action {
+        CalibrateAction::Record {
+            user_id,
+            label,
+            context,
+            duration,
+        } => command_record(user_id, label, context, duration).await,
+        CalibrateAction::Report {
+            files,
+            target_far,
+            model,
+            csv,
+        } => command_report(files, target_far, model, csv),
+    }
+}
+
+async fn command_record(
+    user_id: u32,
+    label: Label,
+    context: String,
+    duration: u64,
+) -> anyhow::Result<()> {
+    info!(
+        "Recording {:?} scores for UID {} (context: {}) for {}s",
+        label, user_id, context, duration
+    );
+
+    let conn = Connection::session().await?;
+    let proxy = daemon_proxy(&conn).await?;
+
+    let request = RecordCalibrationRequest {
+        user_id,
+        label: label.into(),
+        context,
+        duration_ms: duration.saturating_mul(1000),
+    };
+    let request_json = serde_json::to_string(&request)?;
+
+    let recorded: u32 = proxy
+        .call("RecordCalibration", &(request_json,))
+        .await
+        .map_err(|e| daemon_call_error("Recording", e))?;
+
+    if recorded == 0 {
+        println!("✗ No face seen, nothing recorded");
+    } else {
+        println!("✓ {} frame(s) recorded", recorded);
+    }
+    Ok(())
+}
+
+fn command_report(
+    files: Vec<PathBuf>,
+    target_far: f64,
+    model: Option<String>,
+    csv: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    if !(target_far > 0.0 && target_far < 1.0) {
+        bail!("--target-far must be between 0 and 1, e.g. 0.001 for 0.1%");
+    }
+    let files = if files.is_empty() {
+        vec![hello_daemon::DaemonConfig::default()
+            .storage_path
+            .join(calibration::SCORES_FILE)]
+    } else {
+        files
+    };
+
+    let mut samples = Vec::new();
+    for file in &files {
+        samples.extend(
+            calibration::load_samples(file)
+                .with_context(|| format!("cannot read scores from {}", file.display()))?,
+        );
+    }
+    if let Some(model) = &model {
+        samples.retain(|sample| &sample.model == model);
+    }
+    let models: BTreeSet<&str> = samples.iter().map(|s| s.model.as_str()).collect();
+    if models.len() > 1 {
+        bail!(
+            "the scores come from several embedding models ({}), which don't compare: \
+             pick one with --model",
+            models.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    let Some(model) = models.first() else {
+        bail!("no recorded scores (see `linux-hello calibrate record`)");
+    };
+
+    let config = hello_config::Config::load()?.config;
+    let matcher = FaceMatcher::new(&config.recognition);
+    let report = render_report(&samples, model, target_far, |context| {
+        matcher.get_threshold(context)
+    });
+    print!("{report}");
+
+    if let Some(path) = csv {
+        std::fs::write(&path, render_csv(&samples))
+            .with_context(|| format!("cannot write {}", path.display()))?;
+        info!("Curves written to {}", path.display());
+    }
+    Ok(())
+}
+
+fn percent(rate: f64) -> String {
+    format!("{:.3}%", rate * 100.0)
+}
+
+/// One line of error rates at `point`
+fn rates(point: &OperatingPoint) -> String {
+    format!("FAR {}  FRR {}", percent(point.far), percent(point.frr))
+}
+
+/// Contexts with recorded similarity scores, in order
+fn contexts(samples: &[ScoreSample]) -> BTreeSet<&str> {
+    samples
+        .iter()
+        .filter(|s| s.label != SampleLabel::Spoof)
+        .map(|s| s.context.as_str())
+        .collect()
+}
+
+fn render_report(
+    samples: &[ScoreSample],
+    model: &str,
+    target_far: f64,
+    current_threshold: impl Fn(&str) -> f32,
+) -> String {
+    let count = |label| samples.iter().filter(|s| s.label == label).count();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} scores of model {}: {} genuine, {} impostor, {} spoof",
+        samples.len(),
+        model,
+        count(SampleLabel::Genuine),
+        count(SampleLabel::Impostor),
+        count(SampleLabel::Spoof)
+    );
+    let _ = writeln!(out, "Target FAR: {}", percent(target_far));
+
+    let pooled = ScoreSet::similarity(samples, None);
+    let mut recommended = Vec::new();
+    for context in contexts(samples) {
+        let own = ScoreSet::similarity(samples, Some(context));
+        let _ = writeln!(
+            out,
+            "\nSimilarity [{}]: {} genuine, {} impostor",
+            context,
+            own.genuine.len(),
+            own.impostor.len()
+        );
+        let set = if own.is_usable() {
+            &own
+        } else if pooled.is_usable() {
+            let _ = writeln!(
+                out,
+                "  not both genuine and impostor scores here: using every context's"
+            );
+            &pooled
+        } else {
+            let _ = writeln!(
+                out,
+                "  needs both genuine and impostor scores: record some of each"
+            );
+            continue;
+        };
+
+        let current = current_threshold(context);
+        if let Some(point) = render_curve(
+            &mut out,
+            set,
+            "impostor",
+            current,
+            target_far,
+            MIN_SIMILARITY_THRESHOLD,
+        ) {
+            recommended.push((context, point.threshold));
+        }
+    }
+
+    for source in [LivenessSource::Ir, LivenessSource::Rgb] {
+        let set = ScoreSet::liveness(samples, source);
+        if set.genuine.is_empty() && set.impostor.is_empty() {
+            continue;
+        }
+        let _ = writeln!(
+            out,
+            "\nLiveness [{}]: {} live, {} spoof",
+            match source {
+                LivenessSource::Ir => "IR",
+                LivenessSource::Rgb => "RGB fallback",
+            },
+            set.genuine.len(),
+            set.impostor.len()
+        );
+        if !set.is_usable() {
+            let _ = writeln!(
+                out,
+                "  needs both live and spoof scores: record some of each"
+            );
+            continue;
+        }
+        render_curve(&mut out, &set, "spoof", source.gate(), target_far, 0.0);
+        let _ = writeln!(
+            out,
+            "  (the liveness gates are built in: changing one is a code change)"
+        );
+    }
+
+    if !recommended.is_empty() {
+        let _ = writeln!(out, "\nSuggested config:");
+        for (context, threshold) in recommended {
+            let _ = writeln!(
+                out,
+                "\n[recognition.contexts.{context}]\nsimilarity_threshold = {threshold:.2}"
+            );
+        }
+    }
+    out
+}
+
+/// EER, the current threshold, the recommended one and DET points of
+/// `set`, whose scores to reject are `rejected` ones; returns the
+/// recommended point
+fn render_curve(
+    out: &mut String,
+    set: &ScoreSet,
+    rejected: &str,
+    current: f32,
+    target_far: f64,
+    floor: f32,
+) -> Option<OperatingPoint> {
+    if let Some(eer) = set.eer() {
+        let _ = writeln!(out, "  EER {} at {:.3}", percent(eer.rate), eer.threshold);
+    }
+    let _ = writeln!(
+        out,
+        "  current   {:.2}: {}",
+        current,
+        rates(&set.at(current))
+    );
+    let recommended = set.recommend(target_far, floor)?;
+    let _ = writeln!(
+        out,
+        "  for FAR ≤ {} {:.2}: {}",
+        percent(target_far),
+        recommended.threshold,
+        rates(&recommended)
+    );
+    if target_far < set.supported_far() {
+        let _ = writeln!(
+            out,
+            "  ! {} {} scores only back up a FAR of about {}: record more",
+            set.impostor.len(),
+            rejected,
+            percent(set.supported_far())
+        );
+    }
+
+    // DET points every 0.05 over the range the scores span
+    let lowest = set.genuine[0].min(set.impostor[0]);
+    let highest = set.genuine[set.genuine.len() - 1].max(set.impostor[set.impostor.len() - 1]);
+    let _ = writeln!(out, "  threshold  FAR       FRR       TAR");
+    let mut step = (lowest * 20.0).floor() as i32;
+    while step as f32 / 20.0 <= highest {
+        let point = set.at(step as f32 / 20.0);
+        let _ = writeln!(
+            out,
+            "  {:<9.2}  {:<8}  {:<8}  {}",
+            point.threshold,
+            percent(point.far),
+            percent(point.frr),
+            percent(1.0 - point.frr)
+        );
+        step += 1;
+    }
+    Some(recommended)
+}
+
+/// `curve,threshold,far,frr` for every point of every curve
+fn render_csv(samples: &[ScoreSample]) -> String {
+    let mut out = String::from("curve,threshold,far,frr\n");
+    let mut curve = |name: String, set: ScoreSet| {
+        for point in set.curve() {
+            let _ = writeln!(
+                out,
+                "{},{},{},{}",
+                name, point.threshold, point.far, point.frr
+            );
+        }
+    };
+    for context in contexts(samples) {
+        curve(
+            format!("similarity:{context}"),
+            ScoreSet::similarity(samples, Some(context)),
+        );
+    }
+    curve(
+        "liveness:ir".into(),
+        ScoreSet::liveness(samples, LivenessSource::Ir),
+    );
+    curve(
+        "liveness:rgb".into(),
+        ScoreSet::liveness(samples, LivenessSource::Rgb),
+    );
+    out
+}
@@ -0,0 +1,196 @@
+//! `linux-hello config` — read and edit `config.toml`
+//!
+//! Edits go through `hello_config::ConfigDocument`, so comments and
+//! formatting in the file survive, and every change is type- and
+//! range-checked the way the daemon checks the file when it loads it. A
+//! running `hello-daemon` picks the saved file up by itself (see
+//! `hello_daemon::config_reload`); nothing needs restarting.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::Subcommand;
+use hello_config::{
+    Config, ConfigDocument, Policy, OPTIONAL_KEYS, POLICY_PATH, SYSTEM_CONFIG_PATH,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the effective value of a key (e.g. recognition.similarity_threshold)
+    Get {
+        /// Dotted key
+        key: String,
+    },
+
+    /// Set a key in your config file, keeping its comments
+    Set {
+        /// Dotted key
+        key: String,
+
+        /// New value (strings don't need quoting)
+        value: String,
+
+        /// Edit /etc/linux-hello/config.toml instead (needs root)
+        #[arg(long)]
+        system: bool,
+    },
+
+    /// Remove a key from your config file, so the system value or default applies
+    Unset {
+        /// Dotted key
+        key: String,
+
+        /// Edit /etc/linux-hello/config.toml instead (needs root)
+        #[arg(long)]
+        system: bool,
+    },
+
+    /// Check the configuration the daemon would load, or a single file
+    Validate {
+        /// File to check on its own
+        path: Option<PathBuf>,
+    },
+
+    /// Print your config file, or every effective value and where it came from
+    Show {
+        /// Every effective value, with its origin (default, system, user, policy)
+        #[arg(long)]
+        effective: bool,
+
+        /// Print /etc/linux-hello/config.toml instead
+        #[arg(long)]
+        system: bool,
+    },
+
+    /// Print the JSON Schema of config.toml (types, defaults, ranges, and
+    /// which keys need a daemon restart)
+    Schema,
+}
+
+pub fn run(action: ConfigAction) -> anyhow::Result<()> {
+    match action {
+        ConfigAction::Get { key } => command_get(&key),
+        ConfigAction::Set { key, value, system } => command_set(&key, &value, system),
+        ConfigAction::Unset { key, system } => command_unset(&key, system),
+        ConfigAction::Validate { path } => command_validate(path.as_deref()),
+        ConfigAction::Show { effective, system } => command_show(effective, system),
+        ConfigAction::Schema => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&hello_config::schema::json_schema())?
+            );
+            Ok(())
+        }
+    }
+}
+
+/// File `set`/`unset`/`show` work on
+fn target_path(system: bool) -> anyhow::Result<PathBuf> {
+    if system {
+        return Ok(PathBuf::from(SYSTEM_CONFIG_PATH));
+    }
+    hello_config::user_config_path().context("cannot determine the user config directory")
+}
+
+fn command_get(key: &str) -> anyhow::Result<()> {
+    let loaded = Config::load()?;
+    match loaded.config.value(key) {
+        Some(value) => {
+            println!("{}", value);
+            Ok(())
+        }
+        None if Config::default().value(key).is_none() && !OPTIONAL_KEYS.contains(&key) => {
+            bail!("Unknown configuration key `{}`", key)
+        }
+        None => bail!("`{}` is not set", key),
+    }
+}
+
+fn command_set(key: &str, value: &str, system: bool) -> anyhow::Result<()> {
+    let (policy, _) = Policy::load(Path::new(POLICY_PATH))?;
+    if policy.is_locked(key) {
+        if !system {
+            bail!(
+                "`{}` is locked by administrator policy ({})",
+                key,
+                POLICY_PATH
+            );
+        }
+        eprintln!(
+            "Note: `{}` is locked by {}; the policy's value stays in effect",
+            key, POLICY_PATH
+        );
+    }
+
+    let path = target_path(system)?;
+    let mut document = ConfigDocument::open(&path)?;
+    document.set(key, value)?;
+    document.save()?;
+    println!(
+        "✓ {} = {} in {}",
+        key,
+        document.get(key).unwrap_or_default(),
+        path.display()
+    );
+    Ok(())
+}
+
+fn command_unset(key: &str, system: bool) -> anyhow::Result<()> {
+    let path = target_path(system)?;
+    let mut document = ConfigDocument::open(&path)?;
+    if !document.unset(key) {
+        println!("{} is not set in {}", key, path.display());
+        return Ok(());
+    }
+    document.save()?;
+    println!("✓ Removed {} from {}", key, path.display());
+    Ok(())
+}
+
+fn command_validate(path: Option<&Path>) -> anyhow::Result<()> {
+    if let Some(path) = path {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        for unknown in Config::check_text(path, &text)? {
+            println!("⚠ {} (ignored)", unknown);
+        }
+        println!("✓ {} is valid", path.display());
+        return Ok(());
+    }
+
+    let loaded = Config::load()?;
+    for source in &loaded.sources {
+        println!("  read {}", source.display());
+    }
+    for unknown in &loaded.unknown_keys {
+        println!("⚠ {} (ignored)", unknown);
+    }
+    for overridden in &loaded.locked_overrides {
+        println!("⚠ {} (ignored)", overridden);
+    }
+    println!("✓ Configuration is valid");
+    Ok(())
+}
+
+fn command_show(effective: bool, system: bool) -> anyhow::Result<()> {
+    if effective {
+        let loaded = Config::load()?;
+        for (key, value, origin) in loaded.effective_values() {
+            println!("{} = {}  # {}", key, value, origin);
+        }
+        return Ok(());
+    }
+
+    let path = target_path(system)?;
+    match std::fs::read_to_string(&path) {
+        Ok(text) => print!("{}", text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!(
+                "# {} does not exist; every key has its default",
+                path.display()
+            )
+        }
+        Err(e) => return Err(e).with_context(|| format!("cannot read {}", path.display())),
+    }
+    Ok(())
+}
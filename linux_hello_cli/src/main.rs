@@ -5,12 +5,23 @@
 //! - linux-hello enroll $UID : enroll a face
 //! - linux-hello verify $UID : test a verification
 //! - linux-hello list $UID   : list enrolled faces
+//! - linux-hello label $UID $FACE_ID NAME : name an enrolled face
+//! - linux-hello contexts $UID $FACE_ID CONTEXT... : restrict a face to contexts
+//! - linux-hello config ...  : read/edit config.toml (see `config`)
+//! - linux-hello backup ...  : export/import encrypted enrollments (see `backup`)
+//! - linux-hello calibrate ... : record labelled scores, report FAR/FRR (see `calibrate`)
+
+mod backup;
+mod calibrate;
+mod config;
 
 use clap::{Parser, Subcommand};
 use hello_daemon::dbus_interface::{
-    DeleteFaceRequest, RegisterFaceRequest, RegisterFaceResponse, VerifyRequest, VerifyResult,
+    DeleteFaceRequest, RegisterFaceRequest, RegisterFaceResponse, ResetAdaptationRequest,
+    UpdateFaceRequest, VerifyRequest, VerifyResult,
 };
-use hello_daemon::FaceRecord;
+use hello_daemon::storage::FaceStats;
+use hello_daemon::{FaceRecord, SECS_PER_DAY};
 use tracing::{info, Level};
 use zbus::Connection;
 
@@ -51,6 +62,11 @@ enum Commands {
         /// Number of samples to take
         #[arg(short, long, default_value = "3")]
         samples: u32,
+
+        /// Only accept this face for the given context (repeatable);
+        /// without it the face works for every context
+        #[arg(long = "allow", value_name = "CONTEXT")]
+        allowed_contexts: Vec<String>,
     },
 
     /// Test the verification
@@ -73,6 +89,39 @@ enum Commands {
         user_id: u32,
     },
 
+    /// Set the label and/or notes of an enrolled face
+    Label {
+        /// User UID
+        user_id: u32,
+
+        /// Face ID (see `list`)
+        face_id: String,
+
+        /// New label, e.g. "with glasses" ("" removes it)
+        label: Option<String>,
+
+        /// New notes ("" removes them)
+        #[arg(short, long)]
+        notes: Option<String>,
+    },
+
+    /// Restrict an enrolled face to some contexts, or lift the restriction
+    Contexts {
+        /// User UID
+        user_id: u32,
+
+        /// Face ID (see `list`)
+        face_id: String,
+
+        /// Contexts the face may authenticate (login, sudo, screenlock, ...)
+        #[arg(required_unless_present = "any")]
+        contexts: Vec<String>,
+
+        /// Let the face authenticate every context again
+        #[arg(long, conflicts_with = "contexts")]
+        any: bool,
+    },
+
     /// Delete all faces for a user
     Delete {
         /// UID
@@ -82,12 +131,39 @@ enum Commands {
         face_id: Option<String>,
     },
 
+    /// Undo what adaptation learned from logins, back to the faces as enrolled
+    Rollback {
+        /// UID
+        user_id: u32,
+
+        /// Specific face ID (optional)
+        face_id: Option<String>,
+    },
+
     /// Test the camera
     Camera {
         /// Test duration in seconds
         #[arg(short, long, default_value = "5")]
         duration: u64,
     },
+
+    /// Read, edit or check the configuration
+    Config {
+        #[command(subcommand)]
+        action: config::ConfigAction,
+    },
+
+    /// Export or import a user's enrollments as an encrypted archive
+    Backup {
+        #[command(subcommand)]
+        action: backup::BackupAction,
+    },
+
+    /// Record labelled match scores and derive thresholds from them
+    Calibrate {
+        #[command(subcommand)]
+        action: calibrate::CalibrateAction,
+    },
 }
 
 #[tokio::main]
@@ -101,7 +177,12 @@ async fn main() -> anyhow::Result<()> {
         Level::INFO
     };
 
-    tracing_subscriber::fmt().with_max_level(level).init();
+    // Logs go to stderr so `config get`/`config show` output stays usable
+    // in scripts.
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
 
     info!("Linux Hello CLI v{}", env!("CARGO_PKG_VERSION"));
 
@@ -111,22 +192,52 @@ async fn main() -> anyhow::Result<()> {
             user_id,
             context,
             samples,
-        } => command_enroll(user_id, &context, samples).await,
+            allowed_contexts,
+        } => command_enroll(user_id, &context, samples, allowed_contexts).await,
         Commands::Verify {
             user_id,
             context,
             timeout,
         } => command_verify(user_id, &context, timeout).await,
         Commands::List { user_id } => command_list(user_id).await,
+        Commands::Label {
+            user_id,
+            face_id,
+            label,
+            notes,
+        } => command_label(user_id, face_id, label, notes).await,
+        Commands::Contexts {
+            user_id,
+            face_id,
+            contexts,
+            any: _,
+        } => command_contexts(user_id, face_id, contexts).await,
         Commands::Delete { user_id, face_id } => command_delete(user_id, face_id).await,
+        Commands::Rollback { user_id, face_id } => command_rollback(user_id, face_id).await,
         Commands::Camera { duration } => command_camera(duration).await,
+        Commands::Config { action } => config::run(action),
+        Commands::Backup { action } => backup::run(action).await,
+        Commands::Calibrate { action } => calibrate::run(action).await,
     }
 }
 
 async fn command_daemon(storage: Option<std::path::PathBuf>) -> anyhow::Result<()> {
     info!("Starting daemon");
 
-    let mut config = hello_daemon::DaemonConfig::default();
+    let loaded = hello_config::Config::load()?;
+    for unknown in &loaded.unknown_keys {
+        tracing::warn!("Configuration: {} (ignored)", unknown);
+    }
+
+    for overridden in &loaded.locked_overrides {
+        tracing::warn!("Configuration: {} (ignored)", overridden);
+    }
+
+    let mut config = hello_daemon::DaemonConfig {
+        settings: loaded.config,
+        policy: loaded.policy,
+        ..hello_daemon::DaemonConfig::default()
+    };
     if let Some(path) = storage {
         config.storage_path = path;
     }
@@ -165,7 +276,12 @@ fn daemon_call_error(action: &str, e: zbus::Error) -> anyhow::Error {
     anyhow::anyhow!("{action} failed: {e}")
 }
 
-async fn command_enroll(user_id: u32, context: &str, samples: u32) -> anyhow::Result<()> {
+async fn command_enroll(
+    user_id: u32,
+    context: &str,
+    samples: u32,
+    allowed_contexts: Vec<String>,
+) -> anyhow::Result<()> {
     info!(
         "Enrolling a face for UID {} (context: {})",
         user_id, context
@@ -180,6 +296,7 @@ async fn command_enroll(user_id: u32, context: &str, samples: u32) -> anyhow::Re
         context: context.to_string(),
         timeout_ms: 10_000,
         num_samples: samples,
+        allowed_contexts,
     };
     let request_json = serde_json::to_string(&request)?;
 
@@ -230,6 +347,36 @@ async fn command_verify(user_id: u32, context: &str, timeout: u64) -> anyhow::Re
     Ok(())
 }
 
+/// The `list` line about how often and how well a face has matched
+fn print_match_stats(stats: &FaceStats) {
+    let Some(last) = stats.last_matched_at else {
+        println!("      never matched");
+        return;
+    };
+    let mut line = format!(
+        "      matches={}  last_matched_at={}",
+        stats.match_count, last
+    );
+    if let (Some(mean), Some(min)) = (stats.mean_score(), stats.min_score()) {
+        line += &format!(
+            "  recent_scores: n={} mean={:.2} min={:.2}",
+            stats.recent_scores.len(),
+            mean,
+            min
+        );
+    }
+    if let Some(trend) = stats.score_trend() {
+        line += &format!(" trend={:+.2}", trend);
+    }
+    if stats.adapted_samples > 0 {
+        line += &format!("  adapted={}", stats.adapted_samples);
+    }
+    println!("{}", line);
+    if stats.declining {
+        println!("      (scores dropping: consider enrolling this face again)");
+    }
+}
+
 async fn command_list(user_id: u32) -> anyhow::Result<()> {
     info!("Listing enrolled faces for UID {}", user_id);
 
@@ -246,17 +393,124 @@ async fn command_list(user_id: u32) -> anyhow::Result<()> {
         println!("No faces enrolled for UID {}", user_id);
     } else {
         println!("{} face(s) enrolled for UID {}:", faces.len(), user_id);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
         for f in &faces {
             println!(
-                "  {}  context={}  quality={:.2}  registered_at={}",
-                f.face_id, f.context, f.quality_score, f.registered_at
+                "  {}{}  context={}{}  quality={:.2}  registered_at={}  age={}d{}{}",
+                f.face_id,
+                f.label
+                    .as_deref()
+                    .map(|label| format!("  \"{}\"", label))
+                    .unwrap_or_default(),
+                f.context,
+                if f.allowed_contexts.is_empty() {
+                    String::new()
+                } else {
+                    format!("  only_for={}", f.allowed_contexts.join(","))
+                },
+                f.quality_score,
+                f.registered_at,
+                now.saturating_sub(f.registered_at) / SECS_PER_DAY,
+                match f.expires_at {
+                    Some(at) if at <= now => "  (expired: enroll again)".to_string(),
+                    Some(at) => format!("  expires_in={}d", (at - now).div_ceil(SECS_PER_DAY)),
+                    None => String::new(),
+                },
+                if f.needs_reenrollment {
+                    "  (re-enrollment required: enrolled with another recognition model)"
+                } else {
+                    ""
+                }
             );
+            print_match_stats(&f.stats);
+            if let Some(notes) = &f.notes {
+                for line in notes.lines() {
+                    println!("      {}", line);
+                }
+            }
         }
     }
 
     Ok(())
 }
 
+async fn command_label(
+    user_id: u32,
+    face_id: String,
+    label: Option<String>,
+    notes: Option<String>,
+) -> anyhow::Result<()> {
+    if label.is_none() && notes.is_none() {
+        anyhow::bail!("Nothing to change: give a label and/or --notes");
+    }
+    info!("Updating face {} for UID {}", face_id, user_id);
+
+    let conn = Connection::session().await?;
+    let proxy = daemon_proxy(&conn).await?;
+
+    let request = UpdateFaceRequest {
+        user_id,
+        face_id,
+        label,
+        notes,
+        allowed_contexts: None,
+    };
+    let request_json = serde_json::to_string(&request)?;
+
+    proxy
+        .call::<_, _, ()>("UpdateFace", &(request_json,))
+        .await
+        .map_err(|e| daemon_call_error("UpdateFace", e))?;
+
+    println!("✓ Updated");
+
+    Ok(())
+}
+
+async fn command_contexts(
+    user_id: u32,
+    face_id: String,
+    contexts: Vec<String>,
+) -> anyhow::Result<()> {
+    if contexts.is_empty() {
+        info!(
+            "Allowing face {} of UID {} for every context",
+            face_id, user_id
+        );
+    } else {
+        info!(
+            "Restricting face {} of UID {} to {}",
+            face_id,
+            user_id,
+            contexts.join(", ")
+        );
+    }
+
+    let conn = Connection::session().await?;
+    let proxy = daemon_proxy(&conn).await?;
+
+    let request = UpdateFaceRequest {
+        user_id,
+        face_id,
+        label: None,
+        notes: None,
+        allowed_contexts: Some(contexts),
+    };
+    let request_json = serde_json::to_string(&request)?;
+
+    proxy
+        .call::<_, _, ()>("UpdateFace", &(request_json,))
+        .await
+        .map_err(|e| daemon_call_error("UpdateFace", e))?;
+
+    println!("✓ Updated");
+
+    Ok(())
+}
+
 async fn command_delete(user_id: u32, face_id: Option<String>) -> anyhow::Result<()> {
     if let Some(id) = &face_id {
         info!("Deleting face {} for UID {}", id, user_id);
@@ -280,12 +534,42 @@ async fn command_delete(user_id: u32, face_id: Option<String>) -> anyhow::Result
     Ok(())
 }
 
+async fn command_rollback(user_id: u32, face_id: Option<String>) -> anyhow::Result<()> {
+    match &face_id {
+        Some(id) => info!("Resetting adaptation of face {} for UID {}", id, user_id),
+        None => info!("Resetting adaptation of all faces for UID {}", user_id),
+    }
+
+    let conn = Connection::session().await?;
+    let proxy = daemon_proxy(&conn).await?;
+
+    let request = ResetAdaptationRequest { user_id, face_id };
+    let request_json = serde_json::to_string(&request)?;
+
+    let dropped: u32 = proxy
+        .call("ResetAdaptation", &(request_json,))
+        .await
+        .map_err(|e| daemon_call_error("ResetAdaptation", e))?;
+
+    println!("✓ Rolled back ({} adapted sample(s) dropped)", dropped);
+
+    Ok(())
+}
+
 async fn command_camera(duration: u64) -> anyhow::Result<()> {
     info!("Camera test for {}s", duration);
 
+    // Same device and mode the daemon would use
+    let detection = hello_config::Config::load()?.config.detection;
+    let device =
+        hello_daemon::camera::select_devices(hello_camera::scan_cameras(), &detection).rgb_device;
+    let mode = hello_daemon::camera::capture_mode(&detection);
+    println!("Camera {} (requested {})", device, mode);
+
     let mut frame_count = 0u32;
     if let Err(e) = hello_camera::capture_rgb_stream_until(
-        "/dev/video0",
+        &device,
+        &mode,
         duration * 1000,
         |data, width, height| {
             frame_count += 1;
@@ -326,6 +610,7 @@ mod tests {
                 user_id,
                 context,
                 samples,
+                ..
             } => {
                 assert_eq!(user_id, 1000);
                 assert_eq!(context, "test");
@@ -344,6 +629,7 @@ mod tests {
                 user_id,
                 context,
                 samples,
+                ..
             } => {
                 assert_eq!(user_id, 1000);
                 assert_eq!(context, "sudo");
@@ -397,6 +683,66 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rollback_parses_uid_and_optional_face_id() {
+        match parse(&["rollback", "1000"]).command {
+            Commands::Rollback { user_id, face_id } => {
+                assert_eq!(user_id, 1000);
+                assert_eq!(face_id, None);
+            }
+            _ => panic!("expected Rollback"),
+        }
+        match parse(&["rollback", "1000", "face_1000_123"]).command {
+            Commands::Rollback { face_id, .. } => {
+                assert_eq!(face_id, Some("face_1000_123".to_string()))
+            }
+            _ => panic!("expected Rollback"),
+        }
+    }
+
+    #[test]
+    fn test_calibrate_parses_record_and_report() {
+        match parse(&["calibrate", "record", "1000", "--label", "impostor"]).command {
+            Commands::Calibrate {
+                action:
+                    calibrate::CalibrateAction::Record {
+                        user_id,
+                        label,
+                        context,
+                        duration,
+                    },
+            } => {
+                assert_eq!(user_id, 1000);
+                assert_eq!(label, calibrate::Label::Impostor);
+                assert_eq!(context, "test");
+                assert_eq!(duration, 10);
+            }
+            _ => panic!("expected Calibrate Record"),
+        }
+        match parse(&[
+            "calibrate",
+            "report",
+            "a.jsonl",
+            "b.jsonl",
+            "--target-far",
+            "0.01",
+        ])
+        .command
+        {
+            Commands::Calibrate {
+                action:
+                    calibrate::CalibrateAction::Report {
+                        files, target_far, ..
+                    },
+            } => {
+                assert_eq!(files.len(), 2);
+                assert_eq!(target_far, 0.01);
+            }
+            _ => panic!("expected Calibrate Report"),
+        }
+        assert!(Cli::try_parse_from(["linux-hello", "calibrate", "record", "1000"]).is_err());
+    }
+
     #[test]
     fn test_delete_parses_uid_with_a_face_id() {
         let cli = parse(&["delete", "1000", "face_1000_123"]);
@@ -440,6 +786,160 @@ mod tests {
         assert!(cli.verbose);
     }
 
+    #[test]
+    fn test_config_set_parses_key_value_and_target() {
+        let cli = parse(&["config", "set", "detection.camera_device", "/dev/video2"]);
+        match cli.command {
+            Commands::Config {
+                action: config::ConfigAction::Set { key, value, system },
+            } => {
+                assert_eq!(key, "detection.camera_device");
+                assert_eq!(value, "/dev/video2");
+                assert!(!system);
+            }
+            _ => panic!("expected Config Set"),
+        }
+
+        let cli = parse(&["config", "unset", "--system", "capture.enrollment_samples"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Config {
+                action: config::ConfigAction::Unset { system: true, .. }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_config_show_parses_effective_flag() {
+        let cli = parse(&["config", "show", "--effective"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Config {
+                action: config::ConfigAction::Show {
+                    effective: true,
+                    system: false
+                }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_config_schema_parses() {
+        let cli = parse(&["config", "schema"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Config {
+                action: config::ConfigAction::Schema
+            }
+        ));
+    }
+
+    #[test]
+    fn test_enroll_and_contexts_parse_allowed_contexts() {
+        let cli = parse(&[
+            "enroll",
+            "1000",
+            "--allow",
+            "screenlock",
+            "--allow",
+            "login",
+        ]);
+        match cli.command {
+            Commands::Enroll {
+                allowed_contexts, ..
+            } => assert_eq!(allowed_contexts, vec!["screenlock", "login"]),
+            _ => panic!("expected Enroll"),
+        }
+
+        let cli = parse(&["contexts", "1000", "face_1000_1", "screenlock"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Contexts { contexts, any: false, .. } if contexts == ["screenlock"]
+        ));
+
+        let cli = parse(&["contexts", "1000", "face_1000_1", "--any"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Contexts { contexts, any: true, .. } if contexts.is_empty()
+        ));
+
+        // Either some contexts or --any, never both or neither
+        assert!(Cli::try_parse_from(["linux-hello", "contexts", "1000", "face_1"]).is_err());
+        assert!(Cli::try_parse_from([
+            "linux-hello",
+            "contexts",
+            "1000",
+            "face_1",
+            "sudo",
+            "--any"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_label_parses_label_and_notes() {
+        let cli = parse(&[
+            "label",
+            "1000",
+            "face_1000_1",
+            "with glasses",
+            "--notes",
+            "office",
+        ]);
+        match cli.command {
+            Commands::Label {
+                user_id,
+                face_id,
+                label,
+                notes,
+            } => {
+                assert_eq!(user_id, 1000);
+                assert_eq!(face_id, "face_1000_1");
+                assert_eq!(label.as_deref(), Some("with glasses"));
+                assert_eq!(notes.as_deref(), Some("office"));
+            }
+            _ => panic!("expected Label"),
+        }
+    }
+
+    #[test]
+    fn test_backup_export_and_import_parse() {
+        let cli = parse(&["backup", "export", "1000", "faces.lhbak"]);
+        match cli.command {
+            Commands::Backup {
+                action:
+                    backup::BackupAction::Export {
+                        user_id,
+                        file,
+                        passphrase_file,
+                    },
+            } => {
+                assert_eq!(user_id, 1000);
+                assert_eq!(file, std::path::PathBuf::from("faces.lhbak"));
+                assert!(passphrase_file.is_none());
+            }
+            _ => panic!("expected Backup Export"),
+        }
+
+        let cli = parse(&[
+            "backup",
+            "import",
+            "1000",
+            "faces.lhbak",
+            "--passphrase-file",
+            "pass.txt",
+        ]);
+        assert!(matches!(
+            cli.command,
+            Commands::Backup {
+                action: backup::BackupAction::Import {
+                    passphrase_file: Some(_),
+                    ..
+                }
+            }
+        ));
+    }
+
     #[test]
     fn test_daemon_call_error_wraps_a_generic_zbus_error() {
         let err = daemon_call_error("Enrollment", zbus::Error::Address("bad address".into()));
@@ -13,12 +13,31 @@ QtObject {
     property bool sddmAvailable: false
     property bool sddmBusy: false
     property string sddmError: ""
+    // Config keys locked by /etc/linux-hello/policy.toml — see
+    // loadPolicy(). Locked settings render read-only.
+    property var lockedKeys: []
+    property bool policyManaged: false
     property string ctrlPort: "0"
     property string ctrlToken: ""
     property string mjpegToken: ""
     property string lastRegisteredFaceId: ""
     property var uidNameCache: ({})
 
+    // Empty strings clear the label/notes (see the /update-face route)
+    function updateFace(faceId, label, notes) {
+        if (ctrlPort === "0")
+            return;
+        var xhr = new XMLHttpRequest();
+        openAuthedRequest(xhr, "/update-face?id=" + encodeURIComponent(faceId)
+            + "&label=" + encodeURIComponent(label)
+            + "&notes=" + encodeURIComponent(notes));
+        xhr.onreadystatechange = function () {
+            if (xhr.readyState === XMLHttpRequest.DONE && xhr.status === 200)
+                controller.loadFaces();
+        };
+        xhr.send();
+    }
+
     // Whether the active camera has an IR channel — defaults to true so the
     // Enrollment screen doesn't flash a false "weaker security" warning
     // before this loads (or if the daemon is briefly unreachable; see
@@ -115,6 +134,7 @@ QtObject {
         controller.uidNameCache = cache;
         checkDaemonStatus();
         checkSddmStatus();
+        loadPolicy();
         loadFaces();
         loadCameraInfo();
         loadAppInfo();
@@ -184,6 +204,40 @@ QtObject {
         xhr.send();
     }
 
+    // Administrator policy — which config keys users can't change (a plain
+    // file read on the backend, like checkSddmStatus()). Fails toward
+    // "nothing locked": the backend still refuses a locked change.
+    function loadPolicy() {
+        if (ctrlPort === "0")
+            return;
+        var xhr = new XMLHttpRequest();
+        openAuthedRequest(xhr, "/config-policy");
+        xhr.onreadystatechange = function () {
+            if (xhr.readyState !== XMLHttpRequest.DONE)
+                return;
+            try {
+                var resp = JSON.parse(xhr.responseText);
+                controller.lockedKeys = resp.locked || [];
+                controller.policyManaged = !!resp.managed;
+            } catch (e) {
+                controller.lockedKeys = [];
+                controller.policyManaged = false;
+            }
+        };
+        xhr.send();
+    }
+
+    // Whether `key` (dotted, e.g. "authentication.enable_for_sddm") is
+    // locked, itself or through a locked parent table — same rule as
+    // hello_config::Policy::is_locked.
+    function isLocked(key) {
+        for (var i = 0; i < lockedKeys.length; i++) {
+            if (key === lockedKeys[i] || key.indexOf(lockedKeys[i] + ".") === 0)
+                return true;
+        }
+        return false;
+    }
+
     // Enables or disables SDDM login-screen face auth, depending on the
     // last-known state. Triggers a real pkexec prompt on the backend — can
     // take several seconds while the user interacts with it.
@@ -389,6 +443,7 @@ QtObject {
     function navigateToHomeImpl() {
         checkDaemonStatus();
         checkSddmStatus();
+        loadPolicy();
         loadFaces();
         navigateToHomeSignal();
     }
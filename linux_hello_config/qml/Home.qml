@@ -191,7 +191,8 @@ Kirigami.Page {
             // Its trailing element is a busy spinner instead of the default
             // chevron, since there's no sub-page to navigate to.
             ActionCard {
-                enabled: AppController.sddmAvailable && !AppController.sddmBusy
+                readonly property bool locked: AppController.isLocked("authentication.enable_for_sddm")
+                enabled: AppController.sddmAvailable && !AppController.sddmBusy && !locked
                 iconSource: "system-switch-user-symbolic"
                 iconColor: AppController.sddmActive ? Kirigami.Theme.positiveTextColor : Kirigami.Theme.highlightColor
                 badgeColor: AppController.sddmActive
@@ -201,6 +202,8 @@ Kirigami.Page {
                 subtitle: {
                     if (!AppController.sddmAvailable)
                         return I18n.tr("home.sddmUnavailableSub");
+                    if (locked)
+                        return I18n.tr("home.sddmLockedSub");
                     if (AppController.sddmBusy)
                         return I18n.tr("home.sddmBusySub");
                     return AppController.sddmActive ? I18n.tr("home.sddmActiveSub") : I18n.tr("home.sddmInactiveSub");
@@ -18,6 +18,47 @@ Kirigami.Page {
     // Reload the list every time the page becomes visible
     Component.onCompleted: AppController.loadFaces()
 
+    // Label/notes editor for one face; saved through the daemon's
+    // UpdateFace (behind the same re-authentication as deleting)
+    Kirigami.PromptDialog {
+        id: editDialog
+        property string faceId: ""
+
+        function openFor(face) {
+            faceId = face.face_id;
+            labelField.text = face.label || "";
+            notesField.text = face.notes || "";
+            open();
+        }
+
+        title: I18n.tr("manageFaces.editTitle")
+        standardButtons: Kirigami.Dialog.Save | Kirigami.Dialog.Cancel
+        onAccepted: AppController.updateFace(faceId, labelField.text, notesField.text)
+
+        ColumnLayout {
+            spacing: Kirigami.Units.smallSpacing
+
+            Label {
+                text: I18n.tr("manageFaces.labelField")
+            }
+            TextField {
+                id: labelField
+                Layout.fillWidth: true
+                maximumLength: 64
+                placeholderText: I18n.tr("manageFaces.labelPlaceholder")
+            }
+            Label {
+                text: I18n.tr("manageFaces.notesField")
+            }
+            TextArea {
+                id: notesField
+                Layout.fillWidth: true
+                Layout.preferredHeight: Kirigami.Units.gridUnit * 4
+                wrapMode: TextEdit.Wrap
+            }
+        }
+    }
+
     ColumnLayout {
         anchors.fill: parent
         spacing: Kirigami.Units.largeSpacing
@@ -85,7 +126,7 @@ Kirigami.Page {
                         anchors.left: avatarRect.right
                         anchors.leftMargin: Kirigami.Units.largeSpacing * 0.7
                         anchors.right: parent.right
-                        anchors.rightMargin: Kirigami.Units.mediumSpacing * 2 + Kirigami.Units.gridUnit * 1.8
+                        anchors.rightMargin: Kirigami.Units.mediumSpacing * 2 + Kirigami.Units.gridUnit * 3.6
                         anchors.verticalCenter: parent.verticalCenter
                         spacing: 3
 
@@ -101,13 +142,18 @@ Kirigami.Page {
                                     elide: Text.ElideRight
                                 }
                                 Label {
-                                    // Enrollment sessions aren't scoped to a context — any
-                                    // registered face can authenticate any context (sudo,
-                                    // screenlock, ...); this just distinguishes multiple
-                                    // independent enrollments for the same account.
-                                    text: "· " + I18n.tr("manageFaces.sample") + " " + (faceItem.index + 1)
+                                    // A face authenticates every context (sudo, screenlock,
+                                    // ...) unless restricted (the "Only for" line below);
+                                    // this just distinguishes multiple independent
+                                    // enrollments for the same account, by the label the
+                                    // user gave it if there is one.
+                                    text: "· " + (faceItem.modelData.label
+                                        ? faceItem.modelData.label
+                                        : I18n.tr("manageFaces.sample") + " " + (faceItem.index + 1))
                                     font.pixelSize: 11
-                                    color: Kirigami.Theme.disabledTextColor
+                                    color: faceItem.modelData.label ? Kirigami.Theme.textColor : Kirigami.Theme.disabledTextColor
+                                    elide: Text.ElideRight
+                                    Layout.fillWidth: true
                                 }
                             }
 
@@ -146,7 +192,100 @@ Kirigami.Page {
                                     color: Kirigami.Theme.disabledTextColor
                                 }
                             }
+
+                            // Restricted with `linux-hello contexts` / at enrollment
+                            Label {
+                                visible: (faceItem.modelData.allowed_contexts || []).length > 0
+                                text: I18n.tr("manageFaces.onlyContexts").replace("%1", (faceItem.modelData.allowed_contexts || []).join(", "))
+                                font.pixelSize: 11
+                                color: Kirigami.Theme.disabledTextColor
+                                elide: Text.ElideRight
+                                Layout.fillWidth: true
+                            }
+
+                            // Match history (the daemon's ListFaces "stats"): a
+                            // face that never matches, or matches less and less
+                            // well, is one to remove or enroll again
+                            Label {
+                                readonly property var stats: faceItem.modelData.stats || {}
+                                readonly property var scores: stats.recent_scores || []
+                                text: stats.last_matched_at
+                                    ? I18n.tr("manageFaces.matchStats")
+                                        .replace("%1", stats.match_count)
+                                        .replace("%2", new Date(stats.last_matched_at * 1000).toLocaleDateString())
+                                        .replace("%3", Math.round(scores.reduce((a, b) => a + b, 0) / Math.max(1, scores.length) * 100))
+                                    : I18n.tr("manageFaces.neverMatched")
+                                font.pixelSize: 11
+                                color: Kirigami.Theme.disabledTextColor
+                                elide: Text.ElideRight
+                                Layout.fillWidth: true
+                            }
+
+                            Label {
+                                visible: !!(faceItem.modelData.stats && faceItem.modelData.stats.declining)
+                                text: "⚠ " + I18n.tr("manageFaces.scoresDeclining")
+                                font.pixelSize: 11
+                                color: Kirigami.Theme.neutralTextColor
+                                wrapMode: Text.WordWrap
+                                Layout.fillWidth: true
+                            }
+
+                            Label {
+                                visible: !!faceItem.modelData.notes
+                                text: faceItem.modelData.notes || ""
+                                font.pixelSize: 11
+                                font.italic: true
+                                color: Kirigami.Theme.disabledTextColor
+                                wrapMode: Text.WordWrap
+                                Layout.fillWidth: true
+                            }
+
+                            // Enrolled with another recognition model (set by
+                            // the daemon's ListFaces): it can't match anymore
+                            Label {
+                                visible: faceItem.modelData.needs_reenrollment === true
+                                text: "⚠ " + I18n.tr("manageFaces.reenrollNeeded")
+                                font.pixelSize: 11
+                                color: Kirigami.Theme.negativeTextColor
+                                wrapMode: Text.WordWrap
+                                Layout.fillWidth: true
+                            }
+                        }
+
+                    // Rename — same plain Item + MouseArea as Delete below,
+                    // sitting just left of it
+                    Item {
+                        id: editBtn
+                        width: Kirigami.Units.gridUnit * 1.8
+                        height: Kirigami.Units.gridUnit * 1.8
+                        anchors.right: deleteBtn.left
+                        anchors.verticalCenter: parent.verticalCenter
+
+                        Accessible.name: I18n.tr("manageFaces.editBtn")
+                        ToolTip.visible: editMouse.containsMouse
+                        ToolTip.text: I18n.tr("manageFaces.editBtn")
+
+                        Rectangle {
+                            anchors.fill: parent
+                            radius: width / 2
+                            color: editMouse.containsMouse ? Qt.rgba(Kirigami.Theme.highlightColor.r, Kirigami.Theme.highlightColor.g, Kirigami.Theme.highlightColor.b, 0.12) : "transparent"
+                        }
+                        Kirigami.Icon {
+                            anchors.centerIn: parent
+                            width: Kirigami.Units.gridUnit * 0.85
+                            height: width
+                            source: "document-edit-symbolic"
+                            color: Kirigami.Theme.textColor
+                            isMask: true
                         }
+                        MouseArea {
+                            id: editMouse
+                            anchors.fill: parent
+                            hoverEnabled: true
+                            cursorShape: Qt.PointingHandCursor
+                            onClicked: editDialog.openFor(faceItem.modelData)
+                        }
+                    }
 
                     // Delete — anchored straight to the card's right edge and
                     // vertically centered on the card itself, not laid out
@@ -329,6 +329,32 @@ fn extract_query_param(req: &str, param: &str) -> Option<String> {
     Some(rest[..end].to_string())
 }
 
+/// Decodes a `encodeURIComponent`-encoded query value: `%XX` escapes
+/// become bytes (invalid UTF-8 is replaced), anything else is kept as is.
+/// Face ids never need it; free-text labels and notes do.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
 /// Extracts the face_id from the busctl output of a RegisterFace call.
 ///
 /// Real busctl output escapes embedded quotes — confirmed against a live
@@ -359,6 +385,31 @@ fn sddm_pam_line_present(contents: &str) -> bool {
     contents.contains("pam_linux_hello")
 }
 
+/// JSON body for `/config-policy`: which config keys the administrator
+/// policy (`/etc/linux-hello/policy.toml`, see `hello_config::policy`)
+/// locks, so the GUI can render them read-only. `managed` is false when
+/// there's no policy file at all.
+fn policy_json(policy: &hello_config::Policy) -> String {
+    let locked = policy
+        .locked_keys()
+        .map(|key| format!(r#""{}""#, json_escape(key)))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        r#"{{"managed":{},"locked":[{}]}}"#,
+        policy.source().is_some(),
+        locked
+    )
+}
+
+/// Loads the administrator policy fresh for each request — it's a small
+/// root-owned file that can change while the GUI is open.
+fn load_policy() -> Result<hello_config::Policy, String> {
+    hello_config::Policy::load(std::path::Path::new(hello_config::POLICY_PATH))
+        .map(|(policy, _unknown_keys)| policy)
+        .map_err(|e| e.to_string())
+}
+
 /// Starts a multi-threaded HTTP server on 127.0.0.1 (port allocated by the OS).
 /// Each connection is handled in a dedicated thread.
 /// Returns the assigned port.
@@ -577,6 +628,35 @@ fn handle_ctrl_connection(
                 active, install_pam_available
             ),
         )
+    } else if req.contains("/config-schema") {
+        // Static per-build, like /app-info: the settings pages are rendered
+        // from this (types, defaults, ranges, restart-required markers)
+        // rather than a key list hard-coded in QML. See
+        // hello_config::schema.
+        ("200 OK", hello_config::schema::json_schema().to_string())
+    } else if req.contains("/config-policy") {
+        match load_policy() {
+            Ok(policy) => ("200 OK", policy_json(&policy)),
+            Err(err) => {
+                eprintln!("✗ Policy error: {}", err);
+                (
+                    "200 OK",
+                    format!(
+                        r#"{{"managed":true,"locked":[],"error":"{}"}}"#,
+                        json_escape(&err)
+                    ),
+                )
+            }
+        }
+    } else if (req.contains("/sddm-enable") || req.contains("/sddm-disable"))
+        && load_policy().is_ok_and(|p| p.is_locked("authentication.enable_for_sddm"))
+    {
+        // The GUI already renders the toggle read-only in this case; this
+        // is the backstop for a request made anyway.
+        (
+            "200 OK",
+            r#"{"ok":false,"error":"Locked by administrator policy"}"#.to_string(),
+        )
     } else if req.contains("/sddm-enable") || req.contains("/sddm-disable") {
         // Blocking is fine: each connection already runs on its own thread.
         // pkexec shows the native polkit auth-agent dialog (matched to our
@@ -671,6 +751,40 @@ fn handle_ctrl_connection(
                 )
             }
         }
+    } else if req.contains("/update-face") {
+        // Absent params stay absent (unchanged), an empty one clears the field
+        let face_id = extract_query_param(&req, "id").unwrap_or_default();
+        let optional = |name: &str| {
+            extract_query_param(&req, name)
+                .map(|value| format!(r#""{}""#, json_escape(&percent_decode(&value))))
+                .unwrap_or_else(|| "null".to_string())
+        };
+        let request_json = format!(
+            r#"{{"user_id":{},"face_id":"{}","label":{},"notes":{}}}"#,
+            uid,
+            json_escape(&face_id),
+            optional("label"),
+            optional("notes")
+        );
+        match run_command(Command::new("busctl").args([
+            "--user",
+            "call",
+            "com.linuxhello.FaceAuth",
+            "/com/linuxhello/FaceAuth",
+            "com.linuxhello.FaceAuth",
+            "UpdateFace",
+            "s",
+            &request_json,
+        ])) {
+            Ok(_) => ("200 OK", r#"{"ok":true}"#.to_string()),
+            Err(err) => {
+                eprintln!("✗ UpdateFace error: {}", err);
+                (
+                    "500 Internal Server Error",
+                    format!(r#"{{"ok":false,"error":"{}"}}"#, json_escape(&err)),
+                )
+            }
+        }
     } else if req.contains("/app-info") {
         // Static per-build values (see the consts' doc comments) — no
         // busctl/D-Bus round-trip needed, unlike the routes above.
@@ -725,6 +839,27 @@ mod tests {
         assert!(out.is_err());
     }
 
+    #[test]
+    fn policy_json_lists_locked_keys() {
+        let (policy, _) = hello_config::Policy::parse(
+            std::path::Path::new("policy.toml"),
+            "locked = [\"recognition.contexts\"]\n\n[authentication]\nenable_for_sddm = false\n",
+        )
+        .unwrap();
+        assert_eq!(
+            policy_json(&policy),
+            r#"{"managed":true,"locked":["authentication.enable_for_sddm","recognition.contexts"]}"#
+        );
+    }
+
+    #[test]
+    fn policy_json_without_a_policy_file_is_unmanaged() {
+        assert_eq!(
+            policy_json(&hello_config::Policy::default()),
+            r#"{"managed":false,"locked":[]}"#
+        );
+    }
+
     #[test]
     fn sddm_pam_line_present_detects_configured_line() {
         let contents = "#%PAM-1.0\n\
@@ -979,6 +1114,15 @@ mod tests {
         assert_eq!(extract_query_param("", "id"), None);
     }
 
+    #[test]
+    fn percent_decode_decodes_escapes_and_keeps_the_rest() {
+        assert_eq!(percent_decode("with%20glasses"), "with glasses");
+        assert_eq!(percent_decode("caf%C3%A9%20%26%20co"), "café & co");
+        // A stray or truncated '%' is kept, not dropped
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
     #[test]
     fn extract_face_id_from_busctl_finds_the_id_in_real_escaped_busctl_output() {
         // Regression test for a real bug: busctl escapes embedded quotes in
@@ -19,6 +19,7 @@ use serde::{Deserialize, Serialize};
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int};
 use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
 
 // Basic C bindings
 #[repr(C)]
@@ -29,6 +30,17 @@ pub struct PamHandle {
 extern "C" {
     fn pam_get_user(pamh: *mut PamHandle, user: *mut *const c_char, prompt: *const c_char)
         -> c_int;
+    fn pam_set_data(
+        pamh: *mut PamHandle,
+        module_data_name: *const c_char,
+        data: *mut std::os::raw::c_void,
+        cleanup: Option<unsafe extern "C" fn(*mut PamHandle, *mut std::os::raw::c_void, c_int)>,
+    ) -> c_int;
+    fn pam_get_data(
+        pamh: *const PamHandle,
+        module_data_name: *const c_char,
+        data: *mut *const std::os::raw::c_void,
+    ) -> c_int;
 }
 
 // PAM constants
@@ -43,6 +55,14 @@ const PAM_TEXT_INFO: c_int = 4;
 const PAM_ERROR_MSG: c_int = 3;
 const PAM_PROMPT_ECHO_ON: c_int = 1;
 
+// pam_setcred flags
+const PAM_DELETE_CRED: c_int = 0x0004;
+
+/// PAM data set while the module's last `pam_sm_authenticate` didn't
+/// succeed: if the stack still does, another module (the password) did
+const FACE_NOT_USED_DATA: &CStr = c"linux_hello_face_not_used";
+static FACE_NOT_USED: u8 = 1;
+
 // Item type to retrieve the conversation function
 const PAM_CONV_ITEM: c_int = 5;
 
@@ -185,7 +205,8 @@ fn detect_lang() -> String {
 
 /// Translate a PAM message according to the detected language.
 /// Recognized keys: "looking", "recognized", "not_recognized", "confirm_prompt",
-/// "not_confirmed"
+/// "not_confirmed", "retrying" (followed by " (2/4)" — see `retry_message`),
+/// "reenroll", "no_enrollment", "locked_out"
 ///
 /// `confirm_prompt`'s "[y/N]" keystroke hint is intentionally left in Latin
 /// script in every language (common CLI i18n convention): the confirmation
@@ -201,70 +222,256 @@ fn pam_t(key: &str) -> &'static str {
         (_, "not_recognized") if lang == "en" => "✗ Face not recognized",
         (_, "confirm_prompt") if lang == "en" => "✓ Face recognized. Confirm? [y/N]: ",
         (_, "not_confirmed") if lang == "en" => "✗ Not confirmed",
+        (_, "retrying") if lang == "en" => "↻ Face not recognized, trying again",
+        (_, "reenroll") if lang == "en" => {
+            "✗ Your enrolled face is out of date — run `linux-hello enroll` again"
+        }
+        (_, "no_enrollment") if lang == "en" => {
+            "✗ No usable face enrolled — run `linux-hello enroll`"
+        }
+        (_, "locked_out") if lang == "en" => {
+            "✗ Too many failed attempts — face recognition paused, use your password"
+        }
         // French
         ("fr", "looking") => "🔍 Regardez vers la caméra...",
         ("fr", "recognized") => "✓ Visage reconnu",
         ("fr", "not_recognized") => "✗ Visage non reconnu",
         ("fr", "confirm_prompt") => "✓ Visage reconnu. Confirmer ? [y/N] : ",
         ("fr", "not_confirmed") => "✗ Non confirmé",
+        ("fr", "retrying") => "↻ Visage non reconnu, nouvel essai",
+        ("fr", "reenroll") => {
+            "✗ Votre visage enregistré est obsolète — relancez `linux-hello enroll`"
+        }
+        ("fr", "no_enrollment") => {
+            "✗ Aucun visage enregistré utilisable — lancez `linux-hello enroll`"
+        }
+        ("fr", "locked_out") => {
+            "✗ Trop d'échecs — reconnaissance faciale suspendue, utilisez votre mot de passe"
+        }
         // German
         ("de", "looking") => "🔍 Schauen Sie in die Kamera...",
         ("de", "recognized") => "✓ Gesicht erkannt",
         ("de", "not_recognized") => "✗ Gesicht nicht erkannt",
         ("de", "confirm_prompt") => "✓ Gesicht erkannt. Bestätigen? [y/N]: ",
         ("de", "not_confirmed") => "✗ Nicht bestätigt",
+        ("de", "retrying") => "↻ Gesicht nicht erkannt, neuer Versuch",
+        ("de", "reenroll") => {
+            "✗ Ihr registriertes Gesicht ist veraltet — führen Sie `linux-hello enroll` erneut aus"
+        }
+        ("de", "no_enrollment") => {
+            "✗ Kein verwendbares Gesicht registriert — führen Sie `linux-hello enroll` aus"
+        }
+        ("de", "locked_out") => {
+            "✗ Zu viele Fehlversuche — Gesichtserkennung pausiert, verwenden Sie Ihr Passwort"
+        }
         // Spanish
         ("es", "looking") => "🔍 Mire hacia la cámara...",
         ("es", "recognized") => "✓ Rostro reconocido",
         ("es", "not_recognized") => "✗ Rostro no reconocido",
         ("es", "confirm_prompt") => "✓ Rostro reconocido. ¿Confirmar? [y/N]: ",
         ("es", "not_confirmed") => "✗ No confirmado",
+        ("es", "retrying") => "↻ Rostro no reconocido, intentando de nuevo",
+        ("es", "reenroll") => {
+            "✗ Su rostro registrado está desactualizado — ejecute `linux-hello enroll` de nuevo"
+        }
+        ("es", "no_enrollment") => {
+            "✗ No hay ningún rostro registrado utilizable — ejecute `linux-hello enroll`"
+        }
+        ("es", "locked_out") => {
+            "✗ Demasiados intentos fallidos — reconocimiento facial en pausa, use su contraseña"
+        }
         // Portuguese
         ("pt", "looking") => "🔍 Olhe para a câmera...",
         ("pt", "recognized") => "✓ Rosto reconhecido",
         ("pt", "not_recognized") => "✗ Rosto não reconhecido",
         ("pt", "confirm_prompt") => "✓ Rosto reconhecido. Confirmar? [y/N]: ",
         ("pt", "not_confirmed") => "✗ Não confirmado",
+        ("pt", "retrying") => "↻ Rosto não reconhecido, tentando novamente",
+        ("pt", "reenroll") => {
+            "✗ Seu rosto cadastrado está desatualizado — execute `linux-hello enroll` novamente"
+        }
+        ("pt", "no_enrollment") => {
+            "✗ Nenhum rosto cadastrado utilizável — execute `linux-hello enroll`"
+        }
+        ("pt", "locked_out") => {
+            "✗ Muitas tentativas falhas — reconhecimento facial pausado, use sua senha"
+        }
         // Russian
         ("ru", "looking") => "🔍 Посмотрите на камеру...",
         ("ru", "recognized") => "✓ Лицо распознано",
         ("ru", "not_recognized") => "✗ Лицо не распознано",
         ("ru", "confirm_prompt") => "✓ Лицо распознано. Подтвердить? [y/N]: ",
         ("ru", "not_confirmed") => "✗ Не подтверждено",
+        ("ru", "retrying") => "↻ Лицо не распознано, повторная попытка",
+        ("ru", "reenroll") => "✗ Сохранённое лицо устарело — снова выполните `linux-hello enroll`",
+        ("ru", "no_enrollment") => {
+            "✗ Нет пригодного сохранённого лица — выполните `linux-hello enroll`"
+        }
+        ("ru", "locked_out") => {
+            "✗ Слишком много неудачных попыток — распознавание лица приостановлено, введите пароль"
+        }
         // Japanese
         ("ja", "looking") => "🔍 カメラを見てください...",
         ("ja", "recognized") => "✓ 顔が認識されました",
         ("ja", "not_recognized") => "✗ 顔が認識されませんでした",
         ("ja", "confirm_prompt") => "✓ 顔が認識されました。確認しますか？ [y/N]: ",
         ("ja", "not_confirmed") => "✗ 確認されませんでした",
+        ("ja", "retrying") => "↻ 顔が認識されませんでした。再試行します",
+        ("ja", "reenroll") => {
+            "✗ 登録された顔は古くなっています — `linux-hello enroll` を再実行してください"
+        }
+        ("ja", "no_enrollment") => {
+            "✗ 使用できる登録済みの顔がありません — `linux-hello enroll` を実行してください"
+        }
+        ("ja", "locked_out") => {
+            "✗ 失敗が多すぎます — 顔認証を一時停止しました。パスワードを使用してください"
+        }
         // Chinese
         ("zh", "looking") => "🔍 请看向摄像头...",
         ("zh", "recognized") => "✓ 人脸已识别",
         ("zh", "not_recognized") => "✗ 人脸未识别",
         ("zh", "confirm_prompt") => "✓ 人脸已识别。确认吗？[y/N]: ",
         ("zh", "not_confirmed") => "✗ 未确认",
+        ("zh", "retrying") => "↻ 人脸未识别，正在重试",
+        ("zh", "reenroll") => "✗ 已注册的人脸已过期 — 请重新运行 `linux-hello enroll`",
+        ("zh", "no_enrollment") => "✗ 没有可用的已注册人脸 — 请运行 `linux-hello enroll`",
+        ("zh", "locked_out") => "✗ 失败次数过多 — 人脸识别已暂停，请使用密码",
         // Arabic
         ("ar", "looking") => "🔍 انظر إلى الكاميرا...",
         ("ar", "recognized") => "✓ تم التعرف على الوجه",
         ("ar", "not_recognized") => "✗ لم يتم التعرف على الوجه",
         ("ar", "confirm_prompt") => "✓ تم التعرف على الوجه. تأكيد؟ [y/N]: ",
         ("ar", "not_confirmed") => "✗ لم يتم التأكيد",
+        ("ar", "retrying") => "↻ لم يتم التعرف على الوجه، إعادة المحاولة",
+        ("ar", "reenroll") => "✗ الوجه المسجل قديم — أعد تشغيل `linux-hello enroll`",
+        ("ar", "no_enrollment") => "✗ لا يوجد وجه مسجل صالح للاستخدام — شغّل `linux-hello enroll`",
+        ("ar", "locked_out") => {
+            "✗ محاولات فاشلة كثيرة — تم إيقاف التعرف على الوجه مؤقتًا، استخدم كلمة المرور"
+        }
         // Hindi
         ("hi", "looking") => "🔍 कैमरे की ओर देखें...",
         ("hi", "recognized") => "✓ चेहरा पहचाना गया",
         ("hi", "not_recognized") => "✗ चेहरा नहीं पहचाना गया",
         ("hi", "confirm_prompt") => "✓ चेहरा पहचाना गया। पुष्टि करें? [y/N]: ",
         ("hi", "not_confirmed") => "✗ पुष्टि नहीं हुई",
+        ("hi", "retrying") => "↻ चेहरा नहीं पहचाना गया, फिर से प्रयास",
+        ("hi", "reenroll") => "✗ आपका पंजीकृत चेहरा पुराना है — `linux-hello enroll` फिर से चलाएँ",
+        ("hi", "no_enrollment") => "✗ कोई उपयोग योग्य पंजीकृत चेहरा नहीं — `linux-hello enroll` चलाएँ",
+        ("hi", "locked_out") => "✗ बहुत अधिक विफल प्रयास — चेहरा पहचान रोकी गई, अपना पासवर्ड उपयोग करें",
         // English default
         (_, "looking") => "🔍 Look at the camera...",
         (_, "recognized") => "✓ Face recognized",
         (_, "not_recognized") => "✗ Face not recognized",
         (_, "confirm_prompt") => "✓ Face recognized. Confirm? [y/N]: ",
         (_, "not_confirmed") => "✗ Not confirmed",
+        (_, "retrying") => "↻ Face not recognized, trying again",
+        (_, "reenroll") => "✗ Your enrolled face is out of date — run `linux-hello enroll` again",
+        (_, "no_enrollment") => "✗ No usable face enrolled — run `linux-hello enroll`",
+        (_, "locked_out") => {
+            "✗ Too many failed attempts — face recognition paused, use your password"
+        }
         _ => "",
     }
 }
 
+/// The "trying again" message shown before round `round` of `rounds`
+fn retry_message(round: u32, rounds: u32) -> String {
+    format!("{} ({}/{})", pam_t("retrying"), round, rounds)
+}
+
+/// Failure reason from the helper when every enrolled face is from another
+/// embedding model or too old for the context
+const REENROLLMENT_REQUIRED: &str = "Re-enrollment required";
+
+/// Failure reason from the helper while too many failed attempts keep face
+/// auth off for the user and context (backing off, or until a password
+/// authentication)
+const LOCKED_OUT: &str = "Locked out";
+
+/// Failure reasons from the helper that mean "the camera looked and didn't
+/// find a match" (`hello_daemon::pam_helper`'s wording). Only these are
+/// worth another round: no enrollment, a policy denial or a camera error
+/// would fail the same way again.
+const RETRYABLE_REASONS: &[&str] = &["Face not recognized", "Timeout"];
+
+/// Shortest round worth starting — less than this and the camera has barely
+/// opened before the deadline
+const MIN_ROUND: Duration = Duration::from_millis(1000);
+
+/// How many verify rounds one `pam_sm_authenticate` call may run, and for
+/// how long in total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RetryPolicy {
+    /// Rounds after the first (`advanced.max_retries`)
+    max_retries: u32,
+
+    /// Wall-time cap on all rounds together (`advanced.pam_total_timeout_ms`)
+    total: Duration,
+}
+
+impl RetryPolicy {
+    /// From `/etc/linux-hello/config.toml` and the policy file only — never
+    /// the target user's own config, which would let any user stretch how
+    /// long e.g. a sudo prompt keeps the camera on. A config that doesn't
+    /// load falls back to the built-in defaults rather than failing
+    /// authentication.
+    fn load() -> Self {
+        let advanced = match hello_config::Config::load_system() {
+            Ok(loaded) => loaded.config.advanced,
+            Err(e) => {
+                log_pam(&format!(
+                    "System config not loaded ({}), using default retry settings",
+                    e
+                ));
+                hello_config::AdvancedConfig::default()
+            }
+        };
+        Self::from_config(&advanced)
+    }
+
+    fn from_config(advanced: &hello_config::AdvancedConfig) -> Self {
+        Self {
+            max_retries: advanced.max_retries,
+            total: Duration::from_millis(advanced.pam_total_timeout_ms),
+        }
+    }
+}
+
+/// Run up to `1 + max_retries` verify rounds, stopping at the first answer
+/// that isn't a retryable failure (a match, a disabled context, a helper
+/// error, or a failure another round wouldn't change).
+///
+/// `verify` gets each round's capture timeout: `timeout_ms`, or what's left
+/// of `policy.total` if that's shorter. No new round starts with less than
+/// [`MIN_ROUND`] left; the last failure is returned instead. `on_retry` is
+/// called with `(round, rounds)` before every round after the first.
+fn verify_with_retries(
+    policy: RetryPolicy,
+    timeout_ms: u64,
+    mut verify: impl FnMut(u64) -> Result<PamHelperResponse, String>,
+    mut on_retry: impl FnMut(u32, u32),
+) -> Result<PamHelperResponse, String> {
+    let rounds = policy.max_retries.saturating_add(1);
+    let deadline = Instant::now() + policy.total;
+    let mut round = 1;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let response = verify(timeout_ms.min(remaining.as_millis() as u64))?;
+        let retryable = matches!(
+            &response,
+            PamHelperResponse::Failure { reason } if RETRYABLE_REASONS.contains(&reason.as_str())
+        );
+        if !retryable
+            || round >= rounds
+            || deadline.saturating_duration_since(Instant::now()) < MIN_ROUND
+        {
+            return Ok(response);
+        }
+        round += 1;
+        on_retry(round, rounds);
+    }
+}
+
 /// Send a message via the PAM conversation (pam_conv).
 /// We send even if PAM_SILENT is active: the user must know
 /// that the camera is in use (essential biometric feedback).
@@ -416,6 +623,30 @@ pub unsafe extern "C" fn pam_sm_authenticate(
     flags: c_int,
     argc: c_int,
     argv: *const *const c_char,
+) -> c_int {
+    let result = authenticate(pamh, flags, argc, argv);
+    let data = if result == PAM_SUCCESS {
+        std::ptr::null_mut()
+    } else {
+        std::ptr::addr_of!(FACE_NOT_USED) as *mut std::os::raw::c_void
+    };
+    pam_set_data(pamh, FACE_NOT_USED_DATA.as_ptr(), data, None);
+    result
+}
+
+/// Whether the module's last `pam_sm_authenticate` on `pamh` didn't succeed
+unsafe fn face_not_used(pamh: *mut PamHandle) -> bool {
+    let mut data: *const std::os::raw::c_void = std::ptr::null();
+    pam_get_data(pamh, FACE_NOT_USED_DATA.as_ptr(), &mut data) == PAM_SUCCESS && !data.is_null()
+}
+
+/// `pam_sm_authenticate` itself
+#[allow(unsafe_op_in_unsafe_fn)]
+unsafe fn authenticate(
+    pamh: *mut PamHandle,
+    flags: c_int,
+    argc: c_int,
+    argv: *const *const c_char,
 ) -> c_int {
     log_pam("pam_sm_authenticate called");
     log_pam("pam_sm_authenticate started");
@@ -474,15 +705,38 @@ pub unsafe extern "C" fn pam_sm_authenticate(
     // Inform the user that recognition is in progress
     pam_conv_send(pamh, flags, PAM_TEXT_INFO, pam_t("looking"));
 
-    // Build the request for the PAM helper
-    let helper_req = PamHelperRequest {
-        user_id,
-        context: opts.context.clone(),
-        timeout_ms: opts.timeout_ms,
-    };
+    let retry = RetryPolicy::load();
+    if opts.debug {
+        log_pam(&format!(
+            "Retry policy: max_retries={}, total={}ms",
+            retry.max_retries,
+            retry.total.as_millis()
+        ));
+    }
+
+    // Call the helper via socket instead of D-Bus, once per round
+    let outcome = verify_with_retries(
+        retry,
+        opts.timeout_ms,
+        |timeout_ms| {
+            let helper_req = PamHelperRequest {
+                user_id,
+                context: opts.context.clone(),
+                timeout_ms,
+                action: PamHelperAction::Verify,
+            };
+            call_pam_helper_sync(&helper_req)
+        },
+        |round, rounds| {
+            log_pam(&format!(
+                "helper failure user={}, starting round {}/{}",
+                username, round, rounds
+            ));
+            pam_conv_send(pamh, flags, PAM_TEXT_INFO, &retry_message(round, rounds));
+        },
+    );
 
-    // Call the helper via socket instead of D-Bus
-    match call_pam_helper_sync(&helper_req) {
+    match outcome {
         Ok(response) => match response {
             PamHelperResponse::Success {
                 face_id,
@@ -538,9 +792,39 @@ pub unsafe extern "C" fn pam_sm_authenticate(
                     "helper failure user={} reason={}",
                     username, reason
                 ));
-                pam_conv_send(pamh, flags, PAM_ERROR_MSG, pam_t("not_recognized"));
+                // Enrolled under an embedding model the daemon no longer
+                // uses: "not recognized" would send them looking at the
+                // camera setup instead
+                let message = match reason.as_str() {
+                    REENROLLMENT_REQUIRED => pam_t("reenroll"),
+                    LOCKED_OUT => pam_t("locked_out"),
+                    _ => pam_t("not_recognized"),
+                };
+                pam_conv_send(pamh, flags, PAM_ERROR_MSG, message);
                 PAM_AUTH_ERR
             }
+            PamHelperResponse::NoEnrollment => {
+                // Nothing the camera could match: say so at once, rather
+                // than "not recognized" after every retry round
+                log_pam(&format!("helper no enrollment user={}", username));
+                pam_conv_send(pamh, flags, PAM_ERROR_MSG, pam_t("no_enrollment"));
+                PAM_AUTH_ERR
+            }
+            PamHelperResponse::ContextDisabled { context } => {
+                // Turned off via `enable_for_<context> = false` rather than
+                // by removing this line from /etc/pam.d: behave exactly as
+                // if the line weren't there — no message, no failed attempt.
+                log_pam(&format!(
+                    "helper context disabled user={} context={}",
+                    username, context
+                ));
+                PAM_IGNORE
+            }
+            PamHelperResponse::FailuresCleared => {
+                // Only ever the answer to `Authenticated`
+                log_pam(&format!("helper unexpected answer user={}", username));
+                PAM_IGNORE
+            }
         },
         Err(e) => {
             // Error or helper unavailable = ignore and let pam_unix.so take over
@@ -554,15 +838,58 @@ pub unsafe extern "C" fn pam_sm_authenticate(
     }
 }
 
+/// PAM function for credentials. Applications only establish credentials
+/// once the whole stack authenticated the user, so if the face didn't, the
+/// password that followed did: this is where the daemon learns to forget
+/// the user's failed face attempts in this context (see
+/// `hello_daemon::lockout`) — a face locked out after too many of them
+/// comes back after one password authentication. The daemon only takes
+/// that from root. Never changes the outcome.
+///
+/// # Safety
+/// Same as `pam_sm_authenticate`: `pamh` and `argv` come from libpam and
+/// must be valid.
 #[allow(non_snake_case)]
+#[allow(unsafe_op_in_unsafe_fn)]
 #[no_mangle]
-pub extern "C" fn pam_sm_setcred(
-    _pamh: *mut PamHandle,
-    _flags: c_int,
-    _argc: c_int,
-    _argv: *const *const c_char,
+pub unsafe extern "C" fn pam_sm_setcred(
+    pamh: *mut PamHandle,
+    flags: c_int,
+    argc: c_int,
+    argv: *const *const c_char,
 ) -> c_int {
     log_pam("pam_sm_setcred called");
+    // A face match already cleared them
+    if flags & PAM_DELETE_CRED != 0 || !face_not_used(pamh) {
+        return PAM_SUCCESS;
+    }
+
+    let opts = parse_options(argc, argv);
+    let mut user_ptr: *const c_char = std::ptr::null();
+    if pam_get_user(pamh, &mut user_ptr, std::ptr::null()) != PAM_SUCCESS || user_ptr.is_null() {
+        return PAM_SUCCESS;
+    }
+    let Some(user_id) = CStr::from_ptr(user_ptr)
+        .to_str()
+        .ok()
+        .and_then(uid_from_name)
+    else {
+        return PAM_SUCCESS;
+    };
+
+    let request = PamHelperRequest {
+        user_id,
+        context: opts.context,
+        timeout_ms: 0,
+        action: PamHelperAction::Authenticated,
+    };
+    match call_pam_helper_sync(&request) {
+        Ok(_) => log_pam(&format!("failed attempts cleared uid={}", user_id)),
+        Err(e) => log_pam(&format!(
+            "failed attempts not cleared uid={}: {}",
+            user_id, e
+        )),
+    }
     PAM_SUCCESS
 }
 
@@ -652,6 +979,16 @@ struct PamHelperRequest {
     user_id: u32,
     context: String,
     timeout_ms: u64,
+    action: PamHelperAction,
+}
+
+/// What the helper is asked for (`hello_daemon::pam_helper`'s wording)
+#[derive(Serialize, Deserialize, Debug)]
+enum PamHelperAction {
+    Verify,
+    /// The stack authenticated the user after the face didn't: forget
+    /// their failed attempts in the context
+    Authenticated,
 }
 
 /// Response structure from the PAM helper
@@ -664,6 +1001,16 @@ enum PamHelperResponse {
     Failure {
         reason: String,
     },
+    /// Face auth is turned off for this context in the daemon's config —
+    /// not a failed attempt, so it maps to `PAM_IGNORE`
+    ContextDisabled {
+        context: String,
+    },
+    /// The user has no face that could match in this context — none
+    /// enrolled, or none that passes its integrity check. Never retried.
+    NoEnrollment,
+    /// Answer to `PamHelperAction::Authenticated`
+    FailuresCleared,
 }
 
 /// Returns the UID of the process on the other end of `stream`, via
@@ -855,6 +1202,159 @@ mod tests {
         assert_eq!(peer_uid_of_b, our_uid);
     }
 
+    fn not_recognized() -> Result<PamHelperResponse, String> {
+        Ok(PamHelperResponse::Failure {
+            reason: "Face not recognized".to_string(),
+        })
+    }
+
+    fn policy(max_retries: u32, total_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            total: Duration::from_millis(total_ms),
+        }
+    }
+
+    #[test]
+    fn test_verify_with_retries_runs_one_round_plus_max_retries() {
+        let mut calls = 0;
+        let mut retries = Vec::new();
+        let response = verify_with_retries(
+            policy(2, 60_000),
+            5000,
+            |timeout_ms| {
+                calls += 1;
+                assert_eq!(timeout_ms, 5000);
+                not_recognized()
+            },
+            |round, rounds| retries.push((round, rounds)),
+        );
+        assert!(matches!(response, Ok(PamHelperResponse::Failure { .. })));
+        assert_eq!(calls, 3);
+        assert_eq!(retries, vec![(2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn test_verify_with_retries_stops_at_the_first_match() {
+        let mut calls = 0;
+        let response = verify_with_retries(
+            policy(3, 60_000),
+            5000,
+            |_| {
+                calls += 1;
+                if calls == 2 {
+                    Ok(PamHelperResponse::Success {
+                        face_id: "face".to_string(),
+                        similarity_score: 0.9,
+                    })
+                } else {
+                    not_recognized()
+                }
+            },
+            |_, _| {},
+        );
+        assert!(matches!(response, Ok(PamHelperResponse::Success { .. })));
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_verify_with_retries_does_not_retry_final_answers() {
+        for answer in [
+            Ok(PamHelperResponse::NoEnrollment),
+            Ok(PamHelperResponse::Failure {
+                reason: REENROLLMENT_REQUIRED.to_string(),
+            }),
+            Ok(PamHelperResponse::Failure {
+                reason: LOCKED_OUT.to_string(),
+            }),
+            Ok(PamHelperResponse::ContextDisabled {
+                context: "sudo".to_string(),
+            }),
+            Err("Socket unreachable".to_string()),
+        ] {
+            let mut answer = Some(answer);
+            let mut calls = 0;
+            let _ = verify_with_retries(
+                policy(3, 60_000),
+                5000,
+                |_| {
+                    calls += 1;
+                    answer.take().unwrap()
+                },
+                |_, _| panic!("no retry expected"),
+            );
+            assert_eq!(calls, 1);
+        }
+    }
+
+    #[test]
+    fn test_verify_with_retries_respects_the_total_time_cap() {
+        // Each round is capped at what's left of the total, and no round
+        // starts with less than MIN_ROUND to go
+        let mut timeouts = Vec::new();
+        let response = verify_with_retries(
+            policy(10, 1200),
+            5000,
+            |timeout_ms| {
+                timeouts.push(timeout_ms);
+                std::thread::sleep(Duration::from_millis(300));
+                not_recognized()
+            },
+            |_, _| {},
+        );
+        assert!(matches!(response, Ok(PamHelperResponse::Failure { .. })));
+        assert_eq!(timeouts.len(), 1);
+        assert!(timeouts[0] <= 1200);
+    }
+
+    #[test]
+    fn test_retry_policy_defaults_to_the_config_defaults() {
+        let retry = RetryPolicy::from_config(&hello_config::AdvancedConfig::default());
+        assert_eq!(retry, policy(3, 30_000));
+    }
+
+    #[test]
+    fn test_retry_message_counts_rounds() {
+        // Locale-independent on purpose: the language tests own the env vars
+        let message = retry_message(2, 4);
+        assert!(message.ends_with(" (2/4)"));
+        assert!(message.len() > " (2/4)".len());
+    }
+
+    #[test]
+    fn test_context_disabled_response_matches_the_daemon_wire_format() {
+        // Externally tagged, exactly as hello_daemon's pam_helper
+        // serializes its own PamHelperResponse::ContextDisabled.
+        let response: PamHelperResponse =
+            serde_json::from_str(r#"{"ContextDisabled":{"context":"sudo"}}"#).unwrap();
+        assert!(matches!(
+            response,
+            PamHelperResponse::ContextDisabled { context } if context == "sudo"
+        ));
+    }
+
+    #[test]
+    fn test_no_enrollment_response_matches_the_daemon_wire_format() {
+        let response: PamHelperResponse = serde_json::from_str(r#""NoEnrollment""#).unwrap();
+        assert!(matches!(response, PamHelperResponse::NoEnrollment));
+    }
+
+    #[test]
+    fn test_authenticated_request_matches_the_daemon_wire_format() {
+        let request = PamHelperRequest {
+            user_id: 1000,
+            context: "sudo".to_string(),
+            timeout_ms: 0,
+            action: PamHelperAction::Authenticated,
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"user_id":1000,"context":"sudo","timeout_ms":0,"action":"Authenticated"}"#
+        );
+        let response: PamHelperResponse = serde_json::from_str(r#""FailuresCleared""#).unwrap();
+        assert!(matches!(response, PamHelperResponse::FailuresCleared));
+    }
+
     #[test]
     fn test_parse_options() {
         // Create test arguments